@@ -0,0 +1,45 @@
+/* C interface of the txp library built with `cargo build --release --features ffi` (libtxp.so / txp.dll) */
+#ifndef TXG_H
+#define TXG_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TXG_ABI_VERSION 1
+
+/* results of txg_submit_csv_row */
+#define TXG_OK 0
+#define TXG_REJECTED 1
+#define TXG_INVALID_ROW -1
+#define TXG_INVALID_ARGUMENT -2
+#define TXG_INTERNAL_ERROR -3
+
+typedef struct TxgEngine TxgEngine;
+
+/* version of the interface the library implements, compare with TXG_ABI_VERSION */
+uint32_t txg_abi_version(void);
+
+/* engine with the default configuration, released by txg_finish_and_export or txg_engine_free */
+TxgEngine *txg_engine_new(void);
+
+/* applies a row of the input format without header: "deposit,1,1,2.5" or "dispute,1,1" */
+int txg_submit_csv_row(TxgEngine *engine, const char *row);
+
+/* message of the last failed submit or NULL, owned by the engine and valid until the next submit */
+const char *txg_last_error(const TxgEngine *engine);
+
+/* releases the engine and returns its accounts as CSV (header included), released by txg_string_free */
+char *txg_finish_and_export(TxgEngine *engine);
+
+void txg_engine_free(TxgEngine *engine);
+
+void txg_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TXG_H */
@@ -1,20 +1,176 @@
-use std::collections::HashMap;
-
 use crate::{ClientId, TxId, Money, TxType, Transaction};
+use crate::config::{ClosePolicy, Config};
 use crate::csv::RawAccount;
+use crate::ledger::{Ledger, LedgerError};
+
+use tracing::info;
 
 /// Error types return when processing account's transaction
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum AccountError {
     // Account is frozen, cannot perform any other operation on it
     Frozen(ClientId),
-    InssuficientFundsForWithdrawal(ClientId),
+    // Account was locked before its first transaction (frozen list or client overrides)
+    PreFrozen(ClientId),
+    // Withdrawal of the amount (second value) is over the available funds including the credit limit (third value)
+    InssuficientFundsForWithdrawal(ClientId, Money, Money),
     NoTxForDispute(TxId),
     TxNotInDispute(TxId),
+    // Deposit or withdrawal amount (third value) is over the configured limit (given as the second value)
+    AmountLimitExceeded(TxId, Money, Money),
+    // Deposits or withdrawals of the client within 24 hours (third value, including this one)
+    // would exceed the configured limit (given as the second value)
+    DailyLimitExceeded(TxId, Money, Money),
+    // Account is closed, only `open` is accepted
+    AccountClosed(ClientId),
+    // Account can not be closed while it has funds, available and held funds are given as the second and third value
+    AccountNotEmpty(ClientId, Money, Money),
+    // Transaction rejected by the rule with given name
+    RejectedByRule(TxId, String),
+    // Transaction rejected by the validation script with given reason
+    RejectedByScript(TxId, String),
+    // Transaction can not be reversed while it is in dispute
+    TxInDispute(TxId),
+    // Withdrawal is waiting for settlement, it can not be disputed or reversed yet
+    TxPending(TxId),
+    // Settled transaction is not a withdrawal waiting for settlement
+    TxNotPending(TxId),
+    // Refunded transaction is not a withdrawal
+    NotRefundable(TxId),
+    // Refund (second value) is over the amount of the withdrawal not refunded yet (third value)
+    RefundOverWithdrawal(TxId, Money, Money),
+    // Withdrawal was (partly) refunded, it can not be disputed or reversed
+    TxRefunded(TxId),
+    // Transaction in the given state can not be disputed, e.g. it is in dispute already or charged back
+    InvalidDisputeTransition(TxId, DisputeState),
+    // Deposit or withdrawal with zero (or missing) amount rejected by `zero_amount = "reject"`
+    ZeroAmount(TxId),
+    // Account was quarantined for review after too many anomalies during the run
+    Quarantined(ClientId),
+    // Manual adjustment of the account without the reason it was made for
+    MissingReason(TxId),
+    // Storage behind the ledger of the account failed, with its error message
+    Storage(String),
+}
+
+impl AccountError {
+    /// short name of the error variant, used to count rejections by reason
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AccountError::Frozen(_) => "frozen",
+            AccountError::PreFrozen(_) => "pre_frozen",
+            AccountError::InssuficientFundsForWithdrawal(..) => "insufficient_funds",
+            AccountError::NoTxForDispute(_) => "no_tx_for_dispute",
+            AccountError::TxNotInDispute(_) => "tx_not_in_dispute",
+            AccountError::AmountLimitExceeded(..) => "amount_limit_exceeded",
+            AccountError::DailyLimitExceeded(..) => "daily_limit_exceeded",
+            AccountError::AccountClosed(_) => "account_closed",
+            AccountError::AccountNotEmpty(..) => "account_not_empty",
+            AccountError::RejectedByRule(..) => "rejected_by_rule",
+            AccountError::RejectedByScript(..) => "rejected_by_script",
+            AccountError::TxInDispute(_) => "tx_in_dispute",
+            AccountError::TxPending(_) => "tx_pending",
+            AccountError::TxNotPending(_) => "tx_not_pending",
+            AccountError::NotRefundable(_) => "not_refundable",
+            AccountError::RefundOverWithdrawal(..) => "refund_over_withdrawal",
+            AccountError::TxRefunded(_) => "tx_refunded",
+            AccountError::InvalidDisputeTransition(..) => "invalid_dispute_transition",
+            AccountError::ZeroAmount(_) => "zero_amount",
+            AccountError::Quarantined(_) => "quarantined",
+            AccountError::MissingReason(_) => "missing_reason",
+            AccountError::Storage(_) => "storage",
+        }
+    }
+
+    /// stable code of the error variant, kept unchanged when messages are reworded
+    pub fn code(&self) -> &'static str {
+        match self {
+            AccountError::InssuficientFundsForWithdrawal(..) => "E1001",
+            AccountError::Frozen(_) => "E1002",
+            AccountError::PreFrozen(_) => "E1003",
+            AccountError::NoTxForDispute(_) => "E1004",
+            AccountError::TxNotInDispute(_) => "E1005",
+            AccountError::AmountLimitExceeded(..) => "E1006",
+            AccountError::DailyLimitExceeded(..) => "E1007",
+            AccountError::AccountClosed(_) => "E1008",
+            AccountError::AccountNotEmpty(..) => "E1009",
+            AccountError::RejectedByRule(..) => "E1010",
+            AccountError::RejectedByScript(..) => "E1011",
+            AccountError::TxInDispute(_) => "E1013",
+            AccountError::TxPending(_) => "E1014",
+            AccountError::TxNotPending(_) => "E1015",
+            AccountError::NotRefundable(_) => "E1016",
+            AccountError::RefundOverWithdrawal(..) => "E1017",
+            AccountError::TxRefunded(_) => "E1018",
+            AccountError::InvalidDisputeTransition(..) => "E1019",
+            AccountError::ZeroAmount(_) => "E1020",
+            AccountError::Quarantined(_) => "E1021",
+            AccountError::MissingReason(_) => "E1022",
+            AccountError::Storage(_) => "E1023",
+        }
+    }
+
+    /// description of the error with the amounts involved, used as the reason in the reports
+    pub fn message(&self) -> String {
+        match self {
+            AccountError::Frozen(client) => format!("account {} is frozen", client),
+            AccountError::PreFrozen(client) => format!("account {} was frozen before its first transaction", client),
+            AccountError::InssuficientFundsForWithdrawal(client, amount, available) => format!(
+                "insufficient funds on account {}: withdrawal of {:.4}, available {:.4}",
+                client, amount, available
+            ),
+            AccountError::NoTxForDispute(tx) => format!("transaction {} not found", tx),
+            AccountError::TxNotInDispute(tx) => format!("transaction {} is not in dispute", tx),
+            AccountError::AmountLimitExceeded(tx, limit, amount) => {
+                format!("amount {:.4} of transaction {} is over the limit {:.4}", amount, tx, limit)
+            }
+            AccountError::DailyLimitExceeded(tx, limit, total) => format!(
+                "transaction {} brings the 24 hours total to {:.4}, over the limit {:.4}",
+                tx, total, limit
+            ),
+            AccountError::AccountClosed(client) => format!("account {} is closed", client),
+            AccountError::AccountNotEmpty(client, available, held) => format!(
+                "account {} can not be closed with available {:.4} and held {:.4}",
+                client, available, held
+            ),
+            AccountError::RejectedByRule(tx, rule) => format!("transaction {} rejected by rule '{}'", tx, rule),
+            AccountError::RejectedByScript(tx, reason) => format!("transaction {} rejected by script: {}", tx, reason),
+            AccountError::TxInDispute(tx) => format!("transaction {} is in dispute", tx),
+            AccountError::TxPending(tx) => format!("withdrawal {} is waiting for settlement", tx),
+            AccountError::TxNotPending(tx) => format!("transaction {} is not a pending withdrawal", tx),
+            AccountError::NotRefundable(tx) => format!("transaction {} is not a withdrawal", tx),
+            AccountError::RefundOverWithdrawal(tx, amount, left) => {
+                format!("refund of {:.4} is over {:.4} left to refund of withdrawal {}", amount, left, tx)
+            }
+            AccountError::TxRefunded(tx) => format!("withdrawal {} was refunded", tx),
+            AccountError::InvalidDisputeTransition(tx, state) => {
+                format!("transaction {} is {} and can not be disputed", tx, state)
+            }
+            AccountError::ZeroAmount(tx) => format!("transaction {} has no amount", tx),
+            AccountError::Quarantined(client) => format!("account {} is quarantined for review", client),
+            AccountError::MissingReason(tx) => format!("adjustment {} has no reason", tx),
+            AccountError::Storage(e) => format!("ledger storage failed: {}", e),
+        }
+    }
+}
+
+/// error with its stable code, e.g. `E1001 insufficient funds on account 1: withdrawal of 5.0000, available 1.0000`
+impl std::fmt::Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl From<LedgerError> for AccountError {
+    fn from(e: LedgerError) -> Self {
+        AccountError::Storage(e.0)
+    }
 }
 
 /// data structure representing account state
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Account {
     pub client_id: ClientId,
     // The total funds that are available for trading, staking, withdrawal, etc. This
@@ -22,97 +178,258 @@ pub struct Account {
     pub available_amount: Money,
     // The total funds that are held for dispute. This should be equal to total - available amounts
     pub held_amount: Money,
-    // The total funds that are available or held. This should be equal to available + held
+    // The total funds that are available, held or pending. This should be equal to available + held + pending
     pub total_amount: Money,
+    // Withdrawals waiting for settlement (see `SettlementConfig`), they left available funds but not the account yet
+    pub pending_amount: Money,
     pub is_locked: bool,
+    // Account started locked, e.g. because of sanctions or fraud hold
+    pub is_pre_frozen: bool,
+    // Account was closed with `close` transaction
+    pub is_closed: bool,
+    // How much available funds can go below zero with withdrawals
+    pub credit_limit: Money,
+    // Number of transactions applied to the account, incremented by the caller, so external writers can detect stale updates
+    pub version: u64,
+}
+
+/// Dispute state of a recorded deposit or withdrawal. Dispute opens it (again after resolve), resolve and
+/// chargeback close it, charged back transaction can not be disputed any more.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeState {
+    #[default]
+    None,
+    Open,
+    Resolved,
+    ChargedBack,
+}
+
+impl DisputeState {
+    /// whether the transaction is in dispute in this state
+    pub fn in_dispute(self) -> bool {
+        self == DisputeState::Open
+    }
+
+    /// whether the transaction in this state can be disputed
+    pub fn can_dispute(self) -> bool {
+        matches!(self, DisputeState::None | DisputeState::Resolved)
+    }
+}
+
+impl std::fmt::Display for DisputeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DisputeState::None => "none",
+            DisputeState::Open => "open",
+            DisputeState::Resolved => "resolved",
+            DisputeState::ChargedBack => "charged_back",
+        };
+        f.write_str(name)
+    }
+}
+
+/// New state of the account after a transaction, together with the change of the disputed transaction
+/// which is applied to the history by the caller
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountOutcome {
+    pub new_account: Account,
+    pub history_update: Option<(TxId, DisputeState)>,
+}
+
+impl AccountOutcome {
+    fn new(new_account: Account) -> Self {
+        AccountOutcome { new_account, history_update: None }
+    }
+
+    /// applies `history_update` to the transactions of `ledger` and returns the new account
+    pub(crate) fn apply(self, ledger: &mut dyn Ledger) -> Result<Account, LedgerError> {
+        if let Some((tx_id, state)) = self.history_update {
+            ledger.mark_dispute(tx_id, state)?;
+        }
+        Ok(self.new_account)
+    }
 }
 
 /// converstion from RawAccount to Account
 impl From<Account> for RawAccount {
     fn from(source: Account) -> Self {
         RawAccount {
+            tenant: None,
             client_id: source.client_id,
             available_amount: source.available_amount,
             held_amount: source.held_amount,
             total_amount: source.total_amount,
             is_locked: source.is_locked,
-        }
-    }
-}
-impl Default for Account {
-    fn default() -> Self {
-        Self {
-            client_id: Default::default(),
-            available_amount: Default::default(),
-            held_amount: Default::default(),
-            total_amount: Default::default(),
-            is_locked: Default::default(),
+            is_closed: source.is_closed,
+            last_activity: None,
+            is_dormant: false,
+            version: source.version,
         }
     }
 }
 
 impl Account {
     /// call by the account transaction processing task to handle supplied transaction
-    /// the only side effect can be on a transaction in the history, when we need to change the state of in_dispute
-    /// due to dispute/resolve/chargeback events
+    /// neither the account nor the history is changed, dispute/resolve/chargeback events return
+    /// the new state of the disputed transaction in `AccountOutcome::history_update` instead
     /// 
     /// `t` reference to transaction that is currently processed 
-    /// `history` mutable reference to the history of all transaction for given account
-    /// 
-    /// return new Account instrance
+    /// `disputed` recorded transaction with the id of `t`, referenced by dispute/resolve/chargeback/settle
     /// 
-    /// todo: improvement could be done in order to make this pure function. 
-    /// One ide is to return info that another transaction should be changed
+    /// return new Account instrance together with the history update
     pub(crate) fn process_transaction(
         &self,
         t: &Transaction,
-        history: &mut HashMap<TxId, Transaction>,
-    ) -> core::result::Result<Self, AccountError> {
+        disputed: Option<&Transaction>,
+        config: &Config,
+    ) -> core::result::Result<AccountOutcome, AccountError> {
         use TxType::*;
 
+        if self.is_locked && self.is_pre_frozen {
+            return Err(AccountError::PreFrozen(self.client_id));
+        }
+        if self.is_closed && t.tx_type != Open {
+            return Err(AccountError::AccountClosed(self.client_id));
+        }
         match t.tx_type {
-            Deposit => self.deposit(t.amount),
-            Withdrawal => self.withdrawal(t.amount),
-            Dispute => self.dispute(t.tx_id, history),
-            Resolve => self.resolve(t.tx_id, history),
-            Chargeback => self.chargeback(t.tx_id, history),
+            Deposit => self.deposit(t.amount).map(AccountOutcome::new),
+            Withdrawal if config.settlement.is_some() => self.authorize(t.amount).map(AccountOutcome::new),
+            Withdrawal => self.withdrawal(t.amount).map(AccountOutcome::new),
+            Dispute => self.dispute(t.tx_id, disputed),
+            Resolve => self.resolve(t.tx_id, disputed),
+            Chargeback => self.chargeback(t.tx_id, disputed),
+            Open => self.open().map(AccountOutcome::new),
+            Close => self.close(config.close_policy).map(AccountOutcome::new),
+            Settle => self.settle(t.tx_id, disputed).map(AccountOutcome::new),
+            // the caller checks the refund against the withdrawal it references
+            Refund => self.deposit(t.amount).map(AccountOutcome::new),
         }
     }
 
+    /// Unlocks account frozen by a chargeback or before its first transaction (correction by an administrator)
+    pub(crate) fn unlock(&self) -> Self {
+        let mut a = self.clone();
+        a.is_locked = false;
+        a.is_pre_frozen = false;
+        a
+    }
+
+    /// Reverses applied deposit or withdrawal (correction by an administrator), also on locked or closed account.
+    /// The transaction is removed from the ledger, so it can be neither reversed nor disputed again.
+    /// Transaction in dispute has to be resolved first.
+    pub(crate) fn reverse(
+        &self,
+        tx_id: TxId,
+        ledger: &mut dyn Ledger,
+    ) -> core::result::Result<Self, AccountError> {
+        let tx = ledger.transaction(tx_id).ok_or(AccountError::NoTxForDispute(tx_id))?;
+        if tx.dispute.in_dispute() {
+            return Err(AccountError::TxInDispute(tx_id));
+        }
+        let amount = match tx.tx_type {
+            TxType::Withdrawal => -tx.amount,
+            _ => tx.amount,
+        };
+        let mut a = self.clone();
+        a.available_amount = self.available_amount - amount;
+        a.total_amount = a.total();
+        ledger.remove_tx(tx_id)?;
+        Ok(a)
+    }
+
+    /// available, held and pending funds together
+    fn total(&self) -> Money {
+        self.available_amount + self.held_amount + self.pending_amount
+    }
+
+    /// Opens the account. Accounts are open since their first transaction,
+    /// so this only reopens account which was closed before.
+    fn open(&self) -> core::result::Result<Self, AccountError> {
+        if self.is_locked {
+            return Err(AccountError::Frozen(self.client_id));
+        }
+        let mut a = self.clone();
+        a.is_closed = false;
+        Ok(a)
+    }
+
+    /// Closes the account, following transactions other than `open` are rejected.
+    /// Account has to be empty, unless `ClosePolicy::Sweep` is used which sweeps remaining available funds.
+    /// Held funds (open disputes), pending withdrawals and negative balance always prevent closing.
+    fn close(&self, policy: ClosePolicy) -> core::result::Result<Self, AccountError> {
+        if self.is_locked {
+            return Err(AccountError::Frozen(self.client_id));
+        }
+        let sweepable = policy == ClosePolicy::Sweep && self.available_amount > 0.0;
+        if self.held_amount != 0.0 || self.pending_amount != 0.0 || (self.available_amount != 0.0 && !sweepable) {
+            return Err(AccountError::AccountNotEmpty(self.client_id, self.available_amount, self.held_amount));
+        }
+        let mut a = self.clone();
+        if sweepable {
+            info!("account {} closed, sweeping {:.4}", self.client_id, self.available_amount);
+        }
+        a.available_amount = 0.0;
+        a.total_amount = 0.0;
+        a.is_closed = true;
+        Ok(a)
+    }
+
     /// A deposit is a credit to the client's asset account, meaning it should increase the available and
     /// total funds of the client account
     fn deposit(&self, amount: Money) -> core::result::Result<Self, AccountError> {
         if self.is_locked {
             Err(AccountError::Frozen(self.client_id))
         } else {
-            let mut a = Account::default();
-            a.client_id = self.client_id;
+            let mut a = self.clone();
             a.available_amount = self.available_amount + amount;
             a.held_amount = self.held_amount;
-            a.total_amount = a.available_amount + a.held_amount;
+            a.total_amount = a.total();
             Ok(a)
         }
     }
 
     /// A withdraw is a debit to the client's asset account, meaning it should decrease the available and
     /// total funds of the client account
-    /// If a client does not have sufficient available funds the withdrawal should fail and the total amount
-    /// of funds should not change
+    /// If a client does not have sufficient available funds (including the credit limit) the withdrawal should fail
+    /// and the total amount of funds should not change
     fn withdrawal(&self, amount: Money) -> core::result::Result<Self, AccountError> {
         if self.is_locked {
             Err(AccountError::Frozen(self.client_id))
-        } else if self.available_amount < amount {
-            Err(AccountError::InssuficientFundsForWithdrawal(self.client_id))
+        } else if self.available_amount + self.credit_limit < amount {
+            Err(AccountError::InssuficientFundsForWithdrawal(
+                self.client_id,
+                amount,
+                self.available_amount + self.credit_limit,
+            ))
         } else {
-            let mut a = Account::default();
-            a.client_id = self.client_id;
+            let mut a = self.clone();
             a.available_amount = self.available_amount - amount;
             a.held_amount = self.held_amount;
-            a.total_amount = a.available_amount + a.held_amount;
+            a.total_amount = a.total();
             Ok(a)
         }
     }
 
+    /// First phase of a two-phase withdrawal: funds are checked like for a withdrawal and moved from available
+    /// to pending, they stay part of the total funds until the withdrawal is settled
+    fn authorize(&self, amount: Money) -> core::result::Result<Self, AccountError> {
+        let mut a = self.withdrawal(amount)?;
+        a.pending_amount = self.pending_amount + amount;
+        a.total_amount = a.total();
+        Ok(a)
+    }
+
+    /// Settles pending withdrawal `tx_id`: its amount leaves the pending and total funds. Withdrawals authorized
+    /// before the account was locked are still settled. The caller checks that the withdrawal is pending.
+    pub(crate) fn settle(&self, tx_id: TxId, pending: Option<&Transaction>) -> core::result::Result<Self, AccountError> {
+        let tx = pending.ok_or(AccountError::NoTxForDispute(tx_id))?;
+        let mut a = self.clone();
+        a.pending_amount = self.pending_amount - tx.amount;
+        a.total_amount = a.total();
+        Ok(a)
+    }
+
     /// A dispute represents a client's claim that a transaction was erroneous and should be reversed.
     /// The transaction shouldn't be reversed yet but the associated funds should be held. This means
     /// that the clients available funds should decrease by the amount disputed, their held funds should
@@ -120,25 +437,24 @@ impl Account {
     /// Notice that a dispute does not state the amount disputed. Instead a dispute references the
     /// transaction that is disputed by ID. If the tx specified by the dispute doesn't exist you can ignore it
     /// and assume this is an error on our partners side.
+    /// Transaction already in dispute or charged back can not be disputed (`DisputeState::can_dispute`).
     fn dispute(
         &self,
         tx_id: TxId,
-        history: &mut HashMap<TxId, Transaction>,
-    ) -> core::result::Result<Self, AccountError> {
+        disputed: Option<&Transaction>,
+    ) -> core::result::Result<AccountOutcome, AccountError> {
         if self.is_locked {
             return Err(AccountError::Frozen(self.client_id));
         }
 
-        let t = history.get_mut(&tx_id);
-        match t {
+        match disputed {
+            Some(tx) if !tx.dispute.can_dispute() => Err(AccountError::InvalidDisputeTransition(tx_id, tx.dispute)),
             Some(tx) => {
-                tx.in_dispute = true;
-                let mut a = Account::default();
-                a.client_id = self.client_id;
+                let mut a = self.clone();
                 a.available_amount = self.available_amount - tx.amount;
                 a.held_amount = self.held_amount + tx.amount;
-                a.total_amount = a.available_amount + a.held_amount;
-                Ok(a)
+                a.total_amount = a.total();
+                Ok(AccountOutcome { new_account: a, history_update: Some((tx_id, DisputeState::Open)) })
             }
             None => Err(AccountError::NoTxForDispute(tx_id)),
         }
@@ -154,23 +470,20 @@ impl Account {
     fn resolve(
         &self,
         tx_id: TxId,
-        history: &mut HashMap<TxId, Transaction>,
-    ) -> core::result::Result<Self, AccountError> {
+        disputed: Option<&Transaction>,
+    ) -> core::result::Result<AccountOutcome, AccountError> {
         if self.is_locked {
             return Err(AccountError::Frozen(self.client_id));
         }
 
-        let t = history.get_mut(&tx_id);
-        match t {
+        match disputed {
             Some(tx) => {
-                if tx.in_dispute {
-                    tx.in_dispute = false;
-                    let mut a = Account::default();
-                    a.client_id = self.client_id;
+                if tx.dispute.in_dispute() {
+                    let mut a = self.clone();
                     a.available_amount = self.available_amount + tx.amount;
                     a.held_amount = self.held_amount - tx.amount;
-                    a.total_amount = a.available_amount + a.held_amount;
-                    Ok(a)
+                    a.total_amount = a.total();
+                    Ok(AccountOutcome { new_account: a, history_update: Some((tx_id, DisputeState::Resolved)) })
                 } else {
                     Err(AccountError::TxNotInDispute(tx_id))
                 }
@@ -189,23 +502,20 @@ impl Account {
     fn chargeback(
         &self,
         tx_id: TxId,
-        history: &mut HashMap<TxId, Transaction>,
-    ) -> core::result::Result<Self, AccountError> {
+        disputed: Option<&Transaction>,
+    ) -> core::result::Result<AccountOutcome, AccountError> {
         if self.is_locked {
             return Err(AccountError::Frozen(self.client_id));
         }
-        let t = history.get_mut(&tx_id);
-        match t {
+        match disputed {
             Some(tx) => {
-                if tx.in_dispute {
-                    tx.in_dispute = false;
-                    let mut a = Account::default();
-                    a.client_id = self.client_id;
+                if tx.dispute.in_dispute() {
+                    let mut a = self.clone();
                     a.available_amount = self.available_amount;
                     a.held_amount = self.held_amount - tx.amount;
-                    a.total_amount = a.available_amount + a.held_amount;
+                    a.total_amount = a.total();
                     a.is_locked = true;
-                    Ok(a)
+                    Ok(AccountOutcome { new_account: a, history_update: Some((tx_id, DisputeState::ChargedBack)) })
                 } else {
                     Err(AccountError::TxNotInDispute(tx_id))
                 }
@@ -217,9 +527,11 @@ impl Account {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
-    use crate::{account::Account, TxType, Transaction};
+    use crate::{
+        account::{Account, DisputeState},
+        ledger::{InMemoryLedger, Ledger},
+        testing, TxType, Transaction,
+    };
 
     /// tests for default settings
     #[test]
@@ -229,7 +541,7 @@ mod tests {
         assert_eq!(a.available_amount, 0.0);
         assert_eq!(a.held_amount, 0.0);
         assert_eq!(a.total_amount, 0.0);
-        assert_eq!(a.is_locked, false);
+        assert!(!a.is_locked);
     }
 
     #[test]
@@ -240,6 +552,7 @@ mod tests {
             held_amount: 0.0,
             available_amount: 0.0,
             is_locked: false,
+            ..Default::default()
         };
         let a1 = a.deposit(5.0).unwrap();
         a = Account {
@@ -248,11 +561,32 @@ mod tests {
             held_amount: 0.0,
             available_amount: 5.0,
             is_locked: false,
+            ..Default::default()
         };
 
         assert_eq!(a, a1);
     }
 
+    #[test]
+    fn account_reverse() {
+        let a = Account { client_id: 1, available_amount: 7.0, total_amount: 7.0, is_locked: true, ..Default::default() };
+        let t = |tx_type, tx_id, amount| testing::tx(tx_type, 1, tx_id, amount);
+        let mut ledger = InMemoryLedger::default();
+        ledger.record_tx(t(TxType::Deposit, 1, 10.0)).unwrap();
+        ledger.record_tx(t(TxType::Withdrawal, 2, 3.0)).unwrap();
+        ledger.record_tx(Transaction { dispute: DisputeState::Open, ..t(TxType::Deposit, 3, 1.0) }).unwrap();
+
+        let a1 = a.reverse(1, &mut ledger).unwrap();
+        assert_eq!((a1.available_amount, a1.total_amount, a1.is_locked), (-3.0, -3.0, true));
+        assert_eq!(a1.reverse(1, &mut ledger), Err(crate::account::AccountError::NoTxForDispute(1)));
+        let a2 = a1.reverse(2, &mut ledger).unwrap();
+        assert_eq!((a2.available_amount, a2.total_amount), (0.0, 0.0));
+        assert_eq!(a2.reverse(3, &mut ledger), Err(crate::account::AccountError::TxInDispute(3)));
+
+        let a3 = a2.unlock();
+        assert!(!a3.is_locked && !a3.is_pre_frozen);
+    }
+
     #[test]
     fn account_withdrawal() {
         let mut a = Account {
@@ -261,6 +595,7 @@ mod tests {
             held_amount: 5.0,
             available_amount: 10.0,
             is_locked: false,
+            ..Default::default()
         };
         let a1 = a.withdrawal(5.0).unwrap();
         a = Account {
@@ -269,11 +604,29 @@ mod tests {
             held_amount: 5.0,
             available_amount: 5.0,
             is_locked: false,
+            ..Default::default()
         };
 
         assert_eq!(a, a1);
     }
 
+    #[test]
+    fn account_withdrawal_within_credit_limit() {
+        let a = Account {
+            client_id: 1,
+            total_amount: 10.0,
+            available_amount: 10.0,
+            credit_limit: 5.0,
+            ..Default::default()
+        };
+        let a1 = a.withdrawal(15.0).unwrap();
+        assert_eq!(a1.available_amount, -5.0);
+        assert_eq!(a1.total_amount, -5.0);
+        assert_eq!(a1.credit_limit, 5.0);
+
+        assert!(a1.withdrawal(0.5).is_err());
+    }
+
     #[test]
     fn account_dispute() {
         let mut a = Account {
@@ -282,25 +635,19 @@ mod tests {
             held_amount: 5.0,
             total_amount: 15.0,
             is_locked: false,
+            ..Default::default()
         };
-        let mut history = HashMap::<u32, Transaction>::new();
-        history.insert(
-            1,
-            Transaction {
-                tx_type: TxType::Deposit,
-                client_id: 1,
-                tx_id: 1,
-                amount: 10.0,
-                in_dispute: false,
-            },
-        );
-        let a1 = a.dispute(1, &mut history).unwrap();
+        let disputed = testing::tx(TxType::Deposit, 1, 1, 10.0);
+        let outcome = a.dispute(1, Some(&disputed)).unwrap();
+        assert_eq!(outcome.history_update, Some((1, DisputeState::Open)));
+        let a1 = outcome.new_account;
         a = Account {
             client_id: 1,
             available_amount: 0.0,
             held_amount: 15.0,
             total_amount: 15.0,
             is_locked: false,
+            ..Default::default()
         };
 
         assert_eq!(a, a1);
@@ -314,25 +661,19 @@ mod tests {
             held_amount: 15.0,
             total_amount: 15.0,
             is_locked: false,
+            ..Default::default()
         };
-        let mut history = HashMap::<u32, Transaction>::new();
-        history.insert(
-            1,
-            Transaction {
-                tx_type: TxType::Deposit,
-                client_id: 1,
-                tx_id: 1,
-                amount: 10.0,
-                in_dispute: true,
-            },
-        );
-        let a1 = a.resolve(1, &mut history).unwrap();
+        let disputed = Transaction { dispute: DisputeState::Open, ..testing::tx(TxType::Deposit, 1, 1, 10.0) };
+        let outcome = a.resolve(1, Some(&disputed)).unwrap();
+        assert_eq!(outcome.history_update, Some((1, DisputeState::Resolved)));
+        let a1 = outcome.new_account;
         a = Account {
             client_id: 1,
             available_amount: 10.0,
             held_amount: 5.0,
             total_amount: 15.0,
             is_locked: false,
+            ..Default::default()
         };
 
         assert_eq!(a, a1);
@@ -346,27 +687,164 @@ mod tests {
             held_amount: 15.0,
             total_amount: 25.0,
             is_locked: false,
+            ..Default::default()
         };
-        let mut history = HashMap::<u32, Transaction>::new();
-        history.insert(
-            1,
-            Transaction {
-                tx_type: TxType::Deposit,
-                client_id: 1,
-                tx_id: 1,
-                amount: 10.0,
-                in_dispute: true,
-            },
-        );
-        let a1 = a.chargeback(1, &mut history).unwrap();
+        let disputed = Transaction { dispute: DisputeState::Open, ..testing::tx(TxType::Deposit, 1, 1, 10.0) };
+        let outcome = a.chargeback(1, Some(&disputed)).unwrap();
+        assert_eq!(outcome.history_update, Some((1, DisputeState::ChargedBack)));
+        let a1 = outcome.new_account;
         a = Account {
             client_id: 1,
             available_amount: 10.0,
             held_amount: 5.0,
             total_amount: 15.0,
             is_locked: true,
+            ..Default::default()
         };
 
         assert_eq!(a, a1);
     }
+
+    #[test]
+    fn account_dispute_transitions() {
+        use crate::account::AccountError;
+
+        let a = Account { client_id: 1, available_amount: 10.0, total_amount: 10.0, ..Default::default() };
+        let deposit = |dispute| Transaction { dispute, ..testing::tx(TxType::Deposit, 1, 1, 10.0) };
+
+        assert!(a.dispute(1, Some(&deposit(DisputeState::None))).is_ok());
+        // resolved transaction can be disputed again
+        assert!(a.dispute(1, Some(&deposit(DisputeState::Resolved))).is_ok());
+        assert_eq!(
+            a.dispute(1, Some(&deposit(DisputeState::Open))),
+            Err(AccountError::InvalidDisputeTransition(1, DisputeState::Open))
+        );
+        assert_eq!(
+            a.dispute(1, Some(&deposit(DisputeState::ChargedBack))),
+            Err(AccountError::InvalidDisputeTransition(1, DisputeState::ChargedBack))
+        );
+        assert_eq!(a.resolve(1, Some(&deposit(DisputeState::Resolved))), Err(AccountError::TxNotInDispute(1)));
+        assert_eq!(a.chargeback(1, Some(&deposit(DisputeState::ChargedBack))), Err(AccountError::TxNotInDispute(1)));
+    }
+
+    #[test]
+    fn account_outcome_applied_to_ledger() {
+        use crate::{account::AccountOutcome, config::Config};
+
+        let deposit = testing::tx(TxType::Deposit, 1, 1, 10.0);
+        let mut ledger = InMemoryLedger::default();
+        ledger.record_tx(deposit.clone()).unwrap();
+        let a = Account { client_id: 1, available_amount: 10.0, total_amount: 10.0, ..Default::default() };
+
+        let dispute = Transaction { tx_type: TxType::Dispute, amount: 0.0, ..deposit };
+        let outcome = a.process_transaction(&dispute, ledger.transaction(1).as_ref(), &Config::default()).unwrap();
+        // the ledger is changed only when the outcome is applied
+        assert_eq!(ledger.transaction(1).unwrap().dispute, DisputeState::None);
+        let a1 = outcome.apply(&mut ledger).unwrap();
+        assert_eq!(ledger.transaction(1).unwrap().dispute, DisputeState::Open);
+        assert_eq!(a1.held_amount, 10.0);
+
+        let resolve = Transaction { tx_type: TxType::Resolve, ..dispute };
+        let outcome = a1.process_transaction(&resolve, ledger.transaction(1).as_ref(), &Config::default()).unwrap();
+        let a2 = outcome.apply(&mut ledger).unwrap();
+        assert_eq!(ledger.transaction(1).unwrap().dispute, DisputeState::Resolved);
+        assert_eq!(a2.available_amount, 10.0);
+
+        // update of a transaction missing in the ledger is ignored
+        let outcome = AccountOutcome { new_account: a2.clone(), history_update: Some((2, DisputeState::Open)) };
+        assert_eq!(outcome.apply(&mut ledger), Ok(a2));
+        assert_eq!(ledger.tx_count(), 1);
+    }
+
+    #[test]
+    fn account_close() {
+        use crate::{account::AccountError, config::{ClosePolicy, Config}};
+
+        let a = Account {
+            client_id: 1,
+            total_amount: 5.0,
+            available_amount: 5.0,
+            ..Default::default()
+        };
+        let close = testing::tx(TxType::Close, 1, 0, 0.0);
+        assert_eq!(
+            a.process_transaction(&close, None, &Config::default()),
+            Err(AccountError::AccountNotEmpty(1, 5.0, 0.0))
+        );
+
+        let a1 = a.process_transaction(&close, None, &Config { close_policy: ClosePolicy::Sweep, ..Default::default() }).unwrap().new_account;
+        assert!(a1.is_closed);
+        assert_eq!(a1.total_amount, 0.0);
+
+        let deposit = Transaction { tx_type: TxType::Deposit, amount: 1.0, ..close.clone() };
+        assert!(matches!(
+            a1.process_transaction(&deposit, None, &Config::default()),
+            Err(AccountError::AccountClosed(1))
+        ));
+
+        let open = Transaction { tx_type: TxType::Open, ..close };
+        let a2 = a1.process_transaction(&open, None, &Config::default()).unwrap().new_account;
+        assert!(!a2.is_closed);
+        assert!(a2.process_transaction(&deposit, None, &Config::default()).is_ok());
+    }
+
+    #[test]
+    fn account_two_phase_withdrawal() {
+        use crate::{
+            account::AccountError,
+            config::Config,
+            settlement::SettlementConfig,
+        };
+
+        let config = Config { settlement: Some(SettlementConfig::default()), ..Default::default() };
+        let a = Account { client_id: 1, available_amount: 10.0, total_amount: 10.0, ..Default::default() };
+        let withdrawal = testing::tx(TxType::Withdrawal, 1, 1, 4.0);
+        let a1 = a.process_transaction(&withdrawal, None, &config).unwrap().new_account;
+        assert_eq!((a1.available_amount, a1.pending_amount, a1.total_amount), (6.0, 4.0, 10.0));
+
+        // pending withdrawal prevents closing
+        let close = Transaction { tx_type: TxType::Close, tx_id: 0, amount: 0.0, ..withdrawal.clone() };
+        assert_eq!(a1.process_transaction(&close, None, &config), Err(AccountError::AccountNotEmpty(1, 6.0, 0.0)));
+
+        let settle = Transaction { tx_type: TxType::Settle, amount: 0.0, ..withdrawal.clone() };
+        let a2 = a1.process_transaction(&settle, Some(&withdrawal), &config).unwrap().new_account;
+        assert_eq!((a2.available_amount, a2.pending_amount, a2.total_amount), (6.0, 0.0, 6.0));
+
+        // without settlement configured withdrawal leaves the account right away
+        let a3 = a.process_transaction(&withdrawal, None, &Config::default()).unwrap().new_account;
+        assert_eq!((a3.available_amount, a3.pending_amount, a3.total_amount), (6.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn account_error_codes() {
+        use crate::account::AccountError;
+
+        let errors = [
+            AccountError::InssuficientFundsForWithdrawal(1, 5.0, 1.0),
+            AccountError::Frozen(1),
+            AccountError::PreFrozen(1),
+            AccountError::NoTxForDispute(1),
+            AccountError::TxNotInDispute(1),
+            AccountError::AmountLimitExceeded(1, 1.0, 2.0),
+            AccountError::DailyLimitExceeded(1, 1.0, 2.0),
+            AccountError::AccountClosed(1),
+            AccountError::AccountNotEmpty(1, 1.0, 0.0),
+            AccountError::RejectedByRule(1, "rule".to_string()),
+            AccountError::RejectedByScript(1, "script".to_string()),
+            AccountError::TxInDispute(1),
+            AccountError::TxPending(1),
+            AccountError::TxNotPending(1),
+            AccountError::NotRefundable(1),
+            AccountError::RefundOverWithdrawal(1, 2.0, 1.0),
+            AccountError::TxRefunded(1),
+            AccountError::InvalidDisputeTransition(1, DisputeState::ChargedBack),
+            AccountError::ZeroAmount(1),
+            AccountError::Quarantined(1),
+            AccountError::MissingReason(1),
+            AccountError::Storage("closed".to_string()),
+        ];
+        let codes: std::collections::HashSet<_> = errors.iter().map(|e| e.code()).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(errors[0].to_string(), "E1001 insufficient funds on account 1: withdrawal of 5.0000, available 1.0000");
+    }
 }
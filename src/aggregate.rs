@@ -0,0 +1,128 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{AccountKey, ClientId, Money, Result, TenantId, Transaction, TxType};
+
+/// Receives every applied transaction from the account tasks of `TxProcessor`, so statistics of the input are
+/// computed in the same pass as the balances. Account tasks run in parallel, implementations synchronize themselves.
+pub trait Aggregator: fmt::Debug + Send + Sync {
+    /// called with every applied transaction, rejected and skipped ones are not recorded
+    fn record(&self, t: &Transaction);
+}
+
+/// upper bounds (inclusive) of the buckets of the amount histogram, larger amounts go to one more bucket
+pub const HISTOGRAM_BOUNDS: [Money; 6] = [1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0];
+
+/// Number and sum of amounts of applied transactions
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Totals {
+    pub count: u64,
+    /// sum of the amounts, only deposits and withdrawals carry amount
+    pub amount: Money,
+}
+
+impl Totals {
+    fn add(&mut self, amount: Money) {
+        self.count += 1;
+        self.amount += amount;
+    }
+}
+
+/// Deposits and withdrawals of a single client
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ClientAggregates {
+    pub tenant: Option<TenantId>,
+    pub client: ClientId,
+    pub deposits: Totals,
+    pub withdrawals: Totals,
+}
+
+/// Aggregates of the run collected by `Aggregates`
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct AggregateReport {
+    /// applied transactions of all clients by type name, e.g. `deposit`
+    pub by_type: BTreeMap<String, Totals>,
+    /// clients with at least one applied deposit or withdrawal, ordered by tenant and client id
+    pub clients: Vec<ClientAggregates>,
+    /// number of applied deposits and withdrawals in every bucket of `HISTOGRAM_BOUNDS`
+    pub histogram: Vec<u64>,
+}
+
+impl AggregateReport {
+    /// writes the aggregates as pretty printed JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    by_type: BTreeMap<String, Totals>,
+    clients: HashMap<AccountKey, (Totals, Totals)>,
+    histogram: [u64; HISTOGRAM_BOUNDS.len() + 1],
+}
+
+/// Built-in aggregator: global sums and counts by type, per client sums of deposits and withdrawals
+/// and a histogram of their amounts
+#[derive(Debug, Default)]
+pub struct Aggregates {
+    state: Mutex<State>,
+}
+
+impl Aggregator for Aggregates {
+    fn record(&self, t: &Transaction) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let moves_funds = matches!(t.tx_type, TxType::Deposit | TxType::Withdrawal);
+        let amount = if moves_funds { t.amount } else { 0.0 };
+        state.by_type.entry(t.tx_type.to_string()).or_default().add(amount);
+        if !moves_funds {
+            return;
+        }
+        let client = state.clients.entry(t.account_key()).or_default();
+        match t.tx_type {
+            TxType::Deposit => client.0.add(amount),
+            _ => client.1.add(amount),
+        }
+        let bucket = HISTOGRAM_BOUNDS.iter().position(|b| amount <= *b).unwrap_or(HISTOGRAM_BOUNDS.len());
+        state.histogram[bucket] += 1;
+    }
+}
+
+impl Aggregates {
+    /// aggregates recorded so far
+    pub fn report(&self) -> AggregateReport {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut clients: Vec<_> = state
+            .clients
+            .iter()
+            .map(|((tenant, client), (deposits, withdrawals))| ClientAggregates {
+                tenant: tenant.clone(),
+                client: *client,
+                deposits: *deposits,
+                withdrawals: *withdrawals,
+            })
+            .collect();
+        clients.sort_by(|a, b| (&a.tenant, a.client).cmp(&(&b.tenant, b.client)));
+        AggregateReport {
+            by_type: state.by_type.clone(),
+            clients,
+            histogram: state.histogram.to_vec(),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{io::Write, sync::Arc};
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt16Array, UInt32Array, UInt64Array};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+use crate::{
+    csv::RawAccount,
+    events::{Outcome, ProcessingEvent},
+    history::HistoryEntry,
+    tx::AccountReport,
+    ClientId, Money, Result, Transaction, TxId,
+};
+
+/// rows of the decision trace buffered before they are written as one record batch
+pub const TRACE_BATCH_ROWS: usize = 8192;
+
+/// Schema of the accounts file, columns of the CSV output with the tenant (null for the default one)
+pub fn accounts_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("client", DataType::UInt16, false),
+        Field::new("available", DataType::Float64, false),
+        Field::new("held", DataType::Float64, false),
+        Field::new("total", DataType::Float64, false),
+        Field::new("locked", DataType::Boolean, false),
+        Field::new("status", DataType::Utf8, false),
+    ]))
+}
+
+/// Schema of the decision trace file, columns of `output::DECISIONS_HEADER`; balances are null for skipped rows
+pub fn decisions_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("client", DataType::UInt16, false),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("tx", DataType::UInt32, false),
+        Field::new("amount", DataType::Float64, false),
+        Field::new("outcome", DataType::Utf8, false),
+        Field::new("reason", DataType::Utf8, true),
+        Field::new("available", DataType::Float64, true),
+        Field::new("held", DataType::Float64, true),
+        Field::new("total", DataType::Float64, true),
+        Field::new("locked", DataType::Boolean, true),
+    ]))
+}
+
+/// Schema of the history table, columns of `history::HISTORY_HEADER`
+pub fn history_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("client", DataType::UInt16, false),
+        Field::new("seq", DataType::UInt64, false),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("tx", DataType::UInt32, false),
+        Field::new("amount", DataType::Float64, false),
+        Field::new("outcome", DataType::Utf8, false),
+        Field::new("reason", DataType::Utf8, true),
+        Field::new("available", DataType::Float64, false),
+        Field::new("held", DataType::Float64, false),
+        Field::new("total", DataType::Float64, false),
+        Field::new("locked", DataType::Boolean, false),
+        Field::new("dispute", DataType::Utf8, true),
+    ]))
+}
+
+/// Writes all accounts as a single record batch of Arrow IPC file (Feather v2), see `accounts_batch`
+pub fn write_accounts<W: Write>(w: W, accounts: &[RawAccount], precision: usize) -> Result<()> {
+    let schema = accounts_schema();
+    let mut writer = FileWriter::try_new(w, &schema)?;
+    writer.write(&accounts_batch(accounts, precision)?)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Record batch of the accounts, amounts rounded to `precision` decimal places like in the CSV output
+pub fn accounts_batch(accounts: &[RawAccount], precision: usize) -> Result<RecordBatch> {
+    let amounts = |amount: fn(&RawAccount) -> Money| -> ArrayRef {
+        Arc::new(accounts.iter().map(|a| Some(round(amount(a), precision))).collect::<Float64Array>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(accounts.iter().map(|a| a.tenant.as_deref()).collect::<StringArray>()),
+        Arc::new(accounts.iter().map(|a| Some(a.client_id)).collect::<UInt16Array>()),
+        amounts(|a| a.available_amount),
+        amounts(|a| a.held_amount),
+        amounts(|a| a.total_amount),
+        Arc::new(accounts.iter().map(|a| Some(a.is_locked)).collect::<BooleanArray>()),
+        Arc::new(accounts.iter().map(|a| Some(a.status())).collect::<StringArray>()),
+    ];
+    Ok(RecordBatch::try_new(accounts_schema(), columns)?)
+}
+
+/// Record batch of the retained history of every account (see `history::write_history`), amounts
+/// with 4 decimal places like in the history file
+pub fn history_batch(reports: &[AccountReport]) -> Result<RecordBatch> {
+    let rows: Vec<_> = reports
+        .iter()
+        .flat_map(|r| r.history.iter().enumerate().map(move |(seq, entry)| (&r.account, seq as u64, entry)))
+        .collect();
+    let amounts = |amount: fn(&HistoryEntry) -> Money| -> ArrayRef {
+        Arc::new(rows.iter().map(|(_, _, e)| Some(round(amount(e), 4))).collect::<Float64Array>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(rows.iter().map(|(a, _, _)| a.tenant.as_deref()).collect::<StringArray>()),
+        Arc::new(rows.iter().map(|(a, _, _)| Some(a.client_id)).collect::<UInt16Array>()),
+        Arc::new(rows.iter().map(|(_, seq, _)| Some(*seq)).collect::<UInt64Array>()),
+        Arc::new(rows.iter().map(|(_, _, e)| Some(e.transaction.tx_type.to_string())).collect::<StringArray>()),
+        Arc::new(rows.iter().map(|(_, _, e)| Some(e.transaction.tx_id)).collect::<UInt32Array>()),
+        amounts(|e| e.transaction.amount),
+        Arc::new(
+            rows.iter()
+                .map(|(_, _, e)| Some(if e.is_applied() { "applied" } else { "rejected" }))
+                .collect::<StringArray>(),
+        ),
+        Arc::new(rows.iter().map(|(_, _, e)| e.rejection.as_deref()).collect::<StringArray>()),
+        amounts(|e| e.available_amount),
+        amounts(|e| e.held_amount),
+        amounts(|e| e.total_amount),
+        Arc::new(rows.iter().map(|(_, _, e)| Some(e.is_locked)).collect::<BooleanArray>()),
+        Arc::new(rows.iter().map(|(_, _, e)| e.dispute.map(|s| s.to_string())).collect::<StringArray>()),
+    ];
+    Ok(RecordBatch::try_new(history_schema(), columns)?)
+}
+
+// row of the decision trace
+struct Decision {
+    tenant: Option<String>,
+    client: ClientId,
+    tx_type: String,
+    tx: TxId,
+    amount: Money,
+    outcome: String,
+    reason: Option<String>,
+    // available, held, total and locked after the transaction, `None` when it was skipped
+    balances: Option<(Money, Money, Money, bool)>,
+}
+
+/// Decision trace written as Arrow IPC file in record batches of `TRACE_BATCH_ROWS` rows,
+/// the same rows as `output::write_decision` and `output::write_skipped` write to the CSV trace
+pub struct ArrowTrace<W: Write> {
+    writer: FileWriter<W>,
+    rows: Vec<Decision>,
+}
+
+impl<W: Write> ArrowTrace<W> {
+    pub fn new(w: W) -> Result<Self> {
+        Ok(ArrowTrace { writer: FileWriter::try_new(w, &decisions_schema())?, rows: Vec::new() })
+    }
+
+    /// row of the processed transaction with the account balances right after it
+    pub fn decision(&mut self, e: &ProcessingEvent) -> Result<()> {
+        let a = &e.account;
+        self.push(Decision {
+            tenant: a.tenant.clone(),
+            client: a.client_id,
+            tx_type: e.tx_type.to_string(),
+            tx: e.tx_id,
+            amount: e.amount,
+            outcome: e.outcome.to_string(),
+            reason: e.rejection.clone(),
+            balances: Some((a.available_amount, a.held_amount, a.total_amount, a.is_locked)),
+        })
+    }
+
+    /// row of the transaction skipped before it reached the account
+    pub fn skipped(&mut self, t: &Transaction, reason: &str) -> Result<()> {
+        self.push(Decision {
+            tenant: t.tenant.clone(),
+            client: t.client_id,
+            tx_type: t.tx_type.to_string(),
+            tx: t.tx_id,
+            amount: t.amount,
+            outcome: Outcome::Skipped.to_string(),
+            reason: Some(reason.to_string()),
+            balances: None,
+        })
+    }
+
+    /// writes the buffered rows and the footer of the file
+    pub fn finish(mut self) -> Result<()> {
+        self.write_batch()?;
+        self.writer.finish()?;
+        Ok(())
+    }
+
+    fn push(&mut self, row: Decision) -> Result<()> {
+        self.rows.push(row);
+        match self.rows.len() >= TRACE_BATCH_ROWS {
+            true => self.write_batch(),
+            false => Ok(()),
+        }
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let balance = |balance: fn(&(Money, Money, Money, bool)) -> Money| -> ArrayRef {
+            Arc::new(rows.iter().map(|r| r.balances.as_ref().map(|b| round(balance(b), 4))).collect::<Float64Array>())
+        };
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(rows.iter().map(|r| r.tenant.as_deref()).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| Some(r.client)).collect::<UInt16Array>()),
+            Arc::new(rows.iter().map(|r| Some(r.tx_type.as_str())).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| Some(r.tx)).collect::<UInt32Array>()),
+            Arc::new(rows.iter().map(|r| Some(round(r.amount, 4))).collect::<Float64Array>()),
+            Arc::new(rows.iter().map(|r| Some(r.outcome.as_str())).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| r.reason.as_deref()).collect::<StringArray>()),
+            balance(|b| b.0),
+            balance(|b| b.1),
+            balance(|b| b.2),
+            Arc::new(rows.iter().map(|r| r.balances.map(|b| b.3)).collect::<BooleanArray>()),
+        ];
+        self.writer.write(&RecordBatch::try_new(decisions_schema(), columns)?)?;
+        Ok(())
+    }
+}
+
+// value of the amount as written with `precision` decimal places, so `0.1f32` is `0.1` and not `0.10000000149011612`
+fn round(amount: Money, precision: usize) -> f64 {
+    format!("{:.*}", precision, amount).parse().unwrap_or(f64::from(amount))
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::{
+    crypto::{self, EncryptionKey},
+    events::ProcessingEvent,
+    manifest::to_hex,
+    ClientId, Money, Result, TenantId, TxId,
+};
+
+/// `prev` of the first entry of the log
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Single processing decision recorded in the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// position of the entry in the log, starting with 1
+    pub seq: u64,
+    pub line: Option<u64>,
+    pub tenant: Option<TenantId>,
+    pub client: ClientId,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub tx: TxId,
+    pub amount: Money,
+    pub outcome: String,
+    /// stable code of the error which rejected the transaction, see `AccountError::code`
+    pub code: Option<String>,
+    pub reason: Option<String>,
+    /// columns of the input row kept with the transaction, ordered so the hash does not depend on the order
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// `hash` of the previous entry, `GENESIS` for the first one
+    pub prev: String,
+}
+
+/// Line of the audit log: entry with its SHA-256 (and HMAC when the log is keyed),
+/// both computed over the JSON of the entry, which includes the hash of the previous entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct AuditLine {
+    entry: AuditEntry,
+    hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hmac: Option<String>,
+}
+
+/// Append-only, hash-chained log of processing decisions. Changing, removing or reordering
+/// any entry breaks the chain, which is detected by `verify`.
+pub struct AuditLog {
+    w: BufWriter<File>,
+    key: Option<Vec<u8>>,
+    encryption: Option<EncryptionKey>,
+    prev: String,
+    seq: u64,
+}
+
+impl AuditLog {
+    /// Opens the log for appending, continuing the chain of the entries already in the file.
+    /// `key` enables HMAC of every entry, so the chain can not be recomputed without it,
+    /// `encryption` encrypts every line (the existing ones are decrypted with it).
+    pub fn open(path: &Path, key: Option<Vec<u8>>, encryption: Option<EncryptionKey>) -> Result<AuditLog> {
+        let (mut prev, mut seq) = (GENESIS.to_string(), 0);
+        if path.exists() {
+            for l in BufReader::new(File::open(path)?).lines() {
+                let l = l?;
+                if l.trim().is_empty() {
+                    continue;
+                }
+                let line: AuditLine = serde_json::from_str(&crypto::decrypt_line(encryption.as_ref(), &l)?)?;
+                prev = line.hash;
+                seq = line.entry.seq;
+            }
+        }
+        debug!("audit log {:?} continues after entry {}", path, seq);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog { w: BufWriter::new(file), key, encryption, prev, seq })
+    }
+
+    /// appends entry for the processed transaction
+    pub fn append(&mut self, e: &ProcessingEvent) -> Result<()> {
+        self.seq += 1;
+        let entry = AuditEntry {
+            seq: self.seq,
+            line: e.line,
+            tenant: e.account.tenant.clone(),
+            client: e.account.client_id,
+            tx_type: e.tx_type.to_string(),
+            tx: e.tx_id,
+            amount: e.amount,
+            outcome: e.outcome.to_string(),
+            code: e.error.as_ref().map(|e| e.code().to_string()),
+            reason: e.rejection.clone(),
+            metadata: e.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            prev: self.prev.clone(),
+        };
+        let json = serde_json::to_string(&entry)?;
+        let line = AuditLine {
+            hash: sha256(&json),
+            hmac: self.key.as_deref().map(|key| hmac(key, &json)).transpose()?,
+            entry,
+        };
+        let json = serde_json::to_string(&line)?;
+        match &self.encryption {
+            Some(encryption) => writeln!(self.w, "{}", crypto::encrypt_line(encryption, &json)?)?,
+            None => writeln!(self.w, "{}", json)?,
+        }
+        self.prev = line.hash;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.w.flush()?;
+        Ok(())
+    }
+}
+
+/// Checks integrity of the whole log and returns the number of entries.
+/// With `key` HMAC of every entry is checked as well, entries without HMAC are then rejected.
+/// Encrypted lines are decrypted with `encryption`.
+pub fn verify(path: &Path, key: Option<&[u8]>, encryption: Option<&EncryptionKey>) -> Result<u64> {
+    let mut prev = GENESIS.to_string();
+    let mut count = 0;
+    for (i, l) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let l = l?;
+        if l.trim().is_empty() {
+            continue;
+        }
+        let n = i + 1;
+        let l = crypto::decrypt_line(encryption, &l).map_err(|e| format!("line {}: {}", n, e))?;
+        let line: AuditLine = serde_json::from_str(&l).map_err(|e| format!("line {}: invalid entry: {}", n, e))?;
+        count += 1;
+        if line.entry.seq != count {
+            return Err(format!("line {}: entry {} found where {} was expected", n, line.entry.seq, count).into());
+        }
+        if line.entry.prev != prev {
+            return Err(format!("line {}: entry {} does not follow the previous entry", n, count).into());
+        }
+        let json = serde_json::to_string(&line.entry)?;
+        if sha256(&json) != line.hash {
+            return Err(format!("line {}: hash of entry {} does not match its content", n, count).into());
+        }
+        if let Some(key) = key {
+            match &line.hmac {
+                Some(h) if *h == hmac(key, &json)? => (),
+                Some(_) => return Err(format!("line {}: HMAC of entry {} does not match", n, count).into()),
+                None => return Err(format!("line {}: entry {} has no HMAC", n, count).into()),
+            }
+        }
+        prev = line.hash;
+    }
+    Ok(count)
+}
+
+/// reads HMAC key from `path`, trailing newline is not part of the key
+pub fn load_key(path: &Path) -> Result<Vec<u8>> {
+    let mut key = std::fs::read(path)?;
+    while key.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+        key.pop();
+    }
+    if key.is_empty() {
+        return Err(format!("audit key file {:?} is empty", path).into());
+    }
+    Ok(key)
+}
+
+fn sha256(json: &str) -> String {
+    to_hex(&Sha256::digest(json.as_bytes()))
+}
+
+fn hmac(key: &[u8], json: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|e| format!("invalid audit key: {}", e))?;
+    mac.update(json.as_bytes());
+    Ok(to_hex(&mac.finalize().into_bytes()))
+}
@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use futures::StreamExt;
+use tokio::fs::File;
+use tracing::debug;
+
+use crate::{
+    account::AccountError,
+    audit::AuditLog,
+    csv::RawAccount,
+    engine::{self, Engine},
+    events::{Outcome, ProcessingEvent},
+    AccountKey, ClientId, Money, Result, TenantId, Transaction, TxId, TxType,
+};
+
+/// Header of the delta report written by `write_delta`
+pub const DELTA_HEADER: &str =
+    "tenant,client,available_before,available_after,held_before,held_after,total_before,total_after,locked_before,locked_after";
+
+/// Single row of the correction file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Correction {
+    /// any transaction of the input format, applied as if it was part of the input
+    Transaction(Transaction),
+    /// `unlock` row, unlocks account frozen by a chargeback or before its first transaction
+    Unlock(AccountKey),
+    /// `reversal` row, reverses applied deposit or withdrawal `tx` of the client
+    Reversal(AccountKey, TxId),
+    /// `adjust` row, credits (positive amount) or debits (negative amount) the account for the `reason`, see `Engine::adjust`
+    Adjustment { key: AccountKey, tx_id: TxId, amount: Money, reason: String },
+}
+
+// row of the correction file: columns of the input file, `type` also accepts `unlock`, `reversal` and `adjust`
+#[derive(Deserialize, Debug)]
+struct CorrectionRow {
+    #[serde(rename = "type")]
+    tx_type: String,
+    client: ClientId,
+    #[serde(default)]
+    tx: Option<TxId>,
+    #[serde(default)]
+    amount: Option<String>,
+    #[serde(default)]
+    tenant: Option<TenantId>,
+    // reason of the adjustment
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Correction which was rejected by the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    /// line of the correction file
+    pub line: u64,
+    pub correction: Correction,
+    pub error: AccountError,
+}
+
+/// Reads correction file, every row has `type,client,tx,amount` (and optional `tenant` and `reason`) columns
+pub async fn read_corrections(path: &Path) -> Result<Vec<(u64, Correction)>> {
+    let file = File::open(path).await.map_err(|e| format!("failed opening correction file {:?}: {}", path, e))?;
+    let mut rdr = csv_async::AsyncReaderBuilder::new()
+        .flexible(true)
+        .trim(csv_async::Trim::All)
+        .create_deserializer(file);
+    let mut records = rdr.deserialize_with_pos::<CorrectionRow>();
+    let mut corrections = Vec::new();
+    while let Some((row, pos)) = records.next().await {
+        let line = pos.line();
+        let row = row.map_err(|e| format!("{:?} line {}: {}", path, line, e))?;
+        let correction = row.into_correction().map_err(|e| format!("{:?} line {}: {}", path, line, e))?;
+        corrections.push((line, correction));
+    }
+    debug!("read {} corrections from {:?}", corrections.len(), path);
+    Ok(corrections)
+}
+
+impl CorrectionRow {
+    fn into_correction(self) -> std::result::Result<Correction, String> {
+        let key = (self.tenant.clone(), self.client);
+        let tx = || self.tx.ok_or_else(|| format!("missing tx of {} correction", self.tx_type));
+        match self.tx_type.trim().to_lowercase().as_str() {
+            "unlock" => Ok(Correction::Unlock(key)),
+            "reversal" => Ok(Correction::Reversal(key, tx()?)),
+            "adjust" => {
+                let amount = match &self.amount {
+                    Some(amount) => amount.parse::<f32>().map_err(|_| format!("invalid amount '{}'", amount))?,
+                    None => return Err("missing amount of adjust".to_string()),
+                };
+                let reason = self.reason.clone().unwrap_or_default();
+                Ok(Correction::Adjustment { key, tx_id: tx()?, amount, reason })
+            }
+            other => {
+                let tx_type: TxType = other.parse()?;
+                let amount = match (&tx_type, &self.amount) {
+                    (TxType::Deposit | TxType::Withdrawal | TxType::Refund, Some(amount)) => match amount.parse::<f32>() {
+                        Ok(value) if value >= 0.0 => value,
+                        _ => return Err(format!("invalid amount '{}'", amount)),
+                    },
+                    (TxType::Deposit | TxType::Withdrawal, None) => return Err(format!("missing amount of {}", tx_type)),
+                    _ => 0.0,
+                };
+                Ok(Correction::Transaction(Transaction {
+                    tx_type,
+                    client_id: self.client,
+                    tx_id: tx()?,
+                    amount,
+                    dispute: Default::default(),
+                    tenant: self.tenant,
+                    timestamp: None,
+                    idempotency_key: None,
+                    seq: None,
+                    line: None,
+                    metadata: Default::default(),
+                }))
+            }
+        }
+    }
+}
+
+/// Applies corrections in order and returns the ones rejected by the accounts, the others stay applied
+pub fn apply(engine: &mut Engine, corrections: Vec<(u64, Correction)>) -> Vec<Rejected> {
+    let mut rejected = Vec::new();
+    for (line, correction) in corrections {
+        let r = match &correction {
+            Correction::Transaction(t) => engine.apply(t.clone()).map(|_| ()),
+            Correction::Unlock(key) => {
+                engine.unlock(key);
+                Ok(())
+            }
+            Correction::Reversal(key, tx_id) => engine.reverse(key, *tx_id).map(|_| ()),
+            Correction::Adjustment { key, tx_id, amount, reason } => engine.adjust(key, *tx_id, *amount, reason).map(|_| ()),
+        };
+        if let Err(error) = r {
+            rejected.push(Rejected { line, correction, error });
+        }
+    }
+    rejected
+}
+
+/// Appends entry of every adjustment among the applied corrections to the audit log, with the reason in its metadata
+pub fn audit(log: &mut AuditLog, engine: &Engine, corrections: &[(u64, Correction)]) -> Result<()> {
+    for (line, correction) in corrections {
+        if let Correction::Adjustment { key, tx_id, amount, reason } = correction {
+            let t = engine::adjustment(key, *tx_id, *amount, reason);
+            let account = engine.account(key).ok_or_else(|| format!("no account for client {}", key.1))?;
+            log.append(&ProcessingEvent {
+                line: Some(*line),
+                tx_id: t.tx_id,
+                tx_type: t.tx_type,
+                amount: t.amount,
+                outcome: Outcome::Applied,
+                rejection: None,
+                error: None,
+                account,
+                metadata: t.metadata,
+            })?;
+        }
+    }
+    log.flush()
+}
+
+/// Writes accounts which differ between `before` and `after` with both states, accounts created by the corrections
+/// have zero balances before
+pub fn write_delta<W: Write>(w: &mut W, before: &[RawAccount], after: &[RawAccount], precision: usize) -> Result<()> {
+    let before: BTreeMap<_, _> = before.iter().map(|a| ((a.tenant.clone(), a.client_id), a)).collect();
+    writeln!(w, "{}", DELTA_HEADER)?;
+    for a in after {
+        let b = before.get(&(a.tenant.clone(), a.client_id));
+        if b.is_some_and(|b| *b == a) {
+            continue;
+        }
+        let (available, held, total, locked) =
+            b.map_or((0.0, 0.0, 0.0, false), |b| (b.available_amount, b.held_amount, b.total_amount, b.is_locked));
+        writeln!(
+            w,
+            "{},{},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{},{}",
+            a.tenant.as_deref().unwrap_or_default(),
+            a.client_id,
+            available,
+            a.available_amount,
+            held,
+            a.held_amount,
+            total,
+            a.total_amount,
+            locked,
+            a.is_locked,
+            p = precision
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
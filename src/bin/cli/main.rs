@@ -0,0 +1,1769 @@
+#![deny(warnings)]
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::{mpsc, watch},
+};
+
+use futures::{future, Future};
+use tracing::{debug, error, info, Level};
+
+mod repl;
+#[cfg(feature = "tui")]
+mod tui;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use txp::{
+    ClientId,
+    Money,
+    Timestamp,
+    Transaction,
+    TxType,
+    aggregate::Aggregates,
+    audit::{self, AuditLog},
+    backfill,
+    bisect::{self, BisectResult},
+    cdc::ChangeStream,
+    crypto::EncryptionKey,
+    clients::{ClientDirectory, ClientOverrides, ClientSample, ClientSet},
+    config::{self, Config, ConfigReceiver, ConfigWatcher},
+    csv::{CsvTransactionReader, ParseError, RawTransaction},
+    engine::Engine,
+    events::ProcessingEvent,
+    golden,
+    import,
+    lag::{AccountLag, LagMonitor},
+    pause::IngestControl,
+    manifest::{self, ConfigInfo, Filters, InputFile, RowCounts, RunManifest},
+    merge::{self, MergeOrder, Overlap},
+    ratelimit::RateLimiter,
+    resources::{ResourceGauges, ResourceUsage},
+    seen::SeenStore,
+    snapshot::{Snapshot, SNAPSHOT_VERSION},
+    stats::StatsReport,
+    summary::RunSummary,
+    timing::{RunTimings, StageTimings},
+    output::{self, ErrorRecord, FlushPolicy, FlushingWriter},
+    publish::{RotatingFiles, SnapshotEvery, SnapshotPublisher},
+    history::{self, HistoryFormat, HistoryRetention},
+    tx::{ProcessingOutcome, ProcessorOptions, RejectedRow, TxProcessor},
+    Result,
+};
+
+#[cfg(feature = "iso20022")]
+use txp::iso20022;
+#[cfg(feature = "xlsx")]
+use txp::xlsx;
+#[cfg(feature = "arrow")]
+use txp::arrow::{self, ArrowTrace};
+#[cfg(feature = "sql")]
+use txp::sql::{self, Query};
+
+use structopt::{StructOpt, clap::{arg_enum, AppSettings}};
+
+arg_enum! {
+    #[derive(Debug)]
+    enum TracingLevel {
+        Error,
+        Warn,
+        Info,
+        Debug,
+        Trace
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy)]
+    enum HistoryFileFormat {
+        Csv,
+        Jsonl
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum ErrorFormat {
+        Text,
+        Jsonl
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum SourceOrder {
+        Sequential,
+        Arrival,
+        Timestamp
+    }
+}
+
+impl From<SourceOrder> for MergeOrder {
+    fn from(o: SourceOrder) -> Self {
+        match o {
+            SourceOrder::Sequential => MergeOrder::Sequential,
+            SourceOrder::Arrival => MergeOrder::Arrival,
+            SourceOrder::Timestamp => MergeOrder::Timestamp,
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum RuntimeFlavor {
+        Single,
+        Multi
+    }
+}
+
+impl From<HistoryFileFormat> for HistoryFormat {
+    fn from(f: HistoryFileFormat) -> Self {
+        match f {
+            HistoryFileFormat::Csv => HistoryFormat::Csv,
+            HistoryFileFormat::Jsonl => HistoryFormat::Jsonl,
+        }
+    }
+}
+
+/// exit code of the run stopped by malformed input (`EX_DATAERR`) or by rejected transaction with `--strict-accounts`,
+/// other failures exit with 1
+const EXIT_PARSE_ERROR: i32 = 65;
+
+/// malformed rows skipped with `--skip-bad-lines` before the run is aborted, unless `--max-errors` says otherwise
+const DEFAULT_MAX_ERRORS: u64 = 1000;
+
+/// how often configuration file is checked for changes with `--watch-config`
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// how often resource usage is printed with `--resource-usage` while reading stdin
+const RESOURCE_USAGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// number of the slowest accounts printed by `--lag-watchdog`
+const LAG_WATCHDOG_ACCOUNTS: usize = 10;
+
+// counts allocated bytes for `--resource-usage`
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: txp::resources::CountingAllocator = txp::resources::CountingAllocator;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "txp", version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"), about = "Transaction Processing System")]
+#[structopt(setting = AppSettings::ArgRequiredElseHelp)]
+struct Opt {
+    /// Tracing level.
+    #[structopt(long, short, possible_values = &TracingLevel::variants(), case_insensitive = true)]
+    tracing: Option<TracingLevel>,
+
+    /// Tracing filter in `RUST_LOG` format, e.g. `warn,txp[account{client_id=42}]=trace`, replaces --tracing
+    #[structopt(long, parse(try_from_str = EnvFilter::try_new), conflicts_with = "tracing")]
+    log_filter: Option<EnvFilter>,
+
+    /// Size of the channel buffer
+    #[structopt(short, long, default_value="32")]
+    buffer: usize,
+
+    /// Number of transactions queued for an account which its disputes and chargebacks overtake, so a flood of
+    /// deposits does not delay freezing of the account; transactions with the same id keep their order
+    #[structopt(long, default_value="0")]
+    priority_window: usize,
+
+    /// Number of bytes of the accounts written to stdout buffered before they are written out
+    #[structopt(long, default_value = "65536")]
+    flush_bytes: usize,
+
+    /// Buffered accounts are also written out to stdout when this many milliseconds passed since the last write out,
+    /// so slow consumers see progress
+    #[structopt(long)]
+    flush_interval_ms: Option<u64>,
+
+    /// Runtime the run is executed on: `single` (current thread, no thread spawn overhead for small files)
+    /// or `multi` (worker thread per CPU core)
+    #[structopt(long, possible_values = &RuntimeFlavor::variants(), case_insensitive = true, default_value = "Multi")]
+    runtime: RuntimeFlavor,
+
+    /// Number of worker threads of the `multi` runtime, the number of CPU cores by default
+    #[structopt(long, parse(try_from_str = parse_workers))]
+    workers: Option<usize>,
+
+    /// Pin every worker thread of the `multi` runtime (and so the account tasks it runs) to its own CPU core
+    #[cfg(feature = "pin-workers")]
+    #[structopt(long)]
+    pin_workers: bool,
+
+    /// Tenant assigned to transactions which do not have a `tenant` column value
+    #[structopt(long)]
+    tenant: Option<String>,
+
+    /// Directory where per tenant account files and summary.csv are written, instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+
+    /// Directory where locked, negative balance and other accounts are written to separate files, instead of stdout
+    #[structopt(long, parse(from_os_str), conflicts_with = "output-dir")]
+    split_by_status: Option<PathBuf>,
+
+    /// Directory where a statement file with every applied transaction and running balance is written for each client
+    #[structopt(long, parse(from_os_str))]
+    statements: Option<PathBuf>,
+
+    /// File where full ordered operation history (applied and rejected transactions) of every account is written
+    #[structopt(long, parse(from_os_str))]
+    history: Option<PathBuf>,
+
+    /// Format of the history file
+    #[structopt(long, possible_values = &HistoryFileFormat::variants(), case_insensitive = true, default_value = "Csv")]
+    history_format: HistoryFileFormat,
+
+    /// File with one line per input row: outcome (applied, rejected or skipped), its reason and the resulting balances
+    #[structopt(long, parse(from_os_str))]
+    trace_decisions: Option<PathBuf>,
+
+    /// Arrow IPC (Feather) file with the rows of the decision trace, for loading into Polars or pandas
+    #[cfg(feature = "arrow")]
+    #[structopt(long, parse(from_os_str))]
+    arrow_trace: Option<PathBuf>,
+
+    /// Arrow IPC (Feather) file with the final accounts, written in addition to the CSV output
+    #[cfg(feature = "arrow")]
+    #[structopt(long, parse(from_os_str))]
+    arrow_output: Option<PathBuf>,
+
+    /// SQL query over `accounts` (final accounts) and `history` (history kept for --history or --statements) tables,
+    /// e.g. `SELECT client, total FROM accounts WHERE locked`; its result is printed instead of the accounts
+    #[cfg(feature = "sql")]
+    #[structopt(long)]
+    query: Option<String>,
+
+    /// Format of parse errors and rejections reported on stderr, `jsonl` writes one JSON object per error
+    /// (line, client, tx, code, message) in addition to the logs
+    #[structopt(long, possible_values = &ErrorFormat::variants(), case_insensitive = true, default_value = "Text")]
+    errors: ErrorFormat,
+
+    /// Malformed rows are reported and skipped instead of stopping the run, which still aborts (exit code 65)
+    /// once there are more than `--max-errors` of them
+    #[structopt(long)]
+    skip_bad_lines: bool,
+
+    /// Number of malformed rows skipped with `--skip-bad-lines` before the run is aborted [default: 1000]
+    #[structopt(long, requires = "skip-bad-lines")]
+    max_errors: Option<u64>,
+
+    /// Run is aborted (exit code 65) on the first transaction rejected by its account, after reporting it
+    #[structopt(long)]
+    strict_accounts: bool,
+
+    /// File where every rejected transaction is written together with the reason
+    #[structopt(long, parse(from_os_str))]
+    rejects: Option<PathBuf>,
+
+    /// File where accounts whose available or total funds went negative during the run are written,
+    /// with the transaction which first drove them negative
+    #[structopt(long, parse(from_os_str))]
+    negative_report: Option<PathBuf>,
+
+    /// File where accounts quarantined by the `[quarantine]` configuration are written for review
+    #[structopt(long, parse(from_os_str))]
+    quarantine_report: Option<PathBuf>,
+
+    /// File where transactions still in dispute at the end are written, with their age when timestamps are present
+    #[structopt(long, parse(from_os_str))]
+    open_disputes: Option<PathBuf>,
+
+    /// File where funds held by open disputes are written per client, bucketed by age (0-7, 8-30, 31-90, over 90 days)
+    #[structopt(long, parse(from_os_str))]
+    held_aging: Option<PathBuf>,
+
+    /// File where clients with chargebacks are written: count and amount, ratio to deposits and frozen state
+    #[structopt(long, parse(from_os_str))]
+    chargeback_report: Option<PathBuf>,
+
+    /// File where statistics computed in the same pass as the balances are written as JSON: count and sum
+    /// of applied transactions by type, per client deposits and withdrawals and a histogram of their amounts
+    #[structopt(long, parse(from_os_str))]
+    aggregates: Option<PathBuf>,
+
+    /// File where the change-data-capture stream is written as JSON lines: balances before and after every applied
+    /// transaction with its type (`op`), id and offset in the stream
+    #[structopt(long, parse(from_os_str))]
+    changes: Option<PathBuf>,
+
+    /// Publishes a snapshot of the accounts to the `--snapshot-dir` while processing, every given number of transactions
+    /// (e.g. `10000tx`) or time (e.g. `30s`, units `ms`, `s`, `m` and `h`), and once more at the end
+    #[structopt(long, requires = "snapshot-dir")]
+    snapshot_every: Option<SnapshotEvery>,
+
+    /// Directory where every published snapshot is written into its own file `snapshot-<seq>.csv`
+    #[structopt(long, parse(from_os_str), requires = "snapshot-every")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Publishes only the accounts changed since the previous snapshot, into `delta-<seq>.csv`
+    #[structopt(long, requires = "snapshot-every")]
+    snapshot_delta: bool,
+
+    /// Number of the latest snapshot files kept in the `--snapshot-dir`, older ones are removed
+    #[structopt(long, requires = "snapshot-every")]
+    snapshot_keep: Option<usize>,
+
+    /// Number of clients with the highest turnover and of the largest single deposits and withdrawals written
+    /// to the `--top-file`, kept in bounded memory however large the input is
+    #[structopt(long, requires = "top-file")]
+    top_report: Option<usize>,
+
+    /// File where the `--top-report` is written
+    #[structopt(long, parse(from_os_str), requires = "top-report")]
+    top_file: Option<PathBuf>,
+
+    /// File where disputes, resolves and chargebacks still waiting for their transaction at the end are written
+    #[structopt(long, parse(from_os_str))]
+    unresolved_disputes: Option<PathBuf>,
+
+    /// Accounts without any transaction for this many days are reported with `dormant` status (requires `timestamp` column)
+    #[structopt(long)]
+    dormant_days: Option<u64>,
+
+    /// Time (unix seconds) inactivity and age of open disputes are measured to, latest transaction timestamp by default
+    #[structopt(long)]
+    as_of: Option<Timestamp>,
+
+    /// File where dormant accounts are written
+    #[structopt(long, parse(from_os_str), requires = "dormant-days")]
+    dormant_report: Option<PathBuf>,
+
+    /// Print counts of processed, applied and rejected (per reason) transactions to stderr
+    #[structopt(long)]
+    summary: bool,
+
+    /// Print peak memory, account tasks, retained history entries and allocator stats (with `alloc-stats` feature)
+    /// to stderr at the end of the run, and every minute while reading stdin
+    #[structopt(long)]
+    resource_usage: bool,
+
+    /// While reading stdin, print the accounts with the longest queue wait to stderr every this many seconds
+    #[structopt(long)]
+    lag_watchdog: Option<u64>,
+
+    /// While reading stdin, SIGUSR1 pauses reading of the input and SIGUSR2 resumes it (unix only)
+    #[structopt(long)]
+    pause_signals: bool,
+
+    /// While reading stdin, SIGTERM stops reading the input after the current row, the rows already read have this
+    /// many seconds to be processed and written before the run fails (unix only)
+    #[structopt(long, default_value = "30")]
+    drain_timeout: u64,
+
+    /// Processes the input and writes the outputs and reports, without touching the state other runs and systems use:
+    /// the seen store is only read, the audit log, change stream and snapshots (`--save-state`, published, `backfill`,
+    /// `migrate-state`) are not written
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// File recording applied deposits and withdrawals, transactions found in it are not applied again when input is reprocessed;
+    /// it is committed together with the `--save-state` snapshot, which the next run continues from with `--initial-state`
+    #[structopt(long, parse(from_os_str), requires = "save-state")]
+    seen_store: Option<PathBuf>,
+
+    /// Number of the most recent applied transactions kept in the seen store
+    #[structopt(long, default_value = "1000000")]
+    seen_horizon: usize,
+
+    /// Configuration file (TOML) with processing rules
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Watch configuration file and apply changes to following transactions without restarting
+    #[structopt(long, requires = "config")]
+    watch_config: bool,
+
+    /// Per client settings (credit limit, max amount, frozen, vip) in CSV or TOML (`.toml` extension) file
+    #[structopt(long, parse(from_os_str))]
+    client_overrides: Option<PathBuf>,
+
+    /// CSV file with `client,tenant,name,segment,country` columns whose name, segment and country are appended
+    /// to the accounts, `--statements` and the client reports
+    #[structopt(long, parse(from_os_str))]
+    clients_meta: Option<PathBuf>,
+
+    /// CSV file with `client` (and optional `tenant`) column of accounts which start locked, their transactions are
+    /// rejected as `pre_frozen`
+    #[structopt(long, parse(from_os_str))]
+    frozen_list: Option<PathBuf>,
+
+    /// Render live dashboard while processing, results are printed after it is closed
+    #[cfg(feature = "tui")]
+    #[structopt(long)]
+    tui: bool,
+
+    /// Columns of the input named differently than in the input format (comma separated `expected=found` pairs),
+    /// e.g. `type=kind,client=customer_id`
+    #[structopt(long, use_delimiter = true, require_delimiter = true, parse(try_from_str = parse_column))]
+    columns: Vec<(String, String)>,
+
+    /// Keeps columns of the input which are not in the input format (e.g. merchant or reference) with every
+    /// transaction and appends them to the `--rejects` file, `--audit-log` entries and `--statements`
+    #[structopt(long)]
+    keep_columns: bool,
+
+    /// `account,client` CSV file mapping accounts (IBAN, account number or name) of imported `.xml` ISO 20022
+    /// and `.ofx`/`.qfx`/`.qif` statement input to clients, numeric account ids without mapping are used as client ids
+    #[structopt(long, parse(from_os_str))]
+    accounts: Option<PathBuf>,
+
+    /// Process only transactions of these clients, e.g. `7,42,100-200`, other rows are skipped right after parsing
+    #[structopt(long)]
+    clients: Option<ClientSet>,
+
+    /// Skip transactions of these types (comma separated), e.g. to see balances without chargebacks
+    #[structopt(long, use_delimiter = true, require_delimiter = true, conflicts_with = "only-types")]
+    exclude_types: Vec<TxType>,
+
+    /// Process only transactions of these types (comma separated), e.g. `deposit,withdrawal`
+    #[structopt(long, use_delimiter = true, require_delimiter = true)]
+    only_types: Vec<TxType>,
+
+    /// Share of clients (e.g. `0.01`) whose transactions are processed, for quick approximate runs;
+    /// sampled clients keep all their transactions
+    #[structopt(long, parse(try_from_str = parse_rate))]
+    sample: Option<f64>,
+
+    /// Seed selecting the sampled clients (0 by default), the same seed samples the same clients
+    #[structopt(long, requires = "sample")]
+    seed: Option<u64>,
+
+    /// Verifies SHA-256 of the input file(s) against `<file>.sha256` sidecar file, no results are written when it does not match
+    #[structopt(long)]
+    verify_checksum: bool,
+
+    /// File where the JSON manifest of the run (inputs with checksums, configuration, filters and counts) is written
+    #[structopt(long, parse(from_os_str))]
+    manifest: Option<PathBuf>,
+
+    /// File where the JSON report of the run (counters, errors summary, timings and resource usage) is written
+    #[structopt(long, parse(from_os_str))]
+    stats: Option<PathBuf>,
+
+    /// Hash-chained log of processing decisions, appended to when it exists; checked with `audit-verify`
+    #[structopt(long, parse(from_os_str))]
+    audit_log: Option<PathBuf>,
+
+    /// File with the key for HMAC of every audit log entry
+    #[structopt(long, parse(from_os_str), requires = "audit-log")]
+    audit_key_file: Option<PathBuf>,
+
+    /// File with the key (64 hex digits) encrypting snapshots and audit log with AES-256-GCM, `TXP_ENCRYPTION_KEY`
+    /// environment variable is used when not given
+    #[structopt(long, parse(from_os_str))]
+    encryption_key_file: Option<PathBuf>,
+
+    /// State snapshot (saved with `save` command of the `repl` or by `backfill`) the accounts continue from,
+    /// accounts without transactions in the input are written as they are
+    #[structopt(long, parse(from_os_str))]
+    initial_state: Option<PathBuf>,
+
+    /// Inconsistent snapshot (`--initial-state`, `--state` of the subcommands) is repaired, each repair printed to stderr,
+    /// instead of being refused
+    #[structopt(long)]
+    repair_state: bool,
+
+    /// Saves the state of all accounts at the end of the run into this snapshot file, to continue from it with `--initial-state`
+    #[structopt(long, parse(from_os_str))]
+    save_state: Option<PathBuf>,
+
+    /// Writes only accounts whose balances or lock changed during this run (or which are new), the number
+    /// of the untouched ones is recorded in the manifest
+    #[structopt(long, requires = "initial-state")]
+    output_changed_only: bool,
+
+    /// File where clients found in more than one file of the input directory are written
+    #[structopt(long, parse(from_os_str))]
+    overlaps: Option<PathBuf>,
+
+    /// Another source (file, archive or `-` for stdin) processed together with <file> by the same processor,
+    /// e.g. the live stream following a backfill file, can be repeated
+    #[structopt(long, parse(from_os_str), number_of_values = 1)]
+    merge_source: Vec<PathBuf>,
+
+    /// Order in which transactions of <file> and the merged sources are processed: `sequential` (sources one
+    /// after another), `arrival` (as they are read) or `timestamp` (earliest first, waits for every source)
+    #[structopt(long, possible_values = &SourceOrder::variants(), case_insensitive = true, default_value = "Sequential")]
+    merge_order: SourceOrder,
+
+    /// CSV file to process (`-` for stdin), `.zip` or `.tar` archive of CSV files or directory whose CSV files are
+    /// processed in parallel, required unless subcommand is used
+    #[structopt(name = "file", parse(from_os_str))]
+    csv_file: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+impl Opt {
+    /// buffering of the accounts written to stdout
+    fn flush_policy(&self) -> FlushPolicy {
+        FlushPolicy { bytes: self.flush_bytes, interval: self.flush_interval_ms.map(Duration::from_millis) }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Prints transactions of a single client with the running balance after each of them
+    History {
+        /// Client to report
+        #[structopt(long)]
+        client: ClientId,
+
+        /// Tenant of the client, when input contains several tenants
+        #[structopt(long)]
+        tenant: Option<String>,
+
+        /// Output format
+        #[structopt(long, possible_values = &HistoryFileFormat::variants(), case_insensitive = true, default_value = "Csv")]
+        format: HistoryFileFormat,
+
+        /// CSV file to process
+        #[structopt(name = "file", parse(from_os_str))]
+        csv_file: PathBuf,
+    },
+    /// Applies correction file to a state snapshot, writing the corrected snapshot and the report of changed accounts
+    Backfill {
+        /// State snapshot to correct
+        #[structopt(long, parse(from_os_str))]
+        state: PathBuf,
+
+        /// CSV file with the corrections: rows of the input format, `unlock`, `reversal` and `adjust` rows
+        #[structopt(long, parse(from_os_str))]
+        corrections: PathBuf,
+
+        /// File where the corrected snapshot is written
+        #[structopt(long, parse(from_os_str))]
+        output: PathBuf,
+
+        /// File where the delta report (balances before and after of every changed account) is written, stdout by default
+        #[structopt(long, parse(from_os_str))]
+        delta: Option<PathBuf>,
+    },
+    /// Upgrades state snapshot written by an older version to the current snapshot format
+    MigrateState {
+        /// Snapshot to upgrade
+        #[structopt(long, parse(from_os_str))]
+        state: PathBuf,
+
+        /// File where the upgraded snapshot is written, can be the same as `--state`
+        #[structopt(long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Checks integrity of the hash chain (and HMAC) of the audit log
+    AuditVerify {
+        /// File with the key the log was written with, every entry must then have valid HMAC
+        #[structopt(long, parse(from_os_str))]
+        key_file: Option<PathBuf>,
+
+        /// Audit log to check
+        #[structopt(name = "file", parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Prints state of the accounts as it was right after the given row (or time) of the input
+    StateAt {
+        /// Last data row (1 is the first row after the header) to process, e.g. `1_500_000`
+        #[structopt(long, parse(try_from_str = parse_row), required_unless = "timestamp")]
+        row: Option<u64>,
+
+        /// Last unix time (seconds) to process, rows without timestamp are always processed
+        #[structopt(long)]
+        timestamp: Option<Timestamp>,
+
+        /// Client to report, all clients when not given
+        #[structopt(long)]
+        client: Option<ClientId>,
+
+        /// Tenant of the client, when input contains several tenants
+        #[structopt(long, requires = "client")]
+        tenant: Option<String>,
+
+        /// CSV file to process
+        #[structopt(name = "file", parse(from_os_str))]
+        csv_file: PathBuf,
+    },
+    /// Finds the first transaction of the client after which its computed total diverges from the expected one
+    Bisect {
+        /// Client whose total does not match
+        #[structopt(long)]
+        client: ClientId,
+
+        /// Tenant of the client, when input contains several tenants
+        #[structopt(long)]
+        tenant: Option<String>,
+
+        /// Expected total of the client after the whole file
+        #[structopt(long, allow_hyphen_values = true)]
+        expect_total: Money,
+
+        /// CSV file to process
+        #[structopt(name = "file", parse(from_os_str))]
+        csv_file: PathBuf,
+    },
+    /// Interactive session to explore transaction and dispute semantics
+    Repl {
+        /// State snapshot (saved with `save` command) to start from
+        #[structopt(long, parse(from_os_str))]
+        state: Option<PathBuf>,
+    },
+    /// Runs the regression scenarios and compares their output with the expected files (development tool)
+    Golden {
+        /// Writes the current output as the expected one, to be reviewed with the change which caused it
+        #[structopt(long)]
+        update: bool,
+
+        /// Directory with the scenarios (`<name>.csv`) and their expected output (`<name>.expected`)
+        #[structopt(name = "dir", parse(from_os_str), default_value = "tests/golden")]
+        dir: PathBuf,
+    },
+}
+
+/// Entry point, builds the runtime selected with `--runtime` and `--workers`
+fn main() -> Result<()> {
+    let opt = Opt::from_args();
+    let mut builder = match (opt.runtime, opt.workers) {
+        (RuntimeFlavor::Single, Some(_)) => return Err("--workers requires --runtime multi".into()),
+        (RuntimeFlavor::Single, None) => tokio::runtime::Builder::new_current_thread(),
+        (RuntimeFlavor::Multi, _) => tokio::runtime::Builder::new_multi_thread(),
+    };
+    if let Some(workers) = opt.workers {
+        builder.worker_threads(workers);
+    }
+    #[cfg(feature = "pin-workers")]
+    if opt.pin_workers {
+        if opt.runtime == RuntimeFlavor::Single {
+            return Err("--pin-workers requires --runtime multi".into());
+        }
+        pin_workers(&mut builder, opt.workers);
+    }
+    match builder.enable_all().build()?.block_on(run(opt)) {
+        Err(e) if e.is::<RejectedRow>() => std::process::exit(EXIT_PARSE_ERROR),
+        r => r,
+    }
+}
+
+/// Pins the worker threads to CPU cores in order, one core each. Workers are the first threads started by the runtime,
+/// threads of the blocking pool started later stay unpinned.
+#[cfg(feature = "pin-workers")]
+fn pin_workers(builder: &mut tokio::runtime::Builder, workers: Option<usize>) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let cores = core_affinity::get_core_ids().unwrap_or_default();
+    if cores.is_empty() {
+        eprintln!("CPU cores are not available, worker threads are not pinned");
+        return;
+    }
+    let workers = workers.unwrap_or(cores.len());
+    if workers > cores.len() {
+        eprintln!("{} worker threads share {} CPU cores", workers, cores.len());
+    }
+    let started = AtomicUsize::new(0);
+    builder.on_thread_start(move || {
+        let n = started.fetch_add(1, Ordering::Relaxed);
+        if n < workers && !core_affinity::set_for_current(cores[n % cores.len()]) {
+            eprintln!("failed pinning worker thread {} to a CPU core", n);
+        }
+    });
+}
+
+async fn run(mut opt: Opt) -> Result<()> {
+
+    let tracing_level = match opt.tracing {
+        Some(ref l) => {
+            match l {
+                TracingLevel::Error => Level::ERROR,
+                TracingLevel::Warn => Level::WARN,
+                TracingLevel::Info => Level::INFO,
+                TracingLevel::Debug => Level::DEBUG,
+                TracingLevel::Trace => Level::TRACE,
+            }
+        }
+        None => Level::ERROR
+    };
+
+    // a builder for `FmtSubscriber`.
+    let builder = FmtSubscriber::builder();
+    let result = match opt.log_filter.take() {
+        // events within `account` and `tx` spans can be selected by client, transaction id or type
+        Some(filter) => tracing::subscriber::set_global_default(builder.with_env_filter(filter).finish()),
+        // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
+        // will be written to stdout.
+        None => tracing::subscriber::set_global_default(builder.with_max_level(tracing_level).finish()),
+    };
+    result.expect("setting default subscriber failed");
+
+    // new configuration can be tried on production data without side effects
+    if opt.dry_run {
+        info!("dry run: audit log, change stream and snapshots are not written, seen store is only read");
+        opt.audit_log = None;
+        opt.changes = None;
+        opt.snapshot_every = None;
+        opt.snapshot_dir = None;
+        opt.save_state = None;
+    }
+
+    let config = match &opt.config {
+        Some(path) if opt.watch_config => ConfigWatcher::spawn(path.clone(), CONFIG_WATCH_INTERVAL).await?,
+        Some(path) => config::fixed(Config::load(path).await?),
+        None => config::fixed(Config::default()),
+    };
+
+    let mut clients = match &opt.client_overrides {
+        Some(path) => ClientOverrides::load(path).await?,
+        None => ClientOverrides::default(),
+    };
+    if let Some(path) = &opt.frozen_list {
+        clients.freeze(ClientOverrides::load_frozen_list(path).await?);
+    }
+    let directory = match &opt.clients_meta {
+        Some(path) => ClientDirectory::load(path).await?,
+        None => ClientDirectory::default(),
+    };
+
+    // the run stops on the first parse error (or too many skipped ones) with exit code telling it apart from other failures
+    let errors = opt.errors;
+    let skip_bad_lines = opt.skip_bad_lines;
+    let reader = CsvTransactionReader::with_error_handler(Arc::new(move |e: &ParseError| {
+        match errors {
+            ErrorFormat::Jsonl => report_error(&ErrorRecord::from(e)),
+            ErrorFormat::Text if skip_bad_lines && !e.is_fatal() => {
+                eprintln!("skipping malformed row: {}", e)
+            }
+            ErrorFormat::Text => eprintln!("error reading CSV file: {}", e),
+        }
+        if !skip_bad_lines || e.is_fatal() {
+            std::process::exit(EXIT_PARSE_ERROR);
+        }
+    }));
+    let reader = match opt.skip_bad_lines {
+        true => reader.skip_bad_lines(opt.max_errors.unwrap_or(DEFAULT_MAX_ERRORS)),
+        false => reader,
+    }
+    .with_columns(opt.columns.clone());
+    let reader = match &opt.accounts {
+        Some(path) => reader.with_accounts(import::load_accounts(path)?),
+        None => reader,
+    };
+    let reader = match opt.keep_columns {
+        true => reader.keep_metadata(),
+        false => reader,
+    };
+
+    let encryption = EncryptionKey::load(opt.encryption_key_file.as_deref())?;
+    let flush = opt.flush_policy();
+
+    match opt.cmd {
+        Some(Command::History { client, tenant, format, csv_file }) => {
+            let options = ProcessorOptions {
+                buffer_size: opt.buffer,
+                history: HistoryRetention::All,
+                config,
+                clients: Arc::new(clients),
+                ..Default::default()
+            };
+            // only the requested client is sent for processing, other rows are skipped right after parsing
+            let outcome = process_file(csv_file, opt.tenant, &reader, options, |t: &Transaction| {
+                t.client_id == client && t.tenant == tenant
+            }, None)
+            .await?;
+            history::write_history(&mut FlushingWriter::new(io::stdout().lock(), flush), format.into(), &outcome.reports)
+        }
+        Some(Command::Backfill { state, corrections, output, delta }) => {
+            let mut engine = Engine::from_snapshot(load_state(&state, encryption.as_ref(), opt.repair_state)?, HistoryRetention::None);
+            engine.set_config(config.borrow().as_ref().clone());
+            engine.set_client_overrides(clients);
+            let before = engine.accounts();
+            let rows = backfill::read_corrections(&corrections).await?;
+            let rejected = backfill::apply(&mut engine, rows.clone());
+            // corrections are applied all or nothing
+            if !rejected.is_empty() {
+                for r in &rejected {
+                    eprintln!("{:?} line {}: {}", corrections, r.line, r.error);
+                }
+                return Err(format!("{} corrections rejected, nothing written", rejected.len()).into());
+            }
+            if !opt.dry_run {
+                engine.snapshot().save(&output, encryption.as_ref())?;
+            }
+            if let Some(path) = &opt.audit_log {
+                let key = opt.audit_key_file.as_deref().map(audit::load_key).transpose()?;
+                let mut log = AuditLog::open(path, key, encryption.clone())?;
+                backfill::audit(&mut log, &engine, &rows)?;
+            }
+            let precision = engine.config().precision();
+            match delta {
+                Some(path) => backfill::write_delta(&mut BufWriter::new(File::create(path)?), &before, &engine.accounts(), precision),
+                None => backfill::write_delta(&mut io::stdout().lock(), &before, &engine.accounts(), precision),
+            }
+        }
+        Some(Command::MigrateState { state, output }) => {
+            let snapshot = load_state(&state, encryption.as_ref(), opt.repair_state)?;
+            if opt.dry_run {
+                println!("{:?}: {} accounts can be upgraded to snapshot version {}", state, snapshot.accounts.len(), SNAPSHOT_VERSION);
+                return Ok(());
+            }
+            snapshot.save(&output, encryption.as_ref())?;
+            println!("{:?}: {} accounts written with snapshot version {}", output, snapshot.accounts.len(), SNAPSHOT_VERSION);
+            Ok(())
+        }
+        Some(Command::AuditVerify { key_file, file }) => {
+            let key = key_file.as_deref().map(audit::load_key).transpose()?;
+            let entries = audit::verify(&file, key.as_deref(), encryption.as_ref())?;
+            println!("{:?}: {} entries, chain is intact", file, entries);
+            Ok(())
+        }
+        Some(Command::StateAt { row, timestamp, client, tenant, csv_file }) => {
+            let book = config.borrow().clone();
+            let options = ProcessorOptions {
+                buffer_size: opt.buffer,
+                config,
+                clients: Arc::new(clients),
+                ..Default::default()
+            };
+            // rows after the point in time are skipped right after parsing, the header is line 1
+            let outcome = process_file(csv_file, opt.tenant, &reader, options, |t: &Transaction| {
+                row.is_none_or(|row| t.line.is_none_or(|line| line <= row + 1))
+                    && timestamp.is_none_or(|ts| t.timestamp.is_none_or(|t| t <= ts))
+                    && client.is_none_or(|c| t.client_id == c && t.tenant == tenant)
+            }, None)
+            .await?;
+            let accounts: Vec<_> = outcome.reports.into_iter().map(|r| r.account).collect();
+            let mut w = FlushingWriter::new(io::stdout().lock(), flush);
+            output::write_accounts_with_clients(&mut w, &accounts, book.precision(), &directory)
+        }
+        Some(Command::Bisect { client, tenant, expect_total, csv_file }) => {
+            let rows = Mutex::new(Vec::new());
+            let collect = |t: Option<RawTransaction>| {
+                if let Some(t) = t.map(Transaction::from) {
+                    let t = Transaction { tenant: t.tenant.clone().or_else(|| opt.tenant.clone()), ..t };
+                    if t.client_id == client && t.tenant == tenant {
+                        rows.lock().expect("rows lock poisoned").push(t);
+                    }
+                }
+                async { Ok(()) }
+            };
+            if CsvTransactionReader::is_archive(&csv_file) {
+                reader.read_archive(csv_file, collect).await;
+            } else {
+                reader.read_data_file(csv_file, collect).await;
+            }
+            let rows = rows.into_inner().expect("rows lock poisoned");
+            let book = config.borrow().clone();
+            match bisect::bisect(&rows, expect_total, &book) {
+                BisectResult::Matches => println!("total of client {} matches {:.*}", client, book.precision(), expect_total),
+                BisectResult::InputDiverges { ledger_total } => println!(
+                    "input does not add up to {:.p$}: applying every row of client {} gives {:.p$}",
+                    expect_total, client, ledger_total, p = book.precision()
+                ),
+                BisectResult::Diverges(d) => {
+                    let t = &d.transaction;
+                    println!(
+                        "first diverging row: line {} {} tx {} amount {:.p$}, total {:.p$} -> {:.p$}{}",
+                        t.line.unwrap_or_default(), t.tx_type, t.tx_id, t.amount, d.total_before, d.total_after,
+                        d.error.map(|e| format!(" (rejected: {})", e)).unwrap_or_default(),
+                        p = book.precision()
+                    );
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Repl { state }) => {
+            let mut engine = match state {
+                Some(path) => Engine::from_snapshot(load_state(&path, encryption.as_ref(), opt.repair_state)?, HistoryRetention::None),
+                None => Engine::default(),
+            };
+            engine.set_config(config.borrow().as_ref().clone());
+            engine.set_client_overrides(clients);
+            repl::Repl::new(engine).with_encryption(encryption).run(io::stdin().lock(), &mut io::stdout())
+        }
+        Some(Command::Golden { update, dir }) => {
+            let mismatches = golden::check(&dir, update).await?;
+            for m in &mismatches {
+                eprintln!("{}", m);
+            }
+            match mismatches.len() {
+                0 if update => println!("expected output of the scenarios in {:?} updated", dir),
+                0 => println!("all scenarios in {:?} match", dir),
+                n => return Err(format!("{} scenarios differ, review and run with --update to accept the change", n).into()),
+            }
+            Ok(())
+        }
+        None => process(opt, config, clients, directory, reader, encryption, flush).await,
+    }
+}
+
+/// writes error of the JSONL error stream to stderr
+fn report_error(e: &ErrorRecord) {
+    if let Err(err) = output::write_error_json(&mut io::stderr().lock(), e) {
+        error!("failed writing error {:?}: {}", e, err);
+    }
+}
+
+/// ends the input on SIGTERM and fails the run when it is not finished within the `deadline` after it
+#[cfg(unix)]
+async fn drain_on_sigterm(drain: watch::Sender<bool>, deadline: Duration) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            error!("failed listening for SIGTERM: {}", e);
+            return;
+        }
+    };
+    terminate.recv().await;
+    info!("SIGTERM received, draining within {} s", deadline.as_secs());
+    drain.send_replace(true);
+    tokio::time::sleep(deadline).await;
+    error!("run not drained within {} s of SIGTERM", deadline.as_secs());
+    std::process::exit(1);
+}
+
+#[cfg(not(unix))]
+async fn drain_on_sigterm(_drain: watch::Sender<bool>, _deadline: Duration) {}
+
+/// loads the snapshot, repairing its inconsistencies (printed to stderr) when `repair` is set
+fn load_state(path: &Path, encryption: Option<&EncryptionKey>, repair: bool) -> Result<Snapshot> {
+    if !repair {
+        return Snapshot::load(path, encryption);
+    }
+    let (snapshot, repaired) = Snapshot::load_repaired(path, encryption)?;
+    for inconsistency in &repaired {
+        eprintln!("{:?}: repaired: {}", path, inconsistency);
+    }
+    Ok(snapshot)
+}
+
+/// pauses the ingestion on SIGUSR1 and resumes it on SIGUSR2
+#[cfg(unix)]
+async fn pause_on_signals(control: IngestControl) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut pause, mut resume) = match (signal(SignalKind::user_defined1()), signal(SignalKind::user_defined2())) {
+        (Ok(pause), Ok(resume)) => (pause, resume),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("failed listening for pause signals: {}", e);
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            _ = pause.recv() => control.pause(),
+            _ = resume.recv() => control.resume(),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn pause_on_signals(_control: IngestControl) {
+    error!("--pause-signals is only supported on unix");
+}
+
+/// sampling rate between 0 and 1
+fn parse_rate(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!("'{}' is not a number between 0 and 1", s)),
+    }
+}
+
+/// `expected=found` column mapping
+fn parse_column(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((expected, found)) if !expected.trim().is_empty() && !found.trim().is_empty() => {
+            Ok((expected.trim().to_string(), found.trim().to_string()))
+        }
+        _ => Err(format!("'{}' is not an `expected=found` column mapping", s)),
+    }
+}
+
+/// number of worker threads, at least one
+fn parse_workers(s: &str) -> std::result::Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(workers) if workers > 0 => Ok(workers),
+        _ => Err(format!("'{}' is not a positive number of workers", s)),
+    }
+}
+
+/// row number, `_` can be used to separate thousands
+fn parse_row(s: &str) -> std::result::Result<u64, String> {
+    match s.replace('_', "").parse::<u64>() {
+        Ok(row) if row > 0 => Ok(row),
+        _ => Err(format!("'{}' is not a row number", s)),
+    }
+}
+
+/// processes the whole file and writes requested outputs
+async fn process(
+    opt: Opt,
+    config: ConfigReceiver,
+    clients: ClientOverrides,
+    directory: ClientDirectory,
+    reader: CsvTransactionReader,
+    encryption: Option<EncryptionKey>,
+    flush: FlushPolicy,
+) -> Result<()> {
+    let started = manifest::now();
+    let csv_file = match opt.csv_file {
+        Some(f) => f,
+        None => return Err("missing <file> argument, run with --help for usage information".into()),
+    };
+    if !opt.merge_source.is_empty() {
+        if csv_file.is_dir() {
+            return Err("--merge-source requires a single input file".into());
+        }
+        if opt.verify_checksum {
+            return Err("--verify-checksum can not be used with --merge-source".into());
+        }
+    }
+    // inputs are verified before any of their transactions is routed, so no output or store sees a mismatching file
+    let digests = match (opt.verify_checksum, csv_file.as_os_str() == "-") {
+        (false, _) => Vec::new(),
+        (true, true) => return Err("--verify-checksum can not be used when reading stdin".into()),
+        (true, false) => verify_checksums(&csv_file)?,
+    };
+
+    // currency of the configuration in effect at the start, reloads are expected to keep it
+    let book = config.borrow().clone();
+    let initial = match &opt.initial_state {
+        // every processor of a directory would report the untouched accounts again
+        Some(_) if csv_file.is_dir() => return Err("--initial-state requires a single input file".into()),
+        Some(path) => load_state(path, encryption.as_ref(), opt.repair_state)?,
+        None => Snapshot::default(),
+    };
+    // transactions skipped as already applied have to be in the state the run continues from
+    let seen = match &opt.seen_store {
+        Some(path) if opt.dry_run => Some(Arc::new(SeenStore::open_read_only(path, opt.seen_horizon, initial.seen_checkpoint)?)),
+        Some(path) => Some(Arc::new(SeenStore::open(path, opt.seen_horizon, initial.seen_checkpoint)?)),
+        None => None,
+    };
+    let mut options = ProcessorOptions {
+        buffer_size: opt.buffer,
+        history: if opt.history.is_some() {
+            HistoryRetention::All
+        } else if opt.statements.is_some() {
+            HistoryRetention::Applied
+        } else {
+            HistoryRetention::None
+        },
+        config,
+        clients: Arc::new(clients),
+        rejects: opt.rejects.is_some(),
+        seen: seen.clone(),
+        state: opt.save_state.is_some(),
+        strict: opt.strict_accounts,
+        top: opt.top_report.unwrap_or_default(),
+        priority_window: opt.priority_window,
+        initial: initial.into_accounts(),
+        ..Default::default()
+    };
+    let timings = (opt.manifest.is_some() || opt.stats.is_some()).then(|| Arc::new(StageTimings::default()));
+    options.timings = timings.clone();
+    let gauges = (opt.resource_usage || opt.stats.is_some()).then(|| Arc::new(ResourceGauges::default()));
+    options.gauges = gauges.clone();
+    // streaming input has no end to wait for
+    let streaming = std::iter::once(&csv_file).chain(&opt.merge_source).any(|f| f.as_os_str() == "-");
+    let usage_reporter = match &gauges {
+        Some(gauges) if opt.resource_usage && streaming => {
+            let gauges = gauges.clone();
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(RESOURCE_USAGE_INTERVAL);
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    eprint!("{}", ResourceUsage::measure(&gauges));
+                }
+            }))
+        }
+        _ => None,
+    };
+    // accounts falling behind are reported before they stall the whole stream
+    let lag_watchdog = match opt.lag_watchdog {
+        Some(seconds) if streaming => {
+            let lag = Arc::new(LagMonitor::default());
+            options.lag = Some(lag.clone());
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(seconds.max(1)));
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let slowest = lag.slowest(LAG_WATCHDOG_ACCOUNTS);
+                    if let Some(a) = slowest.first() {
+                        info!("slowest account {} waits {} ms with {} queued", a.key.1, a.max_wait.as_millis(), a.depth);
+                    }
+                    eprintln!("{}", AccountLag::HEADER);
+                    slowest.iter().for_each(|a| eprintln!("{}", a));
+                }
+            }))
+        }
+        _ => None,
+    };
+
+    // SIGTERM of a rolling deploy loses no row: the input ends, the accounts process what was read and the outputs are written
+    let (reader, drain) = match streaming {
+        true => {
+            let (sender, receiver) = watch::channel(false);
+            let deadline = Duration::from_secs(opt.drain_timeout);
+            (reader.drain_on(receiver), Some(tokio::spawn(drain_on_sigterm(sender, deadline))))
+        }
+        false => (reader, None),
+    };
+
+    // maintenance of the downstream sinks without losing the state of the accounts
+    let pause_signals = match opt.pause_signals && streaming {
+        true => {
+            let control = IngestControl::default();
+            options.middleware.insert(0, Arc::new(control.clone()));
+            Some(tokio::spawn(pause_on_signals(control)))
+        }
+        false => None,
+    };
+
+    // initial state the output is compared with
+    let unchanged_from = opt.output_changed_only.then(|| options.initial.clone());
+
+    let aggregates = opt.aggregates.as_ref().map(|_| Arc::new(Aggregates::default()));
+    if let Some(aggregates) = &aggregates {
+        options.aggregators.push(aggregates.clone());
+    }
+
+    #[cfg(feature = "tui")]
+    let dashboard = if opt.tui {
+        let (events_sender, events_receiver) = mpsc::unbounded_channel();
+        options.events = Some(events_sender);
+        Some(tokio::spawn(tui::run(events_receiver)))
+    } else {
+        None
+    };
+
+    let trace = match &opt.trace_decisions {
+        Some(path) => {
+            let mut w = BufWriter::new(File::create(path)?);
+            writeln!(w, "{}", output::DECISIONS_HEADER)?;
+            Some(Arc::new(Mutex::new(w)))
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "arrow")]
+    let arrow_trace = match &opt.arrow_trace {
+        Some(path) => Some(Arc::new(Mutex::new(ArrowTrace::new(BufWriter::new(File::create(path)?))?))),
+        None => None,
+    };
+    #[cfg(feature = "arrow")]
+    let arrow_tracing = arrow_trace.is_some();
+    #[cfg(not(feature = "arrow"))]
+    let arrow_tracing = false;
+
+    let errors_jsonl = opt.errors == ErrorFormat::Jsonl;
+
+    let mut audit = match &opt.audit_log {
+        Some(path) => {
+            let key = opt.audit_key_file.as_deref().map(audit::load_key).transpose()?;
+            Some(AuditLog::open(path, key, encryption.clone())?)
+        },
+        None => None,
+    };
+
+    let mut snapshots = match (opt.snapshot_every, &opt.snapshot_dir) {
+        (Some(every), Some(dir)) => {
+            let files = RotatingFiles::new(dir.clone(), opt.snapshot_keep, book.precision())?;
+            Some(SnapshotPublisher::new(every, opt.snapshot_delta, files))
+        }
+        _ => None,
+    };
+
+    let mut changes = match &opt.changes {
+        Some(path) => Some(ChangeStream::new(BufWriter::new(File::create(path)?)).with_initial(&options.initial)),
+        None => None,
+    };
+
+    // decisions, errors, changes and snapshots are written from the event stream, events are passed on to the dashboard (if any)
+    let tracer = if trace.is_some()
+        || arrow_tracing
+        || errors_jsonl
+        || audit.is_some()
+        || changes.is_some()
+        || snapshots.is_some()
+    {
+        let (events_sender, mut events_receiver) = mpsc::unbounded_channel::<ProcessingEvent>();
+        let forward = options.events.replace(events_sender);
+        let trace = trace.clone();
+        #[cfg(feature = "arrow")]
+        let arrow_trace = arrow_trace.clone();
+        // time based snapshots are published even when no events arrive
+        let tick = snapshots.as_ref().and_then(|s| s.interval());
+        Some(tokio::spawn(async move {
+            loop {
+                let e = match tick {
+                    Some(tick) => match tokio::time::timeout(tick, events_receiver.recv()).await {
+                        Ok(e) => e,
+                        Err(_) => {
+                            if let Some(snapshots) = &mut snapshots {
+                                snapshots.tick()?;
+                            }
+                            continue;
+                        }
+                    },
+                    None => events_receiver.recv().await,
+                };
+                let Some(e) = e else { break };
+                if let Some(Ok(mut w)) = trace.as_ref().map(|t| t.lock()) {
+                    output::write_decision(&mut *w, &e)?;
+                }
+                #[cfg(feature = "arrow")]
+                if let Some(Ok(mut w)) = arrow_trace.as_ref().map(|t| t.lock()) {
+                    w.decision(&e)?;
+                }
+                if let (true, Some(record)) = (errors_jsonl, ErrorRecord::rejected(&e)) {
+                    report_error(&record);
+                }
+                if let Some(audit) = &mut audit {
+                    audit.append(&e)?;
+                }
+                if let Some(changes) = &mut changes {
+                    changes.record(&e)?;
+                }
+                if let Some(snapshots) = &mut snapshots {
+                    snapshots.record(&e)?;
+                }
+                if let Some(forward) = &forward {
+                    let _ = forward.send(e);
+                }
+            }
+            if let Some(audit) = &mut audit {
+                audit.flush()?;
+            }
+            if let Some(changes) = &mut changes {
+                changes.flush()?;
+            }
+            if let Some(snapshots) = snapshots {
+                snapshots.finish()?;
+            }
+            Result::Ok(())
+        }))
+    } else {
+        None
+    };
+
+    let filters = Filters {
+        clients: opt.clients.as_ref().map(|c| c.to_string()),
+        exclude_types: opt.exclude_types.iter().map(|t| t.to_string()).collect(),
+        only_types: opt.only_types.iter().map(|t| t.to_string()).collect(),
+        sample: opt.sample,
+        seed: opt.seed,
+    };
+
+    // filter stage between the reader and the processor
+    let (selected, exclude_types, only_types) = (opt.clients, opt.exclude_types, opt.only_types);
+    let sample = opt.sample.map(|rate| ClientSample { rate, seed: opt.seed.unwrap_or_default() });
+    let (rows_read, rows_filtered) = (AtomicU64::new(0), AtomicU64::new(0));
+    let filter = |t: &Transaction| {
+        let keep = selected.as_ref().is_none_or(|c| c.contains(t.client_id))
+            && sample.is_none_or(|s| s.contains(&t.account_key()))
+            && !exclude_types.contains(&t.tx_type)
+            && (only_types.is_empty() || only_types.contains(&t.tx_type));
+        rows_read.fetch_add(1, Ordering::Relaxed);
+        if !keep {
+            rows_filtered.fetch_add(1, Ordering::Relaxed);
+        }
+        if let (false, Some(Ok(mut w))) = (keep, trace.as_ref().map(|t| t.lock())) {
+            if let Err(e) = output::write_skipped(&mut *w, t, "filtered") {
+                error!("failed writing decision trace: {}", e);
+            }
+        }
+        #[cfg(feature = "arrow")]
+        if let (false, Some(Ok(mut w))) = (keep, arrow_trace.as_ref().map(|t| t.lock())) {
+            if let Err(e) = w.skipped(t, "filtered") {
+                error!("failed writing decision trace: {}", e);
+            }
+        }
+        keep
+    };
+    let mut inputs = if csv_file.is_dir() {
+        list_csv_files(&csv_file)?
+    } else if csv_file.as_os_str() == "-" {
+        Vec::new()
+    } else {
+        vec![csv_file.clone()]
+    };
+    inputs.extend(opt.merge_source.iter().filter(|f| f.as_os_str() != "-").cloned());
+
+    let limiter = book.rate_limit.as_ref().map(RateLimiter::new).transpose()?;
+
+    let processing = Instant::now();
+    let mut outcome = if csv_file.is_dir() {
+        let (outcome, overlaps) = process_dir(&csv_file, opt.tenant.clone(), &reader, options, &filter, limiter.as_ref()).await?;
+        if !overlaps.is_empty() {
+            eprintln!("{} clients found in more than one file of {:?}", overlaps.len(), csv_file);
+        }
+        if let Some(path) = &opt.overlaps {
+            let mut w = BufWriter::new(File::create(path)?);
+            output::write_overlaps(&mut w, &overlaps)?;
+        }
+        outcome
+    } else if !opt.merge_source.is_empty() {
+        let mut options = options;
+        if let Some(limiter) = &limiter {
+            options.middleware.push(Arc::new(limiter.for_source()));
+        }
+        let sources = std::iter::once(csv_file).chain(opt.merge_source).collect();
+        process_sources(sources, opt.merge_order.into(), opt.tenant, &reader, options, filter).await?
+    } else {
+        process_file(csv_file, opt.tenant, &reader, options, filter, limiter.as_ref()).await?
+    };
+    if let Some(limiter) = &limiter {
+        outcome.stats.ingest = limiter.stats();
+        if outcome.stats.ingest.shed > 0 {
+            eprintln!("{} rows over the rate limit were dropped", outcome.stats.ingest.shed);
+        }
+    }
+    outcome.stats.ingest.malformed = reader.skipped();
+    if outcome.stats.ingest.malformed > 0 {
+        eprintln!("{} malformed rows were skipped", outcome.stats.ingest.malformed);
+    }
+    let reports = &outcome.reports;
+    let rows = RowCounts { read: rows_read.into_inner(), filtered: rows_filtered.into_inner() };
+    let timings = timings.map(|t| RunTimings::new(&t, processing.elapsed(), rows.read));
+
+    // resources are measured at the end, once all the outputs are written
+    let mut stats_report = opt.stats.as_ref().map(|_| StatsReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started,
+        rows: rows.clone(),
+        summary: RunSummary::from_outcome(&outcome),
+        parse_errors: reader.skipped_by_code(),
+        stats: outcome.stats.clone(),
+        timings: timings.clone().unwrap_or_default(),
+        ..Default::default()
+    });
+
+    let mut run_manifest = match &opt.manifest {
+        Some(_) => Some(RunManifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started,
+            inputs: inputs
+                .iter()
+                .zip(digests.into_iter().chain(std::iter::repeat(None)))
+                .map(|(path, digest)| {
+                    Ok(InputFile {
+                        path: path.display().to_string(),
+                        verified: digest.is_some(),
+                        sha256: match digest {
+                            Some(digest) => digest,
+                            None => manifest::sha256_file(path)?,
+                        },
+                    })
+                })
+                .collect::<Result<_>>()?,
+            rows,
+            config: ConfigInfo::new(opt.config.as_deref(), &book)?,
+            filters,
+            summary: RunSummary::from_outcome(&outcome),
+            stats: outcome.stats.clone(),
+            timings,
+            ..Default::default()
+        }),
+        None => None,
+    };
+
+    if let Some(tracer) = tracer {
+        tracer.await??;
+    }
+    // with `--strict-accounts` the first rejection stops the run once the outputs of the events are flushed,
+    // neither the state nor the accounts are written
+    if let Some(rejected) = outcome.aborted.take() {
+        if let Some(Ok(mut w)) = trace.as_ref().map(|t| t.lock()) {
+            w.flush()?;
+        }
+        if !errors_jsonl {
+            eprintln!("{}", rejected);
+        }
+        eprintln!("run aborted on the first rejected transaction (--strict-accounts)");
+        return Err(rejected.into());
+    }
+    if let Some(path) = &opt.save_state {
+        // the seen store is committed first: a crash before the state is saved drops the entries of this run
+        let seen_checkpoint = seen.as_ref().map(|s| s.commit()).transpose()?;
+        let mut accounts: Vec<_> = reports.iter().filter_map(|r| r.state.clone()).collect();
+        accounts.sort_by(|a, b| (&a.tenant, a.client_id).cmp(&(&b.tenant, b.client_id)));
+        Snapshot { accounts, seen_checkpoint }.save(path, encryption.as_ref())?;
+    }
+    if let Some(Ok(mut w)) = trace.as_ref().map(|t| t.lock()) {
+        w.flush()?;
+    }
+    #[cfg(feature = "arrow")]
+    if let Some(arrow_trace) = arrow_trace {
+        match Arc::try_unwrap(arrow_trace) {
+            Ok(w) => w.into_inner().unwrap_or_else(|e| e.into_inner()).finish()?,
+            Err(_) => return Err("decision trace is still in use".into()),
+        }
+    }
+
+    #[cfg(feature = "tui")]
+    if let Some(dashboard) = dashboard {
+        dashboard.await??;
+    }
+
+    if let Some(dir) = &opt.statements {
+        output::write_statements(dir, reports, &directory)?;
+    }
+
+    if let Some(path) = &opt.history {
+        let mut w = BufWriter::new(File::create(path)?);
+        history::write_history(&mut w, opt.history_format.into(), reports)?;
+    }
+
+    if let Some(path) = &opt.rejects {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_rejects(&mut w, reports)?;
+    }
+
+    if let Some(path) = &opt.negative_report {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_negative(&mut w, reports, &directory)?;
+    }
+
+    if let Some(path) = &opt.quarantine_report {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_quarantined(&mut w, reports, &directory)?;
+    }
+
+    // age of the open disputes is measured to the same time in all reports
+    let as_of = opt.as_of.or_else(|| reports.iter().filter_map(|r| r.account.last_activity).max());
+    if let Some(path) = &opt.open_disputes {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_open_disputes(&mut w, reports, as_of, &directory)?;
+    }
+
+    if let Some(path) = &opt.held_aging {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_held_aging(&mut w, reports, as_of)?;
+    }
+
+    if let Some(path) = &opt.chargeback_report {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_chargebacks(&mut w, reports, &directory)?;
+    }
+
+    if let (Some(aggregates), Some(path)) = (&aggregates, &opt.aggregates) {
+        aggregates.report().save(path)?;
+    }
+
+    if let (Some(n), Some(path)) = (opt.top_report, &opt.top_file) {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_top(&mut w, reports, n)?;
+    }
+
+    if let Some(path) = &opt.unresolved_disputes {
+        let mut w = BufWriter::new(File::create(path)?);
+        output::write_unresolved(&mut w, reports)?;
+    }
+
+    if opt.summary {
+        eprint!("{}", RunSummary::from_outcome(&outcome));
+    }
+
+    if let Some(reporter) = usage_reporter {
+        reporter.abort();
+    }
+    if let Some(watchdog) = lag_watchdog {
+        watchdog.abort();
+    }
+    if let Some(signals) = pause_signals {
+        signals.abort();
+    }
+    if let (true, Some(gauges)) = (opt.resource_usage, &gauges) {
+        eprint!("{}", ResourceUsage::measure(gauges));
+    }
+
+    #[cfg(feature = "sql")]
+    let query = match &opt.query {
+        Some(_) => Some(Query::default().with_history(&outcome.reports)?),
+        None => None,
+    };
+
+    let mut accounts: Vec<_> = outcome.reports.into_iter().map(|r| r.account).collect();
+
+    if let Some(days) = opt.dormant_days {
+        let as_of = output::mark_dormant(&mut accounts, days, opt.as_of);
+        if let (Some(path), Some(as_of)) = (&opt.dormant_report, as_of) {
+            let mut w = BufWriter::new(File::create(path)?);
+            output::write_dormant(&mut w, &accounts, as_of)?;
+        }
+    }
+
+    if let Some(initial) = &unchanged_from {
+        let untouched = output::retain_changed(&mut accounts, initial);
+        if let Some(m) = &mut run_manifest {
+            m.untouched = Some(untouched);
+        }
+    }
+
+    #[cfg(feature = "arrow")]
+    if let Some(path) = &opt.arrow_output {
+        arrow::write_accounts(BufWriter::new(File::create(path)?), &accounts, book.precision())?;
+    }
+
+    #[cfg(feature = "sql")]
+    let queried = match (&opt.query, query) {
+        (Some(statement), Some(query)) => {
+            let batches = query.with_accounts(&accounts, book.precision())?.run(statement).await?;
+            println!("{}", sql::format(&batches)?);
+            true
+        }
+        _ => false,
+    };
+    #[cfg(not(feature = "sql"))]
+    let queried = false;
+
+    match (opt.output_dir, &opt.split_by_status) {
+        (Some(dir), _) => {
+            output::write_tenant_files(&dir, &accounts, book.precision(), &directory)?;
+            output::write_metadata(&dir, &book)?;
+        }
+        (None, _) if queried => (),
+        (None, Some(dir)) => output::write_status_files(dir, &accounts, book.precision(), &directory)?,
+        (None, None) => {
+            let mut w = FlushingWriter::new(io::stdout().lock(), flush);
+            output::write_accounts_with_clients(&mut w, &accounts, book.precision(), &directory)?
+        }
+    }
+
+    if let (Some(path), Some(m)) = (&opt.manifest, &mut run_manifest) {
+        m.finished = manifest::now();
+        m.save(path)?;
+    }
+
+    if let (Some(path), Some(report), Some(gauges)) = (&opt.stats, &mut stats_report, &gauges) {
+        report.finished = manifest::now();
+        report.resources = ResourceUsage::measure(gauges);
+        report.save(path)?;
+    }
+
+    if let Some(drain) = drain {
+        drain.abort();
+    }
+    Ok(())
+}
+
+/// Processes every CSV file of `dir` concurrently, each with its own processor, and merges the results
+/// (see `merge::merge_outcomes`)
+async fn process_dir<P>(
+    dir: &Path,
+    tenant: Option<String>,
+    reader: &CsvTransactionReader,
+    options: ProcessorOptions,
+    filter: &P,
+    limiter: Option<&RateLimiter>,
+) -> Result<(ProcessingOutcome, Vec<Overlap>)>
+where
+    P: Fn(&Transaction) -> bool,
+{
+    let files = list_csv_files(dir)?;
+    debug!("processing {} files of {:?}", files.len(), dir);
+
+    let results = future::join_all(files.into_iter().map(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let outcome = process_file(path, tenant.clone(), reader, options.clone(), filter, limiter);
+        async move { (name, outcome.await) }
+    }))
+    .await;
+
+    let mut outcomes = Vec::new();
+    for (name, result) in results {
+        outcomes.push((name, result?));
+    }
+    Ok(merge::merge_outcomes(outcomes))
+}
+
+/// Compares SHA-256 of the input file (every CSV file of the input directory) with its `<file>.sha256` sidecar file,
+/// returns the digests in the order of the files
+fn verify_checksums(input: &Path) -> Result<Vec<Option<String>>> {
+    let files = if input.is_dir() { list_csv_files(input)? } else { vec![input.to_path_buf()] };
+    files
+        .iter()
+        .map(|path| {
+            let (expected, actual) = (manifest::expected_sha256(path)?, manifest::sha256_file(path)?);
+            if expected != actual {
+                return Err(format!("checksum of {:?} does not match: expected {}, computed {}", path, expected, actual).into());
+            }
+            debug!("checksum of {:?} verified", path);
+            Ok(Some(actual))
+        })
+        .collect()
+}
+
+/// CSV files of `dir` ordered by name
+fn list_csv_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "csv") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads `csv_file` and processes transactions accepted by `filter`
+///
+/// `tenant` tenant assigned to transactions without one
+async fn process_file<P>(
+    csv_file: PathBuf,
+    tenant: Option<String>,
+    reader: &CsvTransactionReader,
+    options: ProcessorOptions,
+    filter: P,
+    limiter: Option<&RateLimiter>,
+) -> Result<ProcessingOutcome>
+where
+    P: Fn(&Transaction) -> bool,
+{
+    // every file is limited on its own, within the limit shared by all of them
+    let mut options = options;
+    if let Some(limiter) = limiter {
+        options.middleware.push(Arc::new(limiter.for_source()));
+    }
+
+    // channels to exchange data
+    let (tx_sender, tx_receiver) = mpsc::channel::<Option<Transaction>>(options.buffer_size);
+    let timings = options.timings.clone();
+    // time the reader waited for the processing to take the transactions, it is not parsing time
+    let waited = AtomicU64::new(0);
+
+    let process_raw_transaction = |t| forward(&tx_sender, t, &tenant, &filter, &waited);
+
+    // raw transaction reader task
+    let source = csv_file.clone();
+    let data_reader = async {
+        let started = Instant::now();
+        read_source(reader, source, process_raw_transaction).await;
+        started.elapsed()
+    };
+
+    // tx processing task
+    let process_transactions = TxProcessor::run(tx_receiver, options);
+
+    //todo: at the moment reader future returns (), we could extend it to return Result and print errors if any
+    let (read, outcome) = tokio::join!(data_reader, process_transactions);
+    if let Some(timings) = timings {
+        timings.parsed(read.saturating_sub(Duration::from_nanos(waited.into_inner())));
+    }
+    Ok(outcome)
+}
+
+/// Reads `sources` concurrently and processes their transactions accepted by `filter` with a single processor,
+/// in the `order` (see `merge::merge_sources`)
+async fn process_sources<P>(
+    sources: Vec<PathBuf>,
+    order: MergeOrder,
+    tenant: Option<String>,
+    reader: &CsvTransactionReader,
+    options: ProcessorOptions,
+    filter: P,
+) -> Result<ProcessingOutcome>
+where
+    P: Fn(&Transaction) -> bool,
+{
+    let (tx_sender, tx_receiver) = mpsc::channel::<Option<Transaction>>(options.buffer_size);
+    let timings = options.timings.clone();
+    let waited = AtomicU64::new(0);
+
+    let mut senders = Vec::new();
+    let mut receivers = Vec::new();
+    for _ in &sources {
+        let (sender, receiver) = mpsc::channel::<Option<Transaction>>(options.buffer_size);
+        senders.push(sender);
+        receivers.push(receiver);
+    }
+    let (tenant, filter, waited_by) = (&tenant, &filter, &waited);
+    // the sender is dropped when its source ends, also when it fails before sending `None`
+    let data_readers = future::join_all(sources.into_iter().zip(senders).map(|(source, sender)| async move {
+        let started = Instant::now();
+        read_source(reader, source, |t| forward(&sender, t, tenant, filter, waited_by)).await;
+        started.elapsed()
+    }));
+    let merge = merge::merge_sources(receivers, order, tx_sender);
+
+    let (read, counts, outcome) = tokio::join!(data_readers, merge, TxProcessor::run(tx_receiver, options));
+    debug!("transactions processed from the merged sources: {:?}", counts);
+    if let Some(timings) = timings {
+        let read: Duration = read.into_iter().sum();
+        timings.parsed(read.saturating_sub(Duration::from_nanos(waited.into_inner())));
+    }
+    Ok(outcome)
+}
+
+/// Reads transactions of `source` with the reader of its format
+///
+/// `-` reads transactions from stdin until it is closed, e.g. when fed continuously by another process
+async fn read_source<F, Fut>(reader: &CsvTransactionReader, source: PathBuf, raw_transaction_handler: F)
+where
+    F: Fn(Option<RawTransaction>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
+    if source.as_os_str() == "-" {
+        reader.read(tokio::io::stdin(), raw_transaction_handler).await
+    } else if CsvTransactionReader::is_archive(&source) {
+        reader.read_archive(source, raw_transaction_handler).await
+    } else if import::is_statement(&source) {
+        reader.read_statement(source, raw_transaction_handler).await
+    } else {
+        #[cfg(feature = "iso20022")]
+        if iso20022::is_iso20022(&source) {
+            return reader.read_iso20022(source, raw_transaction_handler).await;
+        }
+        #[cfg(feature = "xlsx")]
+        if xlsx::is_xlsx(&source) {
+            return reader.read_xlsx(source, raw_transaction_handler).await;
+        }
+        reader.read_data_file(source, raw_transaction_handler).await
+    }
+}
+
+/// Converts raw transaction into transaction and sends it down for processing unless `filter` drops it,
+/// `None` is the signal to finish processing
+///
+/// `waited` time spent waiting for the processing to take the transaction, it is not parsing time
+async fn forward<P>(
+    sender: &mpsc::Sender<Option<Transaction>>,
+    t: Option<RawTransaction>,
+    tenant: &Option<String>,
+    filter: &P,
+    waited: &AtomicU64,
+) -> std::result::Result<(), String>
+where
+    P: Fn(&Transaction) -> bool,
+{
+    let send_result = match t {
+        Some(rt) => {
+            let mut t: Transaction = rt.into();
+            if t.tenant.is_none() {
+                t.tenant = tenant.clone();
+            }
+            if !filter(&t) {
+                return Ok(());
+            }
+            let started = Instant::now();
+            let sent = sender.send(Some(t)).await;
+            waited.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            sent
+        }
+        None => sender.send(Option::None).await,
+    };
+    match send_result {
+        Ok(_) => Ok(()),
+        Err(_e) => Err("Failed to send transaction down the channel".to_string()),
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use txp::{
+    crypto::EncryptionKey, engine::Engine, history::HistoryRetention, output, snapshot::Snapshot, ClientId, Money, Result,
+    TenantId, Transaction, TxId, TxType,
+};
+
+const HELP: &str = "commands:
+  deposit <client> <amount> [tx]      credit the account, tx id is assigned when omitted
+  withdrawal <client> <amount> [tx]   debit the account, tx id is assigned when omitted
+  dispute <client> <tx>               hold funds of the transaction
+  resolve <client> <tx>               release held funds of the transaction
+  chargeback <client> <tx>            reverse the transaction and lock the account
+  settle <client> <tx>                settle pending withdrawal (with `[settlement]` configured)
+  refund <client> <tx> [amount]       credit back the withdrawal, what is left of it when amount is omitted
+  adjust <client> <amount> <reason>   credit (debit when negative) the account, the reason is kept in the history
+  open <client>                       reopen closed account
+  close <client>                      close empty account
+  show [client]                       print state of one or all accounts
+  tenant [name]                       switch tenant used by the following commands (no name for none)
+  save <file>                         save state snapshot (encrypted when the encryption key is set)
+  load <file>                         replace current state with the snapshot
+  help                                print this message
+  quit                                exit";
+
+/// Interactive session reading commands from `input` and writing responses to `out`
+pub struct Repl {
+    engine: Engine,
+    tenant: Option<TenantId>,
+    next_tx_id: TxId,
+    /// key of the saved and loaded snapshots
+    encryption: Option<EncryptionKey>,
+}
+
+impl Repl {
+    pub fn new(engine: Engine) -> Self {
+        let next_tx_id = engine.max_tx_id().map_or(1, |id| id + 1);
+        Repl {
+            engine,
+            tenant: None,
+            next_tx_id,
+            encryption: None,
+        }
+    }
+
+    pub fn with_encryption(mut self, key: Option<EncryptionKey>) -> Self {
+        self.encryption = key;
+        self
+    }
+
+    /// reads commands until `quit` or end of input
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> Result<()> {
+        writeln!(out, "type `help` for the list of commands")?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => (),
+                ["quit"] | ["exit"] => return Ok(()),
+                _ => {
+                    if let Err(e) = self.execute(&words, out) {
+                        writeln!(out, "error: {}", e)?;
+                    }
+                }
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn execute<W: Write>(&mut self, words: &[&str], out: &mut W) -> Result<()> {
+        match words {
+            ["help"] => writeln!(out, "{}", HELP)?,
+            ["deposit", client, amount, rest @ ..] | ["withdrawal", client, amount, rest @ ..]
+                if rest.len() <= 1 =>
+            {
+                let tx_type = if words[0] == "deposit" {
+                    TxType::Deposit
+                } else {
+                    TxType::Withdrawal
+                };
+                let tx_id = match rest.first() {
+                    Some(tx) => tx.parse::<TxId>()?,
+                    None => self.next_tx_id,
+                };
+                let amount = amount.parse::<Money>()?;
+                if amount < 0.0 {
+                    return Err(format!("amount '{}' < 0.0", amount).into());
+                }
+                self.submit(tx_type, client.parse()?, tx_id, amount, out)?;
+            }
+            ["dispute", client, tx] | ["resolve", client, tx] | ["chargeback", client, tx] | ["settle", client, tx] => {
+                let tx_type = match words[0] {
+                    "dispute" => TxType::Dispute,
+                    "resolve" => TxType::Resolve,
+                    "settle" => TxType::Settle,
+                    _ => TxType::Chargeback,
+                };
+                self.submit(tx_type, client.parse()?, tx.parse()?, 0.0, out)?;
+            }
+            ["refund", client, tx, rest @ ..] if rest.len() <= 1 => {
+                let amount = match rest.first() {
+                    Some(amount) => amount.parse::<Money>()?,
+                    None => 0.0,
+                };
+                if amount < 0.0 {
+                    return Err(format!("amount '{}' < 0.0", amount).into());
+                }
+                self.submit(TxType::Refund, client.parse()?, tx.parse()?, amount, out)?;
+            }
+            ["adjust", client, amount, reason @ ..] => {
+                let key = (self.tenant.clone(), client.parse::<ClientId>()?);
+                let tx_id = self.next_tx_id;
+                match self.engine.adjust(&key, tx_id, amount.parse()?, &reason.join(" ")) {
+                    Ok(a) => {
+                        self.next_tx_id += 1;
+                        writeln!(out, "tx {} applied\n{}\n{}", tx_id, output::ACCOUNT_HEADER, a)?
+                    }
+                    Err(e) => writeln!(out, "rejected: {}", e)?,
+                }
+            }
+            ["open", client] | ["close", client] => {
+                let tx_type = if words[0] == "open" { TxType::Open } else { TxType::Close };
+                self.submit(tx_type, client.parse()?, 0, 0.0, out)?;
+            }
+            ["show"] => output::write_accounts(out, &self.engine.accounts(), self.engine.config().precision())?,
+            ["show", client] => {
+                let client_id = client.parse::<ClientId>()?;
+                match self.engine.account(&(self.tenant.clone(), client_id)) {
+                    Some(a) => writeln!(out, "{}\n{:.*}", output::ACCOUNT_HEADER, self.engine.config().precision(), a)?,
+                    None => writeln!(out, "no account for client {}", client_id)?,
+                }
+            }
+            ["tenant"] => self.tenant = None,
+            ["tenant", name] => self.tenant = Some(name.to_string()),
+            ["save", file] => {
+                self.engine.snapshot().save(Path::new(file), self.encryption.as_ref())?;
+                writeln!(out, "saved to {}", file)?;
+            }
+            ["load", file] => {
+                let snapshot = Snapshot::load(Path::new(file), self.encryption.as_ref())?;
+                *self = Repl {
+                    tenant: self.tenant.take(),
+                    encryption: self.encryption.take(),
+                    ..Repl::new(Engine::from_snapshot(snapshot, HistoryRetention::None))
+                };
+                writeln!(out, "loaded {} accounts", self.engine.accounts().len())?;
+            }
+            _ => writeln!(out, "unknown command, type `help` for the list of commands")?,
+        }
+        Ok(())
+    }
+
+    fn submit<W: Write>(
+        &mut self,
+        tx_type: TxType,
+        client_id: ClientId,
+        tx_id: TxId,
+        amount: Money,
+        out: &mut W,
+    ) -> Result<()> {
+        let creates_tx = tx_type == TxType::Deposit || tx_type == TxType::Withdrawal;
+        let t = Transaction {
+            tx_type,
+            client_id,
+            tx_id,
+            amount,
+            dispute: Default::default(),
+            tenant: self.tenant.clone(),
+            timestamp: None,
+            idempotency_key: None,
+            seq: None,
+            line: None,
+            metadata: Default::default(),
+        };
+        if creates_tx {
+            self.next_tx_id = self.next_tx_id.max(tx_id.saturating_add(1));
+        }
+        match self.engine.apply(t) {
+            Ok(a) => {
+                if creates_tx {
+                    writeln!(out, "tx {} applied", tx_id)?;
+                }
+                writeln!(out, "{}\n{}", output::ACCOUNT_HEADER, a)?
+            }
+            Err(e) => writeln!(out, "rejected: {}", e)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode},
+    layout::{Constraint, Layout},
+    widgets::{Block, Borders, Paragraph, Row, Table},
+    Frame,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+use txp::{events::{Outcome, ProcessingEvent}, AccountKey, Money, Result};
+
+/// number of accounts listed in the held funds table
+const TOP_ACCOUNTS: usize = 10;
+
+/// Live statistics computed from the processing event stream
+struct Dashboard {
+    started: Instant,
+    finished: Option<Instant>,
+    processed: u64,
+    rejected: u64,
+    // time of rejections within the last minute
+    recent_rejects: VecDeque<Instant>,
+    // held amount and locked flag of every account seen so far
+    accounts: HashMap<AccountKey, (Money, bool)>,
+}
+
+impl Dashboard {
+    fn new() -> Self {
+        Dashboard {
+            started: Instant::now(),
+            finished: None,
+            processed: 0,
+            rejected: 0,
+            recent_rejects: VecDeque::new(),
+            accounts: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, e: ProcessingEvent) {
+        self.processed += 1;
+        if e.outcome == Outcome::Rejected {
+            self.rejected += 1;
+            self.recent_rejects.push_back(Instant::now());
+        }
+        let a = e.account;
+        self.accounts
+            .insert((a.tenant, a.client_id), (a.held_amount, a.is_locked));
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let now = self.finished.unwrap_or_else(Instant::now);
+        while let Some(t) = self.recent_rejects.front() {
+            if now.duration_since(*t) > Duration::from_secs(60) {
+                self.recent_rejects.pop_front();
+            } else {
+                break;
+            }
+        }
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let throughput = if elapsed > 0.0 {
+            self.processed as f64 / elapsed
+        } else {
+            0.0
+        };
+        let frozen = self.accounts.values().filter(|(_, locked)| *locked).count();
+
+        let [stats_area, table_area] =
+            Layout::vertical([Constraint::Length(9), Constraint::Min(0)]).areas(frame.area());
+
+        let status = match self.finished {
+            Some(_) => "finished, press q to exit",
+            None => "processing, press q to close the dashboard",
+        };
+        let stats = Paragraph::new(format!(
+            "status:            {}\nelapsed:           {:.1}s\nprocessed:         {}\nthroughput:        {:.0} tx/s\nactive accounts:   {}\nfrozen accounts:   {}\nrejects/minute:    {} (total {})",
+            status,
+            elapsed,
+            self.processed,
+            throughput,
+            self.accounts.len(),
+            frozen,
+            self.recent_rejects.len(),
+            self.rejected
+        ))
+        .block(Block::default().title("txp").borders(Borders::ALL));
+        frame.render_widget(stats, stats_area);
+
+        let mut top: Vec<_> = self
+            .accounts
+            .iter()
+            .filter(|(_, (held, _))| *held > 0.0)
+            .collect();
+        top.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0));
+        let rows = top.into_iter().take(TOP_ACCOUNTS).map(|((tenant, client), (held, locked))| {
+            Row::new(vec![
+                tenant.clone().unwrap_or_default(),
+                client.to_string(),
+                format!("{:.4}", held),
+                locked.to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(12),
+                Constraint::Length(8),
+                Constraint::Length(16),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(vec!["tenant", "client", "held", "locked"]))
+        .block(
+            Block::default()
+                .title("top accounts by held funds")
+                .borders(Borders::ALL),
+        );
+        frame.render_widget(table, table_area);
+    }
+}
+
+/// Renders the dashboard until user presses `q`, processing results are printed after the dashboard is closed
+pub async fn run(mut events: UnboundedReceiver<ProcessingEvent>) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let mut dashboard = Dashboard::new();
+    let mut refresh = tokio::time::interval(Duration::from_millis(250));
+
+    let result = loop {
+        tokio::select! {
+            e = events.recv(), if dashboard.finished.is_none() => match e {
+                Some(e) => dashboard.record(e),
+                None => dashboard.finished = Some(Instant::now()),
+            },
+            _ = refresh.tick() => {
+                if let Err(e) = terminal.draw(|f| dashboard.render(f)) {
+                    break Err(e.into());
+                }
+                match event::poll(Duration::ZERO) {
+                    Ok(true) => match event::read() {
+                        Ok(Event::Key(k)) if k.code == KeyCode::Char('q') => break Ok(()),
+                        Ok(_) => (),
+                        Err(e) => break Err(e.into()),
+                    },
+                    Ok(false) => (),
+                    Err(e) => break Err(e.into()),
+                }
+            }
+        }
+    };
+
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use crate::{config::Config, engine::Engine, Money, Transaction, TxId, TxType};
+
+/// Row which made the computed balance of the client diverge from the expectation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// index of the row in the transactions of the client, starting with 0
+    pub index: usize,
+    pub transaction: Transaction,
+    /// total of the client computed right before and right after the row
+    pub total_before: Money,
+    pub total_after: Money,
+    /// rejection of the row, `None` when it was applied
+    pub error: Option<String>,
+}
+
+/// Result of `bisect`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BisectResult {
+    /// computed total matches the expectation
+    Matches,
+    /// even applying every row as it is does not give the expected total, the input itself does not match
+    InputDiverges { ledger_total: Money },
+    Diverges(Divergence),
+}
+
+/// Finds the first transaction of a single client after which its computed total no longer leads to `expected`.
+///
+/// The expectation is the total of the client after all `transactions`. The prefix of the first `k` rows is checked
+/// by processing it with the engine and adding the remaining rows as a plain ledger (every deposit, withdrawal and
+/// chargeback applied as it is), so a prefix diverges once the engine handled one of its rows differently than
+/// the expectation assumes. Prefixes are checked by binary search, re-running the deterministic engine each time.
+/// Totals closer than half of the smallest unit of the configured precision are equal.
+pub fn bisect(transactions: &[Transaction], expected: Money, config: &Config) -> BisectResult {
+    let tolerance = 0.5 * 10f64.powi(-(config.precision() as i32));
+    let ledger = ledger_deltas(transactions);
+    // ledger of rows from index `k` to the end
+    let mut rest = vec![0f64; transactions.len() + 1];
+    for k in (0..transactions.len()).rev() {
+        rest[k] = rest[k + 1] + ledger[k];
+    }
+    let diverges = |k: usize| (run(transactions, k, config).0 + rest[k] - expected as f64).abs() > tolerance;
+
+    if !diverges(transactions.len()) {
+        return BisectResult::Matches;
+    }
+    if diverges(0) {
+        return BisectResult::InputDiverges { ledger_total: rest[0] as Money };
+    }
+    // prefix of `good` rows matches, prefix of `bad` rows diverges
+    let (mut good, mut bad) = (0, transactions.len());
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        if diverges(mid) {
+            bad = mid;
+        } else {
+            good = mid;
+        }
+    }
+    let (total_after, error) = run(transactions, bad, config);
+    BisectResult::Diverges(Divergence {
+        index: bad - 1,
+        transaction: transactions[bad - 1].clone(),
+        total_before: run(transactions, good, config).0 as Money,
+        total_after: total_after as Money,
+        error,
+    })
+}
+
+// total of the client after processing first `k` transactions with the engine and the rejection of the last one
+fn run(transactions: &[Transaction], k: usize, config: &Config) -> (f64, Option<String>) {
+    let mut engine = Engine::default();
+    engine.set_config(config.clone());
+    let mut last = None;
+    for t in &transactions[..k] {
+        last = engine.apply(t.clone()).err().map(|e| e.message());
+    }
+    let total = engine.accounts().first().map_or(0.0, |a| a.total_amount);
+    (total as f64, last)
+}
+
+// change of the total by every row when all of them are applied as they are
+fn ledger_deltas(transactions: &[Transaction]) -> Vec<f64> {
+    let mut amounts = HashMap::<TxId, Money>::new();
+    transactions
+        .iter()
+        .map(|t| match t.tx_type {
+            TxType::Deposit => {
+                amounts.insert(t.tx_id, t.amount);
+                t.amount as f64
+            }
+            TxType::Withdrawal => {
+                amounts.insert(t.tx_id, t.amount);
+                -(t.amount as f64)
+            }
+            TxType::Chargeback => -(amounts.get(&t.tx_id).copied().unwrap_or_default() as f64),
+            TxType::Refund => t.amount as f64,
+            _ => 0.0,
+        })
+        .collect()
+}
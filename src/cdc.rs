@@ -0,0 +1,93 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{
+    csv::RawAccount,
+    events::{Outcome, ProcessingEvent},
+    snapshot::AccountSnapshot,
+    AccountKey, ClientId, Money, Result, TenantId, TxId,
+};
+
+/// Balances of the account in a change record
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Balances {
+    pub available: Money,
+    pub held: Money,
+    pub total: Money,
+    pub locked: bool,
+}
+
+impl From<&RawAccount> for Balances {
+    fn from(a: &RawAccount) -> Self {
+        Balances { available: a.available_amount, held: a.held_amount, total: a.total_amount, locked: a.is_locked }
+    }
+}
+
+/// Change of a single account by an applied transaction, one line of the change stream
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChangeRecord {
+    /// position of the record in the stream, starting with 0
+    pub offset: u64,
+    /// type of the applied transaction, e.g. `deposit`
+    pub op: String,
+    pub tx: TxId,
+    pub line: Option<u64>,
+    pub tenant: Option<TenantId>,
+    pub client: ClientId,
+    /// `None` when the account was created by the transaction
+    pub before: Option<Balances>,
+    pub after: Balances,
+    /// version of the account after the change, consumers skip records not newer than the version they applied
+    #[serde(default)]
+    pub version: u64,
+}
+
+/// Change-data-capture stream: JSON line with the balances before and after every applied transaction, built from
+/// the processing events, so downstream systems can maintain their own view of the accounts.
+/// Changes of a single account are in the order they were applied.
+pub struct ChangeStream<W: Write> {
+    w: W,
+    offset: u64,
+    // balances of every account after its last event
+    accounts: HashMap<AccountKey, Balances>,
+}
+
+impl<W: Write> ChangeStream<W> {
+    pub fn new(w: W) -> Self {
+        ChangeStream { w, offset: 0, accounts: HashMap::new() }
+    }
+
+    /// accounts start with the balances of the initial state (see `ProcessorOptions::initial`)
+    pub fn with_initial(mut self, initial: &HashMap<AccountKey, AccountSnapshot>) -> Self {
+        self.accounts.extend(initial.iter().map(|(key, s)| (key.clone(), Balances::from(&s.raw_account()))));
+        self
+    }
+
+    /// writes the change of the account when the transaction was applied
+    pub fn record(&mut self, e: &ProcessingEvent) -> Result<()> {
+        let after = Balances::from(&e.account);
+        let before = self.accounts.insert((e.account.tenant.clone(), e.account.client_id), after);
+        if e.outcome != Outcome::Applied {
+            return Ok(());
+        }
+        let record = ChangeRecord {
+            offset: self.offset,
+            op: e.tx_type.to_string(),
+            tx: e.tx_id,
+            line: e.line,
+            tenant: e.account.tenant.clone(),
+            client: e.account.client_id,
+            before,
+            after,
+            version: e.account.version,
+        };
+        serde_json::to_writer(&mut self.w, &record)?;
+        writeln!(self.w)?;
+        self.offset += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.w.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::{collections::HashMap, fmt, ops::RangeInclusive, path::Path, str::FromStr};
+
+use tokio::fs::{self, File};
+use tokio_stream::StreamExt;
+use tracing::debug;
+
+use crate::{config::Limits, AccountKey, ClientId, Money, Result, TenantId, TxType};
+
+/// Settings of a single client overriding the global configuration
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ClientSettings {
+    pub client: ClientId,
+    pub tenant: Option<TenantId>,
+    /// how much available funds can go below zero with withdrawals
+    pub credit_limit: Option<Money>,
+    /// maximum amount of a single deposit or withdrawal, overrides global `limits.max_amount`
+    pub max_amount: Option<Money>,
+    /// rolling 24 hours deposit limit, overrides global `limits.daily_deposit`
+    pub daily_deposit: Option<Money>,
+    /// rolling 24 hours withdrawal limit, overrides global `limits.daily_withdrawal`
+    pub daily_withdrawal: Option<Money>,
+    /// account starts locked
+    pub frozen: Option<bool>,
+    /// client is exempt from the global limits
+    pub vip: Option<bool>,
+}
+
+impl ClientSettings {
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.unwrap_or_default()
+    }
+
+    pub fn is_vip(&self) -> bool {
+        self.vip.unwrap_or_default()
+    }
+
+    /// maximum amount of a single transaction, given the global limit
+    pub fn max_amount(&self, global: Option<Money>) -> Option<Money> {
+        self.limit(self.max_amount, global)
+    }
+
+    /// rolling 24 hours limit of the transaction type, given the global limits
+    pub fn daily_limit(&self, tx_type: &TxType, global: &Limits) -> Option<Money> {
+        let own = match tx_type {
+            TxType::Deposit => self.daily_deposit,
+            TxType::Withdrawal => self.daily_withdrawal,
+            _ => None,
+        };
+        self.limit(own, global.daily(tx_type))
+    }
+
+    // client limit wins, vip clients are not subject to global limits
+    fn limit(&self, own: Option<Money>, global: Option<Money>) -> Option<Money> {
+        match own {
+            Some(max) => Some(max),
+            None if self.is_vip() => None,
+            None => global,
+        }
+    }
+}
+
+/// single row of the frozen list file
+#[derive(Deserialize, Debug)]
+struct FrozenClient {
+    client: ClientId,
+    #[serde(default)]
+    tenant: Option<TenantId>,
+}
+
+/// content of the TOML overrides file
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ClientsFile {
+    #[serde(default)]
+    clients: Vec<ClientSettings>,
+}
+
+/// Per client settings loaded from a sidecar file, so individual customers can be tuned without code changes.
+///
+/// CSV file has `client,tenant,credit_limit,max_amount,daily_deposit,daily_withdrawal,frozen,vip` columns
+/// (all but `client` can be empty or missing),
+/// TOML file has the same keys in `[[clients]]` tables.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClientOverrides {
+    settings: HashMap<AccountKey, ClientSettings>,
+}
+
+impl ClientOverrides {
+    pub fn new(settings: Vec<ClientSettings>) -> Self {
+        ClientOverrides {
+            settings: settings
+                .into_iter()
+                .map(|s| ((s.tenant.clone(), s.client), s))
+                .collect(),
+        }
+    }
+
+    /// loads overrides from `.toml` or CSV file
+    pub async fn load(path: &Path) -> Result<ClientOverrides> {
+        let settings = if path.extension().is_some_and(|e| e == "toml") {
+            let content = fs::read_to_string(path).await?;
+            toml::from_str::<ClientsFile>(&content)?.clients
+        } else {
+            let mut rdr = csv_async::AsyncReaderBuilder::new()
+                .flexible(true)
+                .trim(csv_async::Trim::All)
+                .create_deserializer(File::open(path).await?);
+            let mut records = rdr.deserialize::<ClientSettings>();
+            let mut settings = Vec::new();
+            while let Some(record) = records.next().await {
+                settings.push(record?);
+            }
+            settings
+        };
+        debug!("loaded settings of {} clients from {:?}", settings.len(), path);
+        Ok(ClientOverrides::new(settings))
+    }
+
+    /// loads list of clients whose accounts must start locked (sanctions or fraud holds)
+    ///
+    /// CSV file has `client` column and optional `tenant` column.
+    pub async fn load_frozen_list(path: &Path) -> Result<Vec<AccountKey>> {
+        let mut rdr = csv_async::AsyncReaderBuilder::new()
+            .flexible(true)
+            .trim(csv_async::Trim::All)
+            .create_deserializer(File::open(path).await?);
+        let mut records = rdr.deserialize::<FrozenClient>();
+        let mut keys = Vec::new();
+        while let Some(record) = records.next().await {
+            let c = record?;
+            keys.push((c.tenant, c.client));
+        }
+        debug!("loaded {} frozen clients from {:?}", keys.len(), path);
+        Ok(keys)
+    }
+
+    /// marks accounts of the clients as frozen, keeping their other settings
+    pub fn freeze(&mut self, keys: impl IntoIterator<Item = AccountKey>) {
+        for key in keys {
+            let settings = self.settings.entry(key.clone()).or_insert_with(|| ClientSettings {
+                client: key.1,
+                tenant: key.0,
+                ..Default::default()
+            });
+            settings.frozen = Some(true);
+        }
+    }
+
+    /// settings of the client, defaults when there are no overrides
+    pub fn get(&self, key: &AccountKey) -> ClientSettings {
+        self.settings.get(key).cloned().unwrap_or_else(|| ClientSettings {
+            client: key.1,
+            tenant: key.0.clone(),
+            ..Default::default()
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.settings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+}
+
+/// Name, segment and country of a client, read from the `--clients-meta` file
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ClientInfo {
+    pub client: ClientId,
+    #[serde(default)]
+    pub tenant: Option<TenantId>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub segment: String,
+    #[serde(default)]
+    pub country: String,
+}
+
+/// Details of the clients joined into the outputs (accounts, statements and reports), so people reviewing them
+/// do not have to look the client ids up. Rows without `tenant` apply to the client in every tenant.
+///
+/// CSV file has `client,tenant,name,segment,country` columns (all but `client` can be empty or missing).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClientDirectory {
+    clients: HashMap<AccountKey, ClientInfo>,
+}
+
+impl ClientDirectory {
+    /// columns appended to the header of the outputs, empty when there are no clients
+    pub const HEADER: &'static str = ",name,segment,country";
+
+    pub fn new(clients: Vec<ClientInfo>) -> Self {
+        ClientDirectory { clients: clients.into_iter().map(|c| ((c.tenant.clone(), c.client), c)).collect() }
+    }
+
+    pub async fn load(path: &Path) -> Result<ClientDirectory> {
+        let mut rdr = csv_async::AsyncReaderBuilder::new()
+            .flexible(true)
+            .trim(csv_async::Trim::All)
+            .create_deserializer(File::open(path).await?);
+        let mut records = rdr.deserialize::<ClientInfo>();
+        let mut clients = Vec::new();
+        while let Some(record) = records.next().await {
+            clients.push(record?);
+        }
+        debug!("loaded details of {} clients from {:?}", clients.len(), path);
+        Ok(ClientDirectory::new(clients))
+    }
+
+    /// details of the client of the account, the row of its tenant first
+    pub fn get(&self, key: &AccountKey) -> Option<&ClientInfo> {
+        self.clients.get(key).or_else(|| self.clients.get(&(None, key.1)))
+    }
+
+    /// `HEADER` when there are clients
+    pub fn header(&self) -> &'static str {
+        if self.is_empty() {
+            ""
+        } else {
+            Self::HEADER
+        }
+    }
+
+    /// columns of `header` for the account, empty values for unknown clients
+    pub fn columns(&self, key: &AccountKey) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let info = self.get(key);
+        let field = |value: Option<&String>| {
+            let value = value.map(String::as_str).unwrap_or_default();
+            if value.contains([',', '"', '\n']) {
+                format!(",\"{}\"", value.replace('"', "\"\""))
+            } else {
+                format!(",{}", value)
+            }
+        };
+        [info.map(|i| &i.name), info.map(|i| &i.segment), info.map(|i| &i.country)].into_iter().map(field).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+}
+
+/// Set of client ids given as comma separated list of ids and inclusive ranges, e.g. `7,42,100-200`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClientSet {
+    ranges: Vec<RangeInclusive<ClientId>>,
+}
+
+impl ClientSet {
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.ranges.iter().any(|r| r.contains(&client_id))
+    }
+}
+
+impl FromStr for ClientSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |id: &str| {
+            id.trim()
+                .parse::<ClientId>()
+                .map_err(|e| format!("invalid client id '{}': {}", id.trim(), e))
+        };
+        let ranges = s
+            .split(',')
+            .map(|part| match part.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (parse(from)?, parse(to)?);
+                    if from > to {
+                        return Err(format!("invalid client range '{}'", part.trim()));
+                    }
+                    Ok(from..=to)
+                }
+                None => parse(part).map(|id| id..=id),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(ClientSet { ranges })
+    }
+}
+
+/// same format as parsed, e.g. `7,42,100-200`
+impl fmt::Display for ClientSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, r) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if r.start() == r.end() {
+                write!(f, "{}", r.start())?;
+            } else {
+                write!(f, "{}-{}", r.start(), r.end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deterministic sample of clients: a client is either sampled with all its transactions or not at all.
+/// The same `seed` selects the same clients in every run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientSample {
+    /// share of the clients kept, between 0 and 1
+    pub rate: f64,
+    pub seed: u64,
+}
+
+impl ClientSample {
+    pub fn contains(&self, key: &AccountKey) -> bool {
+        // FNV-1a of the seed and the account key, finished with splitmix64 to spread the bits
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        let tenant = key.0.as_deref().unwrap_or_default().as_bytes();
+        for b in self.seed.to_le_bytes().iter().chain(tenant).chain(&key.1.to_le_bytes()) {
+            h = (h ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+        (h as f64) < self.rate * u64::MAX as f64
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    fmt,
+    future::{self, Future},
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use futures::future::{select, BoxFuture, Either};
+use tokio::sync::watch;
+
+use crate::rt;
+
+/// Source of the time of the account timers, e.g. release of the held disputes after `dispute_hold.seconds`.
+/// Features driven by the `timestamp` of the transactions (daily limits, settlement timeout) do not need it.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// completes once `now` reaches the `deadline`
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+}
+
+/// Wall clock of the runtime, the default of `ProcessorOptions::clock`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            rt::timeout_at(deadline, future::pending::<()>()).await;
+        })
+    }
+}
+
+/// Clock standing still until `advance`d, so tests move the time deterministically instead of sleeping
+#[derive(Debug)]
+pub struct MockClock {
+    now: watch::Sender<Instant>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock { now: watch::channel(Instant::now()).0 }
+    }
+}
+
+impl MockClock {
+    /// moves the time forward, waking the timers which are due
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            // the clock is gone, so its time never comes
+            if now.wait_for(|now| *now >= deadline).await.is_err() {
+                future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+/// Output of the future, `None` when the `clock` reached the deadline first
+pub async fn timeout_at<F: Future>(clock: &dyn Clock, deadline: Instant, future: F) -> Option<F::Output> {
+    match select(pin!(future), clock.sleep_until(deadline)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::{fs, sync::watch, time};
+use tracing::{debug, error, info};
+
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptHook;
+use crate::{
+    hold::{HoldConfig, RetryConfig},
+    money::{self, RoundingMode},
+    quarantine::QuarantineConfig,
+    ratelimit::RateLimitConfig,
+    rules::Rule,
+    sequence::SequenceConfig,
+    settlement::SettlementConfig,
+    Money, Result, TxType,
+};
+
+/// Processing rules loaded from the TOML configuration file.
+///
+/// Changes to the file can be picked up while running (see `ConfigWatcher`),
+/// new configuration is applied to transactions processed after the reload.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// version label of the configuration, reported when the configuration is (re)loaded
+    pub version: Option<String>,
+    pub limits: Limits,
+    pub idempotency: Idempotency,
+    /// ordering validation of the `seq` column, disabled when the section is missing
+    pub sequence: Option<SequenceConfig>,
+    /// holding of disputes arriving before their transaction, disabled when the section is missing
+    pub dispute_hold: Option<HoldConfig>,
+    /// retrying of disputes rejected for not yet seen transaction, disabled when the section is missing
+    pub dispute_retry: Option<RetryConfig>,
+    /// two-phase withdrawals settled by `settle` transactions, disabled when the section is missing
+    pub settlement: Option<SettlementConfig>,
+    /// rate limiting of the input rows, disabled when the section is missing
+    pub rate_limit: Option<RateLimitConfig>,
+    /// quarantine of accounts with too many anomalies, disabled when the section is missing
+    pub quarantine: Option<QuarantineConfig>,
+    /// what happens with remaining funds when account is closed
+    pub close_policy: ClosePolicy,
+    /// handling of dispute, resolve or chargeback of already charged back transaction
+    pub repeated_dispute: RepeatedDisputePolicy,
+    /// handling of deposits and withdrawals with zero or missing amount
+    pub zero_amount: ZeroAmountPolicy,
+    /// currency code of all amounts, selects the precision amounts are rounded to and written with
+    pub currency: Option<String>,
+    /// decimal places per currency code, extending (or overriding) the built-in `money::precision` table
+    pub precision: BTreeMap<String, usize>,
+    /// rounding of deposit and withdrawal amounts with more decimal places than the currency precision
+    pub rounding: RoundingMode,
+    /// user defined rules evaluated in the order of definition, see `Rule`
+    pub rules: Vec<Rule>,
+    /// validation script (relative to the configuration file), requires `scripting` feature
+    pub script: Option<PathBuf>,
+    /// compiled `script`, set when configuration is loaded from the file
+    #[cfg(feature = "scripting")]
+    #[serde(skip)]
+    pub hook: Option<Arc<ScriptHook>>,
+}
+
+/// Limits applied to every account
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// maximum amount of a single deposit or withdrawal
+    pub max_amount: Option<Money>,
+    /// maximum sum of client deposits within rolling 24 hours, requires `timestamp` column
+    pub daily_deposit: Option<Money>,
+    /// maximum sum of client withdrawals within rolling 24 hours, requires `timestamp` column
+    pub daily_withdrawal: Option<Money>,
+}
+
+/// Handling of the funds left on the account by `close` transaction
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClosePolicy {
+    /// account with any funds can not be closed
+    #[default]
+    RequireEmpty,
+    /// remaining available funds are swept when the account is closed
+    Sweep,
+}
+
+/// Handling of dispute, resolve or chargeback referencing already charged back transaction,
+/// e.g. chargeback notification re-sent by the partner
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatedDisputePolicy {
+    /// transaction is rejected and reported like any other invalid one
+    #[default]
+    Reject,
+    /// transaction is skipped and counted as duplicate
+    Ignore,
+}
+
+/// Handling of deposit or withdrawal with zero amount, missing amount in the input is read as zero
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroAmountPolicy {
+    /// transaction is applied without changing the balances
+    #[default]
+    Allow,
+    /// transaction is skipped with a warning and counted in the summary
+    Skip,
+    /// transaction is rejected with `AccountError::ZeroAmount`
+    Reject,
+}
+
+/// Handling of the transactions resubmitted with the same `idempotency_key`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Idempotency {
+    /// number of the most recent keys of applied transactions remembered for each client, or for all of them
+    /// with the global scope
+    pub window: usize,
+    pub scope: IdempotencyScope,
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        Idempotency { window: 1000, scope: IdempotencyScope::default() }
+    }
+}
+
+/// Accounts sharing the window of idempotency keys
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdempotencyScope {
+    /// each client has its own window, the same key can be used by different clients
+    #[default]
+    Client,
+    /// one window for all clients, the key is applied once across the whole input
+    Global,
+}
+
+impl Limits {
+    /// global rolling 24 hours limit of the transaction type
+    pub fn daily(&self, tx_type: &TxType) -> Option<Money> {
+        match tx_type {
+            TxType::Deposit => self.daily_deposit,
+            TxType::Withdrawal => self.daily_withdrawal,
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// number of decimal places of `currency` amounts, 4 when no currency is configured
+    pub fn precision(&self) -> usize {
+        match &self.currency {
+            Some(c) => self.precision.get(c).copied().unwrap_or_else(|| money::precision(c)),
+            None => money::DEFAULT_PRECISION,
+        }
+    }
+
+    /// parses configuration from TOML
+    pub fn parse(content: &str) -> Result<Config> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// reads and parses configuration file, compiling the validation script if there is one
+    pub async fn load(path: &PathBuf) -> Result<Config> {
+        let content = fs::read_to_string(path).await?;
+        #[cfg_attr(not(feature = "scripting"), allow(unused_mut))]
+        let mut config = Config::parse(&content)?;
+        if let Some(script) = &config.script {
+            let script = path.parent().map_or(script.clone(), |dir| dir.join(script));
+            #[cfg(feature = "scripting")]
+            {
+                let source = fs::read_to_string(&script).await?;
+                config.hook = Some(Arc::new(ScriptHook::compile(&source)?));
+            }
+            #[cfg(not(feature = "scripting"))]
+            return Err(format!("script {:?} configured but `scripting` feature is not enabled", script).into());
+        }
+        Ok(config)
+    }
+}
+
+/// Receiving side of the configuration updates, always holds the effective configuration
+pub type ConfigReceiver = watch::Receiver<Arc<Config>>;
+
+/// Returns receiver which always holds `config`, for runs without configuration reloading
+pub fn fixed(config: Config) -> ConfigReceiver {
+    let (_, receiver) = watch::channel(Arc::new(config));
+    receiver
+}
+
+/// Watches configuration file for changes and publishes every successfully parsed version
+pub struct ConfigWatcher {}
+
+impl ConfigWatcher {
+    /// Loads configuration file and spawns a task checking every `interval` whether the file was modified.
+    /// Configuration which fails to load is reported and ignored, previous configuration stays in effect.
+    /// The task ends when all receivers are dropped.
+    pub async fn spawn(path: PathBuf, interval: Duration) -> Result<ConfigReceiver> {
+        let config = Config::load(&path).await?;
+        let mut modified = fs::metadata(&path).await?.modified()?;
+        info!(path = ?path, version = ?config.version, generation = 0, "configuration loaded");
+
+        let (sender, receiver) = watch::channel(Arc::new(config));
+
+        tokio::spawn(async move {
+            let mut generation = 0u64;
+            let mut ticks = time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if sender.is_closed() {
+                    debug!("no more configuration receivers, stop watching {:?}", &path);
+                    break;
+                }
+                let m = match fs::metadata(&path).await.and_then(|m| m.modified()) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        error!("failed checking configuration file {:?}: {}", &path, e);
+                        continue;
+                    }
+                };
+                if m == modified {
+                    continue;
+                }
+                modified = m;
+                match Config::load(&path).await {
+                    Ok(config) => {
+                        generation += 1;
+                        info!(path = ?path, version = ?config.version, generation, "configuration reloaded");
+                        sender.send_replace(Arc::new(config));
+                    }
+                    Err(e) => error!("failed reloading configuration {:?}, keeping previous one: {}", &path, e),
+                }
+            }
+        });
+
+        Ok(receiver)
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    env, fmt,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+
+use crate::{manifest::to_hex, Result};
+
+/// environment variable with the encryption key, used when no key file is given
+pub const KEY_ENV: &str = "TXP_ENCRYPTION_KEY";
+
+/// first bytes of every encrypted file, files without them are read as plain text
+pub const MAGIC: &[u8] = b"TXPENC1\n";
+
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM key of snapshots and audit logs, 32 bytes written as 64 hex digits
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+// the key never ends up in the logs
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    /// parses 64 hex digits, surrounding whitespace is ignored
+    pub fn parse(hex: &str) -> Result<EncryptionKey> {
+        match from_hex(hex.trim()) {
+            Some(bytes) if bytes.len() == 32 => {
+                let mut key = [0; 32];
+                key.copy_from_slice(&bytes);
+                Ok(EncryptionKey(key))
+            }
+            _ => Err("encryption key has to be 64 hex digits (32 bytes)".into()),
+        }
+    }
+
+    /// reads the key from `path`
+    pub fn from_file(path: &Path) -> Result<EncryptionKey> {
+        let content = fs::read_to_string(path).map_err(|e| format!("failed reading key file {:?}: {}", path, e))?;
+        EncryptionKey::parse(&content).map_err(|e| format!("key file {:?}: {}", path, e).into())
+    }
+
+    /// reads the key from `path` when given, otherwise from `KEY_ENV`; `None` when neither is set
+    pub fn load(path: Option<&Path>) -> Result<Option<EncryptionKey>> {
+        match (path, env::var(KEY_ENV)) {
+            (Some(path), _) => EncryptionKey::from_file(path).map(Some),
+            (None, Ok(hex)) => EncryptionKey::parse(&hex).map(Some).map_err(|e| format!("{}: {}", KEY_ENV, e).into()),
+            (None, Err(_)) => Ok(None),
+        }
+    }
+
+    /// `MAGIC`, random nonce and the ciphertext (with authentication tag) of `data`
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher().encrypt(&nonce, data).map_err(|_| "encryption failed")?;
+        let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// decrypts data written by `encrypt`, fails when it was changed or encrypted with another key
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let data = data.strip_prefix(MAGIC).ok_or("data is not encrypted")?;
+        if data.len() < NONCE_LEN {
+            return Err("encrypted data is truncated".into());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "decryption failed, wrong key or damaged data".into())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+/// `data` starts with `MAGIC`
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Content of the file written either in plain text or encrypted with `key`
+pub fn read_file(path: &Path, key: Option<&EncryptionKey>) -> Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    match (is_encrypted(&data), key) {
+        (false, _) => Ok(data),
+        (true, Some(key)) => key.decrypt(&data).map_err(|e| format!("{:?}: {}", path, e).into()),
+        (true, None) => Err(format!("{:?} is encrypted, set {} or pass the key file", path, KEY_ENV).into()),
+    }
+}
+
+/// Writes `data` to the file, encrypted when `key` is given, replacing it only once all of it is on disk
+pub fn write_file(path: &Path, data: &[u8], key: Option<&EncryptionKey>) -> Result<()> {
+    let data = match key {
+        Some(key) => key.encrypt(data)?,
+        None => data.to_vec(),
+    };
+    // a crash while writing leaves the previous file in place
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(&data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Encrypts a single line of a line based file, the result is hex and contains no newlines
+pub fn encrypt_line(key: &EncryptionKey, line: &str) -> Result<String> {
+    Ok(to_hex(&key.encrypt(line.as_bytes())?))
+}
+
+/// Reverse of `encrypt_line`, lines which are not hex are returned unchanged
+pub fn decrypt_line(key: Option<&EncryptionKey>, line: &str) -> Result<String> {
+    match from_hex(line) {
+        Some(data) if is_encrypted(&data) => {
+            let key = key.ok_or_else(|| format!("line is encrypted, set {} or pass the key file", KEY_ENV))?;
+            Ok(String::from_utf8(key.decrypt(&data)?)?)
+        }
+        _ => Ok(line.to_string()),
+    }
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
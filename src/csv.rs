@@ -1,12 +1,37 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
+use csv_async::ByteRecord;
 use futures::Future;
-use tokio::fs::File;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, BufReader},
+    sync::watch,
+};
 use tokio_stream::StreamExt;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
 
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 
-use crate::{TxType, ClientId, Money, TxId};
+#[cfg(feature = "iso20022")]
+use crate::iso20022::Iso20022Importer;
+use crate::{drain::DrainingReader, import};
+#[cfg(feature = "xlsx")]
+use crate::xlsx;
+use crate::{TxType, ClientId, Money, TenantId, Timestamp, TxId};
+
+/// columns every input has to have in its header row
+pub const REQUIRED_COLUMNS: [&str; 3] = ["type", "client", "tx"];
+
+/// columns the input can have in addition to the required ones, other columns are ignored
+pub const OPTIONAL_COLUMNS: [&str; 5] = ["amount", "tenant", "timestamp", "idempotency_key", "seq"];
 
 /// Representation of the single row in the input CSV file
 ///
@@ -26,34 +51,237 @@ pub struct RawTransaction {
     #[serde(rename(deserialize = "amount"))]
     // work around to handle transactions types where amount is not specified
     pub amount: Option<String>,
+
+    // optional column; files without it belong to the default (or `--tenant` tagged) namespace
+    #[serde(default, rename(deserialize = "tenant"))]
+    pub tenant: Option<TenantId>,
+
+    // optional column with unix time (seconds) of the transaction, required by time based limits
+    #[serde(default, rename(deserialize = "timestamp"))]
+    pub timestamp: Option<Timestamp>,
+
+    // optional column, retried submissions of the transaction carry the same key
+    #[serde(default, rename(deserialize = "idempotency_key"))]
+    pub idempotency_key: Option<String>,
+
+    // optional monotonically increasing sequence number of the row in the feed
+    #[serde(default, rename(deserialize = "seq"))]
+    pub seq: Option<u64>,
+
+    // line of the row in the input, set by the reader
+    #[serde(skip)]
+    pub line: Option<u64>,
+
+    // columns not in the input format, set by the reader when asked to keep them
+    #[serde(skip)]
+    pub metadata: HashMap<String, String>,
 }
 
-#[derive(Serialize, Debug)]
-pub(crate) struct RawAccount {
-    #[serde(rename(deserialize = "client"))]
+impl RawTransaction {
+    /// deposit, withdrawal and refund amount must be a number >= 0.0, other types ignore it
+    pub fn check_amount(&self) -> std::result::Result<(), String> {
+        match (&self.tx_type, &self.amount) {
+            (TxType::Deposit | TxType::Withdrawal | TxType::Refund, Some(amount)) => match amount.parse::<f32>() {
+                Ok(value) if value >= 0.0 => Ok(()),
+                Ok(value) => Err(format!("amount '{}' < 0.0", value)),
+                Err(_) => Err(format!("cannot convert amount '{}' to f32", amount)),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Final state of the account as written to the output
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RawAccount {
+    // tenant is not part of the account row, it selects the output file
+    #[serde(skip)]
+    pub tenant: Option<TenantId>,
+
+    #[serde(rename(serialize = "client"))]
     pub client_id: ClientId,
 
-    #[serde(rename(deserialize = "available"))]
+    #[serde(rename(serialize = "available"))]
     // The total funds that are available for trading, staking, withdrawal, etc. This
     // should be equal to the total - held amounts
     pub available_amount: Money,
 
-    //#[serde(rename(deserialize = "held"), with = "rust_decimal::serde::str")]
-    #[serde(rename(deserialize = "held"))]
+    //#[serde(rename(serialize = "held"), with = "rust_decimal::serde::str")]
+    #[serde(rename(serialize = "held"))]
     // The total funds that are held for dispute. This should be equal to total - available amounts
     pub held_amount: Money,
 
-    #[serde(rename(deserialize = "total"))]
+    #[serde(rename(serialize = "total"))]
     // The total funds that are available or held. This should be equal to available + held
     pub total_amount: Money,
 
-    #[serde(rename(deserialize = "locked"))]
+    #[serde(rename(serialize = "locked"))]
     pub is_locked: bool,
+
+    #[serde(rename(serialize = "closed"))]
+    pub is_closed: bool,
+
+    // time of the last applied transaction with timestamp
+    #[serde(skip)]
+    pub last_activity: Option<Timestamp>,
+
+    // no activity for configured number of days, see `output::mark_dormant`
+    #[serde(skip)]
+    pub is_dormant: bool,
+
+    // applied transactions (and corrections) of the account so far, increases with every change of the state
+    #[serde(skip)]
+    pub version: u64,
+}
+
+impl RawAccount {
+    /// `closed`, `locked`, `dormant` or `active`
+    pub fn status(&self) -> &'static str {
+        if self.is_closed {
+            "closed"
+        } else if self.is_locked {
+            "locked"
+        } else if self.is_dormant {
+            "dormant"
+        } else {
+            "active"
+        }
+    }
+}
+
+/// Why the input row could not be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// missing or extra column, value of the wrong type
+    Malformed,
+    /// deposit or withdrawal amount is not a number >= 0.0
+    InvalidAmount,
+    /// more malformed rows than allowed by `CsvTransactionReader::skip_bad_lines`
+    TooManyErrors,
+    /// header row is missing some of the `REQUIRED_COLUMNS`, reported before any row is read
+    SchemaMismatch,
+    /// document in other format than CSV (e.g. ISO 20022 XML) could not be mapped into rows
+    Unmappable,
+}
+
+/// Input row which could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// line of the row in the input, when known
+    pub line: Option<u64>,
+    /// offset of the first byte of the row in the input, when known
+    pub byte: Option<u64>,
+    /// the row as it was read (fields joined with `,`), when it could be split into fields
+    pub row: Option<String>,
+    /// client and transaction of the row, when they could be parsed
+    pub client_id: Option<ClientId>,
+    pub tx_id: Option<TxId>,
+    pub message: String,
 }
 
-pub struct CsvTransactionReader {}
+impl ParseError {
+    /// stable code of the error kind, kept unchanged when messages are reworded
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            ParseErrorKind::Malformed => "E2001",
+            ParseErrorKind::InvalidAmount => "E2002",
+            ParseErrorKind::TooManyErrors => "E2003",
+            ParseErrorKind::SchemaMismatch => "E2004",
+            ParseErrorKind::Unmappable => "E2005",
+        }
+    }
+
+    /// error stops the reader even when bad lines are skipped
+    pub fn is_fatal(&self) -> bool {
+        matches!(self.kind, ParseErrorKind::TooManyErrors | ParseErrorKind::SchemaMismatch | ParseErrorKind::Unmappable)
+    }
+}
+
+/// error with its stable code and position, e.g. `E2002 line 3 (byte 38): amount '-3' < 0.0, row 'deposit,1,2,-3'`
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())?;
+        match (self.line, self.byte) {
+            (Some(line), Some(byte)) => write!(f, " line {} (byte {}):", line, byte)?,
+            (Some(line), None) => write!(f, " line {}:", line)?,
+            _ => (),
+        }
+        write!(f, " {}", self.message)?;
+        if let Some(row) = &self.row {
+            write!(f, ", row '{}'", row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Called with every parse error, before the reader stops or skips the row
+pub type ParseErrorHandler = Arc<dyn Fn(&ParseError) + Send + Sync>;
+
+/// Reads transactions from CSV input. Associated `process_*` functions use the default reader,
+/// `with_error_handler` creates reader which reports parse errors before it stops.
+#[derive(Default, Clone)]
+pub struct CsvTransactionReader {
+    on_error: Option<ParseErrorHandler>,
+    // maximum number of skipped rows, the reader stops on the first error when not set
+    max_errors: Option<u64>,
+    // skipped rows, shared by the clones reading files of the same run
+    skipped: Arc<AtomicU64>,
+    // skipped rows by `ParseError::code`, shared like `skipped`
+    skipped_codes: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+    // (expected, found) names of the columns renamed in the input
+    columns: Vec<(String, String)>,
+    // clients of the accounts in imported ISO 20022 and statement files
+    accounts: HashMap<String, ClientId>,
+    // values of unknown columns are kept in `RawTransaction::metadata`
+    keep_metadata: bool,
+    // inputs end after the current row once `true` is sent
+    drain: Option<watch::Receiver<bool>>,
+}
 
 impl CsvTransactionReader {
+    pub fn with_error_handler(on_error: ParseErrorHandler) -> Self {
+        CsvTransactionReader { on_error: Some(on_error), ..Default::default() }
+    }
+
+    /// Rows which can not be parsed are reported to the error handler and skipped, the reader stops with
+    /// `ParseErrorKind::TooManyErrors` once more than `max_errors` rows were skipped (in all inputs read by the reader)
+    pub fn skip_bad_lines(self, max_errors: u64) -> Self {
+        CsvTransactionReader { max_errors: Some(max_errors), ..self }
+    }
+
+    /// Reads column `found` of the input as the `expected` one of the input format, for each `(expected, found)` pair
+    pub fn with_columns(self, columns: Vec<(String, String)>) -> Self {
+        CsvTransactionReader { columns, ..self }
+    }
+
+    /// Maps accounts of the imported ISO 20022 and OFX/QIF files to clients, see `import::client`
+    pub fn with_accounts(self, accounts: HashMap<String, ClientId>) -> Self {
+        CsvTransactionReader { accounts, ..self }
+    }
+
+    /// Keeps values of the columns which are not in the input format (e.g. merchant or reference of partner files)
+    /// in `RawTransaction::metadata`, so they can be written to rejects, audit log and statements
+    pub fn keep_metadata(self) -> Self {
+        CsvTransactionReader { keep_metadata: true, ..self }
+    }
+
+    /// Inputs read by the reader end after the row being read once `true` is sent to `drain`, as if they were
+    /// closed there (see `DrainingReader`), so the rows already read can be processed before the shutdown
+    pub fn drain_on(self, drain: watch::Receiver<bool>) -> Self {
+        CsvTransactionReader { drain: Some(drain), ..self }
+    }
+
+    /// number of rows skipped so far
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// number of rows skipped so far by the code of their parse error, e.g. `E2002`
+    pub fn skipped_by_code(&self) -> BTreeMap<String, u64> {
+        let codes = self.skipped_codes.lock().unwrap_or_else(|e| e.into_inner());
+        codes.iter().map(|(code, count)| (code.to_string(), *count)).collect()
+    }
 
     /// Data processing function. Function calls panic! on the first error it gets.
     /// 
@@ -63,6 +291,46 @@ impl CsvTransactionReader {
         data_file_path: PathBuf,
         raw_transaction_handler: F,
     ) 
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        CsvTransactionReader::default().read_data_file(data_file_path, raw_transaction_handler).await
+    }
+
+    /// Same as `process_data_file` but reads CSV data from any reader, e.g. stdin for continuous processing.
+    ///
+    /// `reader` source of the CSV data, including the header row
+    /// `raw_transaction_handler` function that process the raw transaction
+    pub async fn process_reader<R, F, Fut>(reader: R, raw_transaction_handler: F)
+    where
+        R: AsyncRead + Unpin + Send,
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        CsvTransactionReader::default().read(reader, raw_transaction_handler).await
+    }
+
+    /// true when the file is `.zip` or `.tar` archive which can be processed with `process_archive`
+    pub fn is_archive(path: &Path) -> bool {
+        path.extension().is_some_and(|e| e == "zip" || e == "tar")
+    }
+
+    /// Same as `process_data_file` but reads all CSV files stored in `.zip` or `.tar` archive, streaming them
+    /// without extracting to disk. Files are processed in lexical order of their names, each with its own header row.
+    ///
+    /// `archive_path` full path to the archive
+    /// `raw_transaction_handler` function that process the raw transaction
+    pub async fn process_archive<F, Fut>(archive_path: PathBuf, raw_transaction_handler: F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        CsvTransactionReader::default().read_archive(archive_path, raw_transaction_handler).await
+    }
+
+    /// Same as `process_data_file`, reporting parse errors to the error handler of the reader
+    pub async fn read_data_file<F, Fut>(&self, data_file_path: PathBuf, raw_transaction_handler: F)
     where
         F: Fn(Option<RawTransaction>) -> Fut,
         Fut: Future<Output = std::result::Result<(), String>>,
@@ -80,38 +348,358 @@ impl CsvTransactionReader {
 
         trace!("data file opened; creating csv reader");
 
+        self.read(file, raw_transaction_handler).await;
+    }
+
+    /// Same as `process_reader`, reporting parse errors to the error handler of the reader
+    pub async fn read<R, F, Fut>(&self, reader: R, raw_transaction_handler: F)
+    where
+        R: AsyncRead + Unpin + Send,
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        match &self.drain {
+            Some(drain) => self.process_records(DrainingReader::new(reader, drain.clone()), &raw_transaction_handler).await,
+            None => self.process_records(reader, &raw_transaction_handler).await,
+        }
+        CsvTransactionReader::finish(&raw_transaction_handler).await;
+    }
+
+    /// Same as `process_archive`, reporting parse errors to the error handler of the reader
+    pub async fn read_archive<F, Fut>(&self, archive_path: PathBuf, raw_transaction_handler: F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        debug!("processing archive: {:?}", &archive_path);
+
+        let r = if archive_path.extension().is_some_and(|e| e == "zip") {
+            self.process_zip(&archive_path, &raw_transaction_handler).await
+        } else {
+            self.process_tar(&archive_path, &raw_transaction_handler).await
+        };
+        if let Err(e) = r {
+            error!("failed reading archive: {}", e);
+            panic!("failed reading archive: {e}");
+        }
+
+        CsvTransactionReader::finish(&raw_transaction_handler).await;
+    }
+
+    /// Same as `read_data_file` for ISO 20022 pain.001 or camt.054 XML file, mapped into rows with `Iso20022Importer`.
+    /// Rows with invalid amount are skipped or stop the reader like the rows of CSV files.
+    #[cfg(feature = "iso20022")]
+    pub async fn read_iso20022<F, Fut>(&self, path: PathBuf, raw_transaction_handler: F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        debug!("processing ISO 20022 file: {:?}", &path);
+        let importer = Iso20022Importer::with_accounts(self.accounts.clone());
+        self.read_imported(&path, |bytes| importer.import(&String::from_utf8_lossy(bytes)), &raw_transaction_handler)
+            .await;
+    }
+
+    /// Same as `read_iso20022` for OFX or QIF statement, see `import::read_statement`
+    pub async fn read_statement<F, Fut>(&self, path: PathBuf, raw_transaction_handler: F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        debug!("processing statement file: {:?}", &path);
+        let read = |bytes: &[u8]| import::read_statement(&path, &String::from_utf8_lossy(bytes), &self.accounts);
+        self.read_imported(&path, read, &raw_transaction_handler).await;
+    }
+
+    /// Same as `read_data_file` for the first sheet of `.xlsx` workbook, its first row is the header
+    /// (see `xlsx::read_sheet` for the cells); parse errors refer to the row numbers of the sheet.
+    #[cfg(feature = "xlsx")]
+    pub async fn read_xlsx<F, Fut>(&self, path: PathBuf, raw_transaction_handler: F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        debug!("processing workbook: {:?}", &path);
+
+        let unmappable = |message: String| ParseError {
+            kind: ParseErrorKind::Unmappable,
+            line: None,
+            byte: None,
+            row: None,
+            client_id: None,
+            tx_id: None,
+            message: format!("{:?}: {}", path, message),
+        };
+        let rows = match tokio::fs::read(&path).await.map_err(crate::Error::from).and_then(|b| xlsx::read_sheet(&b)) {
+            Ok(rows) => rows,
+            Err(e) => self.fail(unmappable(e.to_string())),
+        };
+        let mut rows = rows.into_iter();
+        let headers = match rows.next() {
+            Some((line, header)) => {
+                let headers = header.iter().map(|h| self.column(h.as_bytes())).collect::<ByteRecord>();
+                if let Err(message) = check_columns(&headers) {
+                    self.fail(ParseError { kind: ParseErrorKind::SchemaMismatch, line: Some(line), ..unmappable(message) });
+                }
+                headers
+            }
+            None => self.fail(unmappable("the first sheet is empty".to_string())),
+        };
+        for (line, row) in rows {
+            let record = row.iter().collect::<ByteRecord>();
+            let checked = check_record(&headers, &record, Some(line), None).map(|t| self.metadata(t, &headers, &record));
+            self.handle(checked, Some(line), &raw_transaction_handler).await;
+        }
+
+        CsvTransactionReader::finish(&raw_transaction_handler).await;
+    }
+
+    // rows of the file mapped by `import`, files which can not be mapped stop the reader with `Unmappable` error
+    async fn read_imported<I, F, Fut>(&self, path: &Path, import: I, raw_transaction_handler: &F)
+    where
+        I: Fn(&[u8]) -> crate::Result<Vec<RawTransaction>>,
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        let unmappable = |message: String| ParseError {
+            kind: ParseErrorKind::Unmappable,
+            line: None,
+            byte: None,
+            row: None,
+            client_id: None,
+            tx_id: None,
+            message: format!("{:?}: {}", path, message),
+        };
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) => self.fail(unmappable(e.to_string())),
+        };
+        let rows = match import(&bytes) {
+            Ok(rows) => rows,
+            Err(e) => self.fail(unmappable(e.to_string())),
+        };
+        for t in rows {
+            if let Err(message) = t.check_amount() {
+                let err = ParseError {
+                    kind: ParseErrorKind::InvalidAmount,
+                    client_id: Some(t.client_id),
+                    tx_id: Some(t.tx_id),
+                    message,
+                    ..unmappable(String::new())
+                };
+                match self.max_errors {
+                    Some(max_errors) => self.skip(err, max_errors),
+                    None => self.fail(err),
+                }
+                continue;
+            }
+            trace!("processing raw transaction: {:?}", &t);
+            if let Err(e) = raw_transaction_handler(Some(t)).await {
+                error!("failed handling raw transaction: {}", e);
+                panic!("failed handling raw transaction: {e}");
+            }
+        }
+
+        CsvTransactionReader::finish(raw_transaction_handler).await;
+    }
+
+    // zip has central directory, so entries can be read directly in any order
+    async fn process_zip<F, Fut>(&self, path: &Path, raw_transaction_handler: &F) -> crate::Result<()>
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        let mut zip = async_zip::base::read::seek::ZipFileReader::with_tokio(BufReader::new(File::open(path).await?)).await?;
+        let mut entries = Vec::new();
+        for (index, entry) in zip.file().entries().iter().enumerate() {
+            let name = entry.filename().as_str()?;
+            if !entry.dir()? && is_csv(name) {
+                entries.push((name.to_string(), index));
+            }
+        }
+        entries.sort();
+
+        for (name, index) in entries {
+            debug!("processing archive entry: {}", name);
+            let reader = zip.reader_without_entry(index).await?;
+            self.process_records(reader.compat(), raw_transaction_handler).await;
+        }
+        Ok(())
+    }
+
+    // tar can only be read sequentially: names are listed first, then the archive is scanned
+    // for the following name as many times as needed (once when entries are already sorted)
+    async fn process_tar<F, Fut>(&self, path: &Path, raw_transaction_handler: &F) -> crate::Result<()>
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        let mut names = Vec::new();
+        let mut archive = tokio_tar::Archive::new(File::open(path).await?);
+        let mut entries = archive.entries()?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if entry.header().entry_type().is_file() && is_csv(&name) {
+                names.push(name);
+            }
+        }
+        names.sort();
+
+        let mut pending = names.iter().peekable();
+        while pending.peek().is_some() {
+            let mut archive = tokio_tar::Archive::new(File::open(path).await?);
+            let mut entries = archive.entries()?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                if pending.next_if_eq(&&name).is_some() {
+                    debug!("processing archive entry: {}", name);
+                    self.process_records(entry, raw_transaction_handler).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // reads all records of one CSV stream, without signalling the end of data
+    async fn process_records<R, F, Fut>(&self, reader: R, raw_transaction_handler: &F)
+    where
+        R: AsyncRead + Unpin + Send,
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
         let mut rdr = csv_async::AsyncReaderBuilder::new()
             .delimiter(b',')
             .flexible(true)
             .trim(csv_async::Trim::All)
             .has_headers(true)
-            .create_deserializer(file);
+            .create_reader(reader);
 
-        let mut records = rdr.deserialize::<RawTransaction>();
+        // rows are read as raw records first, so parse errors can quote the offending row
+        let header_error = |kind, message| ParseError {
+            kind,
+            line: Some(1),
+            byte: Some(0),
+            row: None,
+            client_id: None,
+            tx_id: None,
+            message,
+        };
+        let headers = match rdr.byte_headers().await {
+            Ok(headers) => headers.iter().map(|h| self.column(h)).collect::<ByteRecord>(),
+            Err(e) => self.fail(header_error(ParseErrorKind::Malformed, e.to_string())),
+        };
+        // the header is checked before reading any row, not to fail on every one of them
+        if let Err(message) = check_columns(&headers) {
+            self.fail(header_error(ParseErrorKind::SchemaMismatch, message));
+        }
+        let mut records = rdr.byte_records();
 
         while let Some(record) = records.next().await {
-            match record {
-                Ok(t) => {
-                    trace!("processing raw transaction: {:?}", &t);
-                    let r = raw_transaction_handler(Some(t)).await;
-                    match r {
-                        Ok(_) => continue,
-                        Err(e) => {
-                            error!("failed handling raw transaction: {}", e);
-                            panic!("failed handling raw transaction: {e}");
-                        }
-                    }
-                }
-                Err(err) => {
-                    error!("error reading CSV file: {}", err);
-                    panic!("error reading CSV file: {err}");
+            let pos = match &record {
+                Ok(record) => record.position().cloned(),
+                Err(e) => e.position().cloned(),
+            };
+            let (line, byte) = (pos.as_ref().map(|p| p.line()), pos.as_ref().map(|p| p.byte()));
+            let checked = match record {
+                Err(e) => Err(ParseError {
+                    kind: ParseErrorKind::Malformed,
+                    line,
+                    byte,
+                    row: None,
+                    client_id: None,
+                    tx_id: None,
+                    message: e.to_string(),
+                }),
+                Ok(record) => check_record(&headers, &record, line, byte).map(|t| self.metadata(t, &headers, &record)),
+            };
+            self.handle(checked, line, raw_transaction_handler).await;
+        }
+
+        debug!("all data processed from input file");
+    }
+
+    // passes the parsed row to the handler, parse errors skip the row or stop the reader
+    async fn handle<F, Fut>(
+        &self,
+        checked: std::result::Result<RawTransaction, ParseError>,
+        line: Option<u64>,
+        raw_transaction_handler: &F,
+    ) where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        match checked {
+            Ok(mut t) => {
+                t.line = line;
+                trace!("processing raw transaction: {:?}", &t);
+                if let Err(e) = raw_transaction_handler(Some(t)).await {
+                    error!("failed handling raw transaction: {}", e);
+                    panic!("failed handling raw transaction: {e}");
                 }
             }
+            Err(err) => match self.max_errors {
+                Some(max_errors) => self.skip(err, max_errors),
+                None => self.fail(err),
+            },
         }
+    }
 
-        debug!("all data processed from input file");
+    // name of the input column in the input format
+    fn column<'a>(&'a self, found: &'a [u8]) -> &'a [u8] {
+        self.columns
+            .iter()
+            .find(|(_, f)| f.as_bytes() == found)
+            .map_or(found, |(expected, _)| expected.as_bytes())
+    }
+
+    // values of the named columns not in the input format, when they are kept
+    fn metadata(&self, mut t: RawTransaction, headers: &ByteRecord, record: &ByteRecord) -> RawTransaction {
+        if self.keep_metadata {
+            t.metadata = headers
+                .iter()
+                .zip(record.iter())
+                .map(|(h, v)| (String::from_utf8_lossy(h), v))
+                .filter(|(h, _)| !h.is_empty() && !REQUIRED_COLUMNS.contains(&h.as_ref()) && !OPTIONAL_COLUMNS.contains(&h.as_ref()))
+                .map(|(h, v)| (h.into_owned(), String::from_utf8_lossy(v).into_owned()))
+                .collect();
+        }
+        t
+    }
+
+    // reports the parse error and skips the row, stops the reader when there are more than `max_errors` of them
+    fn skip(&self, err: ParseError, max_errors: u64) {
+        if let Some(on_error) = &self.on_error {
+            on_error(&err);
+        }
+        let skipped = self.skipped.fetch_add(1, Ordering::Relaxed) + 1;
+        *self.skipped_codes.lock().unwrap_or_else(|e| e.into_inner()).entry(err.code()).or_default() += 1;
+        warn!("skipping row: {}", err);
+        if skipped > max_errors {
+            self.fail(ParseError {
+                kind: ParseErrorKind::TooManyErrors,
+                message: format!("{} malformed rows, more than the allowed {}", skipped, max_errors),
+                ..err
+            });
+        }
+    }
+
+    // reports the parse error to the error handler and stops the reader
+    fn fail(&self, err: ParseError) -> ! {
+        if let Some(on_error) = &self.on_error {
+            on_error(&err);
+        }
+        error!("error reading CSV file: {}", err);
+        panic!("error reading CSV file: {err}");
+    }
 
-        // inform that we have finished processing all data
+    // inform that we have finished processing all data
+    async fn finish<F, Fut>(raw_transaction_handler: &F)
+    where
+        F: Fn(Option<RawTransaction>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
         let r = raw_transaction_handler(Option::None).await;
         match r {
             Ok(_) => (),
@@ -121,6 +709,72 @@ impl CsvTransactionReader {
             }
         }
         
-        debug!("finished processing input file");
+        debug!("finished processing input data");
+    }
+}
+
+// row of the input format read with the (mapped) header, deposit and withdrawal amounts are checked
+fn check_record(
+    headers: &ByteRecord,
+    record: &ByteRecord,
+    line: Option<u64>,
+    byte: Option<u64>,
+) -> std::result::Result<RawTransaction, ParseError> {
+    // built only for the failing rows
+    let row = || record.iter().map(String::from_utf8_lossy).collect::<Vec<_>>().join(",");
+    let t = record.deserialize::<RawTransaction>(Some(headers)).map_err(|e| ParseError {
+        kind: ParseErrorKind::Malformed,
+        line,
+        byte,
+        row: Some(row()),
+        client_id: None,
+        tx_id: None,
+        message: e.to_string(),
+    })?;
+    match t.check_amount() {
+        Ok(_) => Ok(t),
+        Err(message) => Err(ParseError {
+            kind: ParseErrorKind::InvalidAmount,
+            line,
+            byte,
+            row: Some(row()),
+            client_id: Some(t.client_id),
+            tx_id: Some(t.tx_id),
+            message,
+        }),
+    }
+}
+
+// lists found and expected columns when some of the required ones are missing, with a hint how to map them
+fn check_columns(headers: &ByteRecord) -> std::result::Result<(), String> {
+    let found: Vec<_> = headers.iter().map(String::from_utf8_lossy).collect();
+    let missing: Vec<_> = REQUIRED_COLUMNS.iter().filter(|c| !found.iter().any(|f| f == *c)).collect();
+    if missing.is_empty() {
+        return Ok(());
     }
+    let unknown: Vec<_> = found
+        .iter()
+        .filter(|f| !f.is_empty() && !REQUIRED_COLUMNS.contains(&f.as_ref()) && !OPTIONAL_COLUMNS.contains(&f.as_ref()))
+        .collect();
+    let hint = if found.first().is_some_and(|f| f.parse::<TxType>().is_ok()) {
+        "the header row seems to be missing".to_string()
+    } else if unknown.is_empty() {
+        "add the missing columns to the header row".to_string()
+    } else {
+        let mapping: Vec<_> = missing.iter().zip(&unknown).map(|(m, u)| format!("{}={}", m, u)).collect();
+        format!("map renamed columns with the column mapping option, e.g. `--columns {}`", mapping.join(","))
+    };
+    let quote = |columns: &[&str]| columns.iter().map(|c| format!("`{}`", c)).collect::<Vec<_>>().join(", ");
+    Err(format!(
+        "header row does not match the input format, missing {}; found {}, expected {} and optionally {}; {}",
+        quote(&missing.iter().map(|c| **c).collect::<Vec<_>>()),
+        quote(&found.iter().map(|c| c.as_ref()).collect::<Vec<_>>()),
+        quote(&REQUIRED_COLUMNS),
+        quote(&OPTIONAL_COLUMNS),
+        hint
+    ))
+}
+
+fn is_csv(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|e| e == "csv")
 }
@@ -0,0 +1,123 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+/// Bounded set of the most recently seen idempotency keys, the oldest key is forgotten when the window is full
+#[derive(Debug, Default)]
+pub(crate) struct DedupeWindow {
+    // keys in the order they were added, oldest first
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl DedupeWindow {
+    pub fn contains(&self, key: &str) -> bool {
+        self.seen.contains(key)
+    }
+
+    /// remembers `key`, keeping at most `capacity` keys
+    pub fn insert(&mut self, key: String, capacity: usize) {
+        if capacity == 0 || !self.seen.insert(key.clone()) {
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Window of idempotency keys shared by all accounts, used with `idempotency.scope = "global"`
+#[derive(Debug, Default)]
+pub(crate) struct SharedDedupeWindow {
+    keys: Mutex<SharedKeys>,
+    // notified when a pending key is committed or released
+    resolved: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SharedKeys {
+    applied: DedupeWindow,
+    // keys of the transactions being applied by an account right now
+    pending: HashSet<String>,
+}
+
+impl SharedDedupeWindow {
+    /// takes `key` before its transaction is applied, false when a transaction with the key was already applied;
+    /// when another account is applying a transaction with the key, waits for its outcome
+    pub fn reserve(&self, key: &str) -> bool {
+        let mut keys = self.lock();
+        loop {
+            if keys.applied.contains(key) {
+                return false;
+            }
+            if !keys.pending.contains(key) {
+                keys.pending.insert(key.to_string());
+                return true;
+            }
+            // the key is held only while the other account applies the transaction, it does not wait on anything
+            keys = self.resolved.wait(keys).expect("dedupe window lock poisoned");
+        }
+    }
+
+    /// remembers reserved `key` of the applied transaction, keeping at most `capacity` keys
+    pub fn commit(&self, key: &str, capacity: usize) {
+        let mut keys = self.lock();
+        keys.pending.remove(key);
+        keys.applied.insert(key.to_string(), capacity);
+        self.resolved.notify_all();
+    }
+
+    /// forgets reserved `key` of the transaction which was not applied, so it can be retried
+    pub fn release(&self, key: &str) {
+        self.lock().pending.remove(key);
+        self.resolved.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SharedKeys> {
+        self.keys.lock().expect("dedupe window lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{mpsc, Arc},
+        thread,
+        time::Duration,
+    };
+
+    use crate::dedupe::SharedDedupeWindow;
+
+    /// key of the rejected transaction is not a duplicate for the account waiting on it
+    #[test]
+    fn waiting_account_takes_the_released_key() {
+        let window = Arc::new(SharedDedupeWindow::default());
+        assert!(window.reserve("k"));
+
+        let (sender, receiver) = mpsc::channel();
+        let waiting = window.clone();
+        let handle = thread::spawn(move || sender.send(waiting.reserve("k")).expect("receiver dropped"));
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err(), "key taken while pending");
+
+        window.release("k");
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+        handle.join().expect("waiting thread failed");
+    }
+
+    /// key of the applied transaction is a duplicate for the account waiting on it
+    #[test]
+    fn waiting_account_sees_the_committed_key() {
+        let window = Arc::new(SharedDedupeWindow::default());
+        assert!(window.reserve("k"));
+
+        let waiting = window.clone();
+        let handle = thread::spawn(move || waiting.reserve("k"));
+        window.commit("k", 10);
+        assert!(!handle.join().expect("waiting thread failed"));
+        assert!(!window.reserve("k"));
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::watch,
+};
+
+/// Reader of the input which ends at the end of the current row once the drain is requested (`true` is sent),
+/// so no row is cut in half. Used by `CsvTransactionReader::drain_on` to stop taking new rows before the shutdown.
+pub struct DrainingReader<R> {
+    inner: R,
+    drain: Pin<Box<dyn Future<Output = ()> + Send>>,
+    drained: bool,
+    // the last byte read was the end of the row (or nothing was read yet)
+    at_row_end: bool,
+}
+
+impl<R> DrainingReader<R> {
+    pub fn new(inner: R, mut drain: watch::Receiver<bool>) -> Self {
+        DrainingReader {
+            inner,
+            drain: Box::pin(async move {
+                // dropped sender never requests the drain
+                if drain.wait_for(|drain| *drain).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            }),
+            drained: false,
+            at_row_end: true,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DrainingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        if self.at_row_end && !self.drained {
+            self.drained = self.drain.as_mut().poll(cx).is_ready();
+        }
+        if self.at_row_end && self.drained {
+            // end of the input
+            return Poll::Ready(Ok(()));
+        }
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        if let Some(last) = buf.filled()[before..].last() {
+            self.at_row_end = *last == b'\n';
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,752 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Instant,
+};
+
+use tracing::{error, info, info_span, trace, warn};
+
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptDecision;
+use crate::{
+    account::{Account, AccountError, DisputeState},
+    clients::{ClientOverrides, ClientSettings},
+    config::{Config, IdempotencyScope, RepeatedDisputePolicy, ZeroAmountPolicy},
+    csv::RawAccount,
+    dedupe::{DedupeWindow, SharedDedupeWindow},
+    events::Outcome,
+    history::{HistoryEntry, HistoryRetention},
+    hold::{HoldBuffer, HoldConfig, RetryQueue},
+    ledger::{InMemoryStore, Ledger, LedgerError, LedgerStore},
+    limits::RollingTotals,
+    money,
+    quarantine::Quarantined,
+    rules::RuleState,
+    seen::SeenStore,
+    settlement::{PendingWithdrawals, SettlementConfig},
+    snapshot::{AccountSnapshot, Snapshot},
+    stats::ProcessingStats,
+    summary::RunSummary,
+    top::TopN,
+    tx::{AccountReport, NegativeBalance},
+    AccountKey, Money, Timestamp, Transaction, TxId, TxType,
+};
+
+/// metadata key of the reason of the manual adjustment, see `Engine::adjust`
+pub const ADJUSTMENT_REASON: &str = "adjustment_reason";
+
+/// Synthetic transaction of the manual adjustment: deposit crediting positive `amount` or withdrawal debiting negative one,
+/// with the reason in its metadata
+pub fn adjustment(key: &AccountKey, tx_id: TxId, amount: Money, reason: &str) -> Transaction {
+    Transaction {
+        tx_type: if amount < 0.0 { TxType::Withdrawal } else { TxType::Deposit },
+        client_id: key.1,
+        tx_id,
+        amount: amount.abs(),
+        dispute: Default::default(),
+        tenant: key.0.clone(),
+        timestamp: None,
+        idempotency_key: None,
+        seq: None,
+        line: None,
+        metadata: HashMap::from([(ADJUSTMENT_REASON.to_string(), reason.to_string())]),
+    }
+}
+
+/// State of a single account: balances, transactions which can be disputed and (optionally) the history.
+/// Used by the account processing task as well as by the synchronous `Engine`.
+#[derive(Debug)]
+pub(crate) struct AccountState {
+    pub key: AccountKey,
+    // balances and transactions made on this account which can be disputed
+    pub ledger: Box<dyn Ledger>,
+    // ordered list of processed transactions, kept only on request
+    pub history: Vec<HistoryEntry>,
+    // rejected transactions, kept only when `keep_rejects` is set
+    pub rejects: Vec<HistoryEntry>,
+    pub keep_rejects: bool,
+    pub summary: RunSummary,
+    pub stats: ProcessingStats,
+    // applied transactions persisted across runs
+    pub seen: Option<Arc<SeenStore>>,
+    retention: HistoryRetention,
+    rules: RuleState,
+    settings: ClientSettings,
+    daily: RollingTotals,
+    dedupe: DedupeWindow,
+    // keys of all accounts, used with the global idempotency scope
+    pub(crate) global_dedupe: Arc<SharedDedupeWindow>,
+    held: HoldBuffer,
+    // disputes rejected for unknown transaction, retried when it arrives
+    parked: RetryQueue,
+    // withdrawals waiting for settlement
+    pending: PendingWithdrawals,
+    // amount refunded so far by withdrawal id
+    refunded: BTreeMap<TxId, Money>,
+    // time of the last applied transaction with timestamp
+    pub last_activity: Option<Timestamp>,
+    // first transaction which made available or total negative
+    pub negative: Option<NegativeBalance>,
+    // largest applied deposits and withdrawals
+    pub largest: TopN<Transaction>,
+    // rejected transactions and the ones leaving the account overdrawn, counted when `[quarantine]` is configured
+    anomalies: u64,
+    // set by the anomaly over the limit, following transactions are rejected
+    pub quarantined: Option<Quarantined>,
+}
+
+/// Outcome of a single transaction submitted to the account together with the account state right after it
+pub(crate) type Applied = (Transaction, core::result::Result<Outcome, AccountError>, Account);
+
+impl AccountState {
+    pub fn new(key: AccountKey, retention: HistoryRetention, settings: ClientSettings, store: &dyn LedgerStore) -> Self {
+        let account = Account {
+            client_id: key.1,
+            is_locked: settings.is_frozen(),
+            is_pre_frozen: settings.is_frozen(),
+            credit_limit: settings.credit_limit.unwrap_or_default(),
+            ..Default::default()
+        };
+        AccountState {
+            ledger: store.open(&key, account),
+            key,
+            history: Vec::new(),
+            rejects: Vec::new(),
+            keep_rejects: false,
+            summary: RunSummary::default(),
+            stats: ProcessingStats::default(),
+            seen: None,
+            retention,
+            rules: RuleState::default(),
+            settings,
+            daily: RollingTotals::default(),
+            dedupe: DedupeWindow::default(),
+            global_dedupe: Arc::default(),
+            held: HoldBuffer::default(),
+            parked: RetryQueue::default(),
+            pending: PendingWithdrawals::default(),
+            refunded: BTreeMap::new(),
+            last_activity: None,
+            negative: None,
+            largest: TopN::default(),
+            anomalies: 0,
+            quarantined: None,
+        }
+    }
+
+    /// Applies transaction like `apply`, holding disputes, resolves and chargebacks referencing unknown transaction
+    /// when `dispute_hold` is configured. Returns all transactions processed as a result, in order.
+    pub fn submit(&mut self, t: Transaction, config: &Config, now: Instant) -> Vec<Applied> {
+        let mut applied = Vec::new();
+        let references_tx = matches!(t.tx_type, TxType::Dispute | TxType::Resolve | TxType::Chargeback);
+        if let (Some(hold), true) = (&config.dispute_hold, references_tx) {
+            if self.ledger.transaction(t.tx_id).is_none() {
+                self.expire_held(hold, config, now, &mut applied);
+                trace!("account {} holding {:?}", self.key.1, t);
+                self.held.hold(t, now);
+                return applied;
+            }
+        }
+        let tx_id = t.tx_id;
+        let moves_funds = t.tx_type == TxType::Deposit || t.tx_type == TxType::Withdrawal;
+        if self.apply_parking(t, config, &mut applied) && moves_funds {
+            // disputes waiting for this transaction
+            let mut released = self.held.release(tx_id);
+            released.extend(self.parked.release(tx_id));
+            for t in released {
+                self.apply_parking(t, config, &mut applied);
+            }
+        }
+        // transaction which arrived counts as one more message for the remaining held ones
+        if let Some(hold) = &config.dispute_hold {
+            self.expire_held(hold, config, now, &mut applied);
+        }
+        applied
+    }
+
+    fn expire_held(&mut self, hold: &HoldConfig, config: &Config, now: Instant, applied: &mut Vec<Applied>) {
+        for t in self.held.tick(hold, now) {
+            self.apply_parking(t, config, applied);
+        }
+    }
+
+    /// applies held transactions which waited too long
+    pub fn expire(&mut self, config: &Config, now: Instant) -> Vec<Applied> {
+        let mut applied = Vec::new();
+        if let Some(hold) = &config.dispute_hold {
+            for t in self.held.expired(hold, now) {
+                self.apply_parking(t, config, &mut applied);
+            }
+        }
+        applied
+    }
+
+    /// applies all held transactions, at the end of the stream
+    pub fn flush(&mut self, config: &Config) -> Vec<Applied> {
+        let mut applied = Vec::new();
+        for t in self.held.drain() {
+            self.apply_parking(t, config, &mut applied);
+        }
+        applied
+    }
+
+    /// time when the next held transaction expires
+    pub fn hold_deadline(&self, config: &Config) -> Option<Instant> {
+        config.dispute_hold.as_ref().and_then(|hold| self.held.deadline(hold))
+    }
+
+    // applies transaction, parking it for retry when it references unknown transaction and retries are configured
+    fn apply_parking(&mut self, t: Transaction, config: &Config, applied: &mut Vec<Applied>) -> bool {
+        let r = self.process(t.clone(), config, config.dispute_retry.is_some());
+        let ok = r.is_ok();
+        if let (Err(AccountError::NoTxForDispute(_)), Some(retry)) = (&r, &config.dispute_retry) {
+            // nothing is reported until it is retried or given up
+            trace!("account {} parking {:?} for retry", self.key.1, t);
+            if let Some(dropped) = self.parked.park(t, retry.capacity) {
+                warn!("retry queue of account {} is full, dropping {:?}", self.key.1, dropped);
+                self.give_up(&dropped);
+            }
+            return false;
+        }
+        applied.push((t, r, self.ledger.account()));
+        ok
+    }
+
+    // counts the parked transaction whose transaction never arrived as rejected
+    fn give_up(&mut self, t: &Transaction) {
+        let e = AccountError::NoTxForDispute(t.tx_id);
+        self.summary.record(Some(&e));
+        self.stats.record(t, Some(&e));
+        self.keep_rejected(t, &e);
+    }
+
+    // keeps the rejected transaction in `rejects` and history, when they are kept
+    fn keep_rejected(&mut self, t: &Transaction, e: &AccountError) {
+        if self.retention == HistoryRetention::All || self.keep_rejects {
+            let entry = HistoryEntry { dispute: self.dispute_state(t), ..HistoryEntry::rejected(t.clone(), &self.ledger.account(), e) };
+            if self.keep_rejects {
+                self.rejects.push(entry.clone());
+            }
+            if self.retention == HistoryRetention::All {
+                self.history.push(entry);
+            }
+        }
+    }
+
+    /// applies transaction to the account using rules and limits from `config`, on error account stays unchanged
+    pub fn apply(&mut self, t: Transaction, config: &Config) -> core::result::Result<(), AccountError> {
+        self.process(t, config, false).map(|_| ())
+    }
+
+    // same as `apply`, telling apart transactions skipped as duplicates; with `park` transaction referencing unknown
+    // transaction is rejected without being counted, it waits for retry
+    fn process(&mut self, t: Transaction, config: &Config, park: bool) -> core::result::Result<Outcome, AccountError> {
+        let global = match (&t.idempotency_key, config.idempotency.scope) {
+            (Some(key), IdempotencyScope::Global) => Some(key.clone()),
+            _ => None,
+        };
+        // global key stays pending while the transaction is applied, accounts with the same key wait for the outcome
+        if let Some(key) = &global {
+            if !self.global_dedupe.reserve(key) {
+                info!("account {} tx {} already applied (idempotency key {:?})", self.key.1, t.tx_id, key);
+                self.summary.record_duplicate();
+                self.stats.record_skipped(&t);
+                return Ok(Outcome::Skipped);
+            }
+        }
+        let r = self.process_transaction(t, config, park);
+        match (&global, &r) {
+            (Some(key), Ok(Outcome::Applied)) => self.global_dedupe.commit(key, config.idempotency.window),
+            (Some(key), _) => self.global_dedupe.release(key),
+            (None, _) => (),
+        }
+        r
+    }
+
+    fn process_transaction(&mut self, mut t: Transaction, config: &Config, park: bool) -> core::result::Result<Outcome, AccountError> {
+        let _span = info_span!("tx", tx_id = t.tx_id, tx_type = %t.tx_type).entered();
+        trace!("account {} processing {:?}", self.key.1, t);
+        let client_scope = config.idempotency.scope == IdempotencyScope::Client;
+        // retried submission of the already applied transaction is acknowledged without applying it again
+        if client_scope && t.idempotency_key.as_ref().is_some_and(|k| self.dedupe.contains(k)) {
+            info!("account {} tx {} already applied (idempotency key {:?})", self.key.1, t.tx_id, t.idempotency_key);
+            self.summary.record_duplicate();
+            self.stats.record_skipped(&t);
+            return Ok(Outcome::Skipped);
+        }
+        if let (Some(settlement), Some(now)) = (&config.settlement, t.timestamp) {
+            self.settle_expired(settlement, now);
+        }
+        if config.repeated_dispute == RepeatedDisputePolicy::Ignore
+            && self.dispute_state(&t) == Some(DisputeState::ChargedBack)
+        {
+            info!("account {} tx {} already charged back, {} ignored", self.key.1, t.tx_id, t.tx_type);
+            self.summary.record_repeated_dispute();
+            self.stats.record_skipped(&t);
+            return Ok(Outcome::Skipped);
+        }
+        let moves_funds = t.tx_type == TxType::Deposit || t.tx_type == TxType::Withdrawal;
+        if moves_funds && self.seen.as_ref().is_some_and(|s| s.contains(&self.key.0, self.key.1, t.tx_id)) {
+            info!("account {} tx {} already applied in previous run", self.key.1, t.tx_id);
+            self.summary.record_duplicate();
+            self.stats.record_skipped(&t);
+            return Ok(Outcome::Skipped);
+        }
+        let zero_amount = moves_funds && t.amount == 0.0;
+        if zero_amount && config.zero_amount == ZeroAmountPolicy::Skip {
+            warn!("account {} {} {} without amount skipped", self.key.1, t.tx_type, t.tx_id);
+            self.summary.record_zero_amount();
+            self.stats.record_skipped(&t);
+            return Ok(Outcome::Skipped);
+        }
+        if moves_funds || t.tx_type == TxType::Refund {
+            t.amount = money::round(t.amount, config.precision(), config.rounding);
+        }
+        let mut rejected_by = match zero_amount && config.zero_amount == ZeroAmountPolicy::Reject {
+            true => Some(AccountError::ZeroAmount(t.tx_id)),
+            false => self.rules.evaluate(&config.rules, &t).map(|rule| AccountError::RejectedByRule(t.tx_id, rule)),
+        };
+        if self.quarantined.is_some() {
+            rejected_by = Some(AccountError::Quarantined(self.key.1));
+        }
+        #[cfg(feature = "scripting")]
+        if let (None, Some(hook)) = (&rejected_by, &config.hook) {
+            match hook.validate(&t, &self.raw_account()) {
+                ScriptDecision::Allow => (),
+                ScriptDecision::Reject(reason) => {
+                    rejected_by = Some(AccountError::RejectedByScript(t.tx_id, reason))
+                }
+                ScriptDecision::Modify(amount) if moves_funds => {
+                    trace!("script changed amount of tx {} from {} to {}", t.tx_id, t.amount, amount);
+                    t.amount = money::round(amount, config.precision(), config.rounding);
+                }
+                // dispute, resolve and chargeback do not carry amount
+                ScriptDecision::Modify(_) => (),
+            }
+        }
+        if rejected_by.is_none() && moves_funds {
+            rejected_by = self.check_limits(&t, config);
+        }
+        if rejected_by.is_none() {
+            rejected_by = self.check_reference(&t);
+        }
+        if rejected_by.is_none() && t.tx_type == TxType::Refund {
+            match self.refundable(t.tx_id) {
+                Ok(left) if left <= 0.0 || t.amount > left => {
+                    rejected_by = Some(AccountError::RefundOverWithdrawal(t.tx_id, t.amount, left.max(0.0)))
+                }
+                // refund without amount returns everything left of the withdrawal
+                Ok(left) if t.amount == 0.0 => t.amount = left,
+                Ok(_) => (),
+                Err(e) => rejected_by = Some(e),
+            }
+        }
+        let rejected = rejected_by.is_some();
+        let before = self.ledger.account();
+        let r = match rejected_by {
+            Some(e) => Err(e),
+            None => before.process_transaction(&t, self.ledger.transaction(t.tx_id).as_ref(), config).and_then(|outcome| {
+                let mut a = outcome.apply(self.ledger.as_mut())?;
+                a.version = before.version + 1;
+                self.ledger.update_account(a.clone())?;
+                if moves_funds {
+                    self.ledger.record_tx(t.clone())?;
+                }
+                Ok(a)
+            }),
+        };
+        let r = match r {
+            Err(e @ AccountError::NoTxForDispute(_)) if park => return Err(e),
+            r => r,
+        };
+        self.summary.record(r.as_ref().err());
+        self.stats.record(&t, r.as_ref().err());
+        let result = match r {
+            Ok(a) => {
+                if a.is_locked && !before.is_locked {
+                    self.stats.accounts_frozen += 1;
+                }
+                if let (TxType::Chargeback, Some(charged)) = (&t.tx_type, self.ledger.transaction(t.tx_id)) {
+                    self.stats.record_chargeback(charged.amount);
+                }
+                if self.negative.is_none() && (a.available_amount < 0.0 || a.total_amount < 0.0) {
+                    info!("account {} balance went negative with tx {}", self.key.1, t.tx_id);
+                    self.negative = Some(NegativeBalance {
+                        tx_id: t.tx_id,
+                        tx_type: t.tx_type.clone(),
+                        available_amount: a.available_amount,
+                        total_amount: a.total_amount,
+                    });
+                }
+                self.last_activity = self.last_activity.max(t.timestamp);
+                if let (true, Some(now)) = (moves_funds, t.timestamp) {
+                    self.daily.add(&t.tx_type, t.amount, now);
+                }
+                match t.tx_type {
+                    TxType::Withdrawal if config.settlement.is_some() => self.pending.insert(t.tx_id, t.timestamp),
+                    TxType::Settle => {
+                        self.pending.remove(t.tx_id);
+                    }
+                    TxType::Refund => *self.refunded.entry(t.tx_id).or_default() += t.amount,
+                    _ => (),
+                }
+                // rejected transactions are not remembered, so they can be retried
+                if let (true, Some(key)) = (client_scope, &t.idempotency_key) {
+                    self.dedupe.insert(key.clone(), config.idempotency.window);
+                }
+                if moves_funds && self.largest.capacity() > 0 {
+                    self.largest.push(t.amount, t.clone());
+                }
+                if let (true, Some(seen)) = (moves_funds, &self.seen) {
+                    seen.insert(&self.key.0, self.key.1, t.tx_id);
+                }
+                if self.retention >= HistoryRetention::Applied {
+                    let entry = HistoryEntry { dispute: self.dispute_state(&t), ..HistoryEntry::new(t.clone(), &a) };
+                    self.history.push(entry);
+                }
+                Ok(Outcome::Applied)
+            }
+            Err(e) => {
+                warn!("{}", e);
+                self.keep_rejected(&t, &e);
+                Err(e)
+            }
+        };
+        if let (Some(quarantine), None) = (&config.quarantine, &self.quarantined) {
+            let a = self.ledger.account();
+            if result.is_err() || a.available_amount + a.credit_limit < 0.0 {
+                self.anomalies += 1;
+            }
+            if self.anomalies > quarantine.max_anomalies {
+                warn!("account {} quarantined after {} anomalies, the last tx {}", self.key.1, self.anomalies, t.tx_id);
+                self.quarantined = Some(Quarantined { tx_id: t.tx_id, tx_type: t.tx_type.clone(), anomalies: self.anomalies });
+            }
+        }
+        // store only Deposit and Withdrawal transactions for possible dispute/resolve/chargeback events, applied ones
+        // are recorded together with the new balances above
+        // for simplicity we assume that we receive only once given transaction
+        // transactions rejected by the rules or over the limits are never disputable, they did not move any funds
+        if moves_funds && !rejected && !matches!(result, Ok(_) | Err(AccountError::Storage(_))) {
+            if let Err(e) = self.ledger.record_tx(t) {
+                error!("account {} rejected tx not recorded: {}", self.key.1, e);
+            }
+        }
+        trace!("account state: {:?}", self.ledger.account());
+        result
+    }
+
+    /// checks single transaction and rolling 24 hours limits of the deposit or withdrawal
+    fn check_limits(&mut self, t: &Transaction, config: &Config) -> Option<AccountError> {
+        if let Some(max) = self.settings.max_amount(config.limits.max_amount) {
+            if t.amount > max {
+                return Some(AccountError::AmountLimitExceeded(t.tx_id, max, t.amount));
+            }
+        }
+        // daily limits can only be enforced for rows with timestamp
+        let now = t.timestamp?;
+        let max = self.settings.daily_limit(&t.tx_type, &config.limits)?;
+        let total = self.daily.total(&t.tx_type, now) + t.amount;
+        if total > max {
+            return Some(AccountError::DailyLimitExceeded(t.tx_id, max, total));
+        }
+        None
+    }
+
+    /// settle has to reference a pending withdrawal, the pending withdrawal can be neither disputed nor refunded
+    /// until it is settled and refunded withdrawal can not be disputed
+    fn check_reference(&self, t: &Transaction) -> Option<AccountError> {
+        match t.tx_type {
+            TxType::Settle if !self.pending.contains(t.tx_id) => Some(AccountError::TxNotPending(t.tx_id)),
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback | TxType::Refund if self.pending.contains(t.tx_id) => {
+                Some(AccountError::TxPending(t.tx_id))
+            }
+            TxType::Dispute if self.refunded.contains_key(&t.tx_id) => Some(AccountError::TxRefunded(t.tx_id)),
+            _ => None,
+        }
+    }
+
+    /// state of the transaction referenced by dispute, resolve or chargeback
+    fn dispute_state(&self, t: &Transaction) -> Option<DisputeState> {
+        match t.tx_type {
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback => self.ledger.transaction(t.tx_id).map(|r| r.dispute),
+            _ => None,
+        }
+    }
+
+    /// amount of the withdrawal `tx_id` which is not refunded yet
+    fn refundable(&self, tx_id: TxId) -> core::result::Result<Money, AccountError> {
+        let withdrawal = self.ledger.transaction(tx_id).ok_or(AccountError::NoTxForDispute(tx_id))?;
+        if withdrawal.tx_type != TxType::Withdrawal {
+            return Err(AccountError::NotRefundable(tx_id));
+        }
+        if withdrawal.dispute.in_dispute() {
+            return Err(AccountError::TxInDispute(tx_id));
+        }
+        Ok(withdrawal.amount - self.refunded.get(&tx_id).copied().unwrap_or_default())
+    }
+
+    /// settles withdrawals pending longer than the settlement timeout at `now`
+    fn settle_expired(&mut self, settlement: &SettlementConfig, now: Timestamp) {
+        for tx_id in self.pending.expired(settlement, now) {
+            let account = self.ledger.account();
+            let settle = account.settle(tx_id, self.ledger.transaction(tx_id).as_ref()).and_then(|a| {
+                let a = Account { version: account.version + 1, ..a };
+                Ok(self.ledger.update_account(a)?)
+            });
+            match settle {
+                Ok(()) => info!("account {} withdrawal {} settled after timeout", self.key.1, tx_id),
+                Err(e) => warn!("withdrawal {} not settled after timeout: {}", tx_id, e),
+            }
+        }
+    }
+
+    /// current state of the account in the output representation
+    pub fn raw_account(&self) -> RawAccount {
+        RawAccount {
+            tenant: self.key.0.clone(),
+            last_activity: self.last_activity,
+            ..self.ledger.account().into()
+        }
+    }
+
+    /// continues from the balances and disputable transactions of the snapshot, the account stays locked
+    /// when the client settings freeze it
+    pub fn restore(&mut self, s: AccountSnapshot) -> core::result::Result<(), LedgerError> {
+        self.last_activity = s.last_activity;
+        let current = self.ledger.account();
+        let account = Account {
+            available_amount: s.available_amount,
+            held_amount: s.held_amount,
+            total_amount: s.total_amount,
+            is_locked: s.is_locked || current.is_locked,
+            is_pre_frozen: s.is_pre_frozen || current.is_pre_frozen,
+            is_closed: s.is_closed,
+            version: s.version,
+            pending_amount: s.pending_amount,
+            ..current
+        };
+        self.ledger.update_account(account)?;
+        for t in s.transactions {
+            self.ledger.record_tx(t)?;
+        }
+        for tx_id in s.pending {
+            let timestamp = self.ledger.transaction(tx_id).and_then(|t| t.timestamp);
+            self.pending.insert(tx_id, timestamp);
+        }
+        self.refunded = s.refunded;
+        Ok(())
+    }
+
+    /// balances and disputable transactions of the account, ordered by id
+    pub fn snapshot(&self) -> AccountSnapshot {
+        let mut transactions = self.ledger.transactions();
+        transactions.sort_by_key(|t| t.tx_id);
+        let a = self.ledger.account();
+        AccountSnapshot {
+            tenant: self.key.0.clone(),
+            client_id: a.client_id,
+            available_amount: a.available_amount,
+            held_amount: a.held_amount,
+            total_amount: a.total_amount,
+            is_locked: a.is_locked,
+            is_pre_frozen: a.is_pre_frozen,
+            is_closed: a.is_closed,
+            last_activity: self.last_activity,
+            version: a.version,
+            pending_amount: a.pending_amount,
+            pending: self.pending.ids(),
+            refunded: self.refunded.clone(),
+            transactions,
+        }
+    }
+
+    /// transactions kept for disputes, history and rejects of the account
+    pub fn retained(&self) -> usize {
+        self.ledger.tx_count() + self.history.len() + self.rejects.len()
+    }
+
+    pub fn into_report(mut self) -> AccountReport {
+        let unresolved = self.parked.drain();
+        unresolved.iter().for_each(|t| self.give_up(t));
+        let mut disputed: Vec<_> = self.ledger.transactions().into_iter().filter(|t| t.dispute.in_dispute()).collect();
+        disputed.sort_by_key(|t| t.tx_id);
+        AccountReport {
+            account: self.raw_account(),
+            history: self.history,
+            rejects: self.rejects,
+            summary: self.summary,
+            stats: self.stats,
+            unresolved,
+            negative: self.negative,
+            disputed,
+            largest: self.largest.into_sorted_vec().into_iter().map(|(_, t)| t).collect(),
+            quarantined: self.quarantined,
+            state: None,
+        }
+    }
+}
+
+/// Synchronous, in memory transaction processing engine.
+///
+/// Applies the same account logic as `TxProcessor` one transaction at a time without spawning any tasks,
+/// which makes it suitable for interactive use and for embedding.
+#[derive(Debug)]
+pub struct Engine {
+    accounts: BTreeMap<AccountKey, AccountState>,
+    retention: HistoryRetention,
+    config: Arc<Config>,
+    clients: Arc<ClientOverrides>,
+    // idempotency keys shared by the accounts with the global scope
+    dedupe: Arc<SharedDedupeWindow>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new(HistoryRetention::None)
+    }
+}
+
+impl Engine {
+    pub fn new(retention: HistoryRetention) -> Self {
+        Engine {
+            accounts: BTreeMap::new(),
+            retention,
+            config: Default::default(),
+            clients: Default::default(),
+            dedupe: Default::default(),
+        }
+    }
+
+    /// sets per client settings, credit limits and limits of existing accounts are updated as well
+    pub fn set_client_overrides(&mut self, clients: ClientOverrides) {
+        for (key, state) in self.accounts.iter_mut() {
+            state.settings = clients.get(key);
+            let mut a = state.ledger.account();
+            a.credit_limit = state.settings.credit_limit.unwrap_or_default();
+            state.ledger.update_account(a).expect("in memory ledger failed");
+        }
+        self.clients = Arc::new(clients);
+    }
+
+    /// replaces configuration applied to following transactions
+    pub fn set_config(&mut self, config: Config) {
+        self.config = Arc::new(config);
+    }
+
+    /// configuration applied to following transactions
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// applies transaction and returns the resulting account state
+    pub fn apply(&mut self, t: Transaction) -> core::result::Result<RawAccount, AccountError> {
+        let key = t.account_key();
+        let _span = info_span!("account", client_id = key.1, tenant = key.0.as_deref()).entered();
+        let retention = self.retention;
+        let clients = &self.clients;
+        let dedupe = &self.dedupe;
+        let state = self.accounts.entry(key.clone()).or_insert_with(|| AccountState {
+            global_dedupe: dedupe.clone(),
+            ..AccountState::new(key.clone(), retention, clients.get(&key), &InMemoryStore)
+        });
+        state.apply(t, &self.config)?;
+        Ok(state.raw_account())
+    }
+
+    /// unlocks the account (correction by an administrator), unknown account is created unlocked
+    pub fn unlock(&mut self, key: &AccountKey) -> RawAccount {
+        let retention = self.retention;
+        let clients = &self.clients;
+        let dedupe = &self.dedupe;
+        let state = self.accounts.entry(key.clone()).or_insert_with(|| AccountState {
+            global_dedupe: dedupe.clone(),
+            ..AccountState::new(key.clone(), retention, clients.get(key), &InMemoryStore)
+        });
+        info!("account {} unlocked", key.1);
+        let mut a = state.ledger.account().unlock();
+        a.version += 1;
+        state.ledger.update_account(a).expect("in memory ledger failed");
+        state.raw_account()
+    }
+
+    /// reverses applied deposit or withdrawal of the account (correction by an administrator)
+    pub fn reverse(&mut self, key: &AccountKey, tx_id: TxId) -> core::result::Result<RawAccount, AccountError> {
+        let state = self.accounts.get_mut(key).ok_or(AccountError::NoTxForDispute(tx_id))?;
+        if state.pending.contains(tx_id) {
+            return Err(AccountError::TxPending(tx_id));
+        }
+        if state.refunded.contains_key(&tx_id) {
+            return Err(AccountError::TxRefunded(tx_id));
+        }
+        let account = state.ledger.account();
+        let mut a = account.reverse(tx_id, state.ledger.as_mut())?;
+        a.version += 1;
+        state.ledger.update_account(a)?;
+        info!("account {} tx {} reversed", key.1, tx_id);
+        Ok(state.raw_account())
+    }
+
+    /// credits (positive amount) or debits (negative amount) the account as a correction by an administrator, applied
+    /// like any other transaction `tx_id` (see `adjustment`), so it is kept in the history with its reason
+    pub fn adjust(
+        &mut self,
+        key: &AccountKey,
+        tx_id: TxId,
+        amount: Money,
+        reason: &str,
+    ) -> core::result::Result<RawAccount, AccountError> {
+        if reason.trim().is_empty() {
+            return Err(AccountError::MissingReason(tx_id));
+        }
+        info!("account {} adjusted by {} with tx {}: {}", key.1, amount, tx_id, reason);
+        self.apply(adjustment(key, tx_id, amount, reason))
+    }
+
+    /// current state of a single account
+    pub fn account(&self, key: &AccountKey) -> Option<RawAccount> {
+        self.accounts.get(key).map(|s| s.raw_account())
+    }
+
+    /// current state of all accounts ordered by tenant and client id
+    pub fn accounts(&self) -> Vec<RawAccount> {
+        self.accounts.values().map(|s| s.raw_account()).collect()
+    }
+
+    /// highest transaction id known by the engine
+    pub fn max_tx_id(&self) -> Option<TxId> {
+        self.accounts
+            .values()
+            .filter_map(|s| s.ledger.transactions().iter().map(|t| t.tx_id).max())
+            .max()
+    }
+
+    /// consumes engine returning final state (and history) of all accounts
+    pub fn into_reports(self) -> Vec<AccountReport> {
+        self.accounts.into_values().map(|s| s.into_report()).collect()
+    }
+
+    /// captures balances and disputable transactions of all accounts
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            accounts: self
+                .accounts
+                .values()
+                .map(AccountState::snapshot)
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// creates engine with the state captured by `snapshot`
+    pub fn from_snapshot(snapshot: Snapshot, retention: HistoryRetention) -> Self {
+        let mut engine = Engine::new(retention);
+        for s in snapshot.accounts {
+            let key = (s.tenant.clone(), s.client_id);
+            let mut state = AccountState::new(key.clone(), retention, ClientSettings::default(), &InMemoryStore);
+            state.global_dedupe = engine.dedupe.clone();
+            state.restore(s).expect("in memory ledger failed");
+            engine.accounts.insert(key, state);
+        }
+        engine
+    }
+}
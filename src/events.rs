@@ -0,0 +1,46 @@
+use std::{collections::HashMap, fmt};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{account::AccountError, csv::RawAccount, Money, TxId, TxType};
+
+/// What happened to the processed transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,
+    Rejected,
+    /// acknowledged without changing the account, e.g. resubmission of already applied transaction
+    Skipped,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Applied => "applied",
+            Outcome::Rejected => "rejected",
+            Outcome::Skipped => "skipped",
+        })
+    }
+}
+
+/// Reported by account task after each processed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingEvent {
+    /// line of the transaction in the input, when read from CSV
+    pub line: Option<u64>,
+    pub tx_id: TxId,
+    pub tx_type: TxType,
+    pub amount: Money,
+    pub outcome: Outcome,
+    /// reason of the rejection or skip, `None` when transaction was applied
+    pub rejection: Option<String>,
+    /// error which rejected the transaction
+    pub error: Option<AccountError>,
+    /// account state after the transaction
+    pub account: RawAccount,
+    /// columns of the input row kept with the transaction, see `Transaction::metadata`
+    pub metadata: HashMap<String, String>,
+}
+
+/// Sending side of the processing event stream
+pub type EventSender = UnboundedSender<ProcessingEvent>;
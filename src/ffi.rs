@@ -0,0 +1,157 @@
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use csv_async::ByteRecord;
+
+use crate::{csv::RawTransaction, engine::Engine, output, Transaction};
+
+// C interface of `include/txg.h`, its functions and result codes only ever get added, never changed
+
+/// version of the C interface, see `txg_abi_version`
+pub const TXG_ABI_VERSION: u32 = 1;
+
+/// the transaction was applied
+pub const TXG_OK: c_int = 0;
+/// the transaction was rejected by the account, e.g. insufficient funds
+pub const TXG_REJECTED: c_int = 1;
+/// the row is not a valid `type,client,tx,amount` row
+pub const TXG_INVALID_ROW: c_int = -1;
+/// null engine or row pointer, or row which is not UTF-8
+pub const TXG_INVALID_ARGUMENT: c_int = -2;
+/// unexpected failure inside the engine
+pub const TXG_INTERNAL_ERROR: c_int = -3;
+
+/// Engine created by `txg_engine_new`, opaque to C
+pub struct TxgEngine {
+    engine: Engine,
+    // columns of the submitted rows
+    headers: ByteRecord,
+    // message of the last failed submit, returned by `txg_last_error`
+    error: Option<CString>,
+}
+
+/// version of the C interface the library implements
+#[no_mangle]
+pub extern "C" fn txg_abi_version() -> u32 {
+    TXG_ABI_VERSION
+}
+
+/// Creates engine with the default configuration, to be released by `txg_finish_and_export` or `txg_engine_free`
+#[no_mangle]
+pub extern "C" fn txg_engine_new() -> *mut TxgEngine {
+    Box::into_raw(Box::new(TxgEngine {
+        engine: Engine::default(),
+        headers: ByteRecord::from(vec!["type", "client", "tx", "amount"]),
+        error: None,
+    }))
+}
+
+/// Applies a row of the input format without header (`deposit,1,1,2.5`), returns `TXG_OK`, `TXG_REJECTED`
+/// or a negative error code; message of the failure is returned by `txg_last_error`.
+///
+/// # Safety
+/// `engine` is a pointer returned by `txg_engine_new` and not yet released, `row` a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn txg_submit_csv_row(engine: *mut TxgEngine, row: *const c_char) -> c_int {
+    let Some(engine) = engine.as_mut() else {
+        return TXG_INVALID_ARGUMENT;
+    };
+    if row.is_null() {
+        return engine.fail(TXG_INVALID_ARGUMENT, "row is null".to_string());
+    }
+    let Ok(row) = CStr::from_ptr(row).to_str() else {
+        return engine.fail(TXG_INVALID_ARGUMENT, "row is not UTF-8".to_string());
+    };
+    // panics must not unwind into C
+    match panic::catch_unwind(AssertUnwindSafe(|| engine.submit(row))) {
+        Ok(code) => code,
+        Err(_) => engine.fail(TXG_INTERNAL_ERROR, "engine panicked".to_string()),
+    }
+}
+
+/// Message of the last failed `txg_submit_csv_row`, NULL when the last submit succeeded. The string is owned
+/// by the engine and valid until the next submit.
+///
+/// # Safety
+/// `engine` is a pointer returned by `txg_engine_new` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn txg_last_error(engine: *const TxgEngine) -> *const c_char {
+    match engine.as_ref().and_then(|e| e.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Releases the engine and returns its accounts in the CSV output format of `txp-cli` (header included),
+/// to be released by `txg_string_free`; NULL for null engine.
+///
+/// # Safety
+/// `engine` is a pointer returned by `txg_engine_new` and not yet released, it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn txg_finish_and_export(engine: *mut TxgEngine) -> *mut c_char {
+    if engine.is_null() {
+        return ptr::null_mut();
+    }
+    let engine = Box::from_raw(engine);
+    let export = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut w = Vec::new();
+        output::write_accounts(&mut w, &engine.engine.accounts(), engine.engine.config().precision()).ok()?;
+        CString::new(w).ok()
+    }));
+    match export {
+        Ok(Some(csv)) => csv.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases the engine without exporting its accounts
+///
+/// # Safety
+/// `engine` is NULL or a pointer returned by `txg_engine_new` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn txg_engine_free(engine: *mut TxgEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Releases a string returned by `txg_finish_and_export`
+///
+/// # Safety
+/// `s` is NULL or a string returned by `txg_finish_and_export` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn txg_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+impl TxgEngine {
+    fn submit(&mut self, row: &str) -> c_int {
+        let record = row.split(',').map(str::trim).collect::<ByteRecord>();
+        let t = match record.deserialize::<RawTransaction>(Some(&self.headers)) {
+            Ok(t) => t,
+            Err(e) => return self.fail(TXG_INVALID_ROW, format!("'{}': {}", row, e)),
+        };
+        if let Err(message) = t.check_amount() {
+            return self.fail(TXG_INVALID_ROW, format!("'{}': {}", row, message));
+        }
+        match self.engine.apply(Transaction::from(t)) {
+            Ok(_) => {
+                self.error = None;
+                TXG_OK
+            }
+            Err(e) => self.fail(TXG_REJECTED, e.message()),
+        }
+    }
+
+    fn fail(&mut self, code: c_int, message: String) -> c_int {
+        // messages with NUL bytes are cut before the first one
+        let message = message.split('\0').next().unwrap_or_default().to_string();
+        self.error = CString::new(message).ok();
+        code
+    }
+}
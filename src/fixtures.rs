@@ -0,0 +1,139 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{golden, output::ACCOUNT_HEADER, Result};
+
+/// Input row of a fixture together with its expected outcome
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// `type,client,tx,amount` row of the input
+    pub row: &'static str,
+    /// error code the row is rejected with, `None` when it is applied
+    pub rejected: Option<&'static str>,
+}
+
+/// Curated sequence of transactions pinning down the semantics of an edge case of the dispute flow
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub steps: Vec<Step>,
+    /// account rows (`client,available,held,total,locked,status`) expected at the end, ordered by client
+    pub accounts: Vec<&'static str>,
+}
+
+const fn applied(row: &'static str) -> Step {
+    Step { row, rejected: None }
+}
+
+const fn rejected(row: &'static str, code: &'static str) -> Step {
+    Step { row, rejected: Some(code) }
+}
+
+impl Fixture {
+    /// input file of the fixture
+    pub fn csv(&self) -> String {
+        let mut csv = String::from("type,client,tx,amount\n");
+        for step in &self.steps {
+            csv.push_str(step.row);
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// expected output in the format of `golden::run_scenario`
+    pub fn expected(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", ACCOUNT_HEADER);
+        for account in &self.accounts {
+            let _ = writeln!(out, "{}", account);
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "{}", golden::REJECTED_HEADER);
+        // the header is line 1
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Some(code) = step.rejected {
+                let fields: Vec<_> = step.row.splitn(4, ',').collect();
+                let _ = writeln!(out, "{},{},{},{},{}", i + 2, fields[0], fields[1], fields[2], code);
+            }
+        }
+        out
+    }
+
+    /// writes `<name>.csv` and `<name>.expected` into `dir`, returns the path of the input
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        let csv = dir.join(format!("{}.csv", self.name));
+        fs::write(&csv, self.csv())?;
+        fs::write(csv.with_extension(golden::EXPECTED_EXTENSION), self.expected())?;
+        Ok(csv)
+    }
+}
+
+/// Pathological sequences of the dispute flow: dispute before deposit, resolve after chargeback, dispute
+/// on locked account, duplicate transaction ids and zero amounts
+pub fn edge_cases() -> Vec<Fixture> {
+    vec![
+        Fixture {
+            name: "dispute_before_deposit",
+            // disputes are not held for the transaction they refer to unless `dispute_hold` is configured
+            steps: vec![
+                rejected("dispute,1,1,", "E1004"),
+                applied("deposit,1,1,5.0"),
+                rejected("resolve,1,1,", "E1005"),
+            ],
+            accounts: vec!["1,5.0000,0.0000,5.0000,false,active"],
+        },
+        Fixture {
+            name: "resolve_after_chargeback",
+            steps: vec![
+                applied("deposit,1,1,10.0"),
+                applied("dispute,1,1,"),
+                applied("chargeback,1,1,"),
+                rejected("resolve,1,1,", "E1002"),
+            ],
+            accounts: vec!["1,0.0000,0.0000,0.0000,true,locked"],
+        },
+        Fixture {
+            name: "dispute_on_locked_account",
+            steps: vec![
+                applied("deposit,1,1,10.0"),
+                applied("deposit,1,2,3.0"),
+                applied("dispute,1,2,"),
+                applied("chargeback,1,2,"),
+                rejected("dispute,1,1,", "E1002"),
+                rejected("withdrawal,1,3,1.0", "E1002"),
+            ],
+            accounts: vec!["1,10.0000,0.0000,10.0000,true,locked"],
+        },
+        Fixture {
+            name: "duplicate_tx_ids",
+            // without idempotency keys every row is applied, disputes refer to the last transaction with the id
+            steps: vec![
+                applied("deposit,1,1,5.0"),
+                applied("deposit,1,1,7.0"),
+                applied("deposit,2,1,2.0"),
+                applied("withdrawal,1,1,1.0"),
+                applied("dispute,1,1,"),
+            ],
+            accounts: vec!["1,10.0000,1.0000,11.0000,false,active", "2,2.0000,0.0000,2.0000,false,active"],
+        },
+        Fixture {
+            name: "zero_amounts",
+            steps: vec![
+                applied("deposit,1,1,0"),
+                applied("withdrawal,1,2,0.0"),
+                applied("deposit,1,3,1.0"),
+                applied("dispute,1,1,"),
+                applied("chargeback,1,1,"),
+            ],
+            accounts: vec!["1,1.0000,0.0000,1.0000,true,locked"],
+        },
+    ]
+}
+
+/// writes all `edge_cases` into `dir`, returns paths of the inputs
+pub fn write_edge_cases(dir: &Path) -> Result<Vec<PathBuf>> {
+    edge_cases().iter().map(|f| f.write(dir)).collect()
+}
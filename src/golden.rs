@@ -0,0 +1,116 @@
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{csv::CsvTransactionReader, engine::Engine, output, Result, Transaction};
+
+/// extension of the expected output of the scenario `<name>.csv`
+pub const EXPECTED_EXTENSION: &str = "expected";
+
+/// header of the rejected transactions section of the output
+pub const REJECTED_HEADER: &str = "line,type,client,tx,code";
+
+/// Scenario whose output differs from its expected file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub scenario: PathBuf,
+    /// `None` when the expected file does not exist yet
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expected = match &self.expected {
+            Some(expected) => expected,
+            None => return write!(f, "{:?}: expected file is missing", self.scenario),
+        };
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = self.actual.lines();
+        for line in 1.. {
+            match (expected_lines.next(), actual_lines.next()) {
+                (None, None) => break,
+                (e, a) if e == a => continue,
+                (e, a) => {
+                    return write!(
+                        f,
+                        "{:?}: line {} expected `{}`, got `{}`",
+                        self.scenario,
+                        line,
+                        e.unwrap_or("<end>"),
+                        a.unwrap_or("<end>")
+                    )
+                }
+            }
+        }
+        write!(f, "{:?}: output differs", self.scenario)
+    }
+}
+
+/// Output of the `Engine` applied to the scenario: sorted account rows and the rejected transactions with their
+/// error codes, in the order of the input
+pub async fn run_scenario(path: &Path) -> Result<String> {
+    let transactions = Mutex::new(Vec::new());
+    CsvTransactionReader::default()
+        .read_data_file(path.to_path_buf(), |t| {
+            transactions.lock().expect("transactions lock poisoned").extend(t.map(Transaction::from));
+            async { Ok(()) }
+        })
+        .await;
+
+    let mut engine = Engine::default();
+    let mut rejected = String::new();
+    for t in transactions.into_inner().expect("transactions lock poisoned") {
+        let (line, tx_type, client_id, tx_id) = (t.line, t.tx_type.clone(), t.client_id, t.tx_id);
+        if let Err(e) = engine.apply(t) {
+            let line = line.map(|l| l.to_string()).unwrap_or_default();
+            writeln!(rejected, "{},{},{},{},{}", line, tx_type, client_id, tx_id, e.code())?;
+        }
+    }
+
+    let mut accounts = Vec::new();
+    output::write_accounts(&mut accounts, &engine.accounts(), 4)?;
+    let accounts = String::from_utf8(accounts)?;
+    let mut lines = accounts.lines();
+    let header = lines.next().unwrap_or_default();
+    let mut rows: Vec<_> = lines.collect();
+    rows.sort_unstable();
+
+    let mut out = String::new();
+    writeln!(out, "{}", header)?;
+    for row in rows {
+        writeln!(out, "{}", row)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{}", REJECTED_HEADER)?;
+    out.push_str(&rejected);
+    Ok(out)
+}
+
+/// Runs every scenario (`*.csv`) of `dir` in name order and compares its output with `<name>.expected`,
+/// with `update` the expected files are written instead and no mismatches are returned
+pub async fn check(dir: &Path, update: bool) -> Result<Vec<Mismatch>> {
+    let mut scenarios: Vec<_> = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    scenarios.retain(|p| p.extension().is_some_and(|e| e == "csv"));
+    scenarios.sort();
+
+    let mut mismatches = Vec::new();
+    for scenario in scenarios {
+        let actual = run_scenario(&scenario).await?;
+        let expected_path = scenario.with_extension(EXPECTED_EXTENSION);
+        if update {
+            fs::write(&expected_path, &actual)?;
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).ok();
+        if expected.as_deref() != Some(actual.as_str()) {
+            mismatches.push(Mismatch { scenario, expected, actual });
+        }
+    }
+    Ok(mismatches)
+}
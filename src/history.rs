@@ -0,0 +1,140 @@
+use std::io::Write;
+
+use crate::{account::{Account, AccountError, DisputeState}, tx::AccountReport, ClientId, Money, Result, Transaction, TxId, TxType};
+
+/// Which transactions are kept in the per account history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HistoryRetention {
+    /// history is not kept
+    None,
+    /// only successfully applied transactions
+    Applied,
+    /// every transaction received by the account, including rejected ones
+    All,
+}
+
+/// Transaction together with the account balances right after it was processed
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub transaction: Transaction,
+    /// reason of the rejection, `None` when transaction was applied
+    pub rejection: Option<String>,
+    /// error which rejected the transaction
+    pub error: Option<AccountError>,
+    pub available_amount: Money,
+    pub held_amount: Money,
+    pub total_amount: Money,
+    pub is_locked: bool,
+    /// state of the transaction referenced by dispute, resolve or chargeback right after it was processed
+    pub dispute: Option<DisputeState>,
+}
+
+impl HistoryEntry {
+    pub(crate) fn new(transaction: Transaction, account: &Account) -> Self {
+        HistoryEntry {
+            transaction,
+            rejection: None,
+            error: None,
+            available_amount: account.available_amount,
+            held_amount: account.held_amount,
+            total_amount: account.total_amount,
+            is_locked: account.is_locked,
+            dispute: None,
+        }
+    }
+
+    pub(crate) fn rejected(transaction: Transaction, account: &Account, error: &AccountError) -> Self {
+        HistoryEntry {
+            rejection: Some(error.message()),
+            error: Some(error.clone()),
+            ..HistoryEntry::new(transaction, account)
+        }
+    }
+
+    /// true when transaction changed the account
+    pub fn is_applied(&self) -> bool {
+        self.rejection.is_none()
+    }
+}
+
+/// Format of the history export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    Csv,
+    Jsonl,
+}
+
+/// single row of the history export
+#[derive(Serialize, Debug)]
+struct HistoryRecord<'a> {
+    tenant: Option<&'a str>,
+    client: ClientId,
+    // position of the operation within account history
+    seq: usize,
+    #[serde(rename = "type")]
+    tx_type: &'a TxType,
+    tx: TxId,
+    amount: Money,
+    outcome: &'static str,
+    reason: Option<&'a str>,
+    available: Money,
+    held: Money,
+    total: Money,
+    locked: bool,
+    dispute: Option<DisputeState>,
+}
+
+/// Header row of the CSV history export
+pub const HISTORY_HEADER: &str = "tenant,client,seq,type,tx,amount,outcome,reason,available,held,total,locked,dispute";
+
+/// Writes the ordered operation history of every account to `w`
+pub fn write_history<W: Write>(w: &mut W, format: HistoryFormat, reports: &[AccountReport]) -> Result<()> {
+    if format == HistoryFormat::Csv {
+        writeln!(w, "{}", HISTORY_HEADER)?;
+    }
+    for report in reports {
+        for (seq, entry) in report.history.iter().enumerate() {
+            let t = &entry.transaction;
+            let record = HistoryRecord {
+                tenant: report.account.tenant.as_deref(),
+                client: report.account.client_id,
+                seq,
+                tx_type: &t.tx_type,
+                tx: t.tx_id,
+                amount: t.amount,
+                outcome: if entry.is_applied() { "applied" } else { "rejected" },
+                reason: entry.rejection.as_deref(),
+                available: entry.available_amount,
+                held: entry.held_amount,
+                total: entry.total_amount,
+                locked: entry.is_locked,
+                dispute: entry.dispute,
+            };
+            match format {
+                HistoryFormat::Csv => writeln!(
+                    w,
+                    "{},{},{},{},{},{:.4},{},{},{:.4},{:.4},{:.4},{},{}",
+                    record.tenant.unwrap_or_default(),
+                    record.client,
+                    record.seq,
+                    record.tx_type,
+                    record.tx,
+                    record.amount,
+                    record.outcome,
+                    record.reason.unwrap_or_default(),
+                    record.available,
+                    record.held,
+                    record.total,
+                    record.locked,
+                    record.dispute.map(|s| s.to_string()).unwrap_or_default()
+                )?,
+                HistoryFormat::Jsonl => {
+                    serde_json::to_writer(&mut *w, &record)?;
+                    writeln!(w)?;
+                }
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
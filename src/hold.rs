@@ -0,0 +1,131 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{Transaction, TxId};
+
+/// Holding of disputes, resolves and chargebacks which arrive before the transaction they reference,
+/// configured in the `[dispute_hold]` section of the configuration file
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HoldConfig {
+    /// held transaction is processed after this many following transactions of the client
+    pub messages: usize,
+    /// held transaction is processed after this many seconds
+    pub seconds: u64,
+}
+
+impl Default for HoldConfig {
+    fn default() -> Self {
+        HoldConfig {
+            messages: 10,
+            seconds: 5,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Held {
+    transaction: Transaction,
+    since: Instant,
+    // following transactions of the client received since the transaction was held
+    messages: usize,
+}
+
+/// Per account buffer of the held transactions, in the order they arrived
+#[derive(Debug, Default)]
+pub(crate) struct HoldBuffer {
+    held: VecDeque<Held>,
+}
+
+impl HoldBuffer {
+    pub fn hold(&mut self, transaction: Transaction, now: Instant) {
+        self.held.push_back(Held {
+            transaction,
+            since: now,
+            messages: 0,
+        });
+    }
+
+    /// takes held transactions referencing `tx_id`
+    pub fn release(&mut self, tx_id: TxId) -> Vec<Transaction> {
+        let (released, kept): (VecDeque<_>, VecDeque<_>) =
+            self.held.drain(..).partition(|h| h.transaction.tx_id == tx_id);
+        self.held = kept;
+        released.into_iter().map(|h| h.transaction).collect()
+    }
+
+    /// counts another received transaction, returns held transactions which waited long enough
+    pub fn tick(&mut self, config: &HoldConfig, now: Instant) -> Vec<Transaction> {
+        for h in self.held.iter_mut() {
+            h.messages += 1;
+        }
+        self.expired(config, now)
+    }
+
+    /// takes held transactions which waited for `config.messages` transactions or `config.seconds`
+    pub fn expired(&mut self, config: &HoldConfig, now: Instant) -> Vec<Transaction> {
+        let timeout = Duration::from_secs(config.seconds);
+        let (expired, kept): (VecDeque<_>, VecDeque<_>) = self
+            .held
+            .drain(..)
+            .partition(|h| h.messages >= config.messages || now.duration_since(h.since) >= timeout);
+        self.held = kept;
+        expired.into_iter().map(|h| h.transaction).collect()
+    }
+
+    /// time when the oldest held transaction expires
+    pub fn deadline(&self, config: &HoldConfig) -> Option<Instant> {
+        self.held.front().map(|h| h.since + Duration::from_secs(config.seconds))
+    }
+
+    /// takes all held transactions
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        self.held.drain(..).map(|h| h.transaction).collect()
+    }
+}
+
+/// Retrying of disputes, resolves and chargebacks rejected because the referenced transaction was not seen yet,
+/// configured in the `[dispute_retry]` section of the configuration file
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// maximum number of transactions waiting for retry per client, the oldest one is dropped when full
+    pub capacity: usize,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig { capacity: 100 }
+    }
+}
+
+/// Per account queue of the rejected transactions waiting for the transaction they reference
+#[derive(Debug, Default)]
+pub(crate) struct RetryQueue {
+    parked: VecDeque<Transaction>,
+}
+
+impl RetryQueue {
+    /// adds transaction to the queue, returns the transaction dropped to keep at most `capacity` of them
+    pub fn park(&mut self, transaction: Transaction, capacity: usize) -> Option<Transaction> {
+        self.parked.push_back(transaction);
+        if self.parked.len() > capacity {
+            return self.parked.pop_front();
+        }
+        None
+    }
+
+    /// takes parked transactions referencing `tx_id`
+    pub fn release(&mut self, tx_id: TxId) -> Vec<Transaction> {
+        let (released, kept): (VecDeque<_>, VecDeque<_>) = self.parked.drain(..).partition(|t| t.tx_id == tx_id);
+        self.parked = kept;
+        released.into()
+    }
+
+    /// takes all parked transactions
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        self.parked.drain(..).collect()
+    }
+}
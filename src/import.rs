@@ -0,0 +1,219 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use sha2::{Digest, Sha256};
+
+use crate::{csv::RawTransaction, ClientId, Result, Timestamp, TxId, TxType};
+
+/// true when the file is OFX (`.ofx`, `.qfx`) or QIF (`.qif`) statement, read with `read_statement`
+pub fn is_statement(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ofx" || e == "qfx" || e == "qif")
+}
+
+/// Reads `account,client` CSV file mapping account identifiers of the imported files (IBAN, account number
+/// or name) to clients
+pub fn load_accounts(path: &Path) -> Result<HashMap<String, ClientId>> {
+    let mut accounts = HashMap::new();
+    for (i, l) in fs::read_to_string(path)?.lines().enumerate() {
+        match l.split_once(',').map(|(a, c)| (a.trim(), c.trim().parse::<ClientId>())) {
+            Some(("account", _)) if i == 0 => (),
+            Some((account, Ok(client))) if !account.is_empty() => {
+                accounts.insert(account.to_string(), client);
+            }
+            _ if l.trim().is_empty() => (),
+            _ => return Err(format!("{:?} line {}: '{}' is not an `account,client` mapping", path, i + 1, l).into()),
+        }
+    }
+    Ok(accounts)
+}
+
+/// client of the account: mapped by `accounts`, numeric account ids without mapping are the client ids
+pub fn client(accounts: &HashMap<String, ClientId>, account: &str) -> Result<ClientId> {
+    match accounts.get(account) {
+        Some(client) => Ok(*client),
+        None => account.parse().map_err(|_| format!("account {} is not mapped to a client", account).into()),
+    }
+}
+
+/// transaction id of the textual reference of the imported transaction: first 4 bytes of its SHA-256,
+/// the same reference always gets the same id
+pub fn reference_tx_id(reference: &str) -> TxId {
+    let digest = Sha256::digest(reference.as_bytes());
+    TxId::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+/// unix time of the UTC date and time, `None` for invalid date
+pub fn unix_time(year: u64, month: u64, day: u64, seconds: u64) -> Option<Timestamp> {
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // days from the civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let (era, yoe) = (y / 400, y % 400);
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146_097 + doe - 719_468) * 86_400 + seconds)
+}
+
+/// Rows of the OFX or QIF statement (see `is_statement`), credits are deposits and debits withdrawals of the client
+/// of the statement account:
+/// - OFX (SGML 1.x or XML 2.x): every `STMTTRN` with `FITID` as the reference of its transaction id (`reference_tx_id`),
+///   the account is `ACCTID` of the statement
+/// - QIF: every record of `!Type:Bank`, `Cash` or `CCard` sections; QIF has no transaction ids, the reference is
+///   the check number (`N`) or the date, amount and payee of the record, the account is the `!Account` name
+///   or the name of the file without extension
+pub fn read_statement(path: &Path, text: &str, accounts: &HashMap<String, ClientId>) -> Result<Vec<RawTransaction>> {
+    match path.extension().is_some_and(|e| e == "qif") {
+        true => {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            read_qif(text, &stem, accounts)
+        }
+        false => read_ofx(text, accounts),
+    }
+}
+
+fn read_ofx(text: &str, accounts: &HashMap<String, ClientId>) -> Result<Vec<RawTransaction>> {
+    let mut rows = Vec::new();
+    let mut account: Option<String> = None;
+    // fields of the current STMTTRN
+    let mut fields: Option<HashMap<&str, &str>> = None;
+    // `<TAG>value` or `</TAG>`, SGML leaf elements are not closed
+    for token in text.split('<').skip(1) {
+        let (tag, value) = token.split_once('>').ok_or("OFX tag is not closed")?;
+        let (tag, value) = (tag.trim(), value.trim());
+        match (tag, &mut fields) {
+            ("STMTTRN", _) => fields = Some(HashMap::new()),
+            ("/STMTTRN", Some(f)) => {
+                let client = match &account {
+                    Some(account) => client(accounts, account)?,
+                    None => return Err("OFX transaction before ACCTID of its statement".into()),
+                };
+                rows.push(ofx_row(f, client)?);
+                fields = None;
+            }
+            ("ACCTID", None) => account = Some(value.to_string()),
+            (tag, Some(f)) if !tag.starts_with('/') => {
+                f.insert(tag, value);
+            }
+            _ => (),
+        }
+    }
+    Ok(rows)
+}
+
+fn ofx_row(f: &HashMap<&str, &str>, client_id: ClientId) -> Result<RawTransaction> {
+    let fitid = f.get("FITID").ok_or("OFX transaction without FITID")?;
+    let amount = f.get("TRNAMT").ok_or_else(|| format!("OFX transaction {} without TRNAMT", fitid))?;
+    // some banks write decimal comma
+    let amount = amount.replace(',', ".");
+    let (tx_type, amount) = match amount.strip_prefix('-') {
+        Some(debit) => (TxType::Withdrawal, debit),
+        None => (TxType::Deposit, amount.trim_start_matches('+')),
+    };
+    // `YYYYMMDD[HHMMSS[.XXX]][[gmt offset:tz name]]`, the offset is ignored
+    let date = f.get("DTPOSTED").ok_or_else(|| format!("OFX transaction {} without DTPOSTED", fitid))?;
+    let number = |r: std::ops::Range<usize>| date.get(r).and_then(|n| n.parse::<u64>().ok());
+    let seconds = match (number(8..10), number(10..12), number(12..14)) {
+        (Some(h), Some(m), Some(s)) => h * 3600 + m * 60 + s,
+        _ => 0,
+    };
+    let timestamp = number(0..4)
+        .zip(number(4..6))
+        .zip(number(6..8))
+        .and_then(|((y, m), d)| unix_time(y, m, d, seconds))
+        .ok_or_else(|| format!("OFX transaction {} with invalid DTPOSTED '{}'", fitid, date))?;
+    Ok(RawTransaction {
+        tx_type,
+        client_id,
+        tx_id: reference_tx_id(fitid),
+        amount: Some(amount.to_string()),
+        tenant: None,
+        timestamp: Some(timestamp),
+        idempotency_key: None,
+        seq: None,
+        line: None,
+        metadata: Default::default(),
+    })
+}
+
+fn read_qif(text: &str, file_account: &str, accounts: &HashMap<String, ClientId>) -> Result<Vec<RawTransaction>> {
+    let mut rows = Vec::new();
+    let (mut account, mut transactions, mut in_account) = (file_account.to_string(), false, false);
+    let mut record: HashMap<char, &str> = HashMap::new();
+    // equal records are told apart by their position
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    for (i, l) in text.lines().enumerate() {
+        let l = l.trim();
+        if let Some(header) = l.strip_prefix('!') {
+            let header = header.to_lowercase();
+            in_account = header == "account";
+            transactions = ["type:bank", "type:cash", "type:ccard"].contains(&header.as_str());
+            continue;
+        }
+        let mut chars = l.chars();
+        let (code, value) = match chars.next() {
+            Some(code) => (code, chars.as_str().trim()),
+            None => continue,
+        };
+        match code {
+            '^' if in_account => (),
+            '^' if transactions && !record.is_empty() => {
+                let client_id = client(accounts, &account)?;
+                rows.push(qif_row(&record, client_id, &mut seen).map_err(|e| format!("QIF line {}: {}", i + 1, e))?);
+                record.clear();
+            }
+            'N' if in_account => account = value.to_string(),
+            code if transactions => {
+                record.insert(code, value);
+            }
+            _ => (),
+        }
+    }
+    Ok(rows)
+}
+
+fn qif_row(record: &HashMap<char, &str>, client_id: ClientId, seen: &mut HashMap<String, u32>) -> Result<RawTransaction> {
+    let date = record.get(&'D').ok_or("record without date (D)")?;
+    let amount = record.get(&'T').or_else(|| record.get(&'U')).ok_or("record without amount (T)")?;
+    // thousands separators
+    let amount = amount.replace(',', "");
+    let (tx_type, amount) = match amount.strip_prefix('-') {
+        Some(debit) => (TxType::Withdrawal, debit.to_string()),
+        None => (TxType::Deposit, amount),
+    };
+    let timestamp = qif_date(date).ok_or_else(|| format!("invalid date '{}'", date))?;
+    let reference = match record.get(&'N').filter(|n| n.chars().all(|c| c.is_ascii_digit()) && !n.is_empty()) {
+        Some(number) => format!("{}|N{}", client_id, number),
+        None => format!("{}|{}|{}|{}", client_id, date, amount, record.get(&'P').unwrap_or(&"")),
+    };
+    let occurrence = seen.entry(reference.clone()).or_default();
+    *occurrence += 1;
+    Ok(RawTransaction {
+        tx_type,
+        client_id,
+        tx_id: reference_tx_id(&format!("{}|{}", reference, occurrence)),
+        amount: Some(amount),
+        tenant: None,
+        timestamp: Some(timestamp),
+        idempotency_key: None,
+        seq: None,
+        line: None,
+        metadata: Default::default(),
+    })
+}
+
+// `MM/DD/YYYY`, `MM/DD/YY`, `MM/DD'YY` (Quicken, years after 2000) or `YYYY-MM-DD`
+fn qif_date(date: &str) -> Option<Timestamp> {
+    if let [y, m, d] = date.split('-').collect::<Vec<_>>()[..] {
+        return unix_time(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?, 0);
+    }
+    let (month_day, year) = date.split_once('\'').or_else(|| date.rsplit_once('/'))?;
+    let (month, day) = month_day.split_once('/')?;
+    let year: u64 = year.trim().parse().ok()?;
+    let year = match year < 100 {
+        true if date.contains('\'') => 2000 + year,
+        true if year < 70 => 2000 + year,
+        true => 1900 + year,
+        false => year,
+    };
+    unix_time(year, month.trim().parse().ok()?, day.trim().parse().ok()?, 0)
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, path::Path};
+
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    csv::RawTransaction,
+    import::{self, reference_tx_id, unix_time},
+    ClientId, Result, Timestamp, TxType,
+};
+
+/// true when the file is `.xml`, read with `Iso20022Importer`
+pub fn is_iso20022(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "xml")
+}
+
+/// Maps ISO 20022 payment files into rows of the input format:
+/// - pain.001 (customer credit transfer initiation): every transfer is a withdrawal of the client of the debtor account
+/// - camt.054 (debit/credit notification): credit entries are deposits, debit entries withdrawals of the client
+///   of the notified account; reversed (`RvslInd`) and returned (`RtrInf`) entries are a dispute and a chargeback
+///   of the original entry
+///
+/// Accounts are mapped to clients by `with_accounts` (see `import::client`). Transaction id is derived from the end
+/// to end reference (entry reference when there is none) of the payment with `import::reference_tx_id`,
+/// so a reversal finds the original payment also when they come in different files.
+#[derive(Debug, Clone, Default)]
+pub struct Iso20022Importer {
+    accounts: HashMap<String, ClientId>,
+}
+
+impl Iso20022Importer {
+    pub fn with_accounts(accounts: HashMap<String, ClientId>) -> Self {
+        Iso20022Importer { accounts }
+    }
+
+    /// rows of the pain.001 or camt.054 document
+    pub fn import(&self, xml: &str) -> Result<Vec<RawTransaction>> {
+        let document = Element::parse(xml)?;
+        if let Some(initiation) = document.child("CstmrCdtTrfInitn") {
+            self.credit_transfers(initiation)
+        } else if let Some(notification) = document.child("BkToCstmrDbtCdtNtfctn") {
+            self.notifications(notification)
+        } else {
+            Err("neither pain.001 (CstmrCdtTrfInitn) nor camt.054 (BkToCstmrDbtCdtNtfctn) document".into())
+        }
+    }
+
+    fn credit_transfers(&self, initiation: &Element) -> Result<Vec<RawTransaction>> {
+        let mut rows = Vec::new();
+        for payment in initiation.children("PmtInf") {
+            let client = self.client(payment.child("DbtrAcct"))?;
+            let date = payment.child("ReqdExctnDt").map(|d| d.child("Dt").unwrap_or(d).text.as_str());
+            let timestamp = date.map(timestamp).transpose()?;
+            for transfer in payment.children("CdtTrfTxInf") {
+                let reference = transfer.path(&["PmtId", "EndToEndId"]).ok_or("credit transfer without EndToEndId")?;
+                let amount = transfer.path(&["Amt", "InstdAmt"]).ok_or("credit transfer without InstdAmt")?;
+                rows.push(row(TxType::Withdrawal, client, reference, Some(amount), timestamp));
+            }
+        }
+        Ok(rows)
+    }
+
+    fn notifications(&self, notification: &Element) -> Result<Vec<RawTransaction>> {
+        let mut rows = Vec::new();
+        for account in notification.children("Ntfctn") {
+            let client = self.client(account.child("Acct"))?;
+            for entry in account.children("Ntry") {
+                let details = entry.path_element(&["NtryDtls", "TxDtls"]);
+                let reference = details
+                    .and_then(|d| d.path(&["Refs", "EndToEndId"]))
+                    .or_else(|| entry.path(&["NtryRef"]))
+                    .ok_or("entry without EndToEndId or NtryRef")?;
+                let date = entry.child("BookgDt").and_then(|d| d.child("Dt").or_else(|| d.child("DtTm")));
+                let timestamp = date.map(|d| timestamp(&d.text)).transpose()?;
+                let reversed = entry.path(&["RvslInd"]).is_some_and(|r| r == "true");
+                let returned = details.is_some_and(|d| d.child("RtrInf").is_some());
+                if reversed || returned {
+                    rows.push(row(TxType::Dispute, client, reference, None, timestamp));
+                    rows.push(row(TxType::Chargeback, client, reference, None, timestamp));
+                    continue;
+                }
+                let tx_type = match entry.path(&["CdtDbtInd"]) {
+                    Some("CRDT") => TxType::Deposit,
+                    Some("DBIT") => TxType::Withdrawal,
+                    other => return Err(format!("entry {} with credit/debit indicator {:?}", reference, other).into()),
+                };
+                let amount = entry.path(&["Amt"]).ok_or("entry without Amt")?;
+                rows.push(row(tx_type, client, reference, Some(amount), timestamp));
+            }
+        }
+        Ok(rows)
+    }
+
+    // client of the `Id/IBAN` or `Id/Othr/Id` account
+    fn client(&self, account: Option<&Element>) -> Result<ClientId> {
+        let id = account
+            .and_then(|a| a.path(&["Id", "IBAN"]).or_else(|| a.path(&["Id", "Othr", "Id"])))
+            .ok_or("account without IBAN or other id")?;
+        import::client(&self.accounts, id)
+    }
+}
+
+fn row(tx_type: TxType, client_id: ClientId, reference: &str, amount: Option<&str>, timestamp: Option<Timestamp>) -> RawTransaction {
+    RawTransaction {
+        tx_type,
+        client_id,
+        tx_id: reference_tx_id(reference),
+        amount: amount.map(String::from),
+        tenant: None,
+        timestamp,
+        idempotency_key: None,
+        seq: None,
+        line: None,
+        metadata: Default::default(),
+    }
+}
+
+// unix time of `YYYY-MM-DD` date or `YYYY-MM-DDThh:mm:ss` date time, the time zone is ignored
+fn timestamp(s: &str) -> Result<Timestamp> {
+    let number = |r: std::ops::Range<usize>| s.get(r).and_then(|n| n.parse::<u64>().ok());
+    let seconds = match s.len() >= 19 {
+        true => number(11..13).zip(number(14..16)).zip(number(17..19)).map(|((h, m), s)| h * 3600 + m * 60 + s),
+        false => Some(0),
+    };
+    match (number(0..4), number(5..7), number(8..10), seconds) {
+        (Some(y), Some(m), Some(d), Some(seconds)) => unix_time(y, m, d, seconds),
+        _ => None,
+    }
+    .ok_or_else(|| format!("'{}' is not an ISO date", s).into())
+}
+
+// element of the document by its local name (without namespace prefix) with its text and child elements
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    // the whole document as children of an unnamed root
+    fn parse(xml: &str) -> Result<Element> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+        let mut stack = vec![Element::default()];
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => stack.push(Element {
+                    name: String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+                    ..Default::default()
+                }),
+                Event::Empty(e) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(Element { name, ..Default::default() });
+                    }
+                }
+                Event::Text(e) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&e.unescape()?);
+                    }
+                }
+                Event::End(_) => match (stack.pop(), stack.last_mut()) {
+                    (Some(element), Some(parent)) => parent.children.push(element),
+                    _ => return Err("unbalanced end tag".into()),
+                },
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+        match stack.pop() {
+            Some(document) if stack.is_empty() => Ok(document.children.into_iter().next().unwrap_or_default()),
+            _ => Err("document ends within an element".into()),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn path_element(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |e, name| e.child(name))
+    }
+
+    // text of the element at the path of child names
+    fn path(&self, path: &[&str]) -> Option<&str> {
+        self.path_element(path).map(|e| e.text.as_str())
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::AccountKey;
+
+/// Queue wait and processing time of every account, updated by `TxProcessor` while processing
+/// when set in `ProcessorOptions::lag`. Wait is the time a transaction spends in the channel of its account.
+#[derive(Debug, Default)]
+pub struct LagMonitor {
+    accounts: Mutex<HashMap<AccountKey, Lag>>,
+}
+
+#[derive(Debug, Default)]
+struct Lag {
+    // times the transactions waiting in the channel were sent, oldest first
+    sent: VecDeque<Instant>,
+    // since the last `slowest`
+    max_wait: Duration,
+    processing: Duration,
+    transactions: u64,
+}
+
+impl LagMonitor {
+    /// transaction is about to be sent to the channel of the account
+    pub(crate) fn sent(&self, key: &AccountKey) {
+        if let Ok(mut accounts) = self.accounts.lock() {
+            accounts.entry(key.clone()).or_default().sent.push_back(Instant::now());
+        }
+    }
+
+    /// the oldest transaction sent to the account was taken from its channel
+    pub(crate) fn received(&self, key: &AccountKey) {
+        if let Some(lag) = self.accounts.lock().ok().as_mut().and_then(|a| a.get_mut(key)) {
+            if let Some(sent) = lag.sent.pop_front() {
+                lag.max_wait = lag.max_wait.max(sent.elapsed());
+            }
+        }
+    }
+
+    /// time the account took to apply the transaction
+    pub(crate) fn applied(&self, key: &AccountKey, took: Duration) {
+        if let Some(lag) = self.accounts.lock().ok().as_mut().and_then(|a| a.get_mut(key)) {
+            lag.processing += took;
+            lag.transactions += 1;
+        }
+    }
+
+    /// `n` accounts with the longest wait since the previous call (or the start), the slowest first,
+    /// their wait and processing time start again from zero
+    pub fn slowest(&self, n: usize) -> Vec<AccountLag> {
+        let mut accounts = match self.accounts.lock() {
+            Ok(accounts) => accounts,
+            Err(_) => return Vec::new(),
+        };
+        let mut slowest: Vec<_> = accounts
+            .iter_mut()
+            .map(|(key, lag)| {
+                // transactions still in the channel are waiting as well
+                let waiting = lag.sent.front().map(|sent| sent.elapsed()).unwrap_or_default();
+                let account = AccountLag {
+                    key: key.clone(),
+                    depth: lag.sent.len(),
+                    max_wait: lag.max_wait.max(waiting),
+                    processing: lag.processing,
+                    transactions: lag.transactions,
+                };
+                (lag.max_wait, lag.processing, lag.transactions) = (Duration::ZERO, Duration::ZERO, 0);
+                account
+            })
+            .collect();
+        slowest.sort_by(|a, b| b.max_wait.cmp(&a.max_wait).then_with(|| a.key.cmp(&b.key)));
+        slowest.truncate(n);
+        slowest
+    }
+}
+
+/// Lag of a single account over the reported interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountLag {
+    pub key: AccountKey,
+    /// transactions waiting in the channel of the account right now
+    pub depth: usize,
+    /// longest wait of a transaction in the channel
+    pub max_wait: Duration,
+    /// time spent applying `transactions`
+    pub processing: Duration,
+    pub transactions: u64,
+}
+
+impl AccountLag {
+    pub const HEADER: &'static str = "tenant,client,depth,max_wait_ms,processing_ms,transactions";
+}
+
+impl fmt::Display for AccountLag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{}",
+            self.key.0.as_deref().unwrap_or_default(),
+            self.key.1,
+            self.depth,
+            self.max_wait.as_millis(),
+            self.processing.as_millis(),
+            self.transactions
+        )
+    }
+}
@@ -0,0 +1,116 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    account::{Account, DisputeState},
+    AccountKey, Transaction, TxId,
+};
+
+/// Failed write to the storage behind a `Ledger`, e.g. a database which is not available
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerError(pub String);
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ledger storage failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+/// Storage of the state of a single account: its balances and the deposits and withdrawals which can be disputed.
+/// Every account task of `TxProcessor` owns the ledger of its account, implementations backed by a shared
+/// database synchronize themselves. Reads return owned values, so a backend does not have to keep the account in
+/// memory, and writes return the error of the storage, the transaction being applied is then rejected.
+pub trait Ledger: fmt::Debug + Send + Sync {
+    /// current balances of the account
+    fn account(&self) -> Account;
+
+    /// replaces the balances after a transaction was applied to the account
+    fn update_account(&mut self, account: Account) -> Result<(), LedgerError>;
+
+    /// recorded transaction with the id
+    fn transaction(&self, tx_id: TxId) -> Option<Transaction>;
+
+    /// records applied deposit or withdrawal so it can be disputed, replaces the one with the same id
+    fn record_tx(&mut self, t: Transaction) -> Result<(), LedgerError>;
+
+    /// moves the recorded transaction to the state, unknown transaction is ignored
+    fn mark_dispute(&mut self, tx_id: TxId, state: DisputeState) -> Result<(), LedgerError>;
+
+    /// forgets the recorded transaction, e.g. when it is reversed
+    fn remove_tx(&mut self, tx_id: TxId) -> Result<Option<Transaction>, LedgerError>;
+
+    /// all recorded transactions, in no particular order
+    fn transactions(&self) -> Vec<Transaction>;
+
+    /// number of recorded transactions
+    fn tx_count(&self) -> usize;
+}
+
+/// Opens the ledger of every account of `TxProcessor` (`ProcessorOptions::ledger`) when the account is created
+pub trait LedgerStore: fmt::Debug + Send + Sync {
+    /// `account` is the initial state of a new account, stores keeping state across runs return the stored one
+    fn open(&self, key: &AccountKey, account: Account) -> Box<dyn Ledger>;
+}
+
+/// Ledger kept in memory for the duration of the run
+#[derive(Debug, Default)]
+pub struct InMemoryLedger {
+    account: Account,
+    transactions: HashMap<TxId, Transaction>,
+}
+
+impl InMemoryLedger {
+    pub fn new(account: Account) -> Self {
+        InMemoryLedger { account, transactions: HashMap::new() }
+    }
+}
+
+impl Ledger for InMemoryLedger {
+    fn account(&self) -> Account {
+        self.account.clone()
+    }
+
+    fn update_account(&mut self, account: Account) -> Result<(), LedgerError> {
+        self.account = account;
+        Ok(())
+    }
+
+    fn transaction(&self, tx_id: TxId) -> Option<Transaction> {
+        self.transactions.get(&tx_id).cloned()
+    }
+
+    fn record_tx(&mut self, t: Transaction) -> Result<(), LedgerError> {
+        self.transactions.insert(t.tx_id, t);
+        Ok(())
+    }
+
+    fn mark_dispute(&mut self, tx_id: TxId, state: DisputeState) -> Result<(), LedgerError> {
+        if let Some(t) = self.transactions.get_mut(&tx_id) {
+            t.dispute = state;
+        }
+        Ok(())
+    }
+
+    fn remove_tx(&mut self, tx_id: TxId) -> Result<Option<Transaction>, LedgerError> {
+        Ok(self.transactions.remove(&tx_id))
+    }
+
+    fn transactions(&self) -> Vec<Transaction> {
+        self.transactions.values().cloned().collect()
+    }
+
+    fn tx_count(&self) -> usize {
+        self.transactions.len()
+    }
+}
+
+/// Store of `InMemoryLedger`s, the default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InMemoryStore;
+
+impl LedgerStore for InMemoryStore {
+    fn open(&self, _key: &AccountKey, account: Account) -> Box<dyn Ledger> {
+        Box::new(InMemoryLedger::new(account))
+    }
+}
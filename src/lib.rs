@@ -1,5 +1,7 @@
 #![deny(warnings)]
 
+use std::collections::HashMap;
+
 /// Error returned by most functions.
 ///
 /// todo: we might want to use specialized error handling crate or defining an error type as an `enum` of causes.
@@ -20,8 +22,17 @@ pub type TxId = u32;
 /// alias for money type
 pub type Money = f32;
 
+/// Time of the transaction, seconds since the unix epoch
+pub type Timestamp = u64;
+
+/// Tenant (brand) identifier type alias
+pub type TenantId = String;
+
+/// Key identifying an account: client ids are only unique within a tenant
+pub type AccountKey = (Option<TenantId>, ClientId);
+
 /// Transaction types
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TxType {
     Deposit,
@@ -29,16 +40,81 @@ pub enum TxType {
     Dispute,
     Resolve,
     Chargeback,
+    Open,
+    Close,
+    Settle,
+    Refund,
+}
+
+impl std::fmt::Display for TxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TxType::Deposit => "deposit",
+            TxType::Withdrawal => "withdrawal",
+            TxType::Dispute => "dispute",
+            TxType::Resolve => "resolve",
+            TxType::Chargeback => "chargeback",
+            TxType::Open => "open",
+            TxType::Close => "close",
+            TxType::Settle => "settle",
+            TxType::Refund => "refund",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for TxType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "deposit" => Ok(TxType::Deposit),
+            "withdrawal" => Ok(TxType::Withdrawal),
+            "dispute" => Ok(TxType::Dispute),
+            "resolve" => Ok(TxType::Resolve),
+            "chargeback" => Ok(TxType::Chargeback),
+            "open" => Ok(TxType::Open),
+            "close" => Ok(TxType::Close),
+            "settle" => Ok(TxType::Settle),
+            "refund" => Ok(TxType::Refund),
+            other => Err(format!("unknown transaction type '{}'", other)),
+        }
+    }
 }
 
 /// Transaction data
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Transaction {
     pub tx_type: TxType,
     pub client_id: ClientId,
     pub tx_id: TxId,
     pub amount: Money,
-    pub in_dispute: bool,
+    /// dispute state of the recorded deposit or withdrawal
+    #[serde(default)]
+    pub dispute: DisputeState,
+    pub tenant: Option<TenantId>,
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
+    /// key identifying resubmissions of the same transaction
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// position of the row in the feed, see `sequence`
+    #[serde(default)]
+    pub seq: Option<u64>,
+    /// line of the row in the input file, when read from CSV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    /// columns of the input row not in the input format, e.g. merchant or reference, kept only when
+    /// the reader is asked to (`CsvTransactionReader::keep_metadata`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl Transaction {
+    /// key of the account this transaction belongs to
+    pub fn account_key(&self) -> AccountKey {
+        (self.tenant.clone(), self.client_id)
+    }
 }
 
 // exposing tx module to be used by clients
@@ -49,5 +125,167 @@ extern crate serde;
 // expose this module for clients
 pub mod csv;
 
+// writing final account states and summaries
+pub mod output;
+
+// per account history of processed transactions
+pub mod history;
+
+// in memory processing engine without tasks
+pub mod engine;
+
+// storage of the account state beneath the processor
+pub mod ledger;
+
+// saving and loading state of all accounts
+pub mod snapshot;
+
+// stream of events reported while processing
+pub mod events;
+
+// processing configuration file
+pub mod config;
+
+// precision and rounding of amounts
+pub mod money;
+
+// user defined rules from the configuration file
+pub mod rules;
+
+// per client settings overriding the configuration
+pub mod clients;
+
+// rolling time based limits
+mod limits;
+
+// recently seen idempotency keys
+mod dedupe;
+
+// persistent store of applied transactions
+pub mod seen;
+
+// ordering validation of the feed
+pub mod sequence;
+
+// disputes waiting for the transaction they reference
+pub mod hold;
+
+// two-phase withdrawals waiting for settlement
+pub mod settlement;
+
+// accounts soft-locked for review after too many anomalies
+pub mod quarantine;
+
+// rate limiting of the input rows
+pub mod ratelimit;
+
+// pausing and resuming of the ingestion
+pub mod pause;
+
+// stopping of the ingestion before the shutdown
+pub mod drain;
+
+// time source of the account timers
+pub mod clock;
+
+// queue wait and processing time of the accounts
+pub mod lag;
+
+// pipeline of stages between the source and the accounts
+pub mod middleware;
+
+// disputes and chargebacks taken before other queued transactions of the account
+pub mod priority;
+
+// counts of processed and rejected transactions
+pub mod summary;
+
+// per transaction type counters for embedding services
+pub mod stats;
+
+// memory and task usage of the run
+pub mod resources;
+
+// throughput and latency of the processing stages
+pub mod timing;
+
+// in memory output and transaction factory for tests of code using the library
+pub mod testing;
+
+// regression scenarios compared with their expected output
+pub mod golden;
+
+// pathological dispute sequences with their expected outcomes
+pub mod fixtures;
+
+// bounded rankings of clients and transactions
+pub mod top;
+
+// statistics computed in the same pass as the balances
+pub mod aggregate;
+
+// tasks and timers of the processing core on tokio or async-std
+pub mod rt;
+
+// periodic snapshots of the accounts published while processing
+pub mod publish;
+
+// change-data-capture stream of the applied transactions
+pub mod cdc;
+
+// description of the run for audits
+pub mod manifest;
+
+// tamper-evident log of processing decisions
+pub mod audit;
+
+// encryption of snapshots and audit logs at rest
+pub mod crypto;
+
+// merging outcomes of files processed in parallel
+pub mod merge;
+
+// month-end corrections applied to a snapshot
+pub mod backfill;
+
+// search for the transaction causing a balance discrepancy
+pub mod bisect;
+
+// validation hook implemented in rhai script
+#[cfg(feature = "scripting")]
+pub mod scripting;
+
+// protobuf codec of transactions, snapshots and events
+#[cfg(feature = "proto")]
+pub mod proto;
+
+// import of OFX and QIF statements, shared by the importers of other formats
+pub mod import;
+
+// import of ISO 20022 payment files
+#[cfg(feature = "iso20022")]
+pub mod iso20022;
+
+// Excel workbooks as input
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+// Arrow IPC output
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+// SQL queries over the results
+#[cfg(feature = "sql")]
+pub mod sql;
+
+// Python module
+#[cfg(feature = "python")]
+pub mod python;
+
+// C interface of the cdylib
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
 // we do not need to expose this module for external use
-mod account;
\ No newline at end of file
+mod account;
+pub use account::{Account, AccountError, DisputeState};
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+use crate::{Money, Timestamp, TxType};
+
+/// length of the rolling window of the daily limits
+pub const DAY: Timestamp = 24 * 60 * 60;
+
+/// length of a single bucket of the rolling window
+const BUCKET: Timestamp = 60 * 60;
+
+/// Deposit and withdrawal totals of the account over the last 24 hours.
+///
+/// Amounts are aggregated in hourly buckets, so at most 24 buckets are kept per account
+/// and the window moves in hour steps: a bucket is dropped once it is entirely older than 24 hours.
+#[derive(Debug, Default)]
+pub(crate) struct RollingTotals {
+    // (bucket start, deposits, withdrawals), oldest first
+    buckets: VecDeque<(Timestamp, Money, Money)>,
+}
+
+impl RollingTotals {
+    /// sum of `tx_type` amounts within 24 hours before `now`
+    pub fn total(&mut self, tx_type: &TxType, now: Timestamp) -> Money {
+        self.evict(now);
+        self.buckets
+            .iter()
+            .map(|(_, deposits, withdrawals)| match tx_type {
+                TxType::Deposit => *deposits,
+                TxType::Withdrawal => *withdrawals,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// adds amount of the applied deposit or withdrawal made at `now`
+    pub fn add(&mut self, tx_type: &TxType, amount: Money, now: Timestamp) {
+        let start = now - now % BUCKET;
+        // rows are expected in time order, late ones are counted in the newest bucket
+        let bucket = match self.buckets.back_mut() {
+            Some(b) if b.0 >= start => b,
+            _ => {
+                self.buckets.push_back((start, 0.0, 0.0));
+                self.buckets.back_mut().expect("bucket was just added")
+            }
+        };
+        match tx_type {
+            TxType::Deposit => bucket.1 += amount,
+            TxType::Withdrawal => bucket.2 += amount,
+            _ => (),
+        }
+    }
+
+    fn evict(&mut self, now: Timestamp) {
+        while self.buckets.front().is_some_and(|b| b.0 + BUCKET + DAY <= now) {
+            self.buckets.pop_front();
+        }
+    }
+}
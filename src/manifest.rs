@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{config::Config, stats::ProcessingStats, summary::RunSummary, timing::RunTimings, Result, Timestamp};
+
+/// Description of a processing run written next to its outputs, so every output file can be traced
+/// back to the exact input, configuration and software which produced it
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct RunManifest {
+    /// version of the software
+    pub version: String,
+    /// start and end of the run, seconds since the unix epoch
+    pub started: Timestamp,
+    pub finished: Timestamp,
+    /// input files in the order they were processed, empty when reading from stdin
+    pub inputs: Vec<InputFile>,
+    pub rows: RowCounts,
+    pub config: ConfigInfo,
+    pub filters: Filters,
+    pub summary: RunSummary,
+    pub stats: ProcessingStats,
+    /// throughput and latency of the processing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<RunTimings>,
+    /// accounts of the initial state left out of the output as unchanged (`--output-changed-only`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub untouched: Option<u64>,
+}
+
+/// Single input file with the digest of its content
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct InputFile {
+    pub path: String,
+    /// SHA-256 of the file, lowercase hex
+    pub sha256: String,
+    /// digest was checked against the `<file>.sha256` sidecar file (`--verify-checksum`)
+    pub verified: bool,
+}
+
+/// Rows read from the input
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct RowCounts {
+    /// all parsed rows
+    pub read: u64,
+    /// rows skipped by the filters before processing
+    pub filtered: u64,
+}
+
+/// Configuration in effect at the start of the run
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ConfigInfo {
+    /// configuration file and its SHA-256, `None` when running with the defaults
+    pub path: Option<String>,
+    pub sha256: Option<String>,
+    pub version: Option<String>,
+    pub currency: Option<String>,
+    pub precision: usize,
+    pub rounding: String,
+}
+
+impl ConfigInfo {
+    pub fn new(path: Option<&Path>, config: &Config) -> Result<ConfigInfo> {
+        Ok(ConfigInfo {
+            path: path.map(|p| p.display().to_string()),
+            sha256: path.map(sha256_file).transpose()?,
+            version: config.version.clone(),
+            currency: config.currency.clone(),
+            precision: config.precision(),
+            rounding: config.rounding.to_string(),
+        })
+    }
+}
+
+/// Filters selecting transactions for processing
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct Filters {
+    /// client ids and ranges, e.g. `7,42,100-200`
+    pub clients: Option<String>,
+    pub exclude_types: Vec<String>,
+    pub only_types: Vec<String>,
+    pub sample: Option<f64>,
+    pub seed: Option<u64>,
+}
+
+impl RunManifest {
+    /// writes the manifest as pretty printed JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// current time in seconds since the unix epoch
+pub fn now() -> Timestamp {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// SHA-256 of the file content, lowercase hex
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// lowercase hex representation of the digest
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// path of the sidecar file with the expected digest of `path`, `<path>.sha256`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    PathBuf::from(sidecar)
+}
+
+/// Reads expected SHA-256 of `path` from its sidecar file, in `sha256sum` format (`<hex digest>  <file name>`)
+/// or just the digest
+pub fn expected_sha256(path: &Path) -> Result<String> {
+    let sidecar = sidecar_path(path);
+    let content = fs::read_to_string(&sidecar).map_err(|e| format!("failed reading checksum file {:?}: {}", sidecar, e))?;
+    match content.split_whitespace().next() {
+        Some(digest) if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(digest.to_ascii_lowercase())
+        }
+        _ => Err(format!("checksum file {:?} does not contain SHA-256 digest", sidecar).into()),
+    }
+}
+
+/// Computes SHA-256 of everything read through it, so the input can be verified while it is parsed
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+/// Digest of the data read so far by `HashingReader`, available after the reader was consumed
+#[derive(Clone, Default)]
+pub struct DigestHandle(Arc<Mutex<Sha256>>);
+
+impl DigestHandle {
+    /// SHA-256 of the data read so far, lowercase hex
+    pub fn hex(&self) -> String {
+        let hasher = self.0.lock().map(|h| h.clone()).unwrap_or_default();
+        to_hex(&hasher.finalize())
+    }
+}
+
+impl<R> HashingReader<R> {
+    pub fn new(inner: R) -> (Self, DigestHandle) {
+        let handle = DigestHandle::default();
+        (HashingReader { inner, hasher: handle.0.clone() }, handle)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Ok(mut hasher)) = (&poll, self.hasher.lock()) {
+            hasher.update(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, task::Poll};
+
+use futures::future;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::warn;
+
+use crate::{
+    sequence::SequenceStats,
+    stats::ProcessingStats,
+    tx::{AccountReport, ProcessingOutcome},
+    AccountKey, Transaction,
+};
+
+/// Account found in the outcomes of more than one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub key: AccountKey,
+    /// names of the files with transactions of the account, in the merge order
+    pub files: Vec<String>,
+}
+
+/// Merges outcomes of files processed independently (one engine per file) into outcome of the whole run.
+///
+/// Files are expected to contain disjoint sets of clients. Accounts found in several files are still merged,
+/// in the order of the file names so the result does not depend on which file finished first:
+/// balances are added up, the account is locked when it is locked in any file, closed state is taken from the last file,
+/// last activity is the latest one and histories, rejects and unresolved disputes are concatenated.
+/// Such accounts are returned as `Overlap`s. The run is aborted by the first aborted file in that order.
+pub fn merge_outcomes(mut outcomes: Vec<(String, ProcessingOutcome)>) -> (ProcessingOutcome, Vec<Overlap>) {
+    outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut sequence: Option<SequenceStats> = None;
+    let mut aborted = None;
+    let mut merged = BTreeMap::<AccountKey, (AccountReport, Vec<String>)>::new();
+    for (file, outcome) in outcomes {
+        if let Some(stats) = outcome.sequence {
+            sequence.get_or_insert_with(SequenceStats::default).merge(&stats);
+        }
+        aborted = aborted.or(outcome.aborted);
+        for report in outcome.reports {
+            let key = (report.account.tenant.clone(), report.account.client_id);
+            match merged.get_mut(&key) {
+                Some((existing, files)) => {
+                    merge_report(existing, report);
+                    files.push(file.clone());
+                }
+                None => {
+                    merged.insert(key, (report, vec![file.clone()]));
+                }
+            }
+        }
+    }
+
+    let mut overlaps = Vec::new();
+    let mut reports = Vec::with_capacity(merged.len());
+    for (key, (report, files)) in merged {
+        if files.len() > 1 {
+            warn!("client {} of tenant {:?} found in files {:?}", key.1, key.0, files);
+            overlaps.push(Overlap { key, files });
+        }
+        reports.push(report);
+    }
+    let stats = ProcessingStats::from_reports(&reports);
+    (ProcessingOutcome { reports, sequence, stats, aborted }, overlaps)
+}
+
+// adds report of the same account from the following file
+fn merge_report(into: &mut AccountReport, report: AccountReport) {
+    let a = &mut into.account;
+    let b = report.account;
+    a.available_amount += b.available_amount;
+    a.held_amount += b.held_amount;
+    a.total_amount += b.total_amount;
+    a.is_locked |= b.is_locked;
+    a.is_closed = b.is_closed;
+    a.last_activity = a.last_activity.max(b.last_activity);
+    into.history.extend(report.history);
+    into.rejects.extend(report.rejects);
+    into.summary.merge(&report.summary);
+    into.stats.merge(&report.stats);
+    into.unresolved.extend(report.unresolved);
+    // balances of a single file, the first file (by name) where the account went negative wins
+    into.negative = into.negative.take().or(report.negative);
+    into.disputed.extend(report.disputed);
+    into.largest.extend(report.largest);
+}
+
+/// Order in which `merge_sources` passes on transactions of concurrent sources
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeOrder {
+    /// sources one after another in the order given, e.g. a backfill file before the live stream,
+    /// later sources are held back (by their bounded channels) until the earlier ones end
+    #[default]
+    Sequential,
+    /// transactions as they arrive, whichever source has one ready
+    Arrival,
+    /// earliest timestamp first, ties and transactions without timestamp in the order of the sources,
+    /// waits until every source that has not ended yet has a transaction ready
+    Timestamp,
+}
+
+/// Merges transactions of concurrent sources into a single stream for one `TxProcessor`.
+///
+/// Each source ends with `None` (or by closing its channel), `None` is sent to `sender` once all of them ended.
+/// Returns the number of transactions passed on from every source, stops early when `sender` is closed.
+pub async fn merge_sources(
+    mut sources: Vec<Receiver<Option<Transaction>>>,
+    order: MergeOrder,
+    sender: Sender<Option<Transaction>>,
+) -> Vec<u64> {
+    let mut counts = vec![0; sources.len()];
+    match order {
+        MergeOrder::Sequential => {
+            for (i, source) in sources.iter_mut().enumerate() {
+                while let Some(Some(t)) = source.recv().await {
+                    if sender.send(Some(t)).await.is_err() {
+                        return counts;
+                    }
+                    counts[i] += 1;
+                }
+            }
+        }
+        MergeOrder::Arrival => {
+            let mut open: Vec<_> = sources.iter_mut().enumerate().collect();
+            // sources are polled starting from a different one every time so a busy source can not starve the others
+            let mut start = 0;
+            while !open.is_empty() {
+                let (i, t) = future::poll_fn(|cx| {
+                    for n in 0..open.len() {
+                        let k = (start + n) % open.len();
+                        if let Poll::Ready(t) = open[k].1.poll_recv(cx) {
+                            return Poll::Ready((k, t.flatten()));
+                        }
+                    }
+                    Poll::Pending
+                })
+                .await;
+                start = i + 1;
+                match t {
+                    Some(t) => {
+                        if sender.send(Some(t)).await.is_err() {
+                            return counts;
+                        }
+                        counts[open[i].0] += 1;
+                    }
+                    None => {
+                        open.remove(i);
+                    }
+                }
+            }
+        }
+        MergeOrder::Timestamp => {
+            let mut heads: Vec<Option<Transaction>> = Vec::with_capacity(sources.len());
+            for source in sources.iter_mut() {
+                heads.push(source.recv().await.flatten());
+            }
+            // `None` timestamps are the smallest, the first source wins ties
+            while let Some(i) = (0..heads.len())
+                .filter(|&i| heads[i].is_some())
+                .min_by_key(|&i| (heads[i].as_ref().and_then(|t| t.timestamp), i))
+            {
+                let next = sources[i].recv().await.flatten();
+                let t = std::mem::replace(&mut heads[i], next);
+                if sender.send(t).await.is_err() {
+                    return counts;
+                }
+                counts[i] += 1;
+            }
+        }
+    }
+    let _ = sender.send(None).await;
+    counts
+}
@@ -0,0 +1,94 @@
+use std::{fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use crate::{pause::IngestControl, ratelimit::RateLimiter, Transaction};
+
+/// Stage of the pipeline between the source of the transactions and the accounts (`ProcessorOptions::middleware`).
+/// Every transaction is passed to `handle` of the first stage, which passes it on with `next.run`, or drops it
+/// by not doing so, or passes on more transactions, e.g. the ones it held back. Stages are called from the
+/// single routing task of `TxProcessor` in the order of the stream, implementations keep their state behind a lock.
+pub trait TxMiddleware: fmt::Debug + Send + Sync {
+    fn handle<'a>(&'a self, t: Transaction, next: Next<'a>) -> BoxFuture<'a, ()>;
+
+    /// called once at the end of the stream, transactions still held back are passed on with `next.run`
+    fn finish<'a>(&'a self, next: Next<'a>) -> BoxFuture<'a, ()> {
+        let _ = next;
+        Box::pin(async {})
+    }
+}
+
+/// Rest of the pipeline after the current stage
+pub struct Next<'a> {
+    stages: &'a [Arc<dyn TxMiddleware>],
+    passed: &'a mut Vec<Transaction>,
+}
+
+impl Next<'_> {
+    /// passes the transaction to the next stage, or to the accounts after the last one
+    pub async fn run(&mut self, t: Transaction) {
+        match self.stages.split_first() {
+            None => self.passed.push(t),
+            Some((stage, rest)) => stage.handle(t, Next { stages: rest, passed: self.passed }).await,
+        }
+    }
+}
+
+/// transactions which passed all `stages`, in the order they are routed to the accounts
+pub async fn run(stages: &[Arc<dyn TxMiddleware>], t: Transaction) -> Vec<Transaction> {
+    let mut passed = Vec::new();
+    Next { stages, passed: &mut passed }.run(t).await;
+    passed
+}
+
+/// transactions released by `finish` of the stages, each stage is finished after the ones before it
+pub async fn finish(stages: &[Arc<dyn TxMiddleware>]) -> Vec<Transaction> {
+    let mut passed = Vec::new();
+    for (i, stage) in stages.iter().enumerate() {
+        stage.finish(Next { stages: &stages[i + 1..], passed: &mut passed }).await;
+    }
+    passed
+}
+
+/// Passes on transactions for which the predicate returns `true`
+pub struct Filter<F>(pub F);
+
+impl<F> fmt::Debug for Filter<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+impl<F> TxMiddleware for Filter<F>
+where
+    F: Fn(&Transaction) -> bool + Send + Sync,
+{
+    fn handle<'a>(&'a self, t: Transaction, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if (self.0)(&t) {
+                next.run(t).await;
+            }
+        })
+    }
+}
+
+/// Holds the transactions while the ingestion is paused, see `IngestControl`
+impl TxMiddleware for IngestControl {
+    fn handle<'a>(&'a self, t: Transaction, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            self.wait_resumed().await;
+            next.run(t).await;
+        })
+    }
+}
+
+/// Limits the rate of the transactions, see `RateLimiter::admit`
+impl TxMiddleware for RateLimiter {
+    fn handle<'a>(&'a self, t: Transaction, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if self.admit().await {
+                next.run(t).await;
+            }
+        })
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use crate::Money;
+
+/// number of decimal places of currencies not found in the precision table
+pub const DEFAULT_PRECISION: usize = 4;
+
+/// number of decimal places of the currencies known without configuration
+const PRECISION_TABLE: &[(&str, usize)] = &[("JPY", 0), ("KRW", 0), ("BTC", 8)];
+
+/// Decimal places of `currency` from the built-in table, `DEFAULT_PRECISION` for unknown codes
+pub fn precision(currency: &str) -> usize {
+    PRECISION_TABLE
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(currency))
+        .map_or(DEFAULT_PRECISION, |(_, decimals)| *decimals)
+}
+
+/// How amounts with more decimal places than the precision of the currency are rounded
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// ties are rounded away from zero, `0.00005` -> `0.0001`
+    HalfUp,
+    /// ties are rounded to the even digit (banker's rounding), `0.00005` -> `0.0000`, `0.00015` -> `0.0002`
+    #[default]
+    HalfEven,
+    /// extra decimal places are dropped, `0.00019` -> `0.0001`
+    Truncate,
+}
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RoundingMode::HalfUp => "half_up",
+            RoundingMode::HalfEven => "half_even",
+            RoundingMode::Truncate => "truncate",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Rounds `amount` to `decimals` decimal places using `mode`.
+///
+/// Rounding works on the shortest decimal representation of the amount (the one it was parsed from),
+/// so `1.00005` is a tie even though the nearest `f32` is slightly below it.
+pub fn round(amount: Money, decimals: usize, mode: RoundingMode) -> Money {
+    if !amount.is_finite() {
+        return amount;
+    }
+    // `Display` of floats never uses the exponent notation
+    let digits = amount.abs().to_string();
+    let (int, frac) = digits.split_once('.').unwrap_or((&digits, ""));
+    if frac.len() <= decimals {
+        return amount;
+    }
+    let (kept, rest) = frac.split_at(decimals);
+    let round_up = match mode {
+        RoundingMode::Truncate => false,
+        RoundingMode::HalfUp => rest.as_bytes()[0] >= b'5',
+        RoundingMode::HalfEven => {
+            let last = kept.bytes().last().unwrap_or_else(|| int.bytes().last().unwrap_or(b'0'));
+            match rest.as_bytes()[0] {
+                b'6'..=b'9' => true,
+                b'5' => rest[1..].bytes().any(|d| d != b'0') || (last - b'0') % 2 == 1,
+                _ => false,
+            }
+        }
+    };
+    let mut rounded: f64 = format!("{}.{}", int, kept).parse().unwrap_or_default();
+    if round_up {
+        rounded += 10f64.powi(-(decimals as i32));
+    }
+    (rounded as Money).copysign(amount)
+}
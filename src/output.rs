@@ -0,0 +1,789 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+use crate::{
+    clients::ClientDirectory,
+    config::Config,
+    csv::{ParseError, RawAccount},
+    events::{Outcome, ProcessingEvent},
+    limits::DAY,
+    merge::Overlap,
+    money::DEFAULT_PRECISION,
+    snapshot::AccountSnapshot,
+    top::TopN,
+    tx::AccountReport,
+    AccountKey, ClientId, Money, Result, TenantId, Timestamp, Transaction, TxId, TxType,
+};
+
+/// header row of the account output
+pub const ACCOUNT_HEADER: &str = "client,available,held,total,locked,status";
+
+/// header row of the per client statement
+pub const STATEMENT_HEADER: &str = "type,tx,amount,available,held,total,locked";
+
+/// header row of the rejects file
+pub const REJECTS_HEADER: &str = "tenant,client,type,tx,amount,code,reason,line";
+
+/// header row of the unresolved disputes report
+pub const UNRESOLVED_HEADER: &str = "tenant,client,type,tx";
+
+/// header row of the dormant accounts report
+pub const DORMANT_HEADER: &str = "tenant,client,last_activity,days_inactive,available,held,total";
+
+/// header row of the decision trace, one row per input row
+pub const DECISIONS_HEADER: &str = "tenant,client,type,tx,amount,outcome,reason,available,held,total,locked";
+
+/// header row of the report of accounts whose balance went negative
+pub const NEGATIVE_HEADER: &str = "tenant,client,tx,type,available,total";
+
+/// header row of the review report of quarantined accounts
+pub const QUARANTINE_HEADER: &str = "tenant,client,tx,type,anomalies,available,held,total";
+
+/// header row of the report of transactions still in dispute
+pub const OPEN_DISPUTES_HEADER: &str = "tenant,client,tx,type,amount,timestamp,age_days";
+
+/// header row of the held funds aging report
+pub const HELD_AGING_HEADER: &str = "tenant,client,days_0_7,days_8_30,days_31_90,days_over_90,no_timestamp";
+
+/// upper bounds (in days, inclusive) of the held funds aging buckets, older funds go to the last bucket
+const AGING_BUCKETS: [u64; 3] = [7, 30, 90];
+
+/// header row of the chargeback report
+pub const CHARGEBACKS_HEADER: &str = "tenant,client,chargebacks,chargeback_amount,deposits,deposit_amount,ratio,frozen";
+
+/// header row of the top report, `kind` is `client` (rows ranked by turnover) or `transaction`
+pub const TOP_HEADER: &str = "kind,rank,tenant,client,tx,amount";
+
+/// header row of the report of clients found in more than one input file
+pub const OVERLAPS_HEADER: &str = "tenant,client,files";
+
+/// name used for the output files of accounts which do not belong to any tenant
+pub const DEFAULT_TENANT: &str = "default";
+
+/// account row in the output format, e.g. `1,1.5000,0.0000,1.5000,false,active`,
+/// amounts are written with the precision of the format (`{:.2}`), 4 decimal places by default
+impl fmt::Display for RawAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let p = f.precision().unwrap_or(DEFAULT_PRECISION);
+        write!(
+            f,
+            "{},{:.p$},{:.p$},{:.p$},{},{}",
+            self.client_id,
+            self.available_amount,
+            self.held_amount,
+            self.total_amount,
+            self.is_locked,
+            self.status()
+        )
+    }
+}
+
+/// Aggregated state of all accounts of one tenant
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TenantSummary {
+    pub tenant: Option<TenantId>,
+    pub accounts: usize,
+    pub locked_accounts: usize,
+    pub available_amount: Money,
+    pub held_amount: Money,
+    pub total_amount: Money,
+}
+
+impl TenantSummary {
+    /// header row of the summary output
+    pub const HEADER: &'static str = "tenant,accounts,locked,available,held,total";
+
+    /// builds one summary per tenant, ordered by tenant
+    pub fn from_accounts(accounts: &[RawAccount]) -> Vec<TenantSummary> {
+        group_by_tenant(accounts)
+            .into_iter()
+            .map(|(tenant, accounts)| {
+                let mut summary = TenantSummary {
+                    tenant: tenant.cloned(),
+                    ..Default::default()
+                };
+                for a in accounts {
+                    summary.accounts += 1;
+                    summary.locked_accounts += a.is_locked as usize;
+                    summary.available_amount += a.available_amount;
+                    summary.held_amount += a.held_amount;
+                    summary.total_amount += a.total_amount;
+                }
+                summary
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TenantSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let p = f.precision().unwrap_or(DEFAULT_PRECISION);
+        write!(
+            f,
+            "{},{},{},{:.p$},{:.p$},{:.p$}",
+            self.tenant.as_deref().unwrap_or(DEFAULT_TENANT),
+            self.accounts,
+            self.locked_accounts,
+            self.available_amount,
+            self.held_amount,
+            self.total_amount
+        )
+    }
+}
+
+/// When the output buffered by `FlushingWriter` is written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// output is written out once this many bytes are buffered
+    pub bytes: usize,
+    /// output is also written out when this much time passed since it was last written out, checked on every write
+    pub interval: Option<Duration>,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy { bytes: 64 * 1024, interval: None }
+    }
+}
+
+/// Buffered writer of the results, e.g. to stdout, which is otherwise written out with a syscall per line.
+/// Output is written out as set by `FlushPolicy`, on `flush` and at the latest when the writer is dropped,
+/// so the output is complete also when the run ends early with an error.
+#[derive(Debug)]
+pub struct FlushingWriter<W: Write> {
+    inner: BufWriter<W>,
+    interval: Option<Duration>,
+    flushed: Instant,
+}
+
+impl<W: Write> FlushingWriter<W> {
+    pub fn new(inner: W, policy: FlushPolicy) -> Self {
+        FlushingWriter {
+            inner: BufWriter::with_capacity(policy.bytes.max(1), inner),
+            interval: policy.interval,
+            flushed: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write> Write for FlushingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if self.interval.is_some_and(|interval| self.flushed.elapsed() >= interval) {
+            self.flush()?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.flushed = Instant::now();
+        Ok(())
+    }
+}
+
+/// Destination of the final state of the accounts written by `TxProcessor::process_transactions_into`
+pub trait OutputSink {
+    fn write_account(&mut self, account: &RawAccount) -> io::Result<()>;
+
+    /// called once after the last account
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// accounts are written as rows without the header
+impl<W: Write> OutputSink for FlushingWriter<W> {
+    fn write_account(&mut self, account: &RawAccount) -> io::Result<()> {
+        writeln!(self, "{}", account)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Writes header and all accounts to `w`, amounts with `precision` decimal places.
+/// When any account belongs to a tenant, rows are prefixed with a `tenant` column
+/// as client ids are then no longer unique.
+pub fn write_accounts<W: Write>(w: &mut W, accounts: &[RawAccount], precision: usize) -> Result<()> {
+    write_accounts_with_clients(w, accounts, precision, &ClientDirectory::default())
+}
+
+/// Same as `write_accounts`, with the details of the clients (see `ClientDirectory`) in the last columns
+pub fn write_accounts_with_clients<W: Write>(
+    w: &mut W,
+    accounts: &[RawAccount],
+    precision: usize,
+    clients: &ClientDirectory,
+) -> Result<()> {
+    let with_tenant = accounts.iter().any(|a| a.tenant.is_some());
+    write_account_rows(w, accounts, with_tenant, precision, clients)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// header and account rows, with a leading `tenant` column when `with_tenant`
+fn write_account_rows<'a, W: Write>(
+    w: &mut W,
+    accounts: impl IntoIterator<Item = &'a RawAccount>,
+    with_tenant: bool,
+    precision: usize,
+    clients: &ClientDirectory,
+) -> Result<()> {
+    if with_tenant {
+        writeln!(w, "tenant,{}{}", ACCOUNT_HEADER, clients.header())?;
+    } else {
+        writeln!(w, "{}{}", ACCOUNT_HEADER, clients.header())?;
+    }
+    for a in accounts {
+        if with_tenant {
+            write!(w, "{},", a.tenant.as_deref().unwrap_or(DEFAULT_TENANT))?;
+        }
+        writeln!(w, "{:.*}{}", precision, a, clients.columns(&(a.tenant.clone(), a.client_id)))?;
+    }
+    Ok(())
+}
+
+/// Writes accounts of every tenant into `<dir>/<tenant>.csv` and
+/// per tenant summaries into `<dir>/summary.csv`, amounts with `precision` decimal places
+pub fn write_tenant_files(dir: &Path, accounts: &[RawAccount], precision: usize, clients: &ClientDirectory) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for (tenant, accounts) in group_by_tenant(accounts) {
+        let path = dir.join(format!("{}.csv", file_stem(tenant)));
+        debug!("writing {} accounts to {:?}", accounts.len(), &path);
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "{}{}", ACCOUNT_HEADER, clients.header())?;
+        for a in accounts {
+            writeln!(w, "{:.*}{}", precision, a, clients.columns(&(a.tenant.clone(), a.client_id)))?;
+        }
+        w.flush()?;
+    }
+
+    let mut w = BufWriter::new(File::create(dir.join("summary.csv"))?);
+    writeln!(w, "{}", TenantSummary::HEADER)?;
+    for summary in TenantSummary::from_accounts(accounts) {
+        writeln!(w, "{:.*}", precision, summary)?;
+    }
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Output file of an account written by `write_status_files`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StatusGroup {
+    Locked,
+    Negative,
+    Normal,
+}
+
+impl StatusGroup {
+    pub const ALL: [StatusGroup; 3] = [StatusGroup::Locked, StatusGroup::Negative, StatusGroup::Normal];
+
+    /// locked accounts are `Locked` whatever their balance, `Negative` when available or total funds are below zero
+    pub fn of(account: &RawAccount) -> Self {
+        if account.is_locked {
+            StatusGroup::Locked
+        } else if account.available_amount < 0.0 || account.total_amount < 0.0 {
+            StatusGroup::Negative
+        } else {
+            StatusGroup::Normal
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            StatusGroup::Locked => "locked.csv",
+            StatusGroup::Negative => "negative.csv",
+            StatusGroup::Normal => "normal.csv",
+        }
+    }
+}
+
+/// Writes accounts of every `StatusGroup` into its own file of `dir` (`locked.csv`, `negative.csv` and `normal.csv`),
+/// every file is written, also when empty, and all of them have the `tenant` column when any account has a tenant
+pub fn write_status_files(dir: &Path, accounts: &[RawAccount], precision: usize, clients: &ClientDirectory) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let with_tenant = accounts.iter().any(|a| a.tenant.is_some());
+    for group in StatusGroup::ALL {
+        let path = dir.join(group.file_name());
+        let members: Vec<_> = accounts.iter().filter(|a| StatusGroup::of(a) == group).collect();
+        debug!("writing {} accounts to {:?}", members.len(), &path);
+        let mut w = BufWriter::new(File::create(path)?);
+        write_account_rows(&mut w, members, with_tenant, precision, clients)?;
+        w.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes `metadata.csv` describing how the amounts in the output files of `dir` were produced
+pub fn write_metadata(dir: &Path, config: &Config) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut w = BufWriter::new(File::create(dir.join("metadata.csv"))?);
+    writeln!(w, "key,value")?;
+    writeln!(w, "currency,{}", config.currency.as_deref().unwrap_or_default())?;
+    writeln!(w, "precision,{}", config.precision())?;
+    writeln!(w, "rounding,{}", config.rounding)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes one statement file per account into `dir`, named `<client>.csv` (`<tenant>-<client>.csv` for tenants).
+/// Statement lists every applied transaction in order with the running balance, followed by the closing position.
+/// Requires reports produced with at least `HistoryRetention::Applied`, rejected transactions are skipped.
+pub fn write_statements(dir: &Path, reports: &[AccountReport], clients: &ClientDirectory) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for report in reports {
+        let account = &report.account;
+        let name = match account.tenant {
+            Some(_) => format!("{}-{}.csv", file_stem(account.tenant.as_ref()), account.client_id),
+            None => format!("{}.csv", account.client_id),
+        };
+        let mut w = BufWriter::new(File::create(dir.join(name))?);
+        let columns = metadata_columns(report.history.iter().filter(|e| e.is_applied()).map(|e| &e.transaction));
+        let client = clients.columns(&(account.tenant.clone(), account.client_id));
+        writeln!(w, "{}{}{}", STATEMENT_HEADER, metadata_header(&columns), clients.header())?;
+        for entry in report.history.iter().filter(|e| e.is_applied()) {
+            let t = &entry.transaction;
+            // dispute, resolve, chargeback and settle only reference the amount of another transaction
+            let amount = match t.tx_type {
+                TxType::Deposit | TxType::Withdrawal | TxType::Refund => format!("{:.4}", t.amount),
+                TxType::Dispute | TxType::Resolve | TxType::Chargeback | TxType::Open | TxType::Close | TxType::Settle => String::new(),
+            };
+            writeln!(
+                w,
+                "{},{},{},{:.4},{:.4},{:.4},{}{}{}",
+                t.tx_type,
+                t.tx_id,
+                amount,
+                entry.available_amount,
+                entry.held_amount,
+                entry.total_amount,
+                entry.is_locked,
+                metadata_fields(t, &columns),
+                client
+            )?;
+        }
+        writeln!(
+            w,
+            "closing,,,{:.4},{:.4},{:.4},{}{}{}",
+            account.available_amount,
+            account.held_amount,
+            account.total_amount,
+            account.is_locked,
+            ",".repeat(columns.len()),
+            client
+        )?;
+        w.flush()?;
+    }
+    debug!("written {} statements to {:?}", reports.len(), dir);
+
+    Ok(())
+}
+
+/// Writes every rejected transaction with the reason of the rejection to `w`.
+/// Requires reports produced with `ProcessorOptions::rejects` set.
+pub fn write_rejects<W: Write>(w: &mut W, reports: &[AccountReport]) -> Result<()> {
+    let columns = metadata_columns(reports.iter().flat_map(|r| &r.rejects).map(|e| &e.transaction));
+    writeln!(w, "{}{}", REJECTS_HEADER, metadata_header(&columns))?;
+    for report in reports {
+        for entry in &report.rejects {
+            let t = &entry.transaction;
+            writeln!(
+                w,
+                "{},{},{},{},{:.4},{},\"{}\",{}{}",
+                t.tenant.as_deref().unwrap_or_default(),
+                t.client_id,
+                t.tx_type,
+                t.tx_id,
+                t.amount,
+                entry.error.as_ref().map(|e| e.code()).unwrap_or_default(),
+                entry.rejection.as_deref().unwrap_or_default().replace('"', "\"\""),
+                t.line.map(|l| l.to_string()).unwrap_or_default(),
+                metadata_fields(t, &columns)
+            )?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+// names of the metadata columns of the transactions, ordered by name
+fn metadata_columns<'a>(transactions: impl Iterator<Item = &'a Transaction>) -> Vec<&'a str> {
+    let columns: BTreeSet<_> = transactions.flat_map(|t| t.metadata.keys().map(String::as_str)).collect();
+    columns.into_iter().collect()
+}
+
+// metadata columns appended to the header
+fn metadata_header(columns: &[&str]) -> String {
+    columns.iter().map(|c| format!(",{}", c)).collect()
+}
+
+// quoted values of the metadata columns appended to the row, empty for the columns the transaction does not have
+fn metadata_fields(t: &Transaction, columns: &[&str]) -> String {
+    columns
+        .iter()
+        .map(|c| format!(",\"{}\"", t.metadata.get(*c).map(String::as_str).unwrap_or_default().replace('"', "\"\"")))
+        .collect()
+}
+
+/// Writes disputes, resolves and chargebacks whose transaction never arrived (see `dispute_retry` configuration)
+pub fn write_unresolved<W: Write>(w: &mut W, reports: &[AccountReport]) -> Result<()> {
+    writeln!(w, "{}", UNRESOLVED_HEADER)?;
+    for t in reports.iter().flat_map(|r| &r.unresolved) {
+        writeln!(w, "{},{},{},{}", t.tenant.as_deref().unwrap_or_default(), t.client_id, t.tx_type, t.tx_id)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes accounts whose available or total funds went negative at any point of the run,
+/// with the transaction which did it first and the balances right after it
+pub fn write_negative<W: Write>(w: &mut W, reports: &[AccountReport], clients: &ClientDirectory) -> Result<()> {
+    writeln!(w, "{}{}", NEGATIVE_HEADER, clients.header())?;
+    for r in reports {
+        if let Some(n) = &r.negative {
+            writeln!(
+                w,
+                "{},{},{},{},{:.4},{:.4}{}",
+                r.account.tenant.as_deref().unwrap_or_default(),
+                r.account.client_id,
+                n.tx_id,
+                n.tx_type,
+                n.available_amount,
+                n.total_amount,
+                clients.columns(&(r.account.tenant.clone(), r.account.client_id))
+            )?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes accounts quarantined during the run for review, with the transaction which got them quarantined,
+/// their number of anomalies and the final balances
+pub fn write_quarantined<W: Write>(w: &mut W, reports: &[AccountReport], clients: &ClientDirectory) -> Result<()> {
+    writeln!(w, "{}{}", QUARANTINE_HEADER, clients.header())?;
+    for r in reports {
+        if let Some(q) = &r.quarantined {
+            writeln!(
+                w,
+                "{},{},{},{},{},{:.4},{:.4},{:.4}{}",
+                r.account.tenant.as_deref().unwrap_or_default(),
+                r.account.client_id,
+                q.tx_id,
+                q.tx_type,
+                q.anomalies,
+                r.account.available_amount,
+                r.account.held_amount,
+                r.account.total_amount,
+                clients.columns(&(r.account.tenant.clone(), r.account.client_id))
+            )?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes every transaction still in dispute at the end of the run, whose funds are held.
+/// Age is the number of full days from the timestamp of the transaction to `as_of`, empty without timestamps.
+pub fn write_open_disputes<W: Write>(
+    w: &mut W,
+    reports: &[AccountReport],
+    as_of: Option<Timestamp>,
+    clients: &ClientDirectory,
+) -> Result<()> {
+    writeln!(w, "{}{}", OPEN_DISPUTES_HEADER, clients.header())?;
+    for t in reports.iter().flat_map(|r| &r.disputed) {
+        let age = t.timestamp.zip(as_of).map(|(ts, as_of)| (as_of.saturating_sub(ts) / DAY).to_string());
+        writeln!(
+            w,
+            "{},{},{},{},{:.4},{},{}{}",
+            t.tenant.as_deref().unwrap_or_default(),
+            t.client_id,
+            t.tx_id,
+            t.tx_type,
+            t.amount,
+            t.timestamp.map(|ts| ts.to_string()).unwrap_or_default(),
+            age.unwrap_or_default(),
+            clients.columns(&t.account_key())
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes funds held by open disputes of every client bucketed by age of the disputed transaction
+/// (full days to `as_of`), funds of transactions without timestamp are in the last column.
+/// The last row, with `*` as tenant and client, sums all clients.
+pub fn write_held_aging<W: Write>(w: &mut W, reports: &[AccountReport], as_of: Option<Timestamp>) -> Result<()> {
+    writeln!(w, "{}", HELD_AGING_HEADER)?;
+    let mut all = [0.0; AGING_BUCKETS.len() + 2];
+    for r in reports.iter().filter(|r| !r.disputed.is_empty()) {
+        let mut buckets = [0.0; AGING_BUCKETS.len() + 2];
+        for t in &r.disputed {
+            let bucket = match t.timestamp.zip(as_of) {
+                Some((ts, as_of)) => {
+                    let days = as_of.saturating_sub(ts) / DAY;
+                    AGING_BUCKETS.iter().position(|max| days <= *max).unwrap_or(AGING_BUCKETS.len())
+                }
+                None => AGING_BUCKETS.len() + 1,
+            };
+            buckets[bucket] += t.amount;
+            all[bucket] += t.amount;
+        }
+        write_aging_row(w, r.account.tenant.as_deref().unwrap_or_default(), &r.account.client_id.to_string(), &buckets)?;
+    }
+    write_aging_row(w, "*", "*", &all)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn write_aging_row<W: Write>(w: &mut W, tenant: &str, client: &str, buckets: &[Money]) -> Result<()> {
+    write!(w, "{},{}", tenant, client)?;
+    for amount in buckets {
+        write!(w, ",{:.4}", amount)?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Writes clients with at least one applied chargeback: number and charged back amount of chargebacks,
+/// applied deposits, ratio of chargebacks to deposits (by count) and whether the account ended frozen
+pub fn write_chargebacks<W: Write>(w: &mut W, reports: &[AccountReport], clients: &ClientDirectory) -> Result<()> {
+    writeln!(w, "{}{}", CHARGEBACKS_HEADER, clients.header())?;
+    let applied = |r: &AccountReport, tx_type: TxType| r.stats.by_type.get(&tx_type.to_string()).cloned().unwrap_or_default();
+    for r in reports {
+        let (chargebacks, deposits) = (applied(r, TxType::Chargeback), applied(r, TxType::Deposit));
+        if chargebacks.applied == 0 {
+            continue;
+        }
+        let ratio = match deposits.applied {
+            0 => 0.0,
+            d => chargebacks.applied as f64 / d as f64,
+        };
+        writeln!(
+            w,
+            "{},{},{},{:.4},{},{:.4},{:.4},{}{}",
+            r.account.tenant.as_deref().unwrap_or_default(),
+            r.account.client_id,
+            chargebacks.applied,
+            chargebacks.amount,
+            deposits.applied,
+            deposits.amount,
+            ratio,
+            r.account.is_locked,
+            clients.columns(&(r.account.tenant.clone(), r.account.client_id))
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes `n` clients with the highest turnover (sum of applied deposits and withdrawals) followed by `n` largest
+/// applied transactions of all accounts, both the largest first; `tx` is empty in the client rows
+pub fn write_top<W: Write>(w: &mut W, reports: &[AccountReport], n: usize) -> Result<()> {
+    writeln!(w, "{}", TOP_HEADER)?;
+    let mut clients = TopN::new(n);
+    let mut transactions = TopN::new(n);
+    for r in reports {
+        let turnover = [TxType::Deposit, TxType::Withdrawal]
+            .iter()
+            .filter_map(|t| r.stats.by_type.get(&t.to_string()))
+            .map(|s| s.amount)
+            .sum::<Money>();
+        clients.push(turnover, &r.account);
+        for t in &r.largest {
+            transactions.push(t.amount, t);
+        }
+    }
+    for (rank, (turnover, a)) in clients.into_sorted_vec().into_iter().enumerate() {
+        writeln!(w, "client,{},{},{},,{:.4}", rank + 1, a.tenant.as_deref().unwrap_or_default(), a.client_id, turnover)?;
+    }
+    for (rank, (amount, t)) in transactions.into_sorted_vec().into_iter().enumerate() {
+        writeln!(
+            w,
+            "transaction,{},{},{},{},{:.4}",
+            rank + 1,
+            t.tenant.as_deref().unwrap_or_default(),
+            t.client_id,
+            t.tx_id,
+            amount
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes decision trace row of the processed transaction with the account balances right after it
+pub fn write_decision<W: Write>(w: &mut W, e: &ProcessingEvent) -> Result<()> {
+    let a = &e.account;
+    writeln!(
+        w,
+        "{},{},{},{},{:.4},{},\"{}\",{:.4},{:.4},{:.4},{}",
+        a.tenant.as_deref().unwrap_or_default(),
+        a.client_id,
+        e.tx_type,
+        e.tx_id,
+        e.amount,
+        e.outcome,
+        e.rejection.as_deref().unwrap_or_default().replace('"', "\"\""),
+        a.available_amount,
+        a.held_amount,
+        a.total_amount,
+        a.is_locked
+    )?;
+    Ok(())
+}
+
+/// Writes decision trace row of the transaction skipped before it reached the account, balances are left empty
+pub fn write_skipped<W: Write>(w: &mut W, t: &Transaction, reason: &str) -> Result<()> {
+    writeln!(
+        w,
+        "{},{},{},{},{:.4},{},\"{}\",,,,",
+        t.tenant.as_deref().unwrap_or_default(),
+        t.client_id,
+        t.tx_type,
+        t.tx_id,
+        t.amount,
+        Outcome::Skipped,
+        reason
+    )?;
+    Ok(())
+}
+
+/// Parse failure or rejected transaction in the JSONL error stream
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ErrorRecord {
+    pub line: Option<u64>,
+    /// offset of the row in the input and the row itself, known for parse errors
+    pub byte: Option<u64>,
+    pub row: Option<String>,
+    pub client: Option<ClientId>,
+    pub tx: Option<TxId>,
+    /// stable code of the error, see `AccountError::code` and `ParseError::code`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ErrorRecord {
+    /// record of the rejected transaction, `None` for applied or skipped one
+    pub fn rejected(e: &ProcessingEvent) -> Option<ErrorRecord> {
+        let error = e.error.as_ref()?;
+        Some(ErrorRecord {
+            line: e.line,
+            byte: None,
+            row: None,
+            client: Some(e.account.client_id),
+            tx: Some(e.tx_id),
+            code: error.code(),
+            message: error.message(),
+        })
+    }
+}
+
+impl From<&ParseError> for ErrorRecord {
+    fn from(e: &ParseError) -> Self {
+        ErrorRecord {
+            line: e.line,
+            byte: e.byte,
+            row: e.row.clone(),
+            client: e.client_id,
+            tx: e.tx_id,
+            code: e.code(),
+            message: e.message.clone(),
+        }
+    }
+}
+
+/// Writes the error as a single line JSON object
+pub fn write_error_json<W: Write>(w: &mut W, e: &ErrorRecord) -> Result<()> {
+    serde_json::to_writer(&mut *w, e)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Writes clients found in more than one file of the input directory, file names are separated with `;`
+pub fn write_overlaps<W: Write>(w: &mut W, overlaps: &[Overlap]) -> Result<()> {
+    writeln!(w, "{}", OVERLAPS_HEADER)?;
+    for o in overlaps {
+        writeln!(w, "{},{},{}", o.key.0.as_deref().unwrap_or_default(), o.key.1, o.files.join(";"))?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Marks active accounts without any transaction for `days` as dormant, returns the time inactivity is measured to.
+/// Inactivity is measured to `as_of`, or to the latest activity of all accounts when not given,
+/// accounts without any timestamped transaction are never dormant.
+pub fn mark_dormant(accounts: &mut [RawAccount], days: u64, as_of: Option<Timestamp>) -> Option<Timestamp> {
+    let as_of = as_of.or_else(|| accounts.iter().filter_map(|a| a.last_activity).max())?;
+    for a in accounts.iter_mut() {
+        a.is_dormant = a
+            .last_activity
+            .is_some_and(|last| as_of.saturating_sub(last) >= days * DAY);
+    }
+    Some(as_of)
+}
+
+/// Keeps only accounts whose balances or lock differ from the initial state (or which are not in it),
+/// returns the number of removed, untouched accounts
+pub fn retain_changed(accounts: &mut Vec<RawAccount>, initial: &HashMap<AccountKey, AccountSnapshot>) -> u64 {
+    let before = accounts.len();
+    accounts.retain(|a| {
+        initial.get(&(a.tenant.clone(), a.client_id)).is_none_or(|s| {
+            (s.available_amount, s.held_amount, s.total_amount, s.is_locked)
+                != (a.available_amount, a.held_amount, a.total_amount, a.is_locked)
+        })
+    });
+    (before - accounts.len()) as u64
+}
+
+/// Writes accounts marked as dormant with the number of full days since their last activity to `w`
+pub fn write_dormant<W: Write>(w: &mut W, accounts: &[RawAccount], as_of: Timestamp) -> Result<()> {
+    writeln!(w, "{}", DORMANT_HEADER)?;
+    for a in accounts.iter().filter(|a| a.status() == "dormant") {
+        let last = a.last_activity.unwrap_or_default();
+        writeln!(
+            w,
+            "{},{},{},{},{:.4},{:.4},{:.4}",
+            a.tenant.as_deref().unwrap_or_default(),
+            a.client_id,
+            last,
+            as_of.saturating_sub(last) / DAY,
+            a.available_amount,
+            a.held_amount,
+            a.total_amount
+        )?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+fn group_by_tenant(accounts: &[RawAccount]) -> BTreeMap<Option<&TenantId>, Vec<&RawAccount>> {
+    let mut groups = BTreeMap::<Option<&TenantId>, Vec<&RawAccount>>::new();
+    for a in accounts {
+        groups.entry(a.tenant.as_ref()).or_default().push(a);
+    }
+    groups
+}
+
+/// tenant names come from the input data, keep only characters safe for a file name
+fn file_stem(tenant: Option<&TenantId>) -> String {
+    match tenant {
+        None => DEFAULT_TENANT.to_string(),
+        Some(t) => t
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect(),
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tracing::info;
+
+/// Pause of the ingestion for maintenance windows of the downstream sinks, shared by the admin and the pipeline.
+/// As a stage of `ProcessorOptions::middleware` it holds the next transaction while paused: sources stop being read
+/// once the channel of the processor is full, transactions already routed are applied and the accounts stay in memory.
+#[derive(Debug, Clone)]
+pub struct IngestControl {
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl Default for IngestControl {
+    fn default() -> Self {
+        IngestControl { paused: Arc::new(watch::channel(false).0) }
+    }
+}
+
+impl IngestControl {
+    pub fn pause(&self) {
+        if !self.paused.send_replace(true) {
+            info!("ingestion paused");
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.paused.send_replace(false) {
+            info!("ingestion resumed");
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// returns once the ingestion is not paused
+    pub async fn wait_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // the sender lives as long as `self`
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
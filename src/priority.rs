@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+use crate::{Transaction, TxType};
+
+/// Transactions waiting for their account: disputes and chargebacks are taken before the other ones, except
+/// when an earlier queued transaction has the same id, so every transaction still follows the ones it refers to
+#[derive(Debug, Default)]
+pub struct PriorityQueue {
+    capacity: usize,
+    queue: VecDeque<Transaction>,
+}
+
+impl PriorityQueue {
+    /// queue of up to `capacity` transactions, with 0 transactions are taken in the order they arrive
+    pub fn new(capacity: usize) -> Self {
+        PriorityQueue { capacity, queue: VecDeque::with_capacity(capacity) }
+    }
+
+    pub fn push(&mut self, t: Transaction) {
+        self.queue.push_back(t);
+    }
+
+    /// the first dispute or chargeback not waiting for an earlier transaction of its id, otherwise the oldest one
+    pub fn pop(&mut self) -> Option<Transaction> {
+        let urgent = self.queue.iter().enumerate().position(|(i, t)| {
+            is_urgent(&t.tx_type) && !self.queue.iter().take(i).any(|earlier| earlier.tx_id == t.tx_id)
+        });
+        self.queue.remove(urgent.unwrap_or(0))
+    }
+
+    /// no more transactions should be queued before the next `pop`
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.capacity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// disputes and chargebacks freeze funds of the account and should not wait behind a flood of deposits
+pub fn is_urgent(tx_type: &TxType) -> bool {
+    matches!(tx_type, TxType::Dispute | TxType::Chargeback)
+}
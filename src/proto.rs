@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+
+use prost::Message;
+
+use crate::{events::ProcessingEvent, snapshot, ClientId, Result};
+
+// Messages of `proto/txp.proto`, written by hand with prost derives so building does not need `protoc`,
+// every change of the messages has to be made in the `.proto` file as well.
+
+/// version written to `Snapshot` and `AccountEvent`, newer versions are rejected when decoding
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TxType {
+    Deposit = 0,
+    Withdrawal = 1,
+    Dispute = 2,
+    Resolve = 3,
+    Chargeback = 4,
+    Open = 5,
+    Close = 6,
+    Settle = 7,
+    Refund = 8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum DisputeState {
+    None = 0,
+    Open = 1,
+    Resolved = 2,
+    ChargedBack = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Outcome {
+    Applied = 0,
+    Rejected = 1,
+    Skipped = 2,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Transaction {
+    #[prost(enumeration = "TxType", tag = "1")]
+    pub tx_type: i32,
+    #[prost(uint32, tag = "2")]
+    pub client_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub tx_id: u32,
+    #[prost(float, tag = "4")]
+    pub amount: f32,
+    #[prost(bool, tag = "5")]
+    pub in_dispute: bool,
+    #[prost(string, optional, tag = "6")]
+    pub tenant: Option<String>,
+    #[prost(uint64, optional, tag = "7")]
+    pub timestamp: Option<u64>,
+    #[prost(string, optional, tag = "8")]
+    pub idempotency_key: Option<String>,
+    #[prost(uint64, optional, tag = "9")]
+    pub seq: Option<u64>,
+    #[prost(uint64, optional, tag = "10")]
+    pub line: Option<u64>,
+    #[prost(map = "string, string", tag = "11")]
+    pub metadata: HashMap<String, String>,
+    #[prost(enumeration = "DisputeState", tag = "12")]
+    pub dispute: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AccountSnapshot {
+    #[prost(string, optional, tag = "1")]
+    pub tenant: Option<String>,
+    #[prost(uint32, tag = "2")]
+    pub client_id: u32,
+    #[prost(float, tag = "3")]
+    pub available: f32,
+    #[prost(float, tag = "4")]
+    pub held: f32,
+    #[prost(float, tag = "5")]
+    pub total: f32,
+    #[prost(bool, tag = "6")]
+    pub locked: bool,
+    #[prost(bool, tag = "7")]
+    pub pre_frozen: bool,
+    #[prost(bool, tag = "8")]
+    pub closed: bool,
+    #[prost(uint64, optional, tag = "10")]
+    pub last_activity: Option<u64>,
+    #[prost(message, repeated, tag = "11")]
+    pub transactions: Vec<Transaction>,
+    #[prost(uint64, tag = "12")]
+    pub account_version: u64,
+    #[prost(float, tag = "13")]
+    pub pending: f32,
+    #[prost(uint32, repeated, tag = "14")]
+    pub pending_withdrawals: Vec<u32>,
+    #[prost(map = "uint32, float", tag = "15")]
+    pub refunded: HashMap<u32, f32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Snapshot {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub accounts: Vec<AccountSnapshot>,
+    #[prost(uint64, optional, tag = "3")]
+    pub seen_checkpoint: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost(string, optional, tag = "1")]
+    pub tenant: Option<String>,
+    #[prost(uint32, tag = "2")]
+    pub client_id: u32,
+    #[prost(float, tag = "3")]
+    pub available: f32,
+    #[prost(float, tag = "4")]
+    pub held: f32,
+    #[prost(float, tag = "5")]
+    pub total: f32,
+    #[prost(bool, tag = "6")]
+    pub locked: bool,
+    #[prost(bool, tag = "7")]
+    pub closed: bool,
+    #[prost(uint64, optional, tag = "9")]
+    pub last_activity: Option<u64>,
+    #[prost(uint64, tag = "10")]
+    pub account_version: u64,
+}
+
+/// Processed transaction with the account state right after it
+#[derive(Clone, PartialEq, Message)]
+pub struct AccountEvent {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(uint64, optional, tag = "2")]
+    pub line: Option<u64>,
+    #[prost(uint32, tag = "3")]
+    pub tx_id: u32,
+    #[prost(enumeration = "TxType", tag = "4")]
+    pub tx_type: i32,
+    #[prost(float, tag = "5")]
+    pub amount: f32,
+    #[prost(enumeration = "Outcome", tag = "6")]
+    pub outcome: i32,
+    #[prost(string, optional, tag = "7")]
+    pub rejection: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub code: Option<String>,
+    #[prost(message, optional, tag = "9")]
+    pub account: Option<Account>,
+}
+
+impl From<&crate::TxType> for TxType {
+    fn from(t: &crate::TxType) -> Self {
+        match t {
+            crate::TxType::Deposit => TxType::Deposit,
+            crate::TxType::Withdrawal => TxType::Withdrawal,
+            crate::TxType::Dispute => TxType::Dispute,
+            crate::TxType::Resolve => TxType::Resolve,
+            crate::TxType::Chargeback => TxType::Chargeback,
+            crate::TxType::Open => TxType::Open,
+            crate::TxType::Close => TxType::Close,
+            crate::TxType::Settle => TxType::Settle,
+            crate::TxType::Refund => TxType::Refund,
+        }
+    }
+}
+
+impl From<TxType> for crate::TxType {
+    fn from(t: TxType) -> Self {
+        match t {
+            TxType::Deposit => crate::TxType::Deposit,
+            TxType::Withdrawal => crate::TxType::Withdrawal,
+            TxType::Dispute => crate::TxType::Dispute,
+            TxType::Resolve => crate::TxType::Resolve,
+            TxType::Chargeback => crate::TxType::Chargeback,
+            TxType::Open => crate::TxType::Open,
+            TxType::Close => crate::TxType::Close,
+            TxType::Settle => crate::TxType::Settle,
+            TxType::Refund => crate::TxType::Refund,
+        }
+    }
+}
+
+impl From<crate::DisputeState> for DisputeState {
+    fn from(s: crate::DisputeState) -> Self {
+        match s {
+            crate::DisputeState::None => DisputeState::None,
+            crate::DisputeState::Open => DisputeState::Open,
+            crate::DisputeState::Resolved => DisputeState::Resolved,
+            crate::DisputeState::ChargedBack => DisputeState::ChargedBack,
+        }
+    }
+}
+
+impl From<DisputeState> for crate::DisputeState {
+    fn from(s: DisputeState) -> Self {
+        match s {
+            DisputeState::None => crate::DisputeState::None,
+            DisputeState::Open => crate::DisputeState::Open,
+            DisputeState::Resolved => crate::DisputeState::Resolved,
+            DisputeState::ChargedBack => crate::DisputeState::ChargedBack,
+        }
+    }
+}
+
+impl From<&crate::Transaction> for Transaction {
+    fn from(t: &crate::Transaction) -> Self {
+        Transaction {
+            tx_type: TxType::from(&t.tx_type) as i32,
+            client_id: t.client_id.into(),
+            tx_id: t.tx_id,
+            amount: t.amount,
+            in_dispute: t.dispute.in_dispute(),
+            tenant: t.tenant.clone(),
+            timestamp: t.timestamp,
+            idempotency_key: t.idempotency_key.clone(),
+            seq: t.seq,
+            line: t.line,
+            metadata: t.metadata.clone(),
+            dispute: DisputeState::from(t.dispute) as i32,
+        }
+    }
+}
+
+impl TryFrom<Transaction> for crate::Transaction {
+    type Error = crate::Error;
+
+    fn try_from(t: Transaction) -> Result<Self> {
+        Ok(crate::Transaction {
+            tx_type: TxType::try_from(t.tx_type).map_err(|_| format!("unknown transaction type {}", t.tx_type))?.into(),
+            client_id: client_id(t.client_id)?,
+            tx_id: t.tx_id,
+            amount: t.amount,
+            dispute: match DisputeState::try_from(t.dispute) {
+                // written before the dispute state was added
+                Ok(DisputeState::None) if t.in_dispute => crate::DisputeState::Open,
+                Ok(state) => state.into(),
+                Err(_) => return Err(format!("unknown dispute state {}", t.dispute).into()),
+            },
+            tenant: t.tenant,
+            timestamp: t.timestamp,
+            idempotency_key: t.idempotency_key,
+            seq: t.seq,
+            line: t.line,
+            metadata: t.metadata,
+        })
+    }
+}
+
+impl From<&snapshot::AccountSnapshot> for AccountSnapshot {
+    fn from(s: &snapshot::AccountSnapshot) -> Self {
+        AccountSnapshot {
+            tenant: s.tenant.clone(),
+            client_id: s.client_id.into(),
+            available: s.available_amount,
+            held: s.held_amount,
+            total: s.total_amount,
+            locked: s.is_locked,
+            pre_frozen: s.is_pre_frozen,
+            closed: s.is_closed,
+            last_activity: s.last_activity,
+            transactions: s.transactions.iter().map(Transaction::from).collect(),
+            account_version: s.version,
+            pending: s.pending_amount,
+            pending_withdrawals: s.pending.clone(),
+            refunded: s.refunded.iter().map(|(tx_id, amount)| (*tx_id, *amount)).collect(),
+        }
+    }
+}
+
+impl TryFrom<AccountSnapshot> for snapshot::AccountSnapshot {
+    type Error = crate::Error;
+
+    fn try_from(s: AccountSnapshot) -> Result<Self> {
+        Ok(snapshot::AccountSnapshot {
+            tenant: s.tenant,
+            client_id: client_id(s.client_id)?,
+            available_amount: s.available,
+            held_amount: s.held,
+            total_amount: s.total,
+            is_locked: s.locked,
+            is_pre_frozen: s.pre_frozen,
+            is_closed: s.closed,
+            last_activity: s.last_activity,
+            version: s.account_version,
+            pending_amount: s.pending,
+            pending: s.pending_withdrawals,
+            refunded: s.refunded.into_iter().collect(),
+            transactions: s.transactions.into_iter().map(crate::Transaction::try_from).collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl From<&ProcessingEvent> for AccountEvent {
+    fn from(e: &ProcessingEvent) -> Self {
+        let a = &e.account;
+        AccountEvent {
+            version: FORMAT_VERSION,
+            line: e.line,
+            tx_id: e.tx_id,
+            tx_type: TxType::from(&e.tx_type) as i32,
+            amount: e.amount,
+            outcome: match e.outcome {
+                crate::events::Outcome::Applied => Outcome::Applied,
+                crate::events::Outcome::Rejected => Outcome::Rejected,
+                crate::events::Outcome::Skipped => Outcome::Skipped,
+            } as i32,
+            rejection: e.rejection.clone(),
+            code: e.error.as_ref().map(|e| e.code().to_string()),
+            account: Some(Account {
+                tenant: a.tenant.clone(),
+                client_id: a.client_id.into(),
+                available: a.available_amount,
+                held: a.held_amount,
+                total: a.total_amount,
+                locked: a.is_locked,
+                closed: a.is_closed,
+                last_activity: a.last_activity,
+                account_version: a.version,
+            }),
+        }
+    }
+}
+
+fn client_id(id: u32) -> Result<ClientId> {
+    ClientId::try_from(id).map_err(|_| format!("client id {} out of range", id).into())
+}
+
+fn check_version(version: u32) -> Result<()> {
+    match version > FORMAT_VERSION {
+        true => Err(format!("format version {} is newer than supported {}", version, FORMAT_VERSION).into()),
+        false => Ok(()),
+    }
+}
+
+/// `Transaction` message of the transaction
+pub fn encode_transaction(t: &crate::Transaction) -> Vec<u8> {
+    Transaction::from(t).encode_to_vec()
+}
+
+/// transaction of the `Transaction` message, fails for unknown type or client id out of range
+pub fn decode_transaction(buf: &[u8]) -> Result<crate::Transaction> {
+    Transaction::decode(buf)?.try_into()
+}
+
+/// `Snapshot` message of the current `FORMAT_VERSION`
+pub fn encode_snapshot(s: &snapshot::Snapshot) -> Vec<u8> {
+    Snapshot {
+        version: FORMAT_VERSION,
+        accounts: s.accounts.iter().map(AccountSnapshot::from).collect(),
+        seen_checkpoint: s.seen_checkpoint,
+    }
+    .encode_to_vec()
+}
+
+/// snapshot of the `Snapshot` message, fails for messages of newer format version
+pub fn decode_snapshot(buf: &[u8]) -> Result<snapshot::Snapshot> {
+    let s = Snapshot::decode(buf)?;
+    check_version(s.version)?;
+    Ok(snapshot::Snapshot {
+        accounts: s.accounts.into_iter().map(snapshot::AccountSnapshot::try_from).collect::<Result<_>>()?,
+        seen_checkpoint: s.seen_checkpoint,
+    })
+}
+
+/// `AccountEvent` message of the processing event
+pub fn encode_event(e: &ProcessingEvent) -> Vec<u8> {
+    AccountEvent::from(e).encode_to_vec()
+}
+
+/// `AccountEvent` message, fails for messages of newer format version
+pub fn decode_event(buf: &[u8]) -> Result<AccountEvent> {
+    let e = AccountEvent::decode(buf)?;
+    check_version(e.version)?;
+    Ok(e)
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+use crate::{csv::RawAccount, events::ProcessingEvent, output, AccountKey, Result};
+
+/// When `SnapshotPublisher` publishes a snapshot, e.g. `10000tx` or `30s` (`ms`, `s`, `m` and `h` units)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotEvery {
+    /// after this many processed transactions
+    Transactions(u64),
+    /// when this much time passed since the previous snapshot
+    Interval(Duration),
+}
+
+impl FromStr for SnapshotEvery {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = (s[..split].parse::<u64>(), s[split..].trim_start_matches('-'));
+        match (value, unit) {
+            (Ok(n), "tx") if n > 0 => Ok(SnapshotEvery::Transactions(n)),
+            (Ok(n), "ms") if n > 0 => Ok(SnapshotEvery::Interval(Duration::from_millis(n))),
+            (Ok(n), "s") if n > 0 => Ok(SnapshotEvery::Interval(Duration::from_secs(n))),
+            (Ok(n), "m") if n > 0 => Ok(SnapshotEvery::Interval(Duration::from_secs(n * 60))),
+            (Ok(n), "h") if n > 0 => Ok(SnapshotEvery::Interval(Duration::from_secs(n * 3600))),
+            _ => Err(format!("'{}' is neither a number of transactions (e.g. `10000tx`) nor a duration (e.g. `30s`)", s)),
+        }
+    }
+}
+
+/// Destination of the snapshots published during the run
+pub trait SnapshotSink: Send {
+    /// `seq` numbers snapshots from 1, with `delta` the `accounts` are only those changed since the previous snapshot
+    fn publish(&mut self, seq: u64, delta: bool, accounts: &[RawAccount]) -> Result<()>;
+}
+
+/// Writes every snapshot into its own file `<dir>/snapshot-<seq>.csv` (`delta-<seq>.csv`) in the account output format,
+/// removing the oldest files over `keep`. Files are written under a temporary name first, so readers never see
+/// a partially written snapshot.
+#[derive(Debug)]
+pub struct RotatingFiles {
+    dir: PathBuf,
+    keep: Option<usize>,
+    precision: usize,
+    written: VecDeque<PathBuf>,
+}
+
+impl RotatingFiles {
+    pub fn new(dir: PathBuf, keep: Option<usize>, precision: usize) -> Result<RotatingFiles> {
+        fs::create_dir_all(&dir)?;
+        Ok(RotatingFiles { dir, keep, precision, written: VecDeque::new() })
+    }
+}
+
+impl SnapshotSink for RotatingFiles {
+    fn publish(&mut self, seq: u64, delta: bool, accounts: &[RawAccount]) -> Result<()> {
+        let name = format!("{}-{:06}.csv", if delta { "delta" } else { "snapshot" }, seq);
+        let (tmp, path) = (self.dir.join(format!(".{}", name)), self.dir.join(name));
+        output::write_accounts(&mut BufWriter::new(File::create(&tmp)?), accounts, self.precision)?;
+        fs::rename(&tmp, &path)?;
+        debug!("published {} accounts to {:?}", accounts.len(), path);
+        self.written.push_back(path);
+        while self.keep.is_some_and(|keep| self.written.len() > keep) {
+            if let Some(old) = self.written.pop_front() {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Publishes snapshots of the accounts to the sink while the input is still processed, built from the processing events
+/// (`ProcessingOptions::events`). A snapshot holds the state of every account after all the events received before it.
+pub struct SnapshotPublisher<S: SnapshotSink> {
+    every: SnapshotEvery,
+    delta: bool,
+    sink: S,
+    accounts: BTreeMap<AccountKey, RawAccount>,
+    // accounts changed since the previous snapshot
+    changed: BTreeSet<AccountKey>,
+    // events since the previous snapshot
+    pending: u64,
+    published: Instant,
+    seq: u64,
+}
+
+impl<S: SnapshotSink> SnapshotPublisher<S> {
+    /// with `delta` only accounts changed since the previous snapshot are published, otherwise all of them
+    pub fn new(every: SnapshotEvery, delta: bool, sink: S) -> Self {
+        SnapshotPublisher {
+            every,
+            delta,
+            sink,
+            accounts: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            pending: 0,
+            published: Instant::now(),
+            seq: 0,
+        }
+    }
+
+    /// how often `tick` should be called when no events arrive, `None` when snapshots are not time based
+    pub fn interval(&self) -> Option<Duration> {
+        match self.every {
+            SnapshotEvery::Interval(interval) => Some(interval),
+            SnapshotEvery::Transactions(_) => None,
+        }
+    }
+
+    /// records the account state after the event and publishes a snapshot when it is due
+    pub fn record(&mut self, e: &ProcessingEvent) -> Result<()> {
+        let key = (e.account.tenant.clone(), e.account.client_id);
+        if self.accounts.get(&key) != Some(&e.account) {
+            self.accounts.insert(key.clone(), e.account.clone());
+            self.changed.insert(key);
+        }
+        self.pending += 1;
+        match self.every {
+            SnapshotEvery::Transactions(n) if self.pending >= n => self.publish(),
+            SnapshotEvery::Interval(_) => self.tick(),
+            SnapshotEvery::Transactions(_) => Ok(()),
+        }
+    }
+
+    /// publishes a snapshot when the interval passed and any account changed since the previous one
+    pub fn tick(&mut self) -> Result<()> {
+        match self.interval() {
+            Some(interval) if self.published.elapsed() >= interval && !self.changed.is_empty() => self.publish(),
+            _ => Ok(()),
+        }
+    }
+
+    /// publishes the final snapshot at the end of the run, unless nothing changed since the previous one
+    pub fn finish(mut self) -> Result<()> {
+        match self.changed.is_empty() {
+            true => Ok(()),
+            false => self.publish(),
+        }
+    }
+
+    fn publish(&mut self) -> Result<()> {
+        let accounts: Vec<_> = match self.delta {
+            true => self.changed.iter().filter_map(|k| self.accounts.get(k)).cloned().collect(),
+            false => self.accounts.values().cloned().collect(),
+        };
+        self.seq += 1;
+        self.sink.publish(self.seq, self.delta, &accounts)?;
+        self.changed.clear();
+        self.pending = 0;
+        self.published = Instant::now();
+        Ok(())
+    }
+}
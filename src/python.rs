@@ -0,0 +1,186 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+use tokio::sync::mpsc;
+
+use crate::{
+    config::{self, Config},
+    csv::{CsvTransactionReader, ParseError, RawAccount},
+    engine::Engine,
+    history::HistoryRetention,
+    tx::{ProcessorOptions, TxProcessor},
+    ClientId, Money, Result, TenantId, Timestamp, Transaction, TxId, TxType,
+};
+
+/// State of an account, amounts rounded like in the CSV output
+#[pyclass(name = "Account", module = "txp", frozen, get_all, skip_from_py_object)]
+#[derive(Debug, Clone)]
+pub struct PyAccount {
+    tenant: Option<TenantId>,
+    client: ClientId,
+    available: f64,
+    held: f64,
+    total: f64,
+    locked: bool,
+    closed: bool,
+    status: &'static str,
+}
+
+impl PyAccount {
+    fn new(a: &RawAccount, precision: usize) -> Self {
+        PyAccount {
+            tenant: a.tenant.clone(),
+            client: a.client_id,
+            available: round(a.available_amount, precision),
+            held: round(a.held_amount, precision),
+            total: round(a.total_amount, precision),
+            locked: a.is_locked,
+            closed: a.is_closed,
+            status: a.status(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyAccount {
+    fn __repr__(&self) -> String {
+        let tenant = self.tenant.as_ref().map_or("None".to_string(), |t| format!("{:?}", t));
+        let locked = if self.locked { "True" } else { "False" };
+        format!(
+            "Account(tenant={}, client={}, available={:?}, held={:?}, total={:?}, locked={}, status={:?})",
+            tenant, self.client, self.available, self.held, self.total, locked, self.status
+        )
+    }
+}
+
+/// Incremental engine (see `engine::Engine`) applying transactions one at a time
+#[pyclass(name = "Engine", module = "txp")]
+pub struct PyEngine {
+    engine: Engine,
+}
+
+#[pymethods]
+impl PyEngine {
+    /// `config` path of the configuration file, `history` keeps the history of every account
+    #[new]
+    #[pyo3(signature = (config=None, history=false))]
+    fn new(config: Option<PathBuf>, history: bool) -> PyResult<Self> {
+        let mut engine = Engine::new(if history { HistoryRetention::All } else { HistoryRetention::None });
+        if let Some(path) = config {
+            engine.set_config(load_config(path).map_err(value_error)?);
+        }
+        Ok(PyEngine { engine })
+    }
+
+    /// applies the transaction and returns the account state after it, rejected transactions raise `ValueError`
+    #[pyo3(signature = (tx_type, client, tx, amount=None, tenant=None, timestamp=None))]
+    fn apply(
+        &mut self,
+        tx_type: &str,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Money>,
+        tenant: Option<TenantId>,
+        timestamp: Option<Timestamp>,
+    ) -> PyResult<PyAccount> {
+        let tx_type: TxType = tx_type.parse().map_err(value_error)?;
+        if amount.is_some_and(|a| a < 0.0) {
+            return Err(PyValueError::new_err(format!("amount {:?} < 0.0", amount)));
+        }
+        let t = Transaction {
+            tx_type,
+            client_id: client,
+            tx_id: tx,
+            amount: amount.unwrap_or_default(),
+            dispute: Default::default(),
+            tenant,
+            timestamp,
+            idempotency_key: None,
+            seq: None,
+            line: None,
+            metadata: Default::default(),
+        };
+        let account = self.engine.apply(t).map_err(|e| PyValueError::new_err(e.message()))?;
+        Ok(PyAccount::new(&account, self.engine.config().precision()))
+    }
+
+    /// current state of the account, `None` when the engine does not know it
+    #[pyo3(signature = (client, tenant=None))]
+    fn account(&self, client: ClientId, tenant: Option<TenantId>) -> Option<PyAccount> {
+        let precision = self.engine.config().precision();
+        self.engine.account(&(tenant, client)).map(|a| PyAccount::new(&a, precision))
+    }
+
+    /// current state of all accounts ordered by tenant and client
+    fn accounts(&self) -> Vec<PyAccount> {
+        let precision = self.engine.config().precision();
+        self.engine.accounts().iter().map(|a| PyAccount::new(a, precision)).collect()
+    }
+}
+
+/// Processes the CSV file the way `txp-cli` does and returns the final accounts ordered by tenant and client,
+/// parse errors raise `ValueError`
+#[pyfunction]
+#[pyo3(signature = (path, config=None))]
+fn process_file(py: Python<'_>, path: PathBuf, config: Option<PathBuf>) -> PyResult<Vec<PyAccount>> {
+    py.detach(|| read_file(path, config)).map_err(value_error)
+}
+
+/// `txp` Python module
+#[pymodule]
+pub fn txp(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyAccount>()?;
+    m.add_class::<PyEngine>()?;
+    m.add_function(wrap_pyfunction!(process_file, m)?)?;
+    Ok(())
+}
+
+fn read_file(path: PathBuf, config: Option<PathBuf>) -> Result<Vec<PyAccount>> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    runtime.block_on(async move {
+        let config = match config {
+            Some(path) => Config::load(&path).await?,
+            None => Config::default(),
+        };
+        let precision = config.precision();
+        // the reader stops on the first parse error, which is then raised
+        let error = Arc::new(Mutex::new(None));
+        let reported = error.clone();
+        let reader = CsvTransactionReader::with_error_handler(Arc::new(move |e: &ParseError| {
+            *reported.lock().expect("error lock poisoned") = Some(e.to_string());
+        }));
+        let (tx_sender, tx_receiver) = mpsc::channel(1024);
+        let read = tokio::spawn(async move {
+            reader
+                .read_data_file(path, |t| async {
+                    tx_sender.send(t.map(Transaction::from)).await.map_err(|e| e.to_string())
+                })
+                .await
+        });
+        let options = ProcessorOptions { config: config::fixed(config), ..Default::default() };
+        let (reports, read) = tokio::join!(TxProcessor::process(tx_receiver, options), read);
+        if read.is_err() {
+            let error = error.lock().expect("error lock poisoned").take();
+            return Err(error.unwrap_or_else(|| "failed reading the file".to_string()).into());
+        }
+        let mut accounts: Vec<_> = reports.iter().map(|r| PyAccount::new(&r.account, precision)).collect();
+        accounts.sort_by(|a, b| (&a.tenant, a.client).cmp(&(&b.tenant, b.client)));
+        Ok(accounts)
+    })
+}
+
+fn load_config(path: PathBuf) -> Result<Config> {
+    tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(Config::load(&path))
+}
+
+fn value_error(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+// value of the amount as written with `precision` decimal places
+fn round(amount: Money, precision: usize) -> f64 {
+    format!("{:.*}", precision, amount).parse().unwrap_or(f64::from(amount))
+}
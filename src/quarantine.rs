@@ -0,0 +1,19 @@
+use crate::{TxId, TxType};
+
+/// Quarantine of accounts with too many anomalies, configured in the `[quarantine]` section of the configuration file.
+/// Anomaly is a rejected transaction or an applied one leaving available funds below the credit limit (zero without it).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct QuarantineConfig {
+    /// account is quarantined by the first anomaly over this number, its following transactions are rejected
+    pub max_anomalies: u64,
+}
+
+/// Transaction whose anomaly got the account quarantined, listed in the review report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantined {
+    pub tx_id: TxId,
+    pub tx_type: TxType,
+    /// anomalies of the account including this one
+    pub anomalies: u64,
+}
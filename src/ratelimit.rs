@@ -0,0 +1,171 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::time::{self, Instant};
+use tracing::{debug, warn};
+
+use crate::{stats::IngestStats, Result};
+
+/// Rate limiting of the rows read from the input, configured in the `[rate_limit]` section of the configuration file.
+/// Protects the processor from an upstream feeding the stdin stream (or many files of the input directory) too fast.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// limit of all sources together
+    pub global: Option<BucketConfig>,
+    /// limit of every single source (stdin, file or file of the input directory)
+    pub per_source: Option<BucketConfig>,
+    /// what happens with the rows over the limit
+    pub on_limit: OnLimit,
+}
+
+/// Token bucket refilled with `rate` tokens per second up to `burst` tokens, every row takes one token
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BucketConfig {
+    /// rows per second
+    pub rate: f64,
+    /// rows which can be read at once after a pause, `rate` when not set
+    pub burst: Option<f64>,
+}
+
+/// Handling of the rows over the limit
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnLimit {
+    /// reading waits until the row fits in the limit (back pressure on the upstream)
+    #[default]
+    Queue,
+    /// row is dropped without processing
+    Shed,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: &BucketConfig) -> TokenBucket {
+        let burst = config.burst.unwrap_or(config.rate).max(1.0);
+        TokenBucket { rate: config.rate, burst, tokens: burst, last: Instant::now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+    }
+
+    fn available(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    // takes a token, going into debt when there is none, and returns how long the row has to wait for it
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    admitted: AtomicU64,
+    shed: AtomicU64,
+    delayed: AtomicU64,
+    waited_ms: AtomicU64,
+}
+
+/// Limiter shared by all sources, every source takes its own copy with `for_source`
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    global: Option<Arc<Mutex<TokenBucket>>>,
+    source: Option<Arc<Mutex<TokenBucket>>>,
+    counters: Arc<Counters>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Result<RateLimiter> {
+        for bucket in config.global.iter().chain(config.per_source.iter()) {
+            if !(bucket.rate > 0.0 && bucket.rate.is_finite()) {
+                return Err(format!("rate limit has to be a positive number of rows per second, got {}", bucket.rate).into());
+            }
+        }
+        Ok(RateLimiter {
+            config: config.clone(),
+            global: config.global.as_ref().map(|b| Arc::new(Mutex::new(TokenBucket::new(b)))),
+            source: None,
+            counters: Arc::default(),
+        })
+    }
+
+    /// limiter of a new source, sharing the global limit and the counters with this one
+    pub fn for_source(&self) -> RateLimiter {
+        RateLimiter {
+            source: self.config.per_source.as_ref().map(|b| Arc::new(Mutex::new(TokenBucket::new(b)))),
+            ..self.clone()
+        }
+    }
+
+    /// Returns `true` when the row can be processed, waiting for it to fit in the limits with `OnLimit::Queue`,
+    /// `false` when it is over the limit and has to be dropped with `OnLimit::Shed`
+    pub async fn admit(&self) -> bool {
+        let now = Instant::now();
+        let buckets = [&self.source, &self.global];
+        let wait = match self.config.on_limit {
+            OnLimit::Shed => {
+                let mut locked: Vec<_> = buckets.iter().filter_map(|b| b.as_ref()?.lock().ok()).collect();
+                if !locked.iter_mut().all(|b| b.available(now)) {
+                    let shed = self.counters.shed.fetch_add(1, Ordering::Relaxed) + 1;
+                    if shed.is_power_of_two() {
+                        warn!("input is over the rate limit, {} rows dropped so far", shed);
+                    }
+                    return false;
+                }
+                locked.iter_mut().for_each(|b| {
+                    b.reserve(now);
+                });
+                Duration::ZERO
+            }
+            OnLimit::Queue => buckets
+                .iter()
+                .filter_map(|b| b.as_ref()?.lock().ok().map(|mut b| b.reserve(now)))
+                .max()
+                .unwrap_or_default(),
+        };
+        if !wait.is_zero() {
+            debug!("input is over the rate limit, waiting {:?}", wait);
+            self.counters.delayed.fetch_add(1, Ordering::Relaxed);
+            self.counters.waited_ms.fetch_add(wait.as_millis() as u64, Ordering::Relaxed);
+            time::sleep(wait).await;
+        }
+        self.counters.admitted.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// counters of all sources of the limiter
+    pub fn stats(&self) -> IngestStats {
+        IngestStats {
+            admitted: self.counters.admitted.load(Ordering::Relaxed),
+            shed: self.counters.shed.load(Ordering::Relaxed),
+            delayed: self.counters.delayed.load(Ordering::Relaxed),
+            waited_ms: self.counters.waited_ms.load(Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+}
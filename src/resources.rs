@@ -0,0 +1,154 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt, fs,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// System allocator counting the allocated bytes, install it with `#[global_allocator]` to get `AllocatorStats`
+#[derive(Debug, Default)]
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn allocated(size: usize) {
+        let allocated = ALLOCATED.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn freed(size: usize) {
+        ALLOCATED.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let p = System.alloc(layout);
+        if !p.is_null() {
+            CountingAllocator::allocated(layout.size());
+        }
+        p
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let p = System.alloc_zeroed(layout);
+        if !p.is_null() {
+            CountingAllocator::allocated(layout.size());
+        }
+        p
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CountingAllocator::freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let p = System.realloc(ptr, layout, new_size);
+        if !p.is_null() {
+            CountingAllocator::freed(layout.size());
+            CountingAllocator::allocated(new_size);
+        }
+        p
+    }
+}
+
+/// Counters of `CountingAllocator`, all zero when it is not the global allocator
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct AllocatorStats {
+    /// bytes allocated right now
+    pub allocated_bytes: u64,
+    /// most bytes allocated at any time of the run
+    pub peak_bytes: u64,
+    /// number of allocations, including reallocations
+    pub allocations: u64,
+}
+
+impl AllocatorStats {
+    pub fn current() -> AllocatorStats {
+        AllocatorStats {
+            allocated_bytes: ALLOCATED.load(Ordering::Relaxed),
+            peak_bytes: PEAK.load(Ordering::Relaxed),
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts of the account tasks and the entries they retain, updated by `TxProcessor` while processing
+/// when set in `ProcessorOptions::gauges`
+#[derive(Debug, Default)]
+pub struct ResourceGauges {
+    account_tasks: AtomicU64,
+    history_entries: AtomicU64,
+}
+
+impl ResourceGauges {
+    pub(crate) fn task_spawned(&self) {
+        self.account_tasks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// replaces `before` entries retained by an account with `after`, returns `after`
+    pub(crate) fn retained(&self, before: usize, after: usize) -> usize {
+        if after > before {
+            self.history_entries.fetch_add((after - before) as u64, Ordering::Relaxed);
+        } else {
+            self.history_entries.fetch_sub((before - after) as u64, Ordering::Relaxed);
+        }
+        after
+    }
+}
+
+/// Memory and task usage of the run for capacity planning, see `ResourceUsage::measure`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ResourceUsage {
+    /// peak resident set size of the process in kilobytes, `None` where `/proc/self/status` is not available
+    pub peak_rss_kb: Option<u64>,
+    /// account tasks spawned so far, one for every account with transactions
+    pub account_tasks: u64,
+    /// transactions kept for disputes together with the history and rejects kept by the accounts
+    pub history_entries: u64,
+    /// `None` when `CountingAllocator` is not the global allocator, e.g. the binary built without `alloc-stats` feature
+    pub allocator: Option<AllocatorStats>,
+}
+
+impl ResourceUsage {
+    pub const HEADER: &'static str = "resource,value";
+
+    /// usage of the process right now
+    pub fn measure(gauges: &ResourceGauges) -> ResourceUsage {
+        ResourceUsage {
+            peak_rss_kb: peak_rss_kb(),
+            account_tasks: gauges.account_tasks.load(Ordering::Relaxed),
+            history_entries: gauges.history_entries.load(Ordering::Relaxed),
+            // every process allocates before it is measured, nothing counted means the allocator is not installed
+            allocator: Some(AllocatorStats::current()).filter(|a| a.allocations > 0),
+        }
+    }
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", ResourceUsage::HEADER)?;
+        if let Some(kb) = self.peak_rss_kb {
+            writeln!(f, "peak_rss_kb,{}", kb)?;
+        }
+        writeln!(f, "account_tasks,{}", self.account_tasks)?;
+        writeln!(f, "history_entries,{}", self.history_entries)?;
+        if let Some(a) = &self.allocator {
+            writeln!(f, "allocator.allocated_bytes,{}", a.allocated_bytes)?;
+            writeln!(f, "allocator.peak_bytes,{}", a.peak_bytes)?;
+            writeln!(f, "allocator.allocations,{}", a.allocations)?;
+        }
+        Ok(())
+    }
+}
+
+/// `VmHWM` of `/proc/self/status`, only available on Linux
+fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()
+}
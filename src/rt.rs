@@ -0,0 +1,41 @@
+use std::{future::Future, time::Instant};
+
+/// Handle of the task started with `spawn`
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    #[cfg(not(feature = "async-std-runtime"))]
+    inner: tokio::task::JoinHandle<T>,
+    #[cfg(feature = "async-std-runtime")]
+    inner: async_std::task::JoinHandle<T>,
+}
+
+impl<T> JoinHandle<T> {
+    /// waits for the task to finish, fails when it panicked
+    pub async fn join(self) -> Result<T, String> {
+        #[cfg(not(feature = "async-std-runtime"))]
+        return self.inner.await.map_err(|e| e.to_string());
+        // async-std propagates the panic of the task to the caller
+        #[cfg(feature = "async-std-runtime")]
+        return Ok(self.inner.await);
+    }
+}
+
+/// Starts the future as a new task of the runtime
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(not(feature = "async-std-runtime"))]
+    return JoinHandle { inner: tokio::spawn(future) };
+    #[cfg(feature = "async-std-runtime")]
+    return JoinHandle { inner: async_std::task::spawn(future) };
+}
+
+/// Output of the future, `None` when it did not finish before the deadline
+pub async fn timeout_at<F: Future>(deadline: Instant, future: F) -> Option<F::Output> {
+    #[cfg(not(feature = "async-std-runtime"))]
+    return tokio::time::timeout_at(deadline.into(), future).await.ok();
+    #[cfg(feature = "async-std-runtime")]
+    return async_std::future::timeout(deadline.saturating_duration_since(Instant::now()), future).await.ok();
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+
+use tracing::{info, warn};
+
+use crate::{ClientId, Money, Transaction, TxType};
+
+/// User defined rule from the configuration file, evaluated against each transaction before it reaches the account.
+///
+/// All specified conditions have to match for the action to be taken, e.g.
+/// ```toml
+/// [[rules]]
+/// name = "large-withdrawal"
+/// tx_types = ["withdrawal"]
+/// amount_above = 10000.0
+/// clients_not_in = [1, 2]
+/// action = "reject"
+///
+/// [[rules]]
+/// name = "deposit-burst"
+/// tx_types = ["deposit"]
+/// frequency = { window = 100, above = 20 }
+/// action = "flag"
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// transaction types the rule applies to, all types when empty
+    #[serde(default)]
+    pub tx_types: Vec<TxType>,
+    /// matches transactions with amount greater than the value
+    pub amount_above: Option<Money>,
+    /// matches only listed clients
+    pub clients: Option<Vec<ClientId>>,
+    /// matches clients which are not listed (allowlist)
+    #[serde(default)]
+    pub clients_not_in: Vec<ClientId>,
+    /// matches when client made too many transactions of the rule types recently
+    pub frequency: Option<Frequency>,
+    pub action: RuleAction,
+}
+
+/// Matches when more than `above` of the last `window` transactions of the client
+/// (including the current one) are of the rule transaction types
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Frequency {
+    pub window: usize,
+    pub above: usize,
+}
+
+/// What happens with transaction matching the rule
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// transaction is rejected and does not reach the account
+    Reject,
+    /// transaction is processed, match is reported as warning
+    Flag,
+    /// transaction is processed, match is reported as info
+    Log,
+}
+
+impl Rule {
+    fn matches_type(&self, tx_type: &TxType) -> bool {
+        self.tx_types.is_empty() || self.tx_types.contains(tx_type)
+    }
+
+    fn matches(&self, t: &Transaction, recent: &VecDeque<TxType>) -> bool {
+        if !self.matches_type(&t.tx_type) {
+            return false;
+        }
+        if self.amount_above.is_some_and(|limit| t.amount <= limit) {
+            return false;
+        }
+        if self.clients.as_ref().is_some_and(|c| !c.contains(&t.client_id)) {
+            return false;
+        }
+        if self.clients_not_in.contains(&t.client_id) {
+            return false;
+        }
+        match self.frequency {
+            Some(f) => recent.iter().take(f.window).filter(|tx_type| self.matches_type(tx_type)).count() > f.above,
+            None => true,
+        }
+    }
+}
+
+/// Per account state needed to evaluate the rules
+#[derive(Debug, Default)]
+pub(crate) struct RuleState {
+    // types of the most recent transactions of the account, newest first
+    recent: VecDeque<TxType>,
+}
+
+impl RuleState {
+    /// Evaluates all rules against `t`, reporting flagged and logged matches.
+    /// Returns name of the first rule rejecting the transaction.
+    pub fn evaluate(&mut self, rules: &[Rule], t: &Transaction) -> Option<String> {
+        if rules.is_empty() {
+            return None;
+        }
+        let window = rules.iter().filter_map(|r| r.frequency.map(|f| f.window)).max().unwrap_or(0);
+        if window > 0 {
+            self.recent.push_front(t.tx_type.clone());
+            self.recent.truncate(window);
+        }
+
+        let mut rejected_by = None;
+        for rule in rules.iter().filter(|r| r.matches(t, &self.recent)) {
+            match rule.action {
+                RuleAction::Reject => {
+                    rejected_by.get_or_insert_with(|| rule.name.clone());
+                }
+                RuleAction::Flag => {
+                    warn!(rule = %rule.name, client = t.client_id, tx = t.tx_id, "transaction flagged by rule")
+                }
+                RuleAction::Log => {
+                    info!(rule = %rule.name, client = t.client_id, tx = t.tx_id, "transaction matched rule")
+                }
+            }
+        }
+        rejected_by
+    }
+}
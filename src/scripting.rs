@@ -0,0 +1,134 @@
+use std::fmt;
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use tracing::{debug, error};
+
+use crate::{csv::RawAccount, Money, Result, Transaction};
+
+/// maximum number of operations a single script call can perform
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Decision returned by the validation script
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptDecision {
+    Allow,
+    Reject(String),
+    /// transaction is processed with the changed amount
+    Modify(Money),
+}
+
+/// Custom validation hook implemented in [rhai](https://rhai.rs) script.
+///
+/// Script has to define `validate(tx, account)` function, where `tx` is a map with
+/// `type`, `client`, `tx`, `amount` and `tenant` keys and `account` is a map with current
+/// `available`, `held`, `total` and `locked` values. It returns one of:
+/// - `"allow"` or `#{ action: "allow" }`
+/// - `"reject"` or `#{ action: "reject", reason: "..." }`
+/// - `#{ action: "modify", amount: 10.0 }` to process transaction with different amount
+///
+/// Scripts run sandboxed: there is no access to files or network, number of operations,
+/// call depth and sizes of strings, arrays and maps are limited.
+pub struct ScriptHook {
+    engine: Engine,
+    ast: AST,
+    source: String,
+}
+
+impl fmt::Debug for ScriptHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptHook").field("source", &self.source).finish()
+    }
+}
+
+impl PartialEq for ScriptHook {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl ScriptHook {
+    /// compiles the script, fails when it does not define `validate(tx, account)`
+    pub fn compile(source: &str) -> Result<ScriptHook> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(4096)
+            .set_max_array_size(1024)
+            .set_max_map_size(256);
+        engine.on_print(|s| debug!("script: {}", s));
+        engine.on_debug(|s, _, _| debug!("script: {}", s));
+
+        let ast = engine.compile(source).map_err(|e| format!("failed compiling script: {}", e))?;
+        if !ast.iter_functions().any(|f| f.name == "validate" && f.params.len() == 2) {
+            return Err("script does not define `validate(tx, account)` function".into());
+        }
+        Ok(ScriptHook {
+            engine,
+            ast,
+            source: source.to_string(),
+        })
+    }
+
+    /// Runs the script for the transaction, script errors reject the transaction
+    pub fn validate(&self, t: &Transaction, account: &RawAccount) -> ScriptDecision {
+        let mut tx = Map::new();
+        tx.insert("type".into(), t.tx_type.to_string().into());
+        tx.insert("client".into(), (t.client_id as rhai::INT).into());
+        tx.insert("tx".into(), (t.tx_id as rhai::INT).into());
+        tx.insert("amount".into(), (t.amount as rhai::FLOAT).into());
+        tx.insert(
+            "tenant".into(),
+            t.tenant.clone().map_or(Dynamic::UNIT, Dynamic::from),
+        );
+
+        let mut acc = Map::new();
+        acc.insert("available".into(), (account.available_amount as rhai::FLOAT).into());
+        acc.insert("held".into(), (account.held_amount as rhai::FLOAT).into());
+        acc.insert("total".into(), (account.total_amount as rhai::FLOAT).into());
+        acc.insert("locked".into(), account.is_locked.into());
+
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "validate", (tx, acc));
+        match result {
+            Ok(value) => ScriptHook::decision(value),
+            Err(e) => {
+                error!("validation script failed for tx {}: {}", t.tx_id, e);
+                ScriptDecision::Reject(format!("script error: {}", e))
+            }
+        }
+    }
+
+    fn decision(value: Dynamic) -> ScriptDecision {
+        if value.is_string() {
+            return match value.into_string().unwrap_or_default().as_str() {
+                "allow" => ScriptDecision::Allow,
+                "reject" => ScriptDecision::Reject("rejected by script".to_string()),
+                other => ScriptDecision::Reject(format!("unknown script decision '{}'", other)),
+            };
+        }
+        let map = match value.try_cast::<Map>() {
+            Some(map) => map,
+            None => return ScriptDecision::Reject("script returned unsupported value".to_string()),
+        };
+        let action = map
+            .get("action")
+            .and_then(|a| a.clone().into_string().ok())
+            .unwrap_or_default();
+        match action.as_str() {
+            "allow" => ScriptDecision::Allow,
+            "reject" => ScriptDecision::Reject(
+                map.get("reason")
+                    .and_then(|r| r.clone().into_string().ok())
+                    .unwrap_or_else(|| "rejected by script".to_string()),
+            ),
+            "modify" => match map.get("amount").and_then(|a| a.as_float().ok()) {
+                Some(amount) if amount >= 0.0 => ScriptDecision::Modify(amount as Money),
+                _ => ScriptDecision::Reject("script returned invalid amount".to_string()),
+            },
+            other => ScriptDecision::Reject(format!("unknown script decision '{}'", other)),
+        }
+    }
+}
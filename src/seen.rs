@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use tracing::{debug, error, warn};
+
+use crate::{ClientId, Result, TenantId, TxId};
+
+/// identifies applied deposit or withdrawal
+type SeenKey = (Option<TenantId>, ClientId, TxId);
+
+// line closing the entries committed together with the state snapshot of the same checkpoint
+const CHECKPOINT: &str = "#checkpoint ";
+
+/// Persistent store of applied deposits and withdrawals, so reprocessing the input after a crash
+/// does not apply them twice.
+///
+/// Applied transactions are only remembered until `commit`, which appends them to the file as `tenant,client,tx` lines
+/// closed by a `#checkpoint <n>` line and syncs it to disk. The state snapshot saved right after records the same
+/// checkpoint (`Snapshot::seen_checkpoint`), so skipped transactions are always part of the state the run continues from:
+/// entries of a checkpoint newer than the state (the run crashed before the snapshot was saved) are dropped when opened,
+/// and the store can not be opened without the state.
+/// Only the `horizon` most recent entries are kept: older ones are dropped by compaction,
+/// which rewrites the file on load and whenever it grows to twice the horizon.
+#[derive(Debug)]
+pub struct SeenStore {
+    path: PathBuf,
+    horizon: usize,
+    inner: Mutex<SeenEntries>,
+}
+
+#[derive(Debug)]
+struct SeenEntries {
+    // committed entries in the order they were applied, oldest first
+    order: VecDeque<SeenKey>,
+    // entries applied since the last commit
+    pending: Vec<SeenKey>,
+    seen: HashSet<SeenKey>,
+    // last committed checkpoint
+    checkpoint: Option<u64>,
+    // `None` when the store is only read
+    file: Option<File>,
+    // number of lines in the file
+    lines: usize,
+}
+
+impl SeenStore {
+    /// Opens (or creates) the store file, keeping at most `horizon` entries. `checkpoint` is the one of the state
+    /// the run continues from, the store has to be committed with the same state.
+    pub fn open(path: &Path, horizon: usize, checkpoint: Option<u64>) -> Result<SeenStore> {
+        let (order, checkpoint) = SeenStore::load(path, horizon, checkpoint)?;
+        SeenStore::rewrite(path, &order, checkpoint)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(SeenStore::with_entries(path, horizon, order, checkpoint, Some(file)))
+    }
+
+    /// Opens the store without ever writing to it (a missing file is an empty store): transactions are still
+    /// remembered during the run, but they can not be committed for the following ones
+    pub fn open_read_only(path: &Path, horizon: usize, checkpoint: Option<u64>) -> Result<SeenStore> {
+        let (order, checkpoint) = SeenStore::load(path, horizon, checkpoint)?;
+        Ok(SeenStore::with_entries(path, horizon, order, checkpoint, None))
+    }
+
+    fn with_entries(
+        path: &Path,
+        horizon: usize,
+        order: VecDeque<SeenKey>,
+        checkpoint: Option<u64>,
+        file: Option<File>,
+    ) -> SeenStore {
+        let seen = order.iter().cloned().collect();
+        let lines = order.len() + usize::from(checkpoint.is_some());
+        SeenStore {
+            path: path.to_path_buf(),
+            horizon,
+            inner: Mutex::new(SeenEntries { order, pending: Vec::new(), seen, checkpoint, file, lines }),
+        }
+    }
+
+    // the most recent `horizon` entries committed up to the `state` checkpoint, with the last checkpoint among them
+    fn load(path: &Path, horizon: usize, state: Option<u64>) -> Result<(VecDeque<SeenKey>, Option<u64>)> {
+        let mut order = VecDeque::new();
+        let mut batch = Vec::new();
+        let mut checkpoint = None;
+        if path.exists() {
+            for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+                let line = line?;
+                if let Some(committed) = line.strip_prefix(CHECKPOINT).and_then(|c| c.parse::<u64>().ok()) {
+                    let Some(state) = state else {
+                        return Err(format!(
+                            "seen store {:?} has transactions committed with a saved state, continue from that state \
+                             (--initial-state) or remove the store to apply them again",
+                            path
+                        )
+                        .into());
+                    };
+                    if committed > state {
+                        warn!("dropping entries of {:?} from checkpoint {} on, newer than the state", path, committed);
+                        batch.clear();
+                        break;
+                    }
+                    order.extend(batch.drain(..));
+                    checkpoint = Some(committed);
+                    continue;
+                }
+                match SeenStore::parse(&line) {
+                    Some(key) => batch.push(key),
+                    // last line can be incomplete when the process was killed while writing it
+                    None => warn!("skipping malformed line {} of {:?}", n + 1, path),
+                }
+            }
+        }
+        if !batch.is_empty() {
+            warn!("dropping {} entries of {:?} which were never committed", batch.len(), path);
+        }
+        if checkpoint != state {
+            return Err(format!(
+                "seen store {:?} is at checkpoint {:?} but the state (--initial-state) is at {:?}, \
+                 both have to be from the same run",
+                path, checkpoint, state
+            )
+            .into());
+        }
+        while order.len() > horizon {
+            order.pop_front();
+        }
+        debug!("loaded {} applied transactions from {:?}", order.len(), path);
+        Ok((order, checkpoint))
+    }
+
+    /// true when the transaction was already applied
+    pub fn contains(&self, tenant: &Option<TenantId>, client_id: ClientId, tx_id: TxId) -> bool {
+        let inner = self.inner.lock().expect("seen store lock poisoned");
+        inner.seen.contains(&(tenant.clone(), client_id, tx_id))
+    }
+
+    /// remembers applied transaction, it is written to the file by the next `commit`
+    pub fn insert(&self, tenant: &Option<TenantId>, client_id: ClientId, tx_id: TxId) {
+        let mut inner = self.inner.lock().expect("seen store lock poisoned");
+        let key = (tenant.clone(), client_id, tx_id);
+        if inner.seen.insert(key.clone()) {
+            inner.pending.push(key);
+        }
+    }
+
+    /// Writes the transactions applied since the last commit to the file and syncs it, returns the new checkpoint
+    /// to be saved with the state snapshot (see `Snapshot::seen_checkpoint`)
+    pub fn commit(&self) -> Result<u64> {
+        let mut guard = self.inner.lock().expect("seen store lock poisoned");
+        let inner = &mut *guard;
+        let checkpoint = inner.checkpoint.map_or(1, |c| c + 1);
+        let file = inner.file.as_mut().ok_or_else(|| format!("seen store {:?} is read only", &self.path))?;
+        let mut w = BufWriter::new(file);
+        for (tenant, client_id, tx_id) in &inner.pending {
+            writeln!(w, "{},{},{}", tenant.as_deref().unwrap_or_default(), client_id, tx_id)?;
+        }
+        writeln!(w, "{}{}", CHECKPOINT, checkpoint)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_data()?;
+        inner.lines += inner.pending.len() + 1;
+        inner.order.extend(inner.pending.drain(..));
+        inner.checkpoint = Some(checkpoint);
+        while inner.order.len() > self.horizon {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.seen.remove(&oldest);
+            }
+        }
+        if inner.lines >= 2 * self.horizon.max(1) {
+            if let Err(e) = self.compact(inner) {
+                error!("failed compacting {:?}: {}", &self.path, e);
+            }
+        }
+        debug!("committed checkpoint {} of {:?}", checkpoint, &self.path);
+        Ok(checkpoint)
+    }
+
+    /// number of the committed entries
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("seen store lock poisoned").order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // drops entries outside of the horizon from the file
+    fn compact(&self, inner: &mut SeenEntries) -> Result<()> {
+        SeenStore::rewrite(&self.path, &inner.order, inner.checkpoint)?;
+        inner.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        inner.lines = inner.order.len() + usize::from(inner.checkpoint.is_some());
+        debug!("compacted {:?} to {} entries", &self.path, inner.order.len());
+        Ok(())
+    }
+
+    // atomically replaces the file with `entries` committed at `checkpoint`
+    fn rewrite(path: &Path, entries: &VecDeque<SeenKey>, checkpoint: Option<u64>) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        for (tenant, client_id, tx_id) in entries {
+            writeln!(w, "{},{},{}", tenant.as_deref().unwrap_or_default(), client_id, tx_id)?;
+        }
+        if let Some(checkpoint) = checkpoint {
+            writeln!(w, "{}{}", CHECKPOINT, checkpoint)?;
+        }
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn parse(line: &str) -> Option<SeenKey> {
+        let mut fields = line.rsplitn(3, ',');
+        let tx_id = fields.next()?.parse().ok()?;
+        let client_id = fields.next()?.parse().ok()?;
+        let tenant = fields.next()?;
+        let tenant = if tenant.is_empty() { None } else { Some(tenant.to_string()) };
+        Some((tenant, client_id, tx_id))
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use tracing::warn;
+
+use crate::Transaction;
+
+/// What happens with rows which do not follow the `seq` order
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SequenceMode {
+    /// gaps and out of order rows are reported, all rows are processed
+    #[default]
+    Warn,
+    /// out of order rows are dropped, gaps are reported
+    Reject,
+    /// rows ahead of the expected one are buffered until the missing ones arrive (at most `window` rows)
+    Reorder,
+}
+
+/// Sequence tracking settings from the `[sequence]` section of the configuration file
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SequenceConfig {
+    pub mode: SequenceMode,
+    /// maximum number of rows buffered by `reorder` mode
+    pub window: usize,
+}
+
+impl Default for SequenceConfig {
+    fn default() -> Self {
+        SequenceConfig {
+            mode: SequenceMode::Warn,
+            window: 100,
+        }
+    }
+}
+
+/// Sequence problems found in the feed
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SequenceStats {
+    /// number of places where some sequence numbers were skipped
+    pub gaps: u64,
+    /// number of sequence numbers which never arrived
+    pub missing: u64,
+    /// rows with sequence number lower than the one expected, including repeated ones
+    pub out_of_order: u64,
+    /// out of order rows which were not processed
+    pub dropped: u64,
+    /// rows which were buffered and processed in the sequence order
+    pub reordered: u64,
+}
+
+impl SequenceStats {
+    /// adds up stats of another stream
+    pub fn merge(&mut self, other: &SequenceStats) {
+        self.gaps += other.gaps;
+        self.missing += other.missing;
+        self.out_of_order += other.out_of_order;
+        self.dropped += other.dropped;
+        self.reordered += other.reordered;
+    }
+}
+
+/// Validates global ordering of the rows by the `seq` column, rows without sequence number pass unchanged
+#[derive(Debug)]
+pub struct Sequencer {
+    config: SequenceConfig,
+    // sequence number of the next row
+    next: Option<u64>,
+    // rows waiting for the missing ones in `reorder` mode
+    pending: BTreeMap<u64, Transaction>,
+    stats: SequenceStats,
+}
+
+impl Sequencer {
+    pub fn new(config: SequenceConfig) -> Self {
+        Sequencer {
+            config,
+            next: None,
+            pending: BTreeMap::new(),
+            stats: SequenceStats::default(),
+        }
+    }
+
+    /// accepts next row of the feed, returns rows which can be processed in order
+    pub fn push(&mut self, t: Transaction) -> Vec<Transaction> {
+        let (seq, next) = match (t.seq, self.next) {
+            (None, _) => return vec![t],
+            (Some(seq), None) => {
+                self.next = Some(seq + 1);
+                return vec![t];
+            }
+            (Some(seq), Some(next)) => (seq, next),
+        };
+
+        if seq < next {
+            self.stats.out_of_order += 1;
+            if self.config.mode == SequenceMode::Warn {
+                warn!("row with seq {} arrived after {}", seq, next - 1);
+                return vec![t];
+            }
+            warn!("dropping row with seq {} which arrived after {}", seq, next - 1);
+            self.stats.dropped += 1;
+            return vec![];
+        }
+
+        if self.config.mode != SequenceMode::Reorder || seq == next {
+            self.skip_to(seq);
+            self.next = Some(seq + 1);
+            let mut ready = vec![t];
+            ready.extend(self.drain());
+            return ready;
+        }
+
+        if self.pending.insert(seq, t).is_some() {
+            warn!("dropping repeated row with seq {}", seq);
+            self.stats.out_of_order += 1;
+            self.stats.dropped += 1;
+        }
+        if self.pending.len() <= self.config.window {
+            return vec![];
+        }
+        // window is full, stop waiting for the missing rows
+        let first = *self.pending.keys().next().expect("pending rows");
+        self.skip_to(first);
+        self.drain()
+    }
+
+    /// end of the feed, returns all buffered rows
+    pub fn finish(&mut self) -> Vec<Transaction> {
+        let mut ready = Vec::new();
+        while let Some(&first) = self.pending.keys().next() {
+            self.skip_to(first);
+            ready.extend(self.drain());
+        }
+        ready
+    }
+
+    pub fn stats(&self) -> &SequenceStats {
+        &self.stats
+    }
+
+    // moves expected sequence number to `seq`, reporting skipped numbers
+    fn skip_to(&mut self, seq: u64) {
+        if let Some(next) = self.next.filter(|next| *next < seq) {
+            warn!("gap in sequence: {} rows missing before seq {}", seq - next, seq);
+            self.stats.gaps += 1;
+            self.stats.missing += seq - next;
+        }
+        self.next = Some(seq);
+    }
+
+    // takes buffered rows from the expected sequence number on, until the first missing one
+    fn drain(&mut self) -> Vec<Transaction> {
+        let mut next = self.next.expect("sequence started");
+        let mut ready = Vec::new();
+        while let Some(t) = self.pending.remove(&next) {
+            self.stats.reordered += 1;
+            ready.push(t);
+            next += 1;
+        }
+        self.next = Some(next);
+        ready
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+
+use crate::{Timestamp, TxId};
+
+/// Two-phase withdrawals, configured in the `[settlement]` section of the configuration file.
+/// Withdrawal only moves the funds from available to pending, `settle` referencing it (or the timeout) takes them
+/// from the account, like authorization and capture of a card payment.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettlementConfig {
+    /// pending withdrawal is settled once a transaction of the client with timestamp this many seconds later arrives,
+    /// withdrawals without timestamp wait for `settle`
+    pub timeout: Option<u64>,
+}
+
+/// Per account withdrawals waiting for settlement with their timestamps
+#[derive(Debug, Default)]
+pub(crate) struct PendingWithdrawals {
+    pending: BTreeMap<TxId, Option<Timestamp>>,
+}
+
+impl PendingWithdrawals {
+    pub fn insert(&mut self, tx_id: TxId, timestamp: Option<Timestamp>) {
+        self.pending.insert(tx_id, timestamp);
+    }
+
+    pub fn contains(&self, tx_id: TxId) -> bool {
+        self.pending.contains_key(&tx_id)
+    }
+
+    pub fn remove(&mut self, tx_id: TxId) -> bool {
+        self.pending.remove(&tx_id).is_some()
+    }
+
+    /// takes withdrawals pending for at least `config.timeout` at `now`, ordered by id
+    pub fn expired(&mut self, config: &SettlementConfig, now: Timestamp) -> Vec<TxId> {
+        let timeout = match config.timeout {
+            Some(timeout) => timeout,
+            None => return Vec::new(),
+        };
+        let expired: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, since)| since.is_some_and(|since| now.saturating_sub(since) >= timeout))
+            .map(|(tx_id, _)| *tx_id)
+            .collect();
+        for tx_id in &expired {
+            self.pending.remove(tx_id);
+        }
+        expired
+    }
+
+    /// ids of the pending withdrawals, ordered
+    pub fn ids(&self) -> Vec<TxId> {
+        self.pending.keys().copied().collect()
+    }
+}
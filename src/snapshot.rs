@@ -0,0 +1,212 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::{
+    crypto::{self, EncryptionKey},
+    csv::RawAccount,
+    AccountKey, ClientId, Money, Result, TenantId, Timestamp, Transaction, TxId,
+};
+
+/// version of the snapshots written by `Snapshot::save`, older ones are upgraded on load (see `migrate`)
+/// and newer ones are rejected
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// State of all accounts which can be saved and loaded later to continue processing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    pub accounts: Vec<AccountSnapshot>,
+    /// checkpoint of the seen store committed together with this state, see `SeenStore::commit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seen_checkpoint: Option<u64>,
+}
+
+/// Balances of the account together with transactions which can still be disputed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountSnapshot {
+    pub tenant: Option<TenantId>,
+    pub client_id: ClientId,
+    pub available_amount: Money,
+    pub held_amount: Money,
+    pub total_amount: Money,
+    pub is_locked: bool,
+    #[serde(default)]
+    pub is_pre_frozen: bool,
+    #[serde(default)]
+    pub is_closed: bool,
+    #[serde(default)]
+    pub last_activity: Option<Timestamp>,
+    /// see `RawAccount::version`
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub pending_amount: Money,
+    /// withdrawals waiting for settlement, they are among `transactions`
+    #[serde(default)]
+    pub pending: Vec<TxId>,
+    /// amount refunded so far by withdrawal id
+    #[serde(default)]
+    pub refunded: BTreeMap<TxId, Money>,
+    pub transactions: Vec<Transaction>,
+}
+
+impl AccountSnapshot {
+    pub fn raw_account(&self) -> RawAccount {
+        RawAccount {
+            tenant: self.tenant.clone(),
+            client_id: self.client_id,
+            available_amount: self.available_amount,
+            held_amount: self.held_amount,
+            total_amount: self.total_amount,
+            is_locked: self.is_locked,
+            is_closed: self.is_closed,
+            last_activity: self.last_activity,
+            is_dormant: false,
+            version: self.version,
+        }
+    }
+}
+
+/// Broken invariant of the snapshot, see `Snapshot::verify`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inconsistency {
+    /// total funds differ from available, held and pending funds together
+    Total { key: AccountKey, total: Money, expected: Money },
+    /// held funds below zero
+    NegativeHeld { key: AccountKey, held: Money },
+    /// the account is in the snapshot more than once
+    Duplicate(AccountKey),
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let account = |key: &AccountKey| match &key.0 {
+            Some(tenant) => format!("account {} of tenant {}", key.1, tenant),
+            None => format!("account {}", key.1),
+        };
+        match self {
+            Inconsistency::Total { key, total, expected } => {
+                write!(f, "{} has total {} instead of available + held + pending {}", account(key), total, expected)
+            }
+            Inconsistency::NegativeHeld { key, held } => write!(f, "{} has negative held funds {}", account(key), held),
+            Inconsistency::Duplicate(key) => write!(f, "{} is in the snapshot more than once", account(key)),
+        }
+    }
+}
+
+impl Snapshot {
+    /// loads snapshot previously written by `save`, encrypted snapshot is decrypted with `key`;
+    /// snapshot failing `verify` is refused
+    pub fn load(path: &Path, key: Option<&EncryptionKey>) -> Result<Snapshot> {
+        let snapshot = Snapshot::read(path, key)?;
+        let inconsistencies = snapshot.verify();
+        if !inconsistencies.is_empty() {
+            let found: Vec<_> = inconsistencies.iter().map(|i| i.to_string()).collect();
+            return Err(format!("snapshot {:?} is inconsistent: {}", path, found.join("; ")).into());
+        }
+        Ok(snapshot)
+    }
+
+    /// Same as `load`, repairing the snapshot failing `verify` instead of refusing it: the last of the duplicate accounts
+    /// is kept, negative held funds are set to zero and total is recomputed. Returns what was repaired.
+    pub fn load_repaired(path: &Path, key: Option<&EncryptionKey>) -> Result<(Snapshot, Vec<Inconsistency>)> {
+        let mut snapshot = Snapshot::read(path, key)?;
+        let inconsistencies = snapshot.verify();
+        if inconsistencies.is_empty() {
+            return Ok((snapshot, inconsistencies));
+        }
+        warn!("repairing {} inconsistencies of snapshot {:?}", inconsistencies.len(), path);
+        let mut seen = HashSet::new();
+        snapshot.accounts.reverse();
+        snapshot.accounts.retain(|a| seen.insert((a.tenant.clone(), a.client_id)));
+        snapshot.accounts.reverse();
+        for a in snapshot.accounts.iter_mut() {
+            a.held_amount = a.held_amount.max(0.0);
+            a.total_amount = a.available_amount + a.held_amount + a.pending_amount;
+        }
+        Ok((snapshot, inconsistencies))
+    }
+
+    fn read(path: &Path, key: Option<&EncryptionKey>) -> Result<Snapshot> {
+        let mut value: Value = serde_json::from_slice(&crypto::read_file(path, key)?)?;
+        let version = migrate(&mut value).map_err(|e| format!("snapshot {:?}: {}", path, e))?;
+        if version < SNAPSHOT_VERSION {
+            info!("snapshot {:?} upgraded from version {} to {}", path, version, SNAPSHOT_VERSION);
+        }
+        let snapshot: Snapshot = serde_json::from_value(value)?;
+        debug!("loaded {} accounts from {:?}", snapshot.accounts.len(), path);
+        Ok(snapshot)
+    }
+
+    /// Checks invariants of the accounts: total funds are available, held and pending funds together, held funds are
+    /// not negative and no account is there twice. Every snapshot saved by the engine passes.
+    pub fn verify(&self) -> Vec<Inconsistency> {
+        let mut found = Vec::new();
+        let mut keys = HashSet::new();
+        for a in &self.accounts {
+            let key = (a.tenant.clone(), a.client_id);
+            if !keys.insert(key.clone()) {
+                found.push(Inconsistency::Duplicate(key.clone()));
+            }
+            if a.held_amount < 0.0 {
+                found.push(Inconsistency::NegativeHeld { key: key.clone(), held: a.held_amount });
+            }
+            let expected = a.available_amount + a.held_amount + a.pending_amount;
+            if (a.total_amount - expected).abs() > f32::EPSILON * expected.abs().max(1.0) {
+                found.push(Inconsistency::Total { key, total: a.total_amount, expected });
+            }
+        }
+        found
+    }
+
+    /// accounts of the snapshot by tenant and client id, see `ProcessorOptions::initial`
+    pub fn into_accounts(self) -> HashMap<AccountKey, AccountSnapshot> {
+        self.accounts.into_iter().map(|s| ((s.tenant.clone(), s.client_id), s)).collect()
+    }
+
+    /// saves the snapshot with the current `SNAPSHOT_VERSION`, encrypted when `key` is given
+    pub fn save(&self, path: &Path, key: Option<&EncryptionKey>) -> Result<()> {
+        let versioned = Versioned { version: SNAPSHOT_VERSION, snapshot: self };
+        crypto::write_file(path, &serde_json::to_vec(&versioned)?, key)?;
+        debug!("saved {} accounts to {:?}", self.accounts.len(), path);
+        Ok(())
+    }
+}
+
+// snapshot as it is saved, together with its format version
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u32,
+    #[serde(flatten)]
+    snapshot: &'a Snapshot,
+}
+
+/// Upgrades JSON of the snapshot written with an older format version to `SNAPSHOT_VERSION` one step at a time,
+/// returns the version it was written with (0 for the snapshots written before the version was recorded)
+pub fn migrate(value: &mut Value) -> Result<u32> {
+    let snapshot = value.as_object_mut().ok_or("snapshot is not a JSON object")?;
+    let version = match snapshot.get("version") {
+        Some(version) => version.as_u64().and_then(|v| u32::try_from(v).ok()).ok_or("invalid format version")?,
+        None => 0,
+    };
+    if version > SNAPSHOT_VERSION {
+        return Err(format!("format version {} is newer than supported {}", version, SNAPSHOT_VERSION).into());
+    }
+    if version < 1 {
+        // 0 to 1: `in_dispute` flag of the transactions was replaced by `dispute` state
+        let accounts = snapshot.get_mut("accounts").and_then(Value::as_array_mut).into_iter().flatten();
+        let transactions = accounts.filter_map(|a| a.get_mut("transactions").and_then(Value::as_array_mut)).flatten();
+        for t in transactions.filter_map(Value::as_object_mut) {
+            if t.remove("in_dispute") == Some(Value::Bool(true)) {
+                t.entry("dispute").or_insert_with(|| Value::from("open"));
+            }
+        }
+    }
+    snapshot.insert("version".to_string(), Value::from(SNAPSHOT_VERSION));
+    Ok(version)
+}
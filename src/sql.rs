@@ -0,0 +1,41 @@
+use arrow_array::RecordBatch;
+use datafusion::{arrow::util::pretty, prelude::SessionContext};
+
+use crate::{arrow, csv::RawAccount, tx::AccountReport, Result};
+
+/// SQL query over the results of the run: `accounts` table with the final accounts (`arrow::accounts_schema`)
+/// and `history` table with the retained history of every account (`arrow::history_schema`),
+/// empty when no history was kept
+pub struct Query {
+    ctx: SessionContext,
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Query { ctx: SessionContext::new() }
+    }
+}
+
+impl Query {
+    /// registers the `accounts` table, amounts rounded to `precision` decimal places like in the CSV output
+    pub fn with_accounts(self, accounts: &[RawAccount], precision: usize) -> Result<Self> {
+        self.ctx.register_batch("accounts", arrow::accounts_batch(accounts, precision)?)?;
+        Ok(self)
+    }
+
+    /// registers the `history` table
+    pub fn with_history(self, reports: &[AccountReport]) -> Result<Self> {
+        self.ctx.register_batch("history", arrow::history_batch(reports)?)?;
+        Ok(self)
+    }
+
+    /// result of the query, fails for invalid SQL or unknown tables and columns
+    pub async fn run(&self, sql: &str) -> Result<Vec<RecordBatch>> {
+        Ok(self.ctx.sql(sql).await?.collect().await?)
+    }
+}
+
+/// result of the query as a text table
+pub fn format(batches: &[RecordBatch]) -> Result<String> {
+    Ok(pretty::pretty_format_batches(batches)?.to_string())
+}
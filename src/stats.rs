@@ -0,0 +1,142 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    account::AccountError, manifest::RowCounts, resources::ResourceUsage, summary::RunSummary, timing::RunTimings,
+    tx::AccountReport, Money, Result, Timestamp, Transaction, TxType,
+};
+
+/// Counters of a single transaction type
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct TypeStats {
+    /// all transactions of the type, including rejected and duplicate ones
+    pub processed: u64,
+    pub applied: u64,
+    /// sum of the amounts of applied deposits and withdrawals, charged back amount of chargebacks
+    pub amount: Money,
+}
+
+/// Counters of the processing run for services embedding `TxProcessor` or `Engine`,
+/// which want to publish their own metrics without parsing the logs
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ProcessingStats {
+    /// counters by transaction type name, e.g. `deposit`
+    pub by_type: BTreeMap<String, TypeStats>,
+    /// number of rejected transactions by `AccountError::kind`
+    pub rejected: BTreeMap<String, u64>,
+    /// accounts which received at least one transaction
+    pub accounts_created: u64,
+    /// accounts locked by a chargeback during the run
+    pub accounts_frozen: u64,
+    /// rate limiting of the input, see `RateLimiter`
+    pub ingest: IngestStats,
+}
+
+/// Counters of the input rate limiting
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct IngestStats {
+    /// rows passed on for processing
+    pub admitted: u64,
+    /// rows over the limit dropped without processing
+    pub shed: u64,
+    /// rows which waited for the limit
+    pub delayed: u64,
+    /// total time rows waited, in milliseconds
+    pub waited_ms: u64,
+    /// malformed rows skipped by the reader (`CsvTransactionReader::skip_bad_lines`)
+    pub malformed: u64,
+}
+
+impl IngestStats {
+    pub fn merge(&mut self, other: &IngestStats) {
+        self.admitted += other.admitted;
+        self.shed += other.shed;
+        self.delayed += other.delayed;
+        self.waited_ms += other.waited_ms;
+        self.malformed += other.malformed;
+    }
+}
+
+impl ProcessingStats {
+    /// counts single processed transaction, `error` is set when it was rejected
+    pub fn record(&mut self, t: &Transaction, error: Option<&AccountError>) {
+        let s = self.by_type.entry(t.tx_type.to_string()).or_default();
+        s.processed += 1;
+        match error {
+            Some(e) => *self.rejected.entry(e.kind().to_string()).or_default() += 1,
+            None => {
+                s.applied += 1;
+                if t.tx_type == TxType::Deposit || t.tx_type == TxType::Withdrawal {
+                    s.amount += t.amount;
+                }
+            }
+        }
+    }
+
+    /// adds amount of the transaction reversed by applied chargeback
+    pub fn record_chargeback(&mut self, amount: Money) {
+        self.by_type.entry(TxType::Chargeback.to_string()).or_default().amount += amount;
+    }
+
+    /// counts transaction acknowledged without applying it, e.g. a duplicate or a skipped zero amount
+    pub fn record_skipped(&mut self, t: &Transaction) {
+        self.by_type.entry(t.tx_type.to_string()).or_default().processed += 1;
+    }
+
+    /// adds counters of `other` to these stats
+    pub fn merge(&mut self, other: &ProcessingStats) {
+        for (tx_type, s) in &other.by_type {
+            let into = self.by_type.entry(tx_type.clone()).or_default();
+            into.processed += s.processed;
+            into.applied += s.applied;
+            into.amount += s.amount;
+        }
+        for (kind, count) in &other.rejected {
+            *self.rejected.entry(kind.clone()).or_default() += count;
+        }
+        self.accounts_created += other.accounts_created;
+        self.accounts_frozen += other.accounts_frozen;
+        self.ingest.merge(&other.ingest);
+    }
+
+    /// stats of the whole run from the reports of all accounts, every report is one created account
+    pub fn from_reports(reports: &[AccountReport]) -> ProcessingStats {
+        reports.iter().fold(ProcessingStats::default(), |mut s, r| {
+            s.merge(&r.stats);
+            s.accounts_created += 1;
+            s
+        })
+    }
+}
+
+/// Machine readable report of a single run for batch orchestrators archiving it next to the outputs
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct StatsReport {
+    /// version of the software
+    pub version: String,
+    /// start and end of the run, seconds since the unix epoch
+    pub started: Timestamp,
+    pub finished: Timestamp,
+    pub rows: RowCounts,
+    /// processed, applied and duplicate transactions, rejections by reason and problems with the feed order
+    pub summary: RunSummary,
+    /// malformed rows skipped by the code of their parse error, e.g. `E2002`
+    pub parse_errors: BTreeMap<String, u64>,
+    pub stats: ProcessingStats,
+    pub timings: RunTimings,
+    pub resources: ResourceUsage,
+}
+
+impl StatsReport {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
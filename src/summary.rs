@@ -0,0 +1,113 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    account::AccountError,
+    sequence::SequenceStats,
+    tx::{AccountReport, ProcessingOutcome},
+};
+
+/// Counts of transactions processed during the run, rejections are counted per reason
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct RunSummary {
+    pub processed: u64,
+    pub applied: u64,
+    /// resubmitted transactions acknowledged without applying them again
+    pub duplicates: u64,
+    /// deposits and withdrawals without amount skipped by `zero_amount = "skip"`
+    pub zero_amounts: u64,
+    /// disputes, resolves and chargebacks of already charged back transactions skipped by `repeated_dispute = "ignore"`
+    pub repeated_disputes: u64,
+    /// number of rejected transactions by `AccountError::kind`
+    pub rejected: BTreeMap<String, u64>,
+    /// problems with the feed order, when sequence tracking is configured
+    pub sequence: Option<SequenceStats>,
+}
+
+impl RunSummary {
+    /// header row of the summary output
+    pub const HEADER: &'static str = "metric,count";
+
+    /// counts single processed transaction, `error` is set when it was rejected
+    pub fn record(&mut self, error: Option<&AccountError>) {
+        self.processed += 1;
+        match error {
+            Some(e) => *self.rejected.entry(e.kind().to_string()).or_default() += 1,
+            None => self.applied += 1,
+        }
+    }
+
+    /// counts transaction with already seen idempotency key
+    pub fn record_duplicate(&mut self) {
+        self.processed += 1;
+        self.duplicates += 1;
+    }
+
+    /// counts deposit or withdrawal skipped for its zero amount
+    pub fn record_zero_amount(&mut self) {
+        self.processed += 1;
+        self.zero_amounts += 1;
+    }
+
+    /// counts dispute, resolve or chargeback of already charged back transaction skipped without applying it
+    pub fn record_repeated_dispute(&mut self) {
+        self.processed += 1;
+        self.repeated_disputes += 1;
+    }
+
+    /// adds counts of `other` to this summary
+    pub fn merge(&mut self, other: &RunSummary) {
+        self.processed += other.processed;
+        self.applied += other.applied;
+        self.duplicates += other.duplicates;
+        self.zero_amounts += other.zero_amounts;
+        self.repeated_disputes += other.repeated_disputes;
+        for (kind, count) in &other.rejected {
+            *self.rejected.entry(kind.clone()).or_default() += count;
+        }
+    }
+
+    /// total number of rejected transactions
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    /// summary of the whole run from the reports of all accounts
+    pub fn from_reports(reports: &[AccountReport]) -> RunSummary {
+        reports.iter().fold(RunSummary::default(), |mut s, r| {
+            s.merge(&r.summary);
+            s
+        })
+    }
+
+    /// summary of the whole run including problems found by the processor
+    pub fn from_outcome(outcome: &ProcessingOutcome) -> RunSummary {
+        RunSummary {
+            sequence: outcome.sequence.clone(),
+            ..RunSummary::from_reports(&outcome.reports)
+        }
+    }
+}
+
+/// summary in the CSV format, rejections by reason are listed as `rejected.<kind>` rows
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", RunSummary::HEADER)?;
+        writeln!(f, "processed,{}", self.processed)?;
+        writeln!(f, "applied,{}", self.applied)?;
+        writeln!(f, "duplicate,{}", self.duplicates)?;
+        writeln!(f, "zero_amount,{}", self.zero_amounts)?;
+        writeln!(f, "repeated_dispute,{}", self.repeated_disputes)?;
+        writeln!(f, "rejected,{}", self.rejected_total())?;
+        for (kind, count) in &self.rejected {
+            writeln!(f, "rejected.{},{}", kind, count)?;
+        }
+        if let Some(s) = &self.sequence {
+            writeln!(f, "sequence.gaps,{}", s.gaps)?;
+            writeln!(f, "sequence.missing,{}", s.missing)?;
+            writeln!(f, "sequence.out_of_order,{}", s.out_of_order)?;
+            writeln!(f, "sequence.dropped,{}", s.dropped)?;
+            writeln!(f, "sequence.reordered,{}", s.reordered)?;
+        }
+        Ok(())
+    }
+}
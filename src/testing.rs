@@ -0,0 +1,41 @@
+use std::io;
+
+use crate::{csv::RawAccount, output::OutputSink, ClientId, Money, Transaction, TxId, TxType};
+
+/// transaction of the client without tenant, timestamp or any other optional field, e.g. `tx(TxType::Deposit, 1, 1, 10.0)`
+pub fn tx(tx_type: TxType, client_id: ClientId, tx_id: TxId, amount: Money) -> Transaction {
+    Transaction {
+        tx_type,
+        client_id,
+        tx_id,
+        amount,
+        dispute: Default::default(),
+        tenant: None,
+        timestamp: None,
+        idempotency_key: None,
+        seq: None,
+        line: None,
+        metadata: Default::default(),
+    }
+}
+
+/// `OutputSink` keeping the accounts in memory, so tests can assert on them on every platform
+/// instead of capturing stdout
+#[derive(Debug, Default)]
+pub struct VecSink {
+    pub accounts: Vec<RawAccount>,
+    /// number of times `finish` was called
+    pub finished: usize,
+}
+
+impl OutputSink for VecSink {
+    fn write_account(&mut self, account: &RawAccount) -> io::Result<()> {
+        self.accounts.push(account.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.finished += 1;
+        Ok(())
+    }
+}
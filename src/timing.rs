@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// most latencies kept by `StageTimings`, every other one is dropped and the sampling rate halved when reached
+const MAX_SAMPLES: usize = 4096;
+
+/// Time spent by the stages of `TxProcessor`, updated while processing when set in `ProcessorOptions::timings`
+#[derive(Debug)]
+pub struct StageTimings {
+    parse_ns: AtomicU64,
+    dispatch_ns: AtomicU64,
+    apply_ns: AtomicU64,
+    applied: AtomicU64,
+    // every `every`-th applied transaction is sampled
+    every: AtomicU64,
+    samples: Mutex<Vec<u64>>,
+}
+
+impl Default for StageTimings {
+    fn default() -> Self {
+        StageTimings {
+            parse_ns: AtomicU64::new(0),
+            dispatch_ns: AtomicU64::new(0),
+            apply_ns: AtomicU64::new(0),
+            applied: AtomicU64::new(0),
+            every: AtomicU64::new(1),
+            samples: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl StageTimings {
+    /// time the source took to read and parse the transactions, measured by the caller of `TxProcessor`
+    pub fn parsed(&self, took: Duration) {
+        self.parse_ns.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// time the transaction took to pass the middleware and reach the queue of its account
+    pub(crate) fn dispatched(&self, took: Duration) {
+        self.dispatch_ns.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// time the account took to apply the transaction
+    pub(crate) fn applied(&self, took: Duration) {
+        let ns = took.as_nanos() as u64;
+        self.apply_ns.fetch_add(ns, Ordering::Relaxed);
+        let n = self.applied.fetch_add(1, Ordering::Relaxed);
+        if !n.is_multiple_of(self.every.load(Ordering::Relaxed)) {
+            return;
+        }
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.push(ns);
+        if samples.len() >= MAX_SAMPLES {
+            let mut i = 0usize;
+            samples.retain(|_| {
+                i += 1;
+                i.is_multiple_of(2)
+            });
+            self.every.fetch_add(self.every.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// `q` quantile (0.0 - 1.0) of the sampled latencies
+    fn quantile(samples: &[u64], q: f64) -> Duration {
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let i = ((samples.len() - 1) as f64 * q).round() as usize;
+        Duration::from_nanos(samples[i])
+    }
+}
+
+/// Throughput and latency of the run, to compare tuning flags like `--buffer`
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct RunTimings {
+    /// wall clock duration of the processing
+    pub wall_ms: u64,
+    /// parsed rows, see `RowCounts::read`
+    pub rows: u64,
+    pub rows_per_sec: f64,
+    /// time spent reading and parsing the input, without the time waiting for the processing to catch up
+    pub parse_ms: u64,
+    /// time spent passing the transactions to their accounts, including the middleware
+    pub dispatch_ms: u64,
+    /// time the accounts spent applying the transactions, summed over all accounts running in parallel
+    pub apply_ms: u64,
+    /// median and 99th percentile of the time to apply a single transaction, in microseconds
+    pub latency_p50_us: u64,
+    pub latency_p99_us: u64,
+    /// applied transactions the latencies were computed from
+    pub latency_samples: u64,
+}
+
+impl RunTimings {
+    pub fn new(timings: &StageTimings, wall: Duration, rows: u64) -> RunTimings {
+        let mut samples = timings.samples.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        samples.sort_unstable();
+        RunTimings {
+            wall_ms: wall.as_millis() as u64,
+            rows,
+            rows_per_sec: if wall.is_zero() { 0.0 } else { rows as f64 / wall.as_secs_f64() },
+            parse_ms: timings.parse_ns.load(Ordering::Relaxed) / 1_000_000,
+            dispatch_ms: timings.dispatch_ns.load(Ordering::Relaxed) / 1_000_000,
+            apply_ms: timings.apply_ns.load(Ordering::Relaxed) / 1_000_000,
+            latency_p50_us: StageTimings::quantile(&samples, 0.5).as_micros() as u64,
+            latency_p99_us: StageTimings::quantile(&samples, 0.99).as_micros() as u64,
+            latency_samples: samples.len() as u64,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+use crate::Money;
+
+/// Keeps `n` items with the highest amounts seen so far, in memory bounded by `n` regardless of the number of items
+#[derive(Debug, Clone)]
+pub struct TopN<T> {
+    n: usize,
+    // min heap, the smallest of the kept items is replaced first
+    heap: BinaryHeap<Reverse<Ranked<T>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Ranked<T> {
+    amount: Money,
+    item: T,
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount.total_cmp(&other.amount) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.amount.total_cmp(&other.amount)
+    }
+}
+
+impl<T> Default for TopN<T> {
+    fn default() -> Self {
+        TopN::new(0)
+    }
+}
+
+impl<T> TopN<T> {
+    /// keeps `n` items, nothing with 0
+    pub fn new(n: usize) -> Self {
+        TopN { n, heap: BinaryHeap::with_capacity(n + 1) }
+    }
+
+    /// number of kept items
+    pub fn capacity(&self) -> usize {
+        self.n
+    }
+
+    /// keeps the item when its amount is among the `n` highest, of equal amounts the earlier one is kept
+    pub fn push(&mut self, amount: Money, item: T) {
+        if self.heap.len() < self.n {
+            self.heap.push(Reverse(Ranked { amount, item }));
+        } else if self.heap.peek().is_some_and(|Reverse(min)| amount > min.amount) {
+            self.heap.pop();
+            self.heap.push(Reverse(Ranked { amount, item }));
+        }
+    }
+
+    /// kept items with their amounts, the highest first
+    pub fn into_sorted_vec(self) -> Vec<(Money, T)> {
+        // ascending order of `Reverse` is the descending order of amounts
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(r)| (r.amount, r.item)).collect()
+    }
+}
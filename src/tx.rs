@@ -1,9 +1,44 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, io,
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
 
-use tokio::sync::mpsc::{self, Receiver, Sender};
-use tracing::{debug, trace, warn};
+use tokio::sync::{
+    mpsc::{self, error::TryRecvError, Receiver, Sender},
+    oneshot,
+};
+use tracing::{debug, error, info_span, trace, Instrument};
 
-use crate::{account::Account, csv::RawTransaction, ClientId, Transaction, TxId, TxType};
+use crate::{
+    aggregate::Aggregator,
+    clock::{self, Clock, SystemClock},
+    account::AccountError,
+    clients::ClientOverrides,
+    csv::{RawAccount, RawTransaction},
+    config::{self, Config, ConfigReceiver},
+    dedupe::SharedDedupeWindow,
+    engine::{AccountState, Applied},
+    events::{EventSender, Outcome, ProcessingEvent},
+    history::{HistoryEntry, HistoryRetention},
+    lag::LagMonitor,
+    ledger::{InMemoryStore, LedgerStore},
+    middleware::{self, TxMiddleware},
+    output::{FlushPolicy, FlushingWriter, OutputSink},
+    priority::PriorityQueue,
+    quarantine::Quarantined,
+    resources::ResourceGauges,
+    rt::{self, JoinHandle},
+    seen::SeenStore,
+    sequence::{SequenceStats, Sequencer},
+    snapshot::{AccountSnapshot, Snapshot},
+    stats::ProcessingStats,
+    summary::RunSummary,
+    timing::StageTimings,
+    top::TopN,
+    AccountKey, ClientId, Money, Result, TenantId, Transaction, TxId, TxType,
+};
 
 /// convert RawTransaction into Transaction
 impl From<RawTransaction> for Transaction {
@@ -11,7 +46,7 @@ impl From<RawTransaction> for Transaction {
         Transaction {
             amount: {
                 match t.tx_type {
-                    TxType::Deposit | TxType::Withdrawal => match t.amount {
+                    TxType::Deposit | TxType::Withdrawal | TxType::Refund => match t.amount {
                         None => 0.0,
                         Some(str_amount) => {
                             let r = str_amount.parse::<f32>();
@@ -27,131 +62,690 @@ impl From<RawTransaction> for Transaction {
                             }
                         }
                     },
-                    TxType::Dispute | TxType::Resolve | TxType::Chargeback => 0.0,
+                    TxType::Dispute | TxType::Resolve | TxType::Chargeback | TxType::Open | TxType::Close | TxType::Settle => 0.0,
                 }
             },
             tx_type: t.tx_type,
             tx_id: t.tx_id,
             client_id: t.client_id,
-            in_dispute: false,
+            dispute: Default::default(),
+            tenant: t.tenant,
+            timestamp: t.timestamp,
+            idempotency_key: t.idempotency_key,
+            seq: t.seq,
+            line: t.line,
+            metadata: t.metadata,
         }
     }
 }
 
-/// simple data storage for account process to store client id and tx_sender
+/// Message of the channel of `TxProcessor::run`, `Some(t)` converts into `Transaction` and `None` into `Shutdown`
+#[derive(Debug)]
+pub enum TxMessage {
+    Transaction(Transaction),
+    /// replies with the current state of all accounts ordered by tenant and client id, processing goes on
+    Flush(oneshot::Sender<Vec<RawAccount>>),
+    /// replies with balances and disputable transactions of all accounts, processing goes on
+    Snapshot(oneshot::Sender<Snapshot>),
+    /// accounts process their queued transactions and `run` returns their reports
+    Shutdown,
+}
+
+impl From<Option<Transaction>> for TxMessage {
+    fn from(t: Option<Transaction>) -> Self {
+        match t {
+            Some(t) => TxMessage::Transaction(t),
+            None => TxMessage::Shutdown,
+        }
+    }
+}
+
+impl From<Transaction> for TxMessage {
+    fn from(t: Transaction) -> Self {
+        TxMessage::Transaction(t)
+    }
+}
+
+/// error of `ProcessorHandle` when `TxProcessor::run` already returned
+const PROCESSOR_STOPPED: &str = "transaction processor is not running";
+
+/// Sending side of the channel of a running `TxProcessor::run`, cloned by every producer
+#[derive(Debug, Clone)]
+pub struct ProcessorHandle {
+    sender: Sender<TxMessage>,
+}
+
+impl ProcessorHandle {
+    pub fn new(sender: Sender<TxMessage>) -> Self {
+        ProcessorHandle { sender }
+    }
+
+    /// passes the transaction on to its account
+    pub async fn send(&self, t: Transaction) -> Result<()> {
+        self.sender.send(TxMessage::Transaction(t)).await.map_err(|_| PROCESSOR_STOPPED.into())
+    }
+
+    /// Consistent point-in-time state of all accounts, keyed by tenant and client id.
+    /// Every account answers once the transactions sent before the call are applied, and no further transaction
+    /// is routed until all of them answered, so the snapshot never contains a transaction sent after the call
+    /// while missing one sent before. Transactions held back by the middleware or the sequencer are not included.
+    pub async fn snapshot_all(&self) -> Result<BTreeMap<AccountKey, AccountSnapshot>> {
+        let (reply, replied) = oneshot::channel();
+        self.sender.send(TxMessage::Snapshot(reply)).await.map_err(|_| PROCESSOR_STOPPED)?;
+        let snapshot = replied.await.map_err(|_| PROCESSOR_STOPPED)?;
+        Ok(snapshot.accounts.into_iter().map(|a| ((a.tenant.clone(), a.client_id), a)).collect())
+    }
+
+    /// stops the processor once the transactions sent before are applied, `run` returns the final reports
+    pub async fn shutdown(&self) -> Result<()> {
+        self.sender.send(TxMessage::Shutdown).await.map_err(|_| PROCESSOR_STOPPED.into())
+    }
+}
+
+/// Message of the channel of a single account task, requests are answered once all transactions
+/// received before them are applied
+#[derive(Debug)]
+pub enum AccountMessage {
+    Transaction(Transaction),
+    Flush(oneshot::Sender<RawAccount>),
+    Snapshot(oneshot::Sender<AccountSnapshot>),
+    Shutdown,
+}
+
+/// simple data storage for account process to store client id, tx_sender and the task handle
 #[derive(Debug)]
 pub struct AccountProcess {
+    pub tenant: Option<TenantId>,
     pub client_id: ClientId,
-    pub tx_sender: Sender<Option<Transaction>>,
+    pub tx_sender: Sender<AccountMessage>,
+    pub handle: JoinHandle<AccountReport>,
+}
+
+/// Options of the transaction processing
+#[derive(Debug, Clone)]
+pub struct ProcessorOptions {
+    /// size of the buffer used when spawning each new account tx task
+    pub buffer_size: usize,
+    /// which transactions are kept together with the resulting balances for each account
+    pub history: HistoryRetention,
+    /// when set, every processed transaction is reported as `ProcessingEvent`
+    pub events: Option<EventSender>,
+    /// effective configuration, read for every transaction so reloaded configuration applies immediately
+    pub config: ConfigReceiver,
+    /// per client settings applied when the account is created
+    pub clients: Arc<ClientOverrides>,
+    /// when set, rejected transactions are kept in `AccountReport::rejects`
+    pub rejects: bool,
+    /// when set, deposits and withdrawals already recorded in the store are not applied again
+    pub seen: Option<Arc<SeenStore>>,
+    /// when set, the final state of every account is kept in `AccountReport::state`, e.g. to save it
+    pub state: bool,
+    /// number of the largest deposits and withdrawals every account keeps in `AccountReport::largest`
+    pub top: usize,
+    /// receive every applied transaction, see `Aggregator`
+    pub aggregators: Vec<Arc<dyn Aggregator>>,
+    /// state the accounts continue from (see `Snapshot::into_accounts`), accounts without transactions
+    /// in this run are reported as they are
+    pub initial: HashMap<AccountKey, AccountSnapshot>,
+    /// number of transactions queued for an account which its disputes and chargebacks can overtake
+    /// (see `PriorityQueue`), 0 keeps the order of arrival
+    pub priority_window: usize,
+    /// stages every transaction passes, in this order, before it is routed to its account (see `TxMiddleware`)
+    pub middleware: Vec<Arc<dyn TxMiddleware>>,
+    /// when set, account tasks and the entries they retain are counted while processing (see `ResourceUsage`)
+    pub gauges: Option<Arc<ResourceGauges>>,
+    /// when set, time spent dispatching and applying the transactions is measured (see `RunTimings`)
+    pub timings: Option<Arc<StageTimings>>,
+    /// when set, queue wait and processing time of every account is measured (see `LagMonitor`)
+    pub lag: Option<Arc<LagMonitor>>,
+    /// storage of the balances and disputable transactions of every account (see `Ledger`)
+    pub ledger: Arc<dyn LedgerStore>,
+    /// time of the account timers, `MockClock` lets tests advance it (see `Clock`)
+    pub clock: Arc<dyn Clock>,
+    /// when set, routing stops at the first transaction rejected by its account, it is returned in
+    /// `ProcessingOutcome::aborted`; transactions already routed to the accounts are still applied
+    pub strict: bool,
+}
+
+impl Default for ProcessorOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: 32,
+            history: HistoryRetention::None,
+            events: None,
+            config: config::fixed(Config::default()),
+            clients: Default::default(),
+            rejects: false,
+            seen: None,
+            state: false,
+            top: 0,
+            aggregators: Vec::new(),
+            initial: HashMap::new(),
+            priority_window: 0,
+            middleware: Vec::new(),
+            gauges: None,
+            timings: None,
+            lag: None,
+            ledger: Arc::new(InMemoryStore),
+            clock: Arc::new(SystemClock),
+            strict: false,
+        }
+    }
+}
+
+/// Final state of the account together with its history (empty unless `history` retention is requested)
+#[derive(Debug, Clone)]
+pub struct AccountReport {
+    pub account: RawAccount,
+    pub history: Vec<HistoryEntry>,
+    /// rejected transactions, empty unless `rejects` option is set
+    pub rejects: Vec<HistoryEntry>,
+    /// counts of transactions processed by the account
+    pub summary: RunSummary,
+    /// counters by transaction type processed by the account
+    pub stats: ProcessingStats,
+    /// disputes, resolves and chargebacks still waiting for retry at the end of the run
+    pub unresolved: Vec<Transaction>,
+    /// first transaction which left available or total funds of the account negative, if any
+    pub negative: Option<NegativeBalance>,
+    /// transactions still in dispute at the end of the run, ordered by id
+    pub disputed: Vec<Transaction>,
+    /// largest applied deposits and withdrawals, the largest first, empty unless `top` option is set
+    pub largest: Vec<Transaction>,
+    /// transaction which got the account quarantined, when `[quarantine]` is configured
+    pub quarantined: Option<Quarantined>,
+    /// final state of the account, unless `state` option is set empty
+    pub state: Option<AccountSnapshot>,
+}
+
+/// Transaction which first drove the account balance below zero, with the balances right after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegativeBalance {
+    pub tx_id: TxId,
+    pub tx_type: TxType,
+    pub available_amount: Money,
+    pub total_amount: Money,
+}
+
+/// Result of the whole processing run
+#[derive(Debug, Clone)]
+pub struct ProcessingOutcome {
+    /// final state of all accounts ordered by tenant and client id
+    pub reports: Vec<AccountReport>,
+    /// ordering problems of the stream, when sequence tracking is configured
+    pub sequence: Option<SequenceStats>,
+    /// counters of the whole run, see `ProcessingStats`
+    pub stats: ProcessingStats,
+    /// transaction which stopped the run with `ProcessorOptions::strict`
+    pub aborted: Option<RejectedRow>,
+}
+
+/// Transaction rejected by its account, e.g. the one which stopped the run with `ProcessorOptions::strict`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    pub transaction: Transaction,
+    pub error: AccountError,
+}
+
+/// e.g. `line 4: client 1 tx 3 rejected: E1001 insufficient funds on account 1: ...`
+impl fmt::Display for RejectedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = self.transaction.line.map(|l| l.to_string()).unwrap_or_else(|| "-".to_string());
+        write!(f, "line {}: client {} tx {} rejected: {}", line, self.transaction.client_id, self.transaction.tx_id, self.error)
+    }
+}
+
+impl std::error::Error for RejectedRow {}
+
+/// State shared by the account tasks of a single run
+#[derive(Debug, Default)]
+struct RunState {
+    /// first rejected transaction of the run, set by the account tasks with `ProcessorOptions::strict`
+    aborted: OnceLock<RejectedRow>,
+    /// idempotency keys of all accounts, used with `idempotency.scope = "global"`
+    dedupe: Arc<SharedDedupeWindow>,
 }
 
 /// Transaction processing functionality
 pub struct TxProcessor {}
 
 impl TxProcessor {
-    /// Transaction processing task
+    /// Transaction processing task, prints final state of all accounts to stdout
+    ///
+    /// `tx_receiver` channel for receiving incomming transactions to process
+    /// `buffer_size` size of the buffer used when spawning each new account tx task
+    pub async fn process_transactions(tx_receiver: Receiver<Option<Transaction>>, buffer_size: usize) {
+        let mut stdout = FlushingWriter::new(io::stdout(), FlushPolicy::default());
+        TxProcessor::process_transactions_into(tx_receiver, buffer_size, &mut stdout).await;
+    }
+
+    /// Same as `process_transactions`, writing final state of all accounts to `sink`
     ///
     /// `tx_receiver` channel for receiving incomming transactions to process
     /// `buffer_size` size of the buffer used when spawning each new account tx task
-    pub async fn process_transactions(
-        mut tx_receiver: Receiver<Option<Transaction>>,
+    /// `sink` destination of the accounts, e.g. `testing::VecSink` in tests
+    pub async fn process_transactions_into<S: OutputSink>(
+        tx_receiver: Receiver<Option<Transaction>>,
         buffer_size: usize,
+        sink: &mut S,
     ) {
-        // map client/account to AccountProcess
-        let mut account_processes = HashMap::<ClientId, AccountProcess>::new();
+        let accounts = TxProcessor::collect_accounts(tx_receiver, buffer_size).await;
+        for account in accounts {
+            if let Err(e) = sink.write_account(&account) {
+                error!("failed writing account {}: {}", account.client_id, e);
+            }
+        }
+        if let Err(e) = sink.finish() {
+            error!("failed writing accounts: {}", e);
+        }
+    }
 
-        while let Some(Some(t)) = tx_receiver.recv().await {
-            trace!("processing tx {:?}", t);
-            let account_process = account_processes.get_key_value(&t.client_id);
-            match account_process {
-                //
-                None => {
-                    let (acc_tx_sender, acc_tx_receiver) =
-                        mpsc::channel::<Option<Transaction>>(buffer_size);
-                    account_processes.insert(
-                        t.client_id,
-                        AccountProcess {
-                            client_id: t.client_id,
-                            tx_sender: acc_tx_sender.clone(),
-                        },
-                    );
-                    //create new task to handle
-                    tokio::spawn(async move {
-                        TxProcessor::process_account_transactions(t.client_id, acc_tx_receiver)
-                            .await;
-                    });
-                    // todo: handle the Result
-                    let _ = acc_tx_sender.send(Some(t)).await;
+    /// Transaction processing task, returns final state of all accounts ordered by tenant and client id
+    ///
+    /// `tx_receiver` channel for receiving incomming transactions to process
+    /// `buffer_size` size of the buffer used when spawning each new account tx task
+    pub async fn collect_accounts(
+        tx_receiver: Receiver<Option<Transaction>>,
+        buffer_size: usize,
+    ) -> Vec<RawAccount> {
+        let options = ProcessorOptions {
+            buffer_size,
+            ..Default::default()
+        };
+        TxProcessor::process(tx_receiver, options)
+            .await
+            .into_iter()
+            .map(|r| r.account)
+            .collect()
+    }
+
+    /// Transaction processing task, returns final state (and history) of all accounts ordered by tenant and client id
+    ///
+    /// `tx_receiver` channel for receiving incomming transactions (or other `TxMessage`s) to process
+    /// `options` processing options
+    pub async fn process<M: Into<TxMessage>>(
+        tx_receiver: Receiver<M>,
+        options: ProcessorOptions,
+    ) -> Vec<AccountReport> {
+        TxProcessor::run(tx_receiver, options).await.reports
+    }
+
+    /// Same as `process`, also returning problems found in the stream of transactions as a whole
+    ///
+    /// `tx_receiver` channel for receiving incomming transactions (or other `TxMessage`s) to process,
+    ///     processing ends with `TxMessage::Shutdown` or when the channel is closed
+    /// `options` processing options
+    pub async fn run<M: Into<TxMessage>>(
+        mut tx_receiver: Receiver<M>,
+        options: ProcessorOptions,
+    ) -> ProcessingOutcome {
+        let options = Arc::new(options);
+        let run = Arc::new(RunState::default());
+        // map tenant and client/account to AccountProcess
+        let mut account_processes = HashMap::<AccountKey, AccountProcess>::new();
+        // sequence configuration is taken at the start, reloads do not change it during the run
+        let mut sequencer = options.config.borrow().sequence.clone().map(Sequencer::new);
+
+        while let Some(message) = tx_receiver.recv().await {
+            let t = match message.into() {
+                TxMessage::Transaction(t) => t,
+                TxMessage::Flush(reply) => {
+                    let mut accounts = TxProcessor::untouched(&account_processes, &options)
+                        .into_iter()
+                        .map(|s| s.raw_account())
+                        .collect::<Vec<_>>();
+                    accounts.extend(TxProcessor::request(&account_processes, AccountMessage::Flush).await);
+                    accounts.sort_by(|a, b| (&a.tenant, a.client_id).cmp(&(&b.tenant, b.client_id)));
+                    let _ = reply.send(accounts);
+                    continue;
                 }
-                Some((_k, proc)) => {
-                    // todo: handle the Result
-                    let _ = proc.tx_sender.send(Some(t)).await;
+                TxMessage::Snapshot(reply) => {
+                    let mut accounts = TxProcessor::untouched(&account_processes, &options)
+                        .into_iter()
+                        .map(|s| s.snapshot())
+                        .collect::<Vec<_>>();
+                    accounts.extend(TxProcessor::request(&account_processes, AccountMessage::Snapshot).await);
+                    accounts.sort_by(|a, b| (&a.tenant, a.client_id).cmp(&(&b.tenant, b.client_id)));
+                    let _ = reply.send(Snapshot { accounts, ..Default::default() });
+                    continue;
+                }
+                TxMessage::Shutdown => break,
+            };
+            // the rest of the stream is still received, so the producers are not stopped by a closed channel
+            if run.aborted.get().is_some() {
+                trace!("run aborted, tx {:?} not routed", t);
+                continue;
+            }
+            trace!("processing tx {:?}", t);
+            let started = Instant::now();
+            for t in middleware::run(&options.middleware, t).await {
+                TxProcessor::sequence(t, sequencer.as_mut(), &mut account_processes, &options, &run).await;
+            }
+            if let Some(timings) = &options.timings {
+                timings.dispatched(started.elapsed());
+            }
+        }
+        if run.aborted.get().is_none() {
+            for t in middleware::finish(&options.middleware).await {
+                TxProcessor::sequence(t, sequencer.as_mut(), &mut account_processes, &options, &run).await;
+            }
+            if let Some(sequencer) = sequencer.as_mut() {
+                for t in sequencer.finish() {
+                    TxProcessor::route(t, &mut account_processes, &options, &run).await;
                 }
             }
         }
 
         debug!("finished distributing transactions: shutting down account tasks");
 
-        // no more transaction to process, inform our account tasks to stop listening and print the account status
-        for p in account_processes.values() {
-            let _ = p.tx_sender.send(Option::None).await;
-            p.tx_sender.closed().await;
-            trace!(
-                "accountprocess {} tx is closed: {}",
-                p.client_id,
-                p.tx_sender.is_closed()
-            );
+        // no more transaction to process, inform our account tasks to stop listening and return the account status
+        let mut reports = Vec::with_capacity(account_processes.len());
+        for state in TxProcessor::untouched(&account_processes, &options) {
+            reports.push(TxProcessor::into_report(state, &options));
+        }
+        for (_key, p) in account_processes.drain() {
+            let _ = p.tx_sender.send(AccountMessage::Shutdown).await;
+            match p.handle.join().await {
+                Ok(report) => reports.push(report),
+                Err(e) => error!("account process {:?}/{} failed: {}", p.tenant, p.client_id, e),
+            }
         }
+        reports.sort_by(|a, b| {
+            (&a.account.tenant, a.account.client_id).cmp(&(&b.account.tenant, b.account.client_id))
+        });
 
         debug!("all account processing tasks has been closed");
+
+        ProcessingOutcome {
+            stats: ProcessingStats::from_reports(&reports),
+            reports,
+            sequence: sequencer.map(|s| s.stats().clone()),
+            aborted: run.aborted.get().cloned(),
+        }
+    }
+
+    /// accounts of the initial state untouched by this run so far
+    fn untouched(
+        account_processes: &HashMap<AccountKey, AccountProcess>,
+        options: &ProcessorOptions,
+    ) -> Vec<AccountState> {
+        options
+            .initial
+            .iter()
+            .filter(|(key, _)| !account_processes.contains_key(*key))
+            .map(|(key, s)| {
+                let mut state = AccountState::new(key.clone(), options.history, options.clients.get(key), options.ledger.as_ref());
+                if let Err(e) = state.restore(s.clone()) {
+                    error!("account {} not restored from the initial state: {}", key.1, e);
+                }
+                state
+            })
+            .collect()
+    }
+
+    /// sends the request made by `message` to every account task and collects the replies
+    async fn request<T>(
+        account_processes: &HashMap<AccountKey, AccountProcess>,
+        message: fn(oneshot::Sender<T>) -> AccountMessage,
+    ) -> Vec<T> {
+        let mut replies = Vec::with_capacity(account_processes.len());
+        for p in account_processes.values() {
+            let (reply, replied) = oneshot::channel();
+            if p.tx_sender.send(message(reply)).await.is_ok() {
+                replies.push(replied);
+            }
+        }
+        let mut answers = Vec::with_capacity(replies.len());
+        for replied in replies {
+            match replied.await {
+                Ok(answer) => answers.push(answer),
+                Err(_) => error!("account process stopped before answering the request"),
+            }
+        }
+        answers
+    }
+
+    /// passes applied transactions to the aggregators and sends processing events of all of them,
+    /// rejected transactions are already logged; with `strict` the first rejected one aborts the run
+    fn report(state: &AccountState, applied: Vec<Applied>, options: &ProcessorOptions, run: &RunState) {
+        for (t, r, _) in &applied {
+            match r {
+                Ok(Outcome::Applied) => options.aggregators.iter().for_each(|a| a.record(t)),
+                Err(e) if options.strict => {
+                    let _ = run.aborted.set(RejectedRow { transaction: t.clone(), error: e.clone() });
+                }
+                _ => (),
+            }
+        }
+        let events = match &options.events {
+            Some(events) => events,
+            None => return,
+        };
+        for (t, r, account) in applied {
+            let (outcome, rejection) = match &r {
+                Ok(Outcome::Skipped) => (Outcome::Skipped, Some("duplicate".to_string())),
+                Ok(outcome) => (*outcome, None),
+                Err(e) => (Outcome::Rejected, Some(e.message())),
+            };
+            // nobody listening anymore is not an error for the processing
+            let _ = events.send(ProcessingEvent {
+                line: t.line,
+                tx_id: t.tx_id,
+                tx_type: t.tx_type,
+                amount: t.amount,
+                outcome,
+                rejection,
+                error: r.err(),
+                account: RawAccount {
+                    tenant: state.key.0.clone(),
+                    last_activity: state.last_activity,
+                    ..account.into()
+                },
+                metadata: t.metadata,
+            });
+        }
+    }
+
+    /// routes the transaction, or the transactions released by the sequencer when there is one
+    async fn sequence(
+        t: Transaction,
+        sequencer: Option<&mut Sequencer>,
+        account_processes: &mut HashMap<AccountKey, AccountProcess>,
+        options: &Arc<ProcessorOptions>,
+        run: &Arc<RunState>,
+    ) {
+        match sequencer {
+            None => TxProcessor::route(t, account_processes, options, run).await,
+            Some(sequencer) => {
+                for t in sequencer.push(t) {
+                    TxProcessor::route(t, account_processes, options, run).await;
+                }
+            }
+        }
+    }
+
+    /// sends transaction to the task of its account, spawning the task for the first transaction of the account
+    async fn route(
+        t: Transaction,
+        account_processes: &mut HashMap<AccountKey, AccountProcess>,
+        options: &Arc<ProcessorOptions>,
+        run: &Arc<RunState>,
+    ) {
+        let key = t.account_key();
+        match account_processes.get(&key) {
+            //
+            None => {
+                let (acc_tx_sender, acc_tx_receiver) =
+                    mpsc::channel::<AccountMessage>(options.buffer_size);
+                //create new task to handle
+                let task_key = key.clone();
+                let task_options = options.clone();
+                let task_run = run.clone();
+                // everything logged by the account task can be filtered by client, e.g. `txp[account{client_id=42}]=trace`
+                let span = info_span!("account", client_id = key.1, tenant = key.0.as_deref());
+                let handle = rt::spawn(
+                    async move {
+                        TxProcessor::process_account_transactions(task_key, acc_tx_receiver, task_options, task_run)
+                            .await
+                    }
+                    .instrument(span),
+                );
+                if let Some(gauges) = &options.gauges {
+                    gauges.task_spawned();
+                }
+                if let Some(lag) = &options.lag {
+                    lag.sent(&key);
+                }
+                // todo: handle the Result
+                let _ = acc_tx_sender.send(AccountMessage::Transaction(t)).await;
+                account_processes.insert(
+                    key.clone(),
+                    AccountProcess {
+                        tenant: key.0,
+                        client_id: key.1,
+                        tx_sender: acc_tx_sender,
+                        handle,
+                    },
+                );
+            }
+            Some(proc) => {
+                if let Some(lag) = &options.lag {
+                    lag.sent(&key);
+                }
+                // todo: handle the Result
+                let _ = proc.tx_sender.send(AccountMessage::Transaction(t)).await;
+            }
+        }
     }
 
     /// this function is spawn for each client account to handle its transactions
     ///
-    /// `id` client id
-    /// `mut tx_reveiver` receiver part of the channel to listen for incomming transactions to process.
-    ///     If None is received its a signal to print the account status and exit
+    /// `key` tenant and client id
+    /// `mut tx_reveiver` receiver part of the channel to listen for incomming transactions and requests.
+    ///     `Shutdown` is a signal to exit and return the account status
+    /// `options` processing options shared by all account tasks
+    /// `run` state shared by the account tasks of the run
     async fn process_account_transactions(
-        id: ClientId,
-        mut tx_reveiver: Receiver<Option<Transaction>>,
-    ) {
-        let mut account = Account::default();
-        account.client_id = id;
+        key: AccountKey,
+        mut tx_reveiver: Receiver<AccountMessage>,
+        options: Arc<ProcessorOptions>,
+        run: Arc<RunState>,
+    ) -> AccountReport {
+        let settings = options.clients.get(&key);
+        let mut state = AccountState::new(key, options.history, settings, options.ledger.as_ref());
+        state.keep_rejects = options.rejects;
+        state.seen = options.seen.clone();
+        state.largest = TopN::new(options.top);
+        state.global_dedupe = run.dedupe.clone();
+        if let Some(s) = options.initial.get(&state.key) {
+            if let Err(e) = state.restore(s.clone()) {
+                error!("account {} not restored from the initial state: {}", state.key.1, e);
+            }
+        }
 
-        debug!("created account {:?}", &account);
+        debug!("created account {:?} for tenant {:?}", state.ledger.account(), &state.key.0);
 
-        //local history of transactions made on this account
-        let mut transactions = HashMap::<TxId, Transaction>::new();
+        let mut queue = PriorityQueue::new(options.priority_window);
+        // set when `Shutdown` is received, the queued transactions are still processed
+        let mut closed = false;
+        // entries of the account counted in the gauges
+        let mut retained = 0;
 
-        // wait for incomming transactions, if None received we exit the loop
-        while let Some(Some(t)) = tx_reveiver.recv().await {
-            trace!("account {} processing {:?}", account.client_id, t);
-            let r = account.process_transaction(&t, &mut transactions);
-            match r {
-                Ok(a) => account = a,
-                Err(e) => {
-                    warn!("{:?}", e);
+        // wait for incomming transactions, if `Shutdown` received we exit the loop
+        loop {
+            if let Some(gauges) = &options.gauges {
+                retained = gauges.retained(retained, state.retained());
+            }
+            let config = options.config.borrow().clone();
+            if queue.is_empty() {
+                if closed {
+                    let applied = state.flush(&config);
+                    TxProcessor::report(&state, applied, &options, &run);
+                    break;
+                }
+                // held disputes are released when their time is up even if no other transaction arrives
+                let received = match state.hold_deadline(&config) {
+                    None => tx_reveiver.recv().await,
+                    Some(deadline) => match clock::timeout_at(options.clock.as_ref(), deadline, tx_reveiver.recv()).await {
+                        Some(received) => received,
+                        None => {
+                            let applied = state.expire(&config, options.clock.now());
+                            TxProcessor::report(&state, applied, &options, &run);
+                            continue;
+                        }
+                    },
+                };
+                match received {
+                    Some(AccountMessage::Transaction(t)) => TxProcessor::enqueue(&mut queue, t, &state.key, &options),
+                    Some(AccountMessage::Shutdown) | None => closed = true,
+                    Some(request) => TxProcessor::answer(&state, request),
                 }
             }
-            // store only Deposit and Withdrawal transactions for possible dispute/resolve/chargeback events
-            // for simplicity we assume that we receive only once given transaction
-            if t.tx_type == TxType::Deposit || t.tx_type == TxType::Withdrawal {
-                transactions.insert(t.tx_id, t);
+            // transactions already waiting in the channel compete for priority with the queued ones
+            while !closed && !queue.is_full() {
+                match tx_reveiver.try_recv() {
+                    Ok(AccountMessage::Transaction(t)) => TxProcessor::enqueue(&mut queue, t, &state.key, &options),
+                    Ok(AccountMessage::Shutdown) | Err(TryRecvError::Disconnected) => closed = true,
+                    Ok(request) => {
+                        // the request sees every transaction sent before it
+                        while let Some(t) = queue.pop() {
+                            TxProcessor::apply(&mut state, t, &config, &options, &run);
+                        }
+                        TxProcessor::answer(&state, request);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+            if let Some(t) = queue.pop() {
+                TxProcessor::apply(&mut state, t, &config, &options, &run);
             }
+        }
 
-            trace!("account state: {:?}", &account);
+        if let Some(gauges) = &options.gauges {
+            gauges.retained(retained, state.retained());
         }
+        debug!("exiting; final account state {:?}", state.ledger.account());
 
-        debug!("exiting; final account state {:?}", account);
+        TxProcessor::into_report(state, &options)
+    }
 
-        // print account data to stdout
-        println!(
-            "{},{:.4},{:.4},{:.4},{}",
-            account.client_id,
-            account.available_amount,
-            account.held_amount,
-            account.total_amount,
-            account.is_locked
-        );
+    // report of the account, with its final state when `state` option is set
+    fn into_report(state: AccountState, options: &ProcessorOptions) -> AccountReport {
+        let snapshot = options.state.then(|| state.snapshot());
+        AccountReport { state: snapshot, ..state.into_report() }
+    }
+
+    /// queues the transaction taken from the channel of the account
+    fn enqueue(queue: &mut PriorityQueue, t: Transaction, key: &AccountKey, options: &ProcessorOptions) {
+        if let Some(lag) = &options.lag {
+            lag.received(key);
+        }
+        queue.push(t);
+    }
+
+    /// submits the transaction to the account and reports the outcome
+    fn apply(state: &mut AccountState, t: Transaction, config: &Config, options: &ProcessorOptions, run: &RunState) {
+        let started = Instant::now();
+        let applied = state.submit(t, config, options.clock.now());
+        if let Some(timings) = &options.timings {
+            timings.applied(started.elapsed());
+        }
+        if let Some(lag) = &options.lag {
+            lag.applied(&state.key, started.elapsed());
+        }
+        TxProcessor::report(state, applied, options, run);
+    }
+
+    /// replies to `Flush` and `Snapshot` requests, nobody waiting for the reply is not an error
+    fn answer(state: &AccountState, request: AccountMessage) {
+        match request {
+            AccountMessage::Flush(reply) => {
+                let _ = reply.send(state.raw_account());
+            }
+            AccountMessage::Snapshot(reply) => {
+                let _ = reply.send(state.snapshot());
+            }
+            AccountMessage::Transaction(_) | AccountMessage::Shutdown => (),
+        }
     }
 }
@@ -0,0 +1,60 @@
+use std::{io::Cursor, path::Path};
+
+use calamine::{Data, Reader, Xlsx};
+
+use crate::{import::unix_time, Result};
+
+/// true when the file is `.xlsx` workbook, read with `CsvTransactionReader::read_xlsx`
+pub fn is_xlsx(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "xlsx")
+}
+
+/// Rows of the first sheet of the workbook as CSV fields, with their row number in the sheet (starting with 1).
+/// Empty rows are left out. Cells are written the way they would be in a CSV export of the sheet:
+/// - whole numbers without decimals, so `7` typed into a client cell is not read as `7.0`
+/// - other numbers rounded to 4 decimal places, as Excel keeps them as `f64` (`0.1 + 0.2` is `0.30000000000000004`)
+/// - dates and times as unix time (seconds) for the `timestamp` column, the sheet time is taken as UTC
+pub fn read_sheet(bytes: &[u8]) -> Result<Vec<(u64, Vec<String>)>> {
+    let mut workbook = Xlsx::new(Cursor::new(bytes))?;
+    let range = workbook.worksheet_range_at(0).ok_or("workbook has no sheet")??;
+    let first_row = range.start().map_or(0, |(row, _)| u64::from(row));
+    let mut rows = Vec::new();
+    for (i, cells) in range.rows().enumerate() {
+        if cells.iter().all(|c| *c == Data::Empty) {
+            continue;
+        }
+        let row = cells.iter().map(field).collect();
+        rows.push((first_row + i as u64 + 1, row));
+    }
+    Ok(rows)
+}
+
+fn field(cell: &Data) -> String {
+    match cell {
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) => number(*f),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.trim().to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(d) if d.is_datetime() => {
+            let (y, m, d, h, min, s, _) = d.to_ymd_hms_milli();
+            let seconds = u64::from(h) * 3600 + u64::from(min) * 60 + u64::from(s);
+            // dates before 1970 stay dates, the row is then malformed
+            match unix_time(y.into(), m.into(), d.into(), seconds) {
+                Some(timestamp) => timestamp.to_string(),
+                None => format!("{}-{:02}-{:02}", y, m, d),
+            }
+        }
+        Data::DateTime(d) => number(d.as_f64()),
+        // e.g. `#DIV/0!`, the row is then malformed
+        Data::Error(e) => e.to_string(),
+        Data::Empty => String::new(),
+    }
+}
+
+fn number(f: f64) -> String {
+    if f.fract() == 0.0 && f.abs() < 1e15 {
+        return format!("{}", f as i64);
+    }
+    let rounded = format!("{:.4}", f);
+    rounded.trim_end_matches('0').trim_end_matches('.').to_string()
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "arrow")]
+
+use std::io::Cursor;
+
+use arrow_array::{Array, BooleanArray, Float64Array, RecordBatch, StringArray, UInt16Array};
+use arrow_ipc::reader::FileReader;
+use tokio::sync::mpsc::channel;
+use txp::{
+    arrow::{self, ArrowTrace},
+    csv::RawAccount,
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    TxType,
+};
+
+fn read_batches(bytes: Vec<u8>) -> Vec<RecordBatch> {
+    FileReader::try_new(Cursor::new(bytes), None)
+        .expect("invalid Arrow IPC file")
+        .collect::<Result<_, _>>()
+        .expect("invalid record batch")
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> &'a T {
+    batch.column_by_name(name).expect("missing column").as_any().downcast_ref::<T>().expect("wrong column type")
+}
+
+#[test]
+fn accounts_are_one_batch_with_rounded_amounts() {
+    let account = |tenant: Option<&str>, client_id, available: f32, held: f32, is_locked| RawAccount {
+        tenant: tenant.map(String::from),
+        client_id,
+        available_amount: available,
+        held_amount: held,
+        total_amount: available + held,
+        is_locked,
+        is_closed: false,
+        last_activity: None,
+        is_dormant: false,
+        version: 0,
+    };
+    let accounts = vec![account(None, 1, 0.1, 2.0, false), account(Some("acme"), 1, 1.23456, 0.0, true)];
+    let mut w = Vec::new();
+    arrow::write_accounts(&mut w, &accounts, 4).expect("failed to write accounts");
+
+    let batches = read_batches(w);
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.schema(), arrow::accounts_schema());
+    let tenant = column::<StringArray>(batch, "tenant");
+    assert_eq!((tenant.is_null(0), tenant.value(1)), (true, "acme"));
+    assert_eq!(column::<UInt16Array>(batch, "client").values().to_vec(), vec![1, 1]);
+    // as written to the CSV output
+    assert_eq!(column::<Float64Array>(batch, "available").values().to_vec(), vec![0.1, 1.2346]);
+    assert_eq!(column::<Float64Array>(batch, "total").values().to_vec(), vec![2.1, 1.2346]);
+    assert_eq!(column::<BooleanArray>(batch, "locked").iter().collect::<Vec<_>>(), vec![Some(false), Some(true)]);
+    assert_eq!(column::<StringArray>(batch, "status").value(1), "locked");
+}
+
+#[tokio::test]
+async fn decision_trace_has_balances_of_processed_rows_only() {
+    let (tx_sender, tx_receiver) = channel(8);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    for t in [tx(TxType::Deposit, 1, 1, 10.0), tx(TxType::Withdrawal, 1, 2, 15.0)] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    TxProcessor::process(tx_receiver, ProcessorOptions { events: Some(events_sender), ..Default::default() }).await;
+
+    let mut w = Vec::new();
+    let mut trace = ArrowTrace::new(&mut w).expect("failed to create trace");
+    while let Some(e) = events_receiver.recv().await {
+        trace.decision(&e).expect("failed to write decision");
+    }
+    trace.skipped(&tx(TxType::Deposit, 2, 3, 1.0), "filtered").expect("failed to write skipped row");
+    trace.finish().expect("failed to finish trace");
+
+    let batches = read_batches(w);
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.schema(), arrow::decisions_schema());
+    let outcome = column::<StringArray>(batch, "outcome");
+    assert_eq!(outcome.iter().collect::<Vec<_>>(), vec![Some("applied"), Some("rejected"), Some("skipped")]);
+    let reason = column::<StringArray>(batch, "reason");
+    assert_eq!((reason.is_null(0), reason.is_null(1), reason.value(2)), (true, false, "filtered"));
+    let available = column::<Float64Array>(batch, "available");
+    assert_eq!(available.iter().collect::<Vec<_>>(), vec![Some(10.0), Some(10.0), None]);
+}
@@ -0,0 +1,109 @@
+use txp::{
+    audit::{self, AuditLog},
+    csv::RawAccount,
+    events::{Outcome, ProcessingEvent},
+    TxType,
+};
+
+fn event(tx_id: u32, amount: f32) -> ProcessingEvent {
+    ProcessingEvent {
+        line: Some(tx_id as u64 + 1),
+        tx_id,
+        tx_type: TxType::Deposit,
+        amount,
+        outcome: Outcome::Applied,
+        rejection: None,
+        error: None,
+        account: RawAccount {
+            tenant: None,
+            client_id: 1,
+            available_amount: amount,
+            held_amount: 0.0,
+            total_amount: amount,
+            is_locked: false,
+            is_closed: false,
+            last_activity: None,
+            is_dormant: false,
+            version: 0,
+        },
+        metadata: Default::default(),
+    }
+}
+
+#[test]
+fn audit_log_detects_tampering() {
+    let path = std::env::temp_dir().join(format!("txp_audit_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let key = b"secret".to_vec();
+
+    let mut log = AuditLog::open(&path, Some(key.clone()), None).expect("failed to open audit log");
+    log.append(&event(1, 10.0)).expect("failed to append");
+    log.append(&event(2, 5.5)).expect("failed to append");
+    log.flush().expect("failed to flush");
+    drop(log);
+
+    // reopened log continues the chain
+    let mut log = AuditLog::open(&path, Some(key.clone()), None).expect("failed to reopen audit log");
+    log.append(&event(3, 1.0)).expect("failed to append");
+    log.flush().expect("failed to flush");
+    drop(log);
+
+    assert_eq!(audit::verify(&path, None, None).expect("chain is broken"), 3);
+    assert_eq!(audit::verify(&path, Some(&key), None).expect("HMAC does not match"), 3);
+    assert!(audit::verify(&path, Some(b"other"), None).is_err());
+
+    let content = std::fs::read_to_string(&path).expect("failed to read audit log");
+    std::fs::write(&path, content.replacen("\"amount\":5.5", "\"amount\":55.0", 1)).expect("failed to write audit log");
+    let e = audit::verify(&path, None, None).expect_err("tampering not detected");
+    assert!(e.to_string().starts_with("line 2:"), "{}", e);
+
+    let lines: Vec<&str> = content.lines().collect();
+    std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).expect("failed to write audit log");
+    assert!(audit::verify(&path, None, None).is_err());
+    std::fs::remove_file(&path).expect("failed to remove audit log");
+}
+
+#[test]
+fn encrypted_audit_log_can_be_verified_with_the_key() {
+    use txp::crypto::EncryptionKey;
+
+    let path = std::env::temp_dir().join(format!("txp_audit_encrypted_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let key = EncryptionKey::parse(&"01".repeat(32)).expect("invalid key");
+
+    let mut log = AuditLog::open(&path, None, Some(key.clone())).expect("failed to open audit log");
+    log.append(&event(1, 10.0)).expect("failed to append");
+    log.flush().expect("failed to flush");
+    drop(log);
+    let mut log = AuditLog::open(&path, None, Some(key.clone())).expect("failed to reopen audit log");
+    log.append(&event(2, 5.5)).expect("failed to append");
+    log.flush().expect("failed to flush");
+    drop(log);
+
+    let content = std::fs::read_to_string(&path).expect("failed to read audit log");
+    assert!(!content.contains("deposit"));
+    assert_eq!(audit::verify(&path, None, Some(&key)).expect("chain is broken"), 2);
+    assert!(audit::verify(&path, None, None).is_err());
+    std::fs::remove_file(&path).expect("failed to remove audit log");
+}
+
+#[test]
+fn metadata_is_part_of_the_chained_entry() {
+    let path = std::env::temp_dir().join(format!("txp_audit_metadata_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut tagged = event(1, 10.0);
+    tagged.metadata = [("reference", "INV-7"), ("merchant", "Acme")].map(|(k, v)| (k.to_string(), v.to_string())).into();
+    let mut log = AuditLog::open(&path, None, None).expect("failed to open audit log");
+    log.append(&tagged).expect("failed to append");
+    log.append(&event(2, 5.5)).expect("failed to append");
+    log.flush().expect("failed to flush");
+    drop(log);
+
+    let content = std::fs::read_to_string(&path).expect("failed to read audit log");
+    assert!(content.contains("\"metadata\":{\"merchant\":\"Acme\",\"reference\":\"INV-7\"}"), "{}", content);
+    assert_eq!(audit::verify(&path, None, None).expect("chain is broken"), 2);
+    std::fs::write(&path, content.replacen("INV-7", "INV-8", 1)).expect("failed to write audit log");
+    assert!(audit::verify(&path, None, None).is_err());
+    std::fs::remove_file(&path).expect("failed to remove audit log");
+}
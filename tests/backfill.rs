@@ -0,0 +1,66 @@
+use txp::{
+    backfill::{self, Correction},
+    engine::{Engine, ADJUSTMENT_REASON},
+    history::HistoryRetention,
+    testing::tx,
+    AccountError, TxType,
+};
+
+#[tokio::test]
+async fn corrections_are_applied_to_snapshot() {
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Deposit, 1, 2, 5.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 2, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Chargeback, 1, 2, 0.0)).expect("chargeback failed");
+    engine.apply(tx(TxType::Deposit, 2, 3, 7.0)).expect("deposit failed");
+
+    let path = std::env::temp_dir().join(format!("txp_corrections_{}.csv", std::process::id()));
+    std::fs::write(&path, "type,client,tx,amount\nunlock,1,,\nreversal,1,1,\ndeposit,1,10,2.5\ndeposit,3,11,1.0\n")
+        .expect("failed to write corrections");
+    let corrections = backfill::read_corrections(&path).await.expect("failed to read corrections");
+    std::fs::remove_file(&path).expect("failed to remove corrections");
+    assert_eq!(corrections[0], (2, Correction::Unlock((None, 1))));
+    assert_eq!(corrections[1], (3, Correction::Reversal((None, 1), 1)));
+
+    let mut engine = Engine::from_snapshot(engine.snapshot(), HistoryRetention::None);
+    let before = engine.accounts();
+    assert!(backfill::apply(&mut engine, corrections).is_empty());
+
+    let mut out = Vec::new();
+    backfill::write_delta(&mut out, &before, &engine.accounts(), 2).expect("failed to write delta");
+    assert_eq!(
+        String::from_utf8(out).expect("invalid output"),
+        format!("{}\n,1,10.00,2.50,0.00,0.00,10.00,2.50,true,false\n,3,0.00,1.00,0.00,0.00,0.00,1.00,false,false\n", backfill::DELTA_HEADER)
+    );
+
+    // reversed transaction can not be reversed again
+    let rejected = backfill::apply(&mut engine, vec![(2, Correction::Reversal((None, 1), 1))]);
+    assert_eq!(rejected[0].error, AccountError::NoTxForDispute(1));
+}
+
+#[tokio::test]
+async fn adjustments_require_reason() {
+    let mut engine = Engine::new(HistoryRetention::Applied);
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+
+    let path = std::env::temp_dir().join(format!("txp_adjustments_{}.csv", std::process::id()));
+    std::fs::write(&path, "type,client,tx,amount,reason\nadjust,1,2,-2.5,duplicate payout\nadjust,1,3,1.0,\n")
+        .expect("failed to write corrections");
+    let corrections = backfill::read_corrections(&path).await.expect("failed to read corrections");
+    std::fs::remove_file(&path).expect("failed to remove corrections");
+    assert_eq!(
+        corrections[0],
+        (2, Correction::Adjustment { key: (None, 1), tx_id: 2, amount: -2.5, reason: "duplicate payout".to_string() })
+    );
+
+    let rejected = backfill::apply(&mut engine, corrections);
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].error, AccountError::MissingReason(3));
+    assert_eq!(engine.account(&(None, 1)).map(|a| a.available_amount), Some(7.5));
+
+    let reports = engine.into_reports();
+    let adjustment = &reports[0].history[1].transaction;
+    assert_eq!(adjustment.tx_type, TxType::Withdrawal);
+    assert_eq!(adjustment.metadata.get(ADJUSTMENT_REASON).map(String::as_str), Some("duplicate payout"));
+}
@@ -0,0 +1,35 @@
+use txp::{
+    bisect::{self, BisectResult},
+    config::Config,
+    testing,
+    Transaction, TxType,
+};
+
+fn tx(tx_type: TxType, tx_id: u32, amount: f32) -> Transaction {
+    Transaction { line: Some(tx_id as u64 + 1), ..testing::tx(tx_type, 1, tx_id, amount) }
+}
+
+#[test]
+fn bisect_finds_row_rejected_by_the_engine() {
+    let rows = vec![
+        tx(TxType::Deposit, 1, 10.0),
+        tx(TxType::Deposit, 2, 5.0),
+        tx(TxType::Withdrawal, 3, 40.0),
+        tx(TxType::Deposit, 4, 2.0),
+        tx(TxType::Dispute, 1, 0.0),
+        tx(TxType::Chargeback, 1, 0.0),
+    ];
+    let config = Config::default();
+
+    // the partner applied the withdrawal which the engine rejected
+    match bisect::bisect(&rows, -33.0, &config) {
+        BisectResult::Diverges(d) => {
+            assert_eq!((d.index, d.transaction.tx_id), (2, 3));
+            assert_eq!((d.total_before, d.total_after), (15.0, 15.0));
+            assert!(d.error.is_some());
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(bisect::bisect(&rows, 7.0, &config), BisectResult::Matches);
+    assert_eq!(bisect::bisect(&rows, 1.0, &config), BisectResult::InputDiverges { ledger_total: -33.0 });
+}
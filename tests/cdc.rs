@@ -0,0 +1,54 @@
+use tokio::sync::mpsc::channel;
+use txp::{
+    cdc::{Balances, ChangeRecord, ChangeStream},
+    engine::Engine,
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    TxType,
+};
+
+#[tokio::test]
+async fn applied_transactions_are_streamed_with_balances_before_and_after() {
+    // client 1 continues from the initial state, client 2 is created by the run
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    let initial = engine.snapshot().into_accounts();
+
+    let (tx_sender, tx_receiver) = channel(8);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    // the withdrawal of client 2 is rejected and not streamed
+    for t in [tx(TxType::Dispute, 1, 1, 0.0), tx(TxType::Deposit, 2, 2, 3.0), tx(TxType::Withdrawal, 2, 3, 5.0)] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { events: Some(events_sender), initial: initial.clone(), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let mut events = Vec::new();
+    while let Some(e) = events_receiver.recv().await {
+        events.push(e);
+    }
+    // accounts are processed in parallel, order events as they were read
+    events.sort_by_key(|e| e.tx_id);
+    let mut w = Vec::new();
+    let mut stream = ChangeStream::new(&mut w).with_initial(&initial);
+    for e in &events {
+        stream.record(e).expect("failed to record event");
+    }
+    stream.flush().expect("failed to flush");
+    drop(stream);
+
+    let records: Vec<ChangeRecord> = String::from_utf8(w)
+        .expect("invalid stream")
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("invalid record"))
+        .collect();
+    let balances = |available, held, total| Balances { available, held, total, locked: false };
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].offset, records[0].op.as_str(), records[0].tx, records[0].client), (0, "dispute", 1, 1));
+    assert_eq!((records[0].before, records[0].after), (Some(balances(10.0, 0.0, 10.0)), balances(0.0, 10.0, 10.0)));
+    assert_eq!((records[1].offset, records[1].op.as_str(), records[1].tx, records[1].client), (1, "deposit", 2, 2));
+    assert_eq!((records[1].before, records[1].after), (None, balances(3.0, 0.0, 3.0)));
+    // client 1 continues from the version of the initial state
+    assert_eq!((records[0].version, records[1].version), (2, 1));
+}
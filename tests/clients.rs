@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use txp::{clients::{ClientDirectory, ClientOverrides, ClientSample, ClientSet}, config::Config, engine::Engine, output, testing::tx, Transaction, TxType};
+
+#[tokio::test]
+async fn load_client_overrides_from_csv() {
+    let clients = ClientOverrides::load(Path::new("tests/client_overrides.csv")).await.expect("failed to load");
+    assert_eq!(clients.len(), 4);
+
+    assert_eq!(clients.get(&(None, 1)).credit_limit, Some(50.0));
+    assert!(clients.get(&(None, 2)).is_frozen());
+    assert!(clients.get(&(None, 3)).is_vip());
+    assert_eq!(clients.get(&(Some("acme".to_string()), 4)).max_amount(Some(100.0)), Some(10.0));
+    // client without overrides gets the defaults
+    assert_eq!(clients.get(&(None, 4)).max_amount(Some(100.0)), Some(100.0));
+}
+
+#[tokio::test]
+async fn engine_applies_client_overrides() {
+    let clients = ClientOverrides::load(Path::new("tests/client_overrides.csv")).await.expect("failed to load");
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[limits]\nmax_amount = 100.0\n").expect("failed to parse"));
+    engine.set_client_overrides(clients);
+
+    // credit limit allows withdrawal below zero
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    let a = engine.apply(tx(TxType::Withdrawal, 1, 2, 60.0)).expect("withdrawal failed");
+    assert_eq!(a.available_amount, -50.0);
+    assert!(engine.apply(tx(TxType::Withdrawal, 1, 3, 1.0)).is_err());
+
+    // frozen account rejects everything
+    assert!(engine.apply(tx(TxType::Deposit, 2, 4, 10.0)).is_err());
+
+    // vip is exempt from the global limit
+    let a = engine.apply(tx(TxType::Deposit, 3, 5, 1000.0)).expect("deposit failed");
+    assert_eq!(a.total_amount, 1000.0);
+    assert!(engine.apply(tx(TxType::Deposit, 5, 6, 1000.0)).is_err());
+}
+
+#[tokio::test]
+async fn frozen_list_accounts_start_locked() {
+    let mut clients = ClientOverrides::load(Path::new("tests/client_overrides.csv")).await.expect("failed to load");
+    let frozen = ClientOverrides::load_frozen_list(Path::new("tests/frozen_list.csv")).await.expect("failed to load");
+    assert_eq!(frozen, vec![(None, 2), (Some("acme".to_string()), 7)]);
+    clients.freeze(frozen);
+    assert_eq!(clients.len(), 5);
+
+    let mut engine = Engine::default();
+    engine.set_client_overrides(clients);
+    match engine.apply(tx(TxType::Deposit, 2, 1, 10.0)) {
+        Err(e) => assert_eq!(e.kind(), "pre_frozen"),
+        Ok(a) => panic!("deposit to frozen account applied: {:?}", a),
+    }
+    let t = Transaction { tenant: Some("acme".to_string()), ..tx(TxType::Deposit, 7, 2, 10.0) };
+    assert!(engine.apply(t).is_err());
+    // other tenant is not affected
+    assert!(engine.apply(tx(TxType::Deposit, 7, 3, 10.0)).is_ok());
+}
+
+#[test]
+fn parse_client_set() {
+    let clients: ClientSet = "7, 42,100-200".parse().expect("failed to parse");
+    assert!(clients.contains(7));
+    assert!(clients.contains(42));
+    assert!(clients.contains(100));
+    assert!(clients.contains(200));
+    assert!(!clients.contains(8));
+    assert!(!clients.contains(201));
+    assert_eq!(clients.to_string(), "7,42,100-200");
+
+    assert!("7,x".parse::<ClientSet>().is_err());
+    assert!("200-100".parse::<ClientSet>().is_err());
+}
+
+#[test]
+fn client_sample_is_deterministic() {
+    let sample = ClientSample { rate: 0.1, seed: 7 };
+    let sampled: Vec<_> = (0..10_000).filter(|c| sample.contains(&(None, *c))).collect();
+    assert!((800..1200).contains(&sampled.len()), "sampled {} clients", sampled.len());
+
+    let again: Vec<_> = (0..10_000).filter(|c| sample.contains(&(None, *c))).collect();
+    assert_eq!(sampled, again);
+
+    let other: Vec<_> = (0..10_000).filter(|c| ClientSample { seed: 8, ..sample }.contains(&(None, *c))).collect();
+    assert_ne!(sampled, other);
+    assert!((0..100).all(|c| ClientSample { rate: 1.0, seed: 7 }.contains(&(None, c))));
+}
+
+#[tokio::test]
+async fn client_details_are_joined_into_accounts() {
+    let directory = ClientDirectory::load(Path::new("tests/clients_meta.csv")).await.expect("failed to load");
+    assert_eq!(directory.len(), 3);
+    assert_eq!(directory.get(&(Some("acme".to_string()), 2)).map(|c| c.name.as_str()), Some("Bob Acme"));
+    // rows without tenant apply to the client in every tenant
+    assert_eq!(directory.get(&(Some("other".to_string()), 1)).map(|c| c.name.as_str()), Some("Alice"));
+    assert_eq!(directory.columns(&(None, 2)), ",\"Bob, Ltd\",business,DE");
+    assert_eq!(directory.columns(&(None, 3)), ",,,");
+    assert_eq!(ClientDirectory::default().columns(&(None, 1)), "");
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 1.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Deposit, 3, 2, 2.0)).expect("deposit failed");
+    let mut out = Vec::new();
+    output::write_accounts_with_clients(&mut out, &engine.accounts(), 4, &directory).expect("failed to write accounts");
+    let out = String::from_utf8(out).expect("invalid output");
+    let mut lines = out.lines();
+    assert_eq!(lines.next().map(|h| h.ends_with(",name,segment,country")), Some(true), "{}", out);
+    let mut rows: Vec<_> = lines.collect();
+    rows.sort();
+    assert!(rows[0].starts_with("1,") && rows[0].ends_with(",Alice,retail,PL"), "{}", out);
+    assert!(rows[1].starts_with("3,") && rows[1].ends_with(",,,"), "{}", out);
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use txp::config::{Config, ConfigWatcher};
+
+#[test]
+fn parse_config() {
+    let config = Config::parse("version = \"2022-01\"\n[dispute_retry]\ncapacity = 10\n").expect("failed to parse");
+    assert_eq!(config.version.as_deref(), Some("2022-01"));
+    assert_eq!(config.dispute_retry.map(|r| r.capacity), Some(10));
+
+    assert_eq!(Config::parse("").expect("failed to parse empty config"), Config::default());
+    assert!(Config::parse("[limits]\nunknown = 1\n").is_err());
+}
+
+#[tokio::test]
+async fn reload_modified_config() {
+    let path = std::env::temp_dir().join(format!("txp_reload_modified_config_{}.toml", std::process::id()));
+    std::fs::write(&path, "version = \"1\"\n").expect("failed to write config");
+
+    let mut config = ConfigWatcher::spawn(path.clone(), Duration::from_millis(10)).await.expect("failed to load");
+    assert_eq!(config.borrow().version.as_deref(), Some("1"));
+
+    // make sure modification time changes even on file systems with coarse timestamps
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    std::fs::write(&path, "version = \"2\"\n").expect("failed to write config");
+
+    tokio::time::timeout(Duration::from_secs(5), config.changed()).await.expect("config not reloaded").expect("watcher stopped");
+    assert_eq!(config.borrow().version.as_deref(), Some("2"));
+
+    std::fs::remove_file(path).expect("failed to remove config");
+}
@@ -1,6 +1,10 @@
 use std::path::PathBuf;
 
-use txp::{csv::{CsvTransactionReader, RawTransaction}, Transaction};
+use txp::{
+    csv::{CsvTransactionReader, ParseError, ParseErrorKind, RawTransaction},
+    output::{self, ErrorRecord},
+    Transaction, TxType,
+};
 
 /// basic test to check if working
 #[tokio::test]
@@ -84,4 +88,195 @@ async fn dummy_read(data_file_path: PathBuf)
         Ok(())
     };
     let _reader = CsvTransactionReader::process_data_file(data_file_path, raw_transaction_handler).await;
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn read_archive_entries_in_lexical_order() {
+    // both archives store c.csv, b.csv and a.csv in this order
+    for archive in ["tests/transactions.zip", "tests/transactions.tar"] {
+        let tx_ids = std::sync::Mutex::new(Vec::new());
+        let raw_transaction_handler = |rt: Option<RawTransaction>| {
+            if let Some(rt) = rt {
+                tx_ids.lock().unwrap().push(rt.tx_id);
+            }
+            async { Ok(()) }
+        };
+        CsvTransactionReader::process_archive(PathBuf::from(archive), raw_transaction_handler).await;
+
+        assert_eq!(tx_ids.into_inner().unwrap(), vec![1, 2, 3, 4, 9], "{}", archive);
+    }
+}
+
+#[test]
+fn parse_tx_type() {
+    assert_eq!("chargeback".parse::<TxType>(), Ok(TxType::Chargeback));
+    assert_eq!(" Deposit".parse::<TxType>(), Ok(TxType::Deposit));
+    assert!("transfer".parse::<TxType>().is_err());
+}
+
+#[tokio::test]
+async fn malformed_row_is_quoted_in_parse_error() {
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let reader = CsvTransactionReader::with_error_handler(std::sync::Arc::new(move |e: &ParseError| {
+        reported.lock().unwrap().push(e.clone())
+    }));
+    let input: &[u8] = b"type,client,tx,amount\ntransfer,1,1,1.0\n";
+
+    let read = tokio::spawn(async move { reader.read(input, |_: Option<RawTransaction>| async { Ok(()) }).await });
+    assert!(read.await.is_err(), "reader should stop on the first error");
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].kind, errors[0].line, errors[0].byte), (ParseErrorKind::Malformed, Some(2), Some(22)));
+    assert_eq!(errors[0].row.as_deref(), Some("transfer,1,1,1.0"));
+}
+
+#[tokio::test]
+async fn bad_lines_are_skipped_up_to_max_errors() {
+    let input: &[u8] = b"type,client,tx,amount\ndeposit,1,1,1.0\ntransfer,1,2,1.0\ndeposit,1,3,-3\ndeposit,2,4,1.0\n";
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let reader = CsvTransactionReader::with_error_handler(std::sync::Arc::new(move |e: &ParseError| {
+        reported.lock().unwrap().push(e.kind)
+    }))
+    .skip_bad_lines(2);
+
+    let tx_ids = std::sync::Mutex::new(Vec::new());
+    reader
+        .read(input, |t: Option<RawTransaction>| {
+            tx_ids.lock().unwrap().extend(t.map(|t| t.tx_id));
+            async { Ok(()) }
+        })
+        .await;
+    assert_eq!(tx_ids.into_inner().unwrap(), vec![1, 4]);
+    assert_eq!(reader.skipped(), 2);
+    assert_eq!(*errors.lock().unwrap(), vec![ParseErrorKind::Malformed, ParseErrorKind::InvalidAmount]);
+    let by_code: Vec<_> = reader.skipped_by_code().into_iter().collect();
+    assert_eq!(by_code, [("E2001".to_string(), 1), ("E2002".to_string(), 1)]);
+
+    // one more than allowed stops the reader
+    let reader = CsvTransactionReader::default().skip_bad_lines(1);
+    let read = tokio::spawn(async move { reader.read(input, |_: Option<RawTransaction>| async { Ok(()) }).await });
+    assert!(read.await.is_err(), "reader should stop over the threshold");
+}
+
+#[tokio::test]
+async fn header_mismatch_is_reported_before_rows() {
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let reader = CsvTransactionReader::with_error_handler(std::sync::Arc::new(move |e: &ParseError| {
+        reported.lock().unwrap().push(e.clone())
+    }))
+    .skip_bad_lines(10);
+    let input: &[u8] = b"kind,customer,tx,amount\ndeposit,1,1,1.0\n";
+
+    let read = tokio::spawn(async move { reader.read(input, |_: Option<RawTransaction>| async { Ok(()) }).await });
+    assert!(read.await.is_err(), "reader should stop on the header even when skipping bad lines");
+
+    let errors = errors.lock().unwrap().clone();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].kind, errors[0].line, errors[0].code()), (ParseErrorKind::SchemaMismatch, Some(1), "E2004"));
+    assert!(errors[0].message.contains("missing `type`, `client`"), "{}", errors[0].message);
+    assert!(errors[0].message.contains("`--columns type=kind,client=customer`"), "{}", errors[0].message);
+
+    // mapped columns are read as the expected ones
+    let reader = CsvTransactionReader::default()
+        .with_columns(vec![("type".to_string(), "kind".to_string()), ("client".to_string(), "customer".to_string())]);
+    let clients = std::sync::Mutex::new(Vec::new());
+    reader
+        .read(input, |t: Option<RawTransaction>| {
+            clients.lock().unwrap().extend(t.map(|t| t.client_id));
+            async { Ok(()) }
+        })
+        .await;
+    assert_eq!(clients.into_inner().unwrap(), vec![1]);
+}
+
+#[tokio::test]
+async fn parse_errors_are_reported_with_line() {
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let reader = CsvTransactionReader::with_error_handler(std::sync::Arc::new(move |e: &ParseError| {
+        reported.lock().unwrap().push(e.clone())
+    }));
+    let input: &[u8] = b"type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,-3\n";
+
+    let read = tokio::spawn(async move { reader.read(input, |_: Option<RawTransaction>| async { Ok(()) }).await });
+    assert!(read.await.is_err(), "reader should stop on the first error");
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(
+        *errors,
+        vec![ParseError {
+            kind: ParseErrorKind::InvalidAmount,
+            line: Some(3),
+            byte: Some(38),
+            row: Some("deposit,1,2,-3".to_string()),
+            client_id: Some(1),
+            tx_id: Some(2),
+            message: "amount '-3' < 0.0".to_string()
+        }]
+    );
+    assert_eq!(errors[0].to_string(), "E2002 line 3 (byte 38): amount '-3' < 0.0, row 'deposit,1,2,-3'");
+    let mut json = Vec::new();
+    output::write_error_json(&mut json, &ErrorRecord::from(&errors[0])).expect("failed to write error");
+    assert_eq!(
+        String::from_utf8(json).expect("invalid json"),
+        "{\"line\":3,\"byte\":38,\"row\":\"deposit,1,2,-3\",\"client\":1,\"tx\":2,\"code\":\"E2002\",\"message\":\"amount '-3' < 0.0\"}\n"
+    );
+}
+
+#[tokio::test]
+async fn unknown_columns_are_kept_as_metadata_when_asked_to() {
+    let input: &[u8] = b"type,client,tx,amount,merchant,reference\ndeposit,1,1,1.0,\"Acme, Inc.\",INV-7\n";
+    let read = |reader: CsvTransactionReader| async move {
+        let metadata = std::sync::Mutex::new(Vec::new());
+        reader
+            .read(input, |t: Option<RawTransaction>| {
+                metadata.lock().unwrap().extend(t.map(|t| Transaction::from(t).metadata));
+                async { Ok(()) }
+            })
+            .await;
+        metadata.into_inner().unwrap()
+    };
+
+    let kept = read(CsvTransactionReader::default().keep_metadata()).await;
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].get("merchant").map(String::as_str), Some("Acme, Inc."));
+    assert_eq!(kept[0].get("reference").map(String::as_str), Some("INV-7"));
+    assert_eq!(kept[0].len(), 2);
+
+    assert!(read(CsvTransactionReader::default()).await[0].is_empty());
+}
+
+#[tokio::test]
+async fn drained_input_ends_after_current_row() {
+    use std::sync::{Arc, Mutex};
+    use tokio::{io::AsyncWriteExt, sync::watch};
+
+    let (mut writer, input) = tokio::io::duplex(1024);
+    let (drain, receiver) = watch::channel(false);
+    let rows = Arc::new(Mutex::new(Vec::new()));
+    let read = rows.clone();
+    let reader = tokio::spawn(async move {
+        CsvTransactionReader::default()
+            .drain_on(receiver)
+            .read(input, |t: Option<RawTransaction>| {
+                if let Some(t) = t {
+                    read.lock().unwrap().push(t.tx_id);
+                }
+                async { Ok(()) }
+            })
+            .await
+    });
+    writer.write_all(b"type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,").await.expect("write failed");
+    while rows.lock().unwrap().is_empty() {
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+
+    // the row being read is completed, the input ends after it although it is still open
+    drain.send_replace(true);
+    writer.write_all(b"2.0\n").await.expect("write failed");
+    tokio::time::timeout(std::time::Duration::from_secs(5), reader).await.expect("input not drained").expect("reader failed");
+    assert_eq!(*rows.lock().unwrap(), vec![1, 2]);
+}
@@ -0,0 +1,314 @@
+use txp::{engine::Engine, history::HistoryRetention, testing, Transaction, TxType};
+
+fn tx(tx_type: TxType, tx_id: u32, amount: f32) -> Transaction {
+    testing::tx(tx_type, 1, tx_id, amount)
+}
+
+#[test]
+fn engine_applies_transactions_one_by_one() {
+    let mut engine = Engine::default();
+
+    let a = engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    assert_eq!(a.available_amount, 10.0);
+
+    assert!(engine.apply(tx(TxType::Withdrawal, 2, 20.0)).is_err());
+
+    let a = engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed");
+    assert_eq!((a.available_amount, a.held_amount, a.total_amount), (0.0, 10.0, 10.0));
+}
+
+#[test]
+fn engine_continues_from_snapshot() {
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed");
+
+    let mut restored = Engine::from_snapshot(engine.snapshot(), HistoryRetention::None);
+    assert_eq!(restored.accounts(), engine.accounts());
+    assert_eq!(restored.max_tx_id(), Some(1));
+
+    // dispute state of the transaction survives the snapshot
+    let a = restored.apply(tx(TxType::Chargeback, 1, 0.0)).expect("chargeback failed");
+    assert_eq!((a.held_amount, a.total_amount, a.is_locked), (0.0, 0.0, true));
+}
+
+#[test]
+fn account_version_counts_applied_transactions() {
+    let mut engine = Engine::default();
+    assert_eq!(engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed").version, 1);
+    assert!(engine.apply(tx(TxType::Withdrawal, 2, 20.0)).is_err());
+    assert_eq!(engine.account(&(None, 1)).expect("missing account").version, 1);
+    assert_eq!(engine.apply(tx(TxType::Deposit, 3, 1.0)).expect("deposit failed").version, 2);
+    // corrections change the state too
+    assert_eq!(engine.reverse(&(None, 1), 3).expect("reversal failed").version, 3);
+
+    let mut restored = Engine::from_snapshot(engine.snapshot(), HistoryRetention::None);
+    assert_eq!(restored.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed").version, 4);
+}
+
+#[test]
+fn withdrawals_wait_for_settlement() {
+    use txp::{config::Config, AccountError};
+
+    let at = |tx_type, tx_id, amount, timestamp| Transaction { timestamp: Some(timestamp), ..tx(tx_type, tx_id, amount) };
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[settlement]\ntimeout = 3600\n").expect("failed to parse"));
+
+    engine.apply(at(TxType::Deposit, 1, 100.0, 0)).expect("deposit failed");
+    let a = engine.apply(at(TxType::Withdrawal, 2, 30.0, 10)).expect("withdrawal failed");
+    assert_eq!((a.available_amount, a.held_amount, a.total_amount), (70.0, 0.0, 100.0));
+    assert_eq!(engine.apply(tx(TxType::Dispute, 2, 0.0)), Err(AccountError::TxPending(2)));
+    assert_eq!(engine.apply(tx(TxType::Settle, 1, 0.0)), Err(AccountError::TxNotPending(1)));
+    assert_eq!(engine.reverse(&(None, 1), 2), Err(AccountError::TxPending(2)));
+
+    // pending withdrawals survive the snapshot
+    let mut engine = Engine::from_snapshot(engine.snapshot(), HistoryRetention::None);
+    engine.set_config(Config::parse("[settlement]\ntimeout = 3600\n").expect("failed to parse"));
+    let a = engine.apply(tx(TxType::Settle, 2, 0.0)).expect("settle failed");
+    assert_eq!((a.available_amount, a.total_amount), (70.0, 70.0));
+    assert_eq!(engine.apply(tx(TxType::Settle, 2, 0.0)), Err(AccountError::TxNotPending(2)));
+
+    // withdrawal without settle is settled by the first transaction after the timeout
+    engine.apply(at(TxType::Withdrawal, 3, 20.0, 100)).expect("withdrawal failed");
+    let a = engine.apply(at(TxType::Deposit, 4, 1.0, 100 + 3600)).expect("deposit failed");
+    assert_eq!((a.available_amount, a.total_amount), (51.0, 51.0));
+}
+
+#[test]
+fn withdrawals_are_refunded_in_parts() {
+    use txp::AccountError;
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 100.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Withdrawal, 2, 30.0)).expect("withdrawal failed");
+
+    let a = engine.apply(tx(TxType::Refund, 2, 10.0)).expect("refund failed");
+    assert_eq!((a.available_amount, a.total_amount), (80.0, 80.0));
+    assert_eq!(engine.apply(tx(TxType::Refund, 2, 25.0)), Err(AccountError::RefundOverWithdrawal(2, 25.0, 20.0)));
+    assert_eq!(engine.apply(tx(TxType::Refund, 1, 5.0)), Err(AccountError::NotRefundable(1)));
+    assert_eq!(engine.apply(tx(TxType::Refund, 3, 5.0)), Err(AccountError::NoTxForDispute(3)));
+    assert_eq!(engine.apply(tx(TxType::Dispute, 2, 0.0)), Err(AccountError::TxRefunded(2)));
+
+    // refunded amounts survive the snapshot, refund without amount returns the rest
+    let mut engine = Engine::from_snapshot(engine.snapshot(), HistoryRetention::None);
+    let a = engine.apply(tx(TxType::Refund, 2, 0.0)).expect("refund failed");
+    assert_eq!(a.available_amount, 100.0);
+    assert_eq!(engine.apply(tx(TxType::Refund, 2, 0.0)), Err(AccountError::RefundOverWithdrawal(2, 0.0, 0.0)));
+}
+
+#[test]
+fn history_shows_dispute_state_of_referenced_transaction() {
+    use txp::history::{self, HistoryFormat};
+
+    let mut engine = Engine::new(HistoryRetention::All);
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed");
+    assert!(engine.apply(tx(TxType::Dispute, 1, 0.0)).is_err());
+    engine.apply(tx(TxType::Resolve, 1, 0.0)).expect("resolve failed");
+
+    let mut out = Vec::new();
+    history::write_history(&mut out, HistoryFormat::Csv, &engine.into_reports()).expect("failed to write history");
+    let out = String::from_utf8(out).expect("invalid history");
+    let dispute: Vec<_> = out.lines().skip(1).map(|l| l.rsplit(',').next().unwrap_or_default()).collect();
+    assert_eq!(dispute, vec!["", "open", "open", "resolved"]);
+}
+
+#[test]
+fn repeated_chargeback_is_ignored_when_configured() {
+    use txp::{config::Config, summary::RunSummary};
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Chargeback, 1, 0.0)).expect("chargeback failed");
+    assert!(engine.apply(tx(TxType::Chargeback, 1, 0.0)).is_err());
+
+    engine.set_config(Config::parse("repeated_dispute = \"ignore\"\n").expect("failed to parse"));
+    let a = engine.apply(tx(TxType::Chargeback, 1, 0.0)).expect("chargeback not ignored");
+    assert_eq!((a.held_amount, a.total_amount, a.is_locked), (0.0, 0.0, true));
+    engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute not ignored");
+
+    let summary = RunSummary::from_reports(&engine.into_reports());
+    assert_eq!((summary.applied, summary.repeated_disputes, summary.duplicates, summary.rejected_total()), (3, 2, 0, 1));
+}
+
+#[test]
+fn zero_amounts_are_handled_by_policy() {
+    use txp::{config::Config, summary::RunSummary, AccountError};
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 0.0)).expect("zero deposit not allowed");
+
+    engine.set_config(Config::parse("zero_amount = \"skip\"\n").expect("failed to parse"));
+    engine.apply(tx(TxType::Deposit, 2, 0.0)).expect("zero deposit not skipped");
+    // skipped deposit is not recorded, so it can not be disputed
+    assert_eq!(engine.apply(tx(TxType::Dispute, 2, 0.0)), Err(AccountError::NoTxForDispute(2)));
+
+    engine.set_config(Config::parse("zero_amount = \"reject\"\n").expect("failed to parse"));
+    assert_eq!(engine.apply(tx(TxType::Withdrawal, 3, 0.0)), Err(AccountError::ZeroAmount(3)));
+    engine.apply(tx(TxType::Deposit, 4, 1.0)).expect("deposit failed");
+
+    let summary = RunSummary::from_reports(&engine.into_reports());
+    assert_eq!((summary.applied, summary.zero_amounts), (2, 1));
+    assert_eq!(summary.rejected.get("zero_amount"), Some(&1));
+}
+
+#[test]
+fn account_is_quarantined_after_too_many_anomalies() {
+    use txp::{config::Config, quarantine::Quarantined, AccountError};
+
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[quarantine]\nmax_anomalies = 2\n").expect("failed to parse"));
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    assert!(engine.apply(tx(TxType::Withdrawal, 2, 20.0)).is_err());
+    assert!(engine.apply(tx(TxType::Resolve, 1, 0.0)).is_err());
+    engine.apply(tx(TxType::Deposit, 3, 1.0)).expect("deposit failed");
+    assert!(engine.apply(tx(TxType::Dispute, 4, 0.0)).is_err());
+    assert_eq!(engine.apply(tx(TxType::Deposit, 5, 1.0)), Err(AccountError::Quarantined(1)));
+
+    let reports = engine.into_reports();
+    assert_eq!(reports[0].quarantined, Some(Quarantined { tx_id: 4, tx_type: TxType::Dispute, anomalies: 3 }));
+    assert_eq!(reports[0].account.available_amount, 11.0);
+}
+
+#[test]
+fn encrypted_snapshot_is_decrypted_on_load() {
+    use txp::{crypto::EncryptionKey, snapshot::Snapshot};
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    let key = EncryptionKey::parse(&"ab".repeat(32)).expect("invalid key");
+    let path = std::env::temp_dir().join(format!("txp_snapshot_{}.bin", std::process::id()));
+    engine.snapshot().save(&path, Some(&key)).expect("failed to save snapshot");
+
+    let content = std::fs::read(&path).expect("failed to read snapshot");
+    assert!(txp::crypto::is_encrypted(&content));
+    assert!(!String::from_utf8_lossy(&content).contains("available_amount"));
+
+    assert_eq!(Snapshot::load(&path, Some(&key)).expect("failed to load snapshot"), engine.snapshot());
+    assert!(Snapshot::load(&path, None).is_err());
+    assert!(Snapshot::load(&path, Some(&EncryptionKey::parse(&"cd".repeat(32)).expect("invalid key"))).is_err());
+    std::fs::remove_file(&path).expect("failed to remove snapshot");
+}
+
+#[test]
+fn inconsistent_snapshot_is_refused_or_repaired() {
+    use txp::snapshot::{Inconsistency, Snapshot};
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    let mut snapshot = engine.snapshot();
+    assert!(snapshot.verify().is_empty());
+    snapshot.accounts[0].total_amount = 12.0;
+    snapshot.accounts.push(engine.snapshot().accounts[0].clone());
+    let path = std::env::temp_dir().join(format!("txp_inconsistent_{}.json", std::process::id()));
+    snapshot.save(&path, None).expect("failed to save snapshot");
+
+    assert!(Snapshot::load(&path, None).is_err());
+    let (repaired, inconsistencies) = Snapshot::load_repaired(&path, None).expect("failed to load snapshot");
+    std::fs::remove_file(&path).expect("failed to remove snapshot");
+    assert_eq!(
+        inconsistencies,
+        vec![Inconsistency::Total { key: (None, 1), total: 12.0, expected: 10.0 }, Inconsistency::Duplicate((None, 1))]
+    );
+    assert_eq!(repaired, engine.snapshot());
+}
+
+#[test]
+fn snapshot_of_older_version_is_migrated() {
+    use txp::snapshot::{migrate, Snapshot, SNAPSHOT_VERSION};
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 0.0)).expect("dispute failed");
+    let path = std::env::temp_dir().join(format!("txp_legacy_{}.json", std::process::id()));
+    engine.snapshot().save(&path, None).expect("failed to save snapshot");
+
+    // snapshots written before the format version had `in_dispute` flag
+    let mut legacy: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).expect("failed to read snapshot"))
+        .expect("invalid snapshot");
+    assert_eq!(legacy["version"], SNAPSHOT_VERSION);
+    legacy.as_object_mut().expect("not an object").remove("version");
+    let t = legacy["accounts"][0]["transactions"][0].as_object_mut().expect("not an object");
+    t.remove("dispute");
+    t.insert("in_dispute".to_string(), true.into());
+    std::fs::write(&path, serde_json::to_vec(&legacy).expect("failed to serialize")).expect("failed to write snapshot");
+
+    let snapshot = Snapshot::load(&path, None).expect("failed to load snapshot");
+    std::fs::remove_file(&path).expect("failed to remove snapshot");
+    assert_eq!(snapshot, engine.snapshot());
+    let mut restored = Engine::from_snapshot(snapshot, HistoryRetention::None);
+    let a = restored.apply(tx(TxType::Resolve, 1, 0.0)).expect("resolve failed");
+    assert_eq!((a.available_amount, a.held_amount), (10.0, 0.0));
+
+    let mut newer = serde_json::json!({ "version": SNAPSHOT_VERSION + 1, "accounts": [] });
+    assert!(migrate(&mut newer).is_err());
+}
+
+#[test]
+fn engine_enforces_rolling_daily_limits() {
+    use txp::{config::Config, AccountError};
+
+    let at = |tx_type, tx_id, amount, timestamp| Transaction { timestamp: Some(timestamp), ..tx(tx_type, tx_id, amount) };
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[limits]\ndaily_withdrawal = 100.0\n").expect("failed to parse"));
+
+    engine.apply(at(TxType::Deposit, 1, 1000.0, 0)).expect("deposit failed");
+    engine.apply(at(TxType::Withdrawal, 2, 60.0, 3600)).expect("withdrawal failed");
+    // rejection carries the limit and the total the withdrawal would bring
+    assert_eq!(
+        engine.apply(at(TxType::Withdrawal, 3, 60.0, 7200)),
+        Err(AccountError::DailyLimitExceeded(3, 100.0, 120.0))
+    );
+    // rows without timestamp are not subject to daily limits
+    engine.apply(tx(TxType::Withdrawal, 4, 60.0)).expect("withdrawal failed");
+
+    // first withdrawal left the window
+    let a = engine.apply(at(TxType::Withdrawal, 5, 60.0, 3600 + 25 * 3600)).expect("withdrawal failed");
+    assert_eq!(a.available_amount, 820.0);
+}
+
+#[test]
+fn engine_acknowledges_resubmitted_transactions() {
+    use txp::summary::RunSummary;
+
+    let keyed = |tx_type, tx_id, amount, key: &str| Transaction { idempotency_key: Some(key.to_string()), ..tx(tx_type, tx_id, amount) };
+    let mut engine = Engine::default();
+
+    engine.apply(keyed(TxType::Deposit, 1, 10.0, "a")).expect("deposit failed");
+    // retry is acknowledged without changing the account
+    let a = engine.apply(keyed(TxType::Deposit, 1, 10.0, "a")).expect("retry failed");
+    assert_eq!(a.total_amount, 10.0);
+
+    // rejected transaction is not remembered and can be retried
+    assert!(engine.apply(keyed(TxType::Withdrawal, 2, 15.0, "b")).is_err());
+    engine.apply(keyed(TxType::Deposit, 3, 10.0, "c")).expect("deposit failed");
+    let a = engine.apply(keyed(TxType::Withdrawal, 2, 15.0, "b")).expect("withdrawal retry failed");
+    assert_eq!(a.total_amount, 5.0);
+
+    let summary = RunSummary::from_reports(&engine.into_reports());
+    assert_eq!((summary.processed, summary.applied, summary.duplicates), (5, 3, 1));
+}
+
+#[test]
+fn engine_shares_idempotency_keys_of_all_clients_with_global_scope() {
+    use txp::config::Config;
+
+    let keyed = |client_id, tx_id, key: &str| Transaction {
+        idempotency_key: Some(key.to_string()),
+        ..testing::tx(TxType::Deposit, client_id, tx_id, 10.0)
+    };
+
+    // by default every client has its own keys
+    let mut engine = Engine::default();
+    engine.apply(keyed(1, 1, "a")).expect("deposit failed");
+    assert_eq!(engine.apply(keyed(2, 2, "a")).expect("deposit failed").total_amount, 10.0);
+
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[idempotency]\nscope = \"global\"\n").expect("failed to parse"));
+    engine.apply(keyed(1, 1, "a")).expect("deposit failed");
+    assert_eq!(engine.apply(keyed(2, 2, "a")).expect("deposit failed").total_amount, 0.0);
+    // key of the rejected transaction is released for the other clients
+    assert!(engine.apply(Transaction { tx_type: TxType::Withdrawal, ..keyed(3, 3, "b") }).is_err());
+    assert_eq!(engine.apply(keyed(2, 4, "b")).expect("deposit failed").total_amount, 10.0);
+}
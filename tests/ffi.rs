@@ -0,0 +1,56 @@
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString};
+
+use txp::ffi::*;
+
+fn submit(engine: *mut TxgEngine, row: &str) -> i32 {
+    let row = CString::new(row).expect("row with nul byte");
+    unsafe { txg_submit_csv_row(engine, row.as_ptr()) }
+}
+
+fn last_error(engine: *mut TxgEngine) -> Option<String> {
+    let error = unsafe { txg_last_error(engine) };
+    (!error.is_null()).then(|| unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned())
+}
+
+#[test]
+fn rows_are_applied_and_accounts_exported() {
+    assert_eq!(txg_abi_version(), TXG_ABI_VERSION);
+    let engine = txg_engine_new();
+
+    assert_eq!(submit(engine, "deposit,1,1,10.5"), TXG_OK);
+    assert_eq!(submit(engine, "deposit, 2, 2, 3"), TXG_OK);
+    assert_eq!(submit(engine, "withdrawal,1,3,20"), TXG_REJECTED);
+    assert!(last_error(engine).is_some_and(|e| e.contains("insufficient funds")));
+    assert_eq!(submit(engine, "dispute,2,2,"), TXG_OK);
+    assert_eq!(last_error(engine), None);
+    assert_eq!(submit(engine, "chargeback,2,2"), TXG_OK);
+
+    assert_eq!(submit(engine, "transfer,1,4,1"), TXG_INVALID_ROW);
+    assert_eq!(submit(engine, "deposit,1,4,-1"), TXG_INVALID_ROW);
+    assert_eq!(submit(engine, "deposit,x,4,1"), TXG_INVALID_ROW);
+    assert_eq!(unsafe { txg_submit_csv_row(engine, std::ptr::null()) }, TXG_INVALID_ARGUMENT);
+    assert_eq!(unsafe { txg_submit_csv_row(std::ptr::null_mut(), c"deposit,1,1,1".as_ptr()) }, TXG_INVALID_ARGUMENT);
+
+    let csv = unsafe { txg_finish_and_export(engine) };
+    assert!(!csv.is_null());
+    let text = unsafe { CStr::from_ptr(csv) }.to_str().expect("export is not UTF-8").to_string();
+    unsafe { txg_string_free(csv) };
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("client,available,held,total,locked,status"), "{}", text);
+    let mut rows: Vec<_> = lines.collect();
+    rows.sort();
+    assert_eq!(rows, ["1,10.5000,0.0000,10.5000,false,active", "2,0.0000,0.0000,0.0000,true,locked"]);
+}
+
+#[test]
+fn null_pointers_are_ignored() {
+    unsafe {
+        assert!(txg_finish_and_export(std::ptr::null_mut()).is_null());
+        assert!(txg_last_error(std::ptr::null()).is_null());
+        txg_engine_free(std::ptr::null_mut());
+        txg_string_free(std::ptr::null_mut());
+        txg_engine_free(txg_engine_new());
+    }
+}
@@ -0,0 +1,21 @@
+use txp::{fixtures, golden};
+
+#[tokio::test]
+async fn edge_cases_have_expected_outcomes() {
+    let dir = std::env::temp_dir().join(format!("txp_fixtures_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("failed to create fixtures dir");
+    let inputs = fixtures::write_edge_cases(&dir).expect("failed to write fixtures");
+    assert_eq!(inputs.len(), fixtures::edge_cases().len());
+
+    let mismatches = golden::check(&dir, false).await.expect("failed to run fixtures");
+    std::fs::remove_dir_all(&dir).expect("failed to remove fixtures dir");
+    let report: Vec<_> = mismatches.iter().map(|m| m.to_string()).collect();
+    assert!(report.is_empty(), "{}", report.join("\n"));
+}
+
+#[test]
+fn expected_output_lists_rejected_rows_with_their_line() {
+    let fixture = fixtures::edge_cases().into_iter().find(|f| f.name == "dispute_before_deposit").expect("missing fixture");
+    assert_eq!(fixture.csv(), "type,client,tx,amount\ndispute,1,1,\ndeposit,1,1,5.0\nresolve,1,1,\n");
+    assert!(fixture.expected().ends_with("line,type,client,tx,code\n2,dispute,1,1,E1004\n4,resolve,1,1,E1005\n"));
+}
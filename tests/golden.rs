@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use txp::golden;
+
+#[tokio::test]
+async fn scenarios_match_expected_output() {
+    let mismatches = golden::check(Path::new("tests/golden"), false).await.expect("failed to run scenarios");
+    let report: Vec<_> = mismatches.iter().map(|m| m.to_string()).collect();
+    assert!(report.is_empty(), "run `txp-cli golden --update` after reviewing the change:\n{}", report.join("\n"));
+}
+
+#[tokio::test]
+async fn mismatch_points_to_the_first_differing_line() {
+    let scenario = Path::new("tests/golden/dispute_resolve.csv");
+    let actual = golden::run_scenario(scenario).await.expect("failed to run scenario");
+    let m = golden::Mismatch {
+        scenario: scenario.to_path_buf(),
+        expected: Some(actual.replace("13.0000,false", "10.0000,false")),
+        actual,
+    };
+    assert_eq!(
+        m.to_string(),
+        "\"tests/golden/dispute_resolve.csv\": line 2 expected `1,13.0000,0.0000,10.0000,false,active`, got `1,13.0000,0.0000,13.0000,false,active`"
+    );
+}
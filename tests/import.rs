@@ -0,0 +1,84 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use txp::{
+    csv::{CsvTransactionReader, RawTransaction},
+    import::{self, reference_tx_id},
+    TxType,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+// 2024-03-01
+const MARCH_1: u64 = 19_783 * DAY;
+
+fn summary(rows: &[RawTransaction]) -> Vec<(TxType, u16, Option<String>, Option<u64>)> {
+    rows.iter().map(|r| (r.tx_type.clone(), r.client_id, r.amount.clone(), r.timestamp)).collect()
+}
+
+#[test]
+fn ofx_credits_are_deposits_and_debits_withdrawals() {
+    let path = Path::new("tests/statement.ofx");
+    let accounts = HashMap::from([("0532013000".to_string(), 42)]);
+    let rows = import::read_statement(path, include_str!("statement.ofx"), &accounts).expect("failed to import OFX");
+    assert_eq!(
+        summary(&rows),
+        vec![
+            (TxType::Deposit, 42, Some("250.00".to_string()), Some(MARCH_1)),
+            (TxType::Withdrawal, 42, Some("40.50".to_string()), Some(MARCH_1 + DAY + 9 * 3600 + 30 * 60)),
+        ]
+    );
+    let tx_ids: Vec<_> = rows.iter().map(|r| r.tx_id).collect();
+    assert_eq!(tx_ids, vec![reference_tx_id("2024030101"), reference_tx_id("2024030201")]);
+
+    // statement account without mapping
+    let err = import::read_statement(path, include_str!("statement.ofx"), &HashMap::new()).expect_err("unmapped account imported");
+    assert_eq!(err.to_string(), "account 0532013000 is not mapped to a client");
+}
+
+#[test]
+fn qif_account_is_the_file_name() {
+    let path = Path::new("tests/7.qif");
+    let rows = import::read_statement(path, include_str!("7.qif"), &HashMap::new()).expect("failed to import QIF");
+    assert_eq!(
+        summary(&rows),
+        vec![
+            (TxType::Deposit, 7, Some("1000.00".to_string()), Some(MARCH_1)),
+            (TxType::Withdrawal, 7, Some("25.00".to_string()), Some(MARCH_1 + DAY)),
+            (TxType::Withdrawal, 7, Some("5.00".to_string()), Some(MARCH_1 + DAY)),
+            (TxType::Withdrawal, 7, Some("5.00".to_string()), Some(MARCH_1 + DAY)),
+        ]
+    );
+    // equal records get different ids, the same file always the same ones
+    assert_ne!(rows[2].tx_id, rows[3].tx_id);
+    let again = import::read_statement(path, include_str!("7.qif"), &HashMap::new()).expect("failed to import QIF");
+    assert_eq!(rows.iter().map(|r| r.tx_id).collect::<Vec<_>>(), again.iter().map(|r| r.tx_id).collect::<Vec<_>>());
+}
+
+#[test]
+fn invalid_statements_are_rejected() {
+    let accounts = HashMap::new();
+    let ofx = Path::new("a.ofx");
+    assert!(import::read_statement(ofx, "<STMTTRN><TRNAMT>1<FITID>1<DTPOSTED>20240301</STMTTRN>", &accounts).is_err());
+    assert!(import::read_statement(ofx, "<ACCTID>1<STMTTRN><TRNAMT>1<DTPOSTED>20240301</STMTTRN>", &accounts).is_err());
+    assert!(import::read_statement(ofx, "<ACCTID>1<STMTTRN><TRNAMT>1<FITID>1<DTPOSTED>2024</STMTTRN>", &accounts).is_err());
+    let qif = Path::new("1.qif");
+    assert!(import::read_statement(qif, "!Type:Bank\nT1.00\n^\n", &accounts).is_err());
+    assert!(import::read_statement(qif, "!Type:Bank\nD13/01/2024\nT1.00\n^\n", &accounts).is_err());
+}
+
+#[tokio::test]
+async fn reader_maps_statement_accounts() {
+    let tx_ids = std::sync::Mutex::new(Vec::new());
+    let raw_transaction_handler = |rt: Option<RawTransaction>| {
+        if let Some(rt) = rt {
+            tx_ids.lock().unwrap().push((rt.client_id, rt.tx_id));
+        }
+        async { Ok(()) }
+    };
+    let accounts = HashMap::from([("0532013000".to_string(), 3)]);
+    CsvTransactionReader::default()
+        .with_accounts(accounts)
+        .read_statement(PathBuf::from("tests/statement.ofx"), raw_transaction_handler)
+        .await;
+
+    assert_eq!(tx_ids.into_inner().unwrap(), vec![(3, reference_tx_id("2024030101")), (3, reference_tx_id("2024030201"))]);
+}
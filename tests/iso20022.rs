@@ -0,0 +1,67 @@
+#![cfg(feature = "iso20022")]
+
+use std::collections::HashMap;
+
+use txp::{
+    engine::Engine,
+    import::reference_tx_id,
+    iso20022::Iso20022Importer,
+    Transaction, TxType,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+// 2024-03-01
+const MARCH_1: u64 = 19_783 * DAY;
+
+#[test]
+fn credit_transfers_are_withdrawals_of_the_debtor() {
+    let accounts = HashMap::from([("DE89370400440532013000".to_string(), 42)]);
+    let rows = Iso20022Importer::with_accounts(accounts)
+        .import(include_str!("pain001.xml"))
+        .expect("failed to import pain.001");
+    let rows: Vec<_> = rows.iter().map(|r| (r.tx_type.clone(), r.client_id, r.tx_id, r.amount.clone(), r.timestamp)).collect();
+    assert_eq!(
+        rows,
+        vec![
+            (TxType::Withdrawal, 42, reference_tx_id("E2E-1"), Some("100.50".to_string()), Some(MARCH_1)),
+            (TxType::Withdrawal, 42, reference_tx_id("E2E-2"), Some("20".to_string()), Some(MARCH_1)),
+        ]
+    );
+
+    // debtor account without mapping
+    let err = Iso20022Importer::default().import(include_str!("pain001.xml")).expect_err("unmapped account imported");
+    assert_eq!(err.to_string(), "account DE89370400440532013000 is not mapped to a client");
+}
+
+#[test]
+fn reversed_entries_charge_back_the_original() {
+    let rows = Iso20022Importer::default().import(include_str!("camt054.xml")).expect("failed to import camt.054");
+    let (deposit, reversal) = (reference_tx_id("IN-1"), reference_tx_id("R-2"));
+    let summary: Vec<_> = rows.iter().map(|r| (r.tx_type.clone(), r.client_id, r.tx_id, r.timestamp)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            (TxType::Deposit, 7, deposit, Some(MARCH_1 + DAY + 10 * 3600 + 30 * 60)),
+            (TxType::Withdrawal, 7, reversal, Some(MARCH_1 + 2 * DAY)),
+            (TxType::Dispute, 7, deposit, Some(MARCH_1 + 3 * DAY)),
+            (TxType::Chargeback, 7, deposit, Some(MARCH_1 + 3 * DAY)),
+        ]
+    );
+
+    let mut engine = Engine::default();
+    for r in rows {
+        let _ = engine.apply(Transaction::from(r));
+    }
+    let account = &engine.accounts()[0];
+    assert_eq!((account.total_amount, account.is_locked), (-40.0, true));
+}
+
+#[test]
+fn other_documents_are_rejected() {
+    let importer = Iso20022Importer::default();
+    assert!(importer.import("<Document><Other/></Document>").is_err());
+    assert!(importer.import("<Document><BkToCstmrDbtCdtNtfctn>").is_err());
+    let missing_indicator = "<Document><BkToCstmrDbtCdtNtfctn><Ntfctn><Acct><Id><Othr><Id>1</Id></Othr></Id></Acct>\
+        <Ntry><NtryRef>R</NtryRef><Amt>1</Amt></Ntry></Ntfctn></BkToCstmrDbtCdtNtfctn></Document>";
+    assert!(importer.import(missing_indicator).is_err());
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    lag::{AccountLag, LagMonitor},
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    TxType,
+};
+
+#[tokio::test]
+async fn lag_of_every_account_is_measured() {
+    let lag = Arc::new(LagMonitor::default());
+    let options = ProcessorOptions { lag: Some(lag.clone()), ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let processor = tokio::spawn(TxProcessor::process(receiver, options));
+    for t in [
+        tx(TxType::Deposit, 1, 1, 10.0),
+        tx(TxType::Withdrawal, 1, 2, 5.0),
+        tx(TxType::Deposit, 2, 3, 1.0),
+    ] {
+        sender.send(Some(t)).await.expect("processor stopped");
+    }
+    sender.send(None).await.expect("processor stopped");
+    processor.await.expect("processor failed");
+
+    let mut slowest = lag.slowest(10);
+    slowest.sort_by_key(|a| a.key.1);
+    let counts: Vec<_> = slowest.iter().map(|a| (a.key.1, a.depth, a.transactions)).collect();
+    assert_eq!(counts, vec![(1, 0, 2), (2, 0, 1)]);
+    assert_eq!(lag.slowest(1).len(), 1);
+    // counters start again after every report
+    assert!(lag.slowest(10).iter().all(|a| a.transactions == 0));
+    assert!(slowest[0].to_string().starts_with(",1,0,"));
+    assert_eq!(AccountLag::HEADER.split(',').count(), 6);
+}
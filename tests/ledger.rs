@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    ledger::{InMemoryLedger, Ledger, LedgerError, LedgerStore},
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    Account, AccountError, AccountKey, DisputeState, Transaction, TxType,
+};
+
+/// store with state of some accounts kept from an earlier run, like a persistent backend would
+#[derive(Debug, Default)]
+struct StoredLedgers {
+    stored: HashMap<AccountKey, (Account, Vec<Transaction>)>,
+    opened: Mutex<Vec<AccountKey>>,
+}
+
+impl LedgerStore for StoredLedgers {
+    fn open(&self, key: &AccountKey, account: Account) -> Box<dyn Ledger> {
+        self.opened.lock().unwrap().push(key.clone());
+        let (account, transactions) = self.stored.get(key).cloned().unwrap_or((account, Vec::new()));
+        let mut ledger = InMemoryLedger::new(account);
+        for t in transactions {
+            ledger.record_tx(t).expect("in memory ledger failed");
+        }
+        Box::new(ledger)
+    }
+}
+
+#[tokio::test]
+async fn accounts_continue_from_the_ledger_store() {
+    let mut store = StoredLedgers::default();
+    let account = Account { client_id: 1, available_amount: 10.0, total_amount: 10.0, ..Default::default() };
+    store.stored.insert((None, 1), (account, vec![tx(TxType::Deposit, 1, 1, 10.0)]));
+    let store = Arc::new(store);
+
+    let options = ProcessorOptions { ledger: store.clone(), ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let processor = tokio::spawn(TxProcessor::process(receiver, options));
+    for t in [
+        // disputes the transaction recorded in the store
+        tx(TxType::Dispute, 1, 1, 0.0),
+        tx(TxType::Deposit, 1, 2, 5.0),
+        tx(TxType::Deposit, 2, 3, 1.0),
+    ] {
+        sender.send(Some(t)).await.expect("processor stopped");
+    }
+    sender.send(None).await.expect("processor stopped");
+    let reports = processor.await.expect("processor failed");
+
+    let balances: Vec<_> =
+        reports.iter().map(|r| (r.account.client_id, r.account.available_amount, r.account.held_amount)).collect();
+    assert_eq!(balances, vec![(1, 5.0, 10.0), (2, 1.0, 0.0)]);
+    assert_eq!(reports[0].disputed.len(), 1);
+
+    let mut opened = store.opened.lock().unwrap().clone();
+    opened.sort();
+    assert_eq!(opened, vec![(None, 1), (None, 2)]);
+}
+
+/// ledger whose database went away: reads still work, every write fails
+#[derive(Debug, Default)]
+struct ReadOnlyLedger(InMemoryLedger);
+
+impl Ledger for ReadOnlyLedger {
+    fn account(&self) -> Account {
+        self.0.account()
+    }
+
+    fn update_account(&mut self, _account: Account) -> Result<(), LedgerError> {
+        Err(LedgerError("read only".to_string()))
+    }
+
+    fn transaction(&self, tx_id: u32) -> Option<Transaction> {
+        self.0.transaction(tx_id)
+    }
+
+    fn record_tx(&mut self, _t: Transaction) -> Result<(), LedgerError> {
+        Err(LedgerError("read only".to_string()))
+    }
+
+    fn mark_dispute(&mut self, _tx_id: u32, _state: DisputeState) -> Result<(), LedgerError> {
+        Err(LedgerError("read only".to_string()))
+    }
+
+    fn remove_tx(&mut self, _tx_id: u32) -> Result<Option<Transaction>, LedgerError> {
+        Err(LedgerError("read only".to_string()))
+    }
+
+    fn transactions(&self) -> Vec<Transaction> {
+        self.0.transactions()
+    }
+
+    fn tx_count(&self) -> usize {
+        self.0.tx_count()
+    }
+}
+
+#[derive(Debug)]
+struct ReadOnlyStore;
+
+impl LedgerStore for ReadOnlyStore {
+    fn open(&self, _key: &AccountKey, account: Account) -> Box<dyn Ledger> {
+        Box::new(ReadOnlyLedger(InMemoryLedger::new(account)))
+    }
+}
+
+#[tokio::test]
+async fn storage_errors_reject_the_transaction() {
+    let options = ProcessorOptions { ledger: Arc::new(ReadOnlyStore), rejects: true, ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let processor = tokio::spawn(TxProcessor::process(receiver, options));
+    sender.send(Some(tx(TxType::Deposit, 1, 1, 5.0))).await.expect("processor stopped");
+    sender.send(None).await.expect("processor stopped");
+    let reports = processor.await.expect("processor failed");
+
+    assert_eq!(reports[0].account.total_amount, 0.0);
+    assert_eq!(reports[0].summary.rejected.get("storage"), Some(&1));
+    assert_eq!(reports[0].rejects[0].error, Some(AccountError::Storage("read only".to_string())));
+}
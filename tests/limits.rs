@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    clients::{ClientOverrides, ClientSettings},
+    config::{self, Config},
+    engine::Engine,
+    summary::RunSummary,
+    testing,
+    tx::{ProcessorOptions, TxProcessor},
+    Transaction, TxType,
+};
+
+fn tx(tx_type: TxType, tx_id: u32, amount: f32) -> Transaction {
+    testing::tx(tx_type, 1, tx_id, amount)
+}
+
+#[test]
+fn engine_rejects_amount_over_configured_limit() {
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("[limits]\nmax_amount = 100.0\n").expect("failed to parse"));
+
+    assert!(engine.apply(tx(TxType::Deposit, 1, 1000.0)).is_err());
+    // rejected deposit can not be disputed
+    assert!(engine.apply(tx(TxType::Dispute, 1, 0.0)).is_err());
+
+    let a = engine.apply(tx(TxType::Deposit, 2, 100.0)).expect("deposit failed");
+    assert_eq!(a.total_amount, 100.0);
+}
+
+#[tokio::test]
+async fn amounts_over_limit_are_counted_and_kept_as_rejects() {
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
+
+    for (client_id, tx_id, amount) in [(1, 1, 50.0), (1, 2, 50000.0), (2, 3, 500.0)] {
+        let t = testing::tx(TxType::Deposit, client_id, tx_id, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let options = ProcessorOptions {
+        config: config::fixed(Config::parse("[limits]\nmax_amount = 1000.0\n").expect("failed to parse")),
+        // client 2 has lower limit than the global one
+        clients: Arc::new(ClientOverrides::new(vec![ClientSettings { client: 2, max_amount: Some(100.0), ..Default::default() }])),
+        rejects: true,
+        ..Default::default()
+    };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    let summary = RunSummary::from_reports(&reports);
+    assert_eq!((summary.processed, summary.applied), (3, 1));
+    assert_eq!(summary.rejected.get("amount_limit_exceeded"), Some(&2));
+
+    let rejected: Vec<_> = reports.iter().flat_map(|r| r.rejects.iter().map(|e| e.transaction.tx_id)).collect();
+    assert_eq!(rejected, vec![2, 3]);
+}
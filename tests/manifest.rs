@@ -0,0 +1,75 @@
+use txp::manifest::{self, ConfigInfo, InputFile, RunManifest};
+
+#[test]
+fn manifest_records_input_checksum_and_config() {
+    use txp::config::Config;
+
+    let path = std::env::temp_dir().join(format!("txp_manifest_input_{}.csv", std::process::id()));
+    std::fs::write(&path, "abc").expect("failed to write input");
+    let sha256 = manifest::sha256_file(&path).expect("failed to hash");
+    std::fs::remove_file(&path).expect("failed to remove input");
+    assert_eq!(sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+    let config = Config::parse("version = \"2022-01\"\ncurrency = \"JPY\"\n").expect("failed to parse");
+    let m = RunManifest {
+        version: "0.1.0".to_string(),
+        inputs: vec![InputFile { path: "input.csv".to_string(), sha256, verified: true }],
+        config: ConfigInfo::new(None, &config).expect("failed to describe config"),
+        ..Default::default()
+    };
+    let json = serde_json::to_value(&m).expect("failed to serialize");
+    assert_eq!(json["inputs"][0]["sha256"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(json["inputs"][0]["verified"], true);
+    assert_eq!((json["config"]["version"].as_str(), json["config"]["precision"].as_u64()), (Some("2022-01"), Some(0)));
+    assert_eq!(json["config"]["rounding"], "half_even");
+}
+
+#[tokio::test]
+async fn input_is_hashed_while_read() {
+    use manifest::HashingReader;
+    use tokio::io::AsyncReadExt;
+
+    let path = std::env::temp_dir().join(format!("txp_manifest_sidecar_{}.csv", std::process::id()));
+    let sidecar = manifest::sidecar_path(&path);
+    std::fs::write(&path, "type,client,tx,amount\ndeposit,1,1,1.0\n").expect("failed to write input");
+    let expected = manifest::sha256_file(&path).expect("failed to hash");
+    std::fs::write(&sidecar, format!("{}  input.csv\n", expected.to_uppercase())).expect("failed to write sidecar");
+
+    let file = tokio::fs::File::open(&path).await.expect("failed to open input");
+    let (mut reader, digest) = HashingReader::new(file);
+    let mut content = String::new();
+    reader.read_to_string(&mut content).await.expect("failed to read input");
+
+    assert_eq!(digest.hex(), expected);
+    assert_eq!(manifest::expected_sha256(&path).expect("failed to read sidecar"), expected);
+
+    std::fs::write(&sidecar, "not a digest\n").expect("failed to write sidecar");
+    assert!(manifest::expected_sha256(&path).is_err());
+    std::fs::remove_file(&path).expect("failed to remove input");
+    std::fs::remove_file(&sidecar).expect("failed to remove sidecar");
+}
+
+#[test]
+fn stats_report_is_saved_as_json() {
+    use txp::{manifest::RowCounts, stats::StatsReport, summary::RunSummary};
+
+    let report = StatsReport {
+        version: "0.1.0".to_string(),
+        rows: RowCounts { read: 10, filtered: 2 },
+        summary: RunSummary { processed: 8, applied: 7, ..Default::default() },
+        parse_errors: [("E2002".to_string(), 1)].into_iter().collect(),
+        ..Default::default()
+    };
+    let path = std::env::temp_dir().join(format!("txp_stats_{}.json", std::process::id()));
+    report.save(&path).expect("failed to save");
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).expect("failed to read")).expect("invalid JSON");
+    std::fs::remove_file(&path).expect("failed to remove report");
+
+    assert_eq!(json["rows"]["read"], 10);
+    assert_eq!(json["summary"]["applied"], 7);
+    assert_eq!(json["parse_errors"]["E2002"], 1);
+    for section in ["stats", "timings", "resources"] {
+        assert!(json[section].is_object(), "missing {} in {}", section, json);
+    }
+}
@@ -0,0 +1,90 @@
+use tokio::sync::mpsc::channel;
+use txp::{
+    engine::Engine,
+    merge::{self, MergeOrder},
+    testing,
+    tx::ProcessingOutcome,
+    Transaction, TxType,
+};
+
+fn deposit(client_id: u16, tx_id: u32, amount: f32) -> Transaction {
+    testing::tx(TxType::Deposit, client_id, tx_id, amount)
+}
+
+fn outcome(transactions: Vec<Transaction>) -> ProcessingOutcome {
+    let mut engine = Engine::default();
+    for t in transactions {
+        engine.apply(t).expect("transaction rejected");
+    }
+    ProcessingOutcome { reports: engine.into_reports(), sequence: None, stats: Default::default(), aborted: None }
+}
+
+#[test]
+fn merge_outcomes_of_files_in_name_order() {
+    let outcomes = vec![
+        ("b.csv".to_string(), outcome(vec![deposit(3, 3, 3.0), deposit(1, 4, 4.0)])),
+        ("a.csv".to_string(), outcome(vec![deposit(2, 1, 1.0), deposit(1, 2, 2.0)])),
+    ];
+    let (merged, overlaps) = merge::merge_outcomes(outcomes);
+
+    let accounts: Vec<_> = merged.reports.iter().map(|r| (r.account.client_id, r.account.total_amount)).collect();
+    assert_eq!(accounts, vec![(1, 6.0), (2, 1.0), (3, 3.0)]);
+    assert_eq!(merged.reports[0].summary.applied, 2);
+
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!(overlaps[0].key, (None, 1));
+    assert_eq!(overlaps[0].files, vec!["a.csv".to_string(), "b.csv".to_string()]);
+}
+
+fn stamped(tx_id: u32, timestamp: Option<u64>) -> Transaction {
+    Transaction { timestamp, ..deposit(1, tx_id, 1.0) }
+}
+
+/// ids of the transactions of `sources` merged in `order`, every source sends all of its transactions upfront
+async fn merged_ids(sources: Vec<Vec<Transaction>>, order: MergeOrder) -> (Vec<u32>, Vec<u64>) {
+    let mut receivers = Vec::new();
+    for transactions in sources {
+        let (sender, receiver) = channel(16);
+        for t in transactions {
+            sender.send(Some(t)).await.expect("merge stopped");
+        }
+        sender.send(None).await.expect("merge stopped");
+        receivers.push(receiver);
+    }
+    let (sender, mut receiver) = channel(64);
+    let counts = merge::merge_sources(receivers, order, sender).await;
+
+    let mut ids = Vec::new();
+    while let Some(t) = receiver.recv().await.expect("merged stream closed before its end") {
+        ids.push(t.tx_id);
+    }
+    (ids, counts)
+}
+
+#[tokio::test]
+async fn sequential_merge_drains_sources_in_order() {
+    let backfill = vec![stamped(1, Some(20)), stamped(2, Some(30))];
+    let live = vec![stamped(3, Some(10))];
+    let (ids, counts) = merged_ids(vec![backfill, live], MergeOrder::Sequential).await;
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(counts, vec![2, 1]);
+}
+
+#[tokio::test]
+async fn timestamp_merge_passes_earliest_first() {
+    let backfill = vec![stamped(1, Some(10)), stamped(2, Some(30)), stamped(3, Some(40))];
+    let live = vec![stamped(4, Some(20)), stamped(5, Some(30)), stamped(6, None)];
+    let (ids, counts) = merged_ids(vec![backfill, live], MergeOrder::Timestamp).await;
+    // ties go to the first source, transactions without timestamp are passed on as soon as they are at the head
+    assert_eq!(ids, vec![1, 4, 2, 5, 6, 3]);
+    assert_eq!(counts, vec![3, 3]);
+}
+
+#[tokio::test]
+async fn arrival_merge_passes_every_transaction() {
+    let (mut ids, counts) =
+        merged_ids(vec![vec![stamped(1, None), stamped(2, None)], vec![], vec![stamped(3, None)]], MergeOrder::Arrival).await;
+    ids.sort_unstable();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(counts, vec![2, 0, 1]);
+}
@@ -0,0 +1,102 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use tokio::sync::mpsc::channel;
+use txp::{
+    middleware::{Filter, Next, TxMiddleware},
+    pause::IngestControl,
+    testing::tx,
+    tx::{ProcessorHandle, ProcessorOptions, TxProcessor},
+    Transaction, TxType,
+};
+
+/// assigns the tenant of the client
+#[derive(Debug)]
+struct Enrich;
+
+impl TxMiddleware for Enrich {
+    fn handle<'a>(&'a self, mut t: Transaction, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            t.tenant = Some(if t.client_id < 10 { "acme" } else { "globex" }.to_string());
+            next.run(t).await;
+        })
+    }
+}
+
+/// holds back deposits until the end of the stream
+#[derive(Debug, Default)]
+struct HoldDeposits(Mutex<Vec<Transaction>>);
+
+impl TxMiddleware for HoldDeposits {
+    fn handle<'a>(&'a self, t: Transaction, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            if t.tx_type == TxType::Deposit {
+                self.0.lock().expect("lock poisoned").push(t);
+            } else {
+                next.run(t).await;
+            }
+        })
+    }
+
+    fn finish<'a>(&'a self, mut next: Next<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let held = std::mem::take(&mut *self.0.lock().expect("lock poisoned"));
+            for t in held {
+                next.run(t).await;
+            }
+        })
+    }
+}
+
+async fn process(middleware: Vec<Arc<dyn TxMiddleware>>) -> Vec<(Option<String>, u16, f32)> {
+    let (tx_sender, tx_receiver) = channel(8);
+    for t in [
+        tx(TxType::Deposit, 1, 1, 10.0),
+        tx(TxType::Withdrawal, 1, 2, 4.0),
+        tx(TxType::Deposit, 2, 3, 1.0),
+        tx(TxType::Deposit, 11, 4, 5.0),
+    ] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let reports = TxProcessor::process(tx_receiver, ProcessorOptions { middleware, ..Default::default() }).await;
+    reports.into_iter().map(|r| (r.account.tenant, r.account.client_id, r.account.available_amount)).collect()
+}
+
+#[tokio::test]
+async fn stages_run_in_order() {
+    let accounts = process(vec![Arc::new(Filter(|t: &Transaction| t.client_id != 2)), Arc::new(Enrich)]).await;
+    assert_eq!(accounts, [(Some("acme".to_string()), 1, 6.0), (Some("globex".to_string()), 11, 5.0)]);
+}
+
+#[tokio::test]
+async fn held_back_transactions_are_released_at_the_end() {
+    // the withdrawal reaches the account before the deposit and is rejected
+    let accounts = process(vec![Arc::new(HoldDeposits::default())]).await;
+    assert_eq!(accounts, [(None, 1, 10.0), (None, 2, 1.0), (None, 11, 5.0)]);
+}
+
+#[tokio::test]
+async fn paused_ingestion_routes_nothing_until_resumed() {
+    let control = IngestControl::default();
+    control.pause();
+    let options = ProcessorOptions { middleware: vec![Arc::new(control.clone())], ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let handle = ProcessorHandle::new(sender);
+    let processor = tokio::spawn(TxProcessor::run(receiver, options));
+    handle.send(tx(TxType::Deposit, 1, 1, 10.0)).await.expect("processor stopped");
+    handle.send(tx(TxType::Deposit, 1, 2, 5.0)).await.expect("processor stopped");
+
+    // the processor waits with the first transaction, so the snapshot is not answered while paused
+    let snapshot = tokio::time::timeout(Duration::from_millis(50), handle.snapshot_all()).await;
+    assert!(snapshot.is_err() && control.is_paused());
+
+    control.resume();
+    let snapshot = handle.snapshot_all().await.expect("processor stopped");
+    assert_eq!(snapshot.get(&(None, 1)).map(|a| a.total_amount), Some(15.0));
+    handle.shutdown().await.expect("processor stopped");
+    processor.await.expect("processor failed");
+}
@@ -0,0 +1,66 @@
+use txp::money::{round, RoundingMode};
+
+#[test]
+fn rounding_modes() {
+    use RoundingMode::*;
+
+    assert_eq!(round(1.00005, 4, HalfUp), 1.0001);
+    assert_eq!(round(1.00005, 4, HalfEven), 1.0);
+    assert_eq!(round(1.00015, 4, HalfEven), 1.0002);
+    assert_eq!(round(1.000051, 4, HalfEven), 1.0001);
+    assert_eq!(round(1.00019, 4, Truncate), 1.0001);
+    assert_eq!(round(-1.00005, 4, HalfUp), -1.0001);
+    assert_eq!(round(-1.00019, 4, Truncate), -1.0001);
+    // amounts within precision are left as they are
+    assert_eq!(round(2.5, 4, HalfUp), 2.5);
+    assert_eq!(round(0.9999, 4, HalfUp), 0.9999);
+    assert_eq!(round(0.99995, 4, HalfUp), 1.0);
+    assert_eq!(round(2.5, 0, HalfEven), 2.0);
+    assert_eq!(round(3.5, 0, HalfEven), 4.0);
+}
+
+#[test]
+fn currency_precision() {
+    use txp::{config::Config, csv::RawAccount, money::precision, output};
+
+    assert_eq!((precision("JPY"), precision("btc"), precision("XYZ")), (0, 8, 4));
+
+    let config = Config::parse("currency = \"JPY\"\n").expect("failed to parse");
+    assert_eq!(config.precision(), 0);
+    let config = Config::parse("currency = \"USD\"\n[precision]\nUSD = 2\n").expect("failed to parse");
+    assert_eq!(config.precision(), 2);
+    assert_eq!(Config::default().precision(), 4);
+
+    let account = RawAccount {
+        tenant: None,
+        client_id: 1,
+        available_amount: 1.5,
+        held_amount: 0.0,
+        total_amount: 1.5,
+        is_locked: false,
+        is_closed: false,
+        last_activity: None,
+        is_dormant: false,
+        version: 0,
+    };
+    let mut out = Vec::new();
+    output::write_accounts(&mut out, &[account], 2).expect("failed to write accounts");
+    assert_eq!(String::from_utf8(out).expect("invalid output"), format!("{}\n1,1.50,0.00,1.50,false,active\n", output::ACCOUNT_HEADER));
+}
+
+#[test]
+fn engine_rounds_amounts_with_configured_mode() {
+    use txp::{config::Config, engine::Engine, testing, TxType};
+
+    let deposit = |tx_id, amount| testing::tx(TxType::Deposit, 1, tx_id, amount);
+    let mut engine = Engine::default();
+    engine.set_config(Config::parse("rounding = \"truncate\"\n").expect("failed to parse"));
+    engine.apply(deposit(1, 0.00019)).expect("deposit failed");
+    let a = engine.apply(deposit(2, 0.00019)).expect("deposit failed");
+    assert_eq!(a.total_amount, 0.0002);
+
+    // amounts are rounded to the precision of the configured currency
+    engine.set_config(Config::parse("currency = \"JPY\"\n").expect("failed to parse"));
+    let a = engine.apply(deposit(3, 1.5)).expect("deposit failed");
+    assert_eq!(a.total_amount, 2.0002);
+}
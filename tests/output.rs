@@ -0,0 +1,302 @@
+use txp::{engine::Engine, output, testing, Transaction, TxType};
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn deposit(client_id: u16, tx_id: u32, timestamp: u64) -> Transaction {
+    Transaction { timestamp: Some(timestamp), ..testing::tx(TxType::Deposit, client_id, tx_id, 1.0) }
+}
+
+#[test]
+fn inactive_accounts_are_marked_dormant() {
+    let mut engine = Engine::default();
+    engine.apply(deposit(1, 1, 0)).expect("deposit failed");
+    engine.apply(deposit(2, 2, 0)).expect("deposit failed");
+    engine.apply(deposit(2, 3, 40 * DAY)).expect("deposit failed");
+    let mut accounts = engine.accounts();
+
+    let as_of = output::mark_dormant(&mut accounts, 30, None);
+    assert_eq!(as_of, Some(40 * DAY));
+    let statuses: Vec<_> = accounts.iter().map(|a| a.status()).collect();
+    assert_eq!(statuses, vec!["dormant", "active"]);
+
+    let mut report = Vec::new();
+    output::write_dormant(&mut report, &accounts, 40 * DAY).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!("{}\n,1,0,40,1.0000,0.0000,1.0000\n", output::DORMANT_HEADER)
+    );
+}
+
+#[test]
+fn accounts_which_went_negative_are_reported() {
+    use testing::tx;
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Withdrawal, 1, 2, 8.0)).expect("withdrawal failed");
+    // disputed deposit was already partly withdrawn
+    engine.apply(tx(TxType::Dispute, 1, 1, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Resolve, 1, 1, 0.0)).expect("resolve failed");
+    engine.apply(tx(TxType::Deposit, 2, 3, 1.0)).expect("deposit failed");
+
+    let mut report = Vec::new();
+    output::write_negative(&mut report, &engine.into_reports(), &Default::default()).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!("{}
+,1,1,dispute,-8.0000,2.0000
+", output::NEGATIVE_HEADER)
+    );
+}
+
+#[test]
+fn transactions_still_in_dispute_are_reported() {
+    let mut engine = Engine::default();
+    engine.apply(deposit(1, 1, 0)).expect("deposit failed");
+    engine.apply(deposit(1, 2, DAY)).expect("deposit failed");
+    engine.apply(Transaction { tx_type: TxType::Dispute, timestamp: None, ..deposit(1, 1, 0) }).expect("dispute failed");
+    engine.apply(Transaction { tx_type: TxType::Dispute, timestamp: None, ..deposit(1, 2, 0) }).expect("dispute failed");
+    engine.apply(Transaction { tx_type: TxType::Resolve, timestamp: None, ..deposit(1, 2, 0) }).expect("resolve failed");
+
+    let mut report = Vec::new();
+    output::write_open_disputes(&mut report, &engine.into_reports(), Some(10 * DAY), &Default::default()).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!("{}
+,1,1,deposit,1.0000,0,10
+", output::OPEN_DISPUTES_HEADER)
+    );
+}
+
+#[test]
+fn held_funds_are_bucketed_by_age() {
+    let dispute = |client_id, tx_id| Transaction { tx_type: TxType::Dispute, timestamp: None, ..deposit(client_id, tx_id, 0) };
+    let mut engine = Engine::default();
+    engine.apply(deposit(1, 1, 0)).expect("deposit failed");
+    engine.apply(deposit(1, 2, 85 * DAY)).expect("deposit failed");
+    engine.apply(deposit(2, 3, 95 * DAY)).expect("deposit failed");
+    engine.apply(Transaction { timestamp: None, ..deposit(2, 4, 0) }).expect("deposit failed");
+    for (client_id, tx_id) in [(1, 1), (1, 2), (2, 3), (2, 4)] {
+        engine.apply(dispute(client_id, tx_id)).expect("dispute failed");
+    }
+
+    let mut report = Vec::new();
+    output::write_held_aging(&mut report, &engine.into_reports(), Some(100 * DAY)).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!(
+            "{}\n,1,0.0000,1.0000,0.0000,1.0000,0.0000\n,2,1.0000,0.0000,0.0000,0.0000,1.0000\n*,*,1.0000,1.0000,0.0000,1.0000,1.0000\n",
+            output::HELD_AGING_HEADER
+        )
+    );
+}
+
+#[test]
+fn chargebacks_are_reported_per_client() {
+    let with_type = |tx_type, client_id, tx_id| Transaction { tx_type, timestamp: None, ..deposit(client_id, tx_id, 0) };
+    let mut engine = Engine::default();
+    for (client_id, tx_id) in [(1, 1), (1, 2), (1, 3), (1, 4), (2, 5)] {
+        engine.apply(deposit(client_id, tx_id, 0)).expect("deposit failed");
+    }
+    engine.apply(Transaction { amount: 2.5, ..deposit(1, 6, 0) }).expect("deposit failed");
+    engine.apply(with_type(TxType::Dispute, 1, 6)).expect("dispute failed");
+    engine.apply(with_type(TxType::Chargeback, 1, 6)).expect("chargeback failed");
+
+    let mut report = Vec::new();
+    output::write_chargebacks(&mut report, &engine.into_reports(), &Default::default()).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!("{}\n,1,1,2.5000,5,6.5000,0.2000,true\n", output::CHARGEBACKS_HEADER)
+    );
+}
+
+#[tokio::test]
+async fn top_report_ranks_clients_and_transactions() {
+    use txp::tx::{ProcessorOptions, TxProcessor};
+
+    let (tx_sender, tx_receiver) = tokio::sync::mpsc::channel(16);
+    let tx = |tx_type, client_id, tx_id, amount| Transaction { tx_type, amount, ..deposit(client_id, tx_id, 0) };
+    for t in [
+        tx(TxType::Deposit, 1, 1, 10.0),
+        tx(TxType::Withdrawal, 1, 2, 4.0),
+        tx(TxType::Deposit, 2, 3, 12.0),
+        tx(TxType::Deposit, 3, 4, 1.0),
+        tx(TxType::Deposit, 3, 5, 3.0),
+        // rejected withdrawal counts neither to turnover nor to the largest transactions
+        tx(TxType::Withdrawal, 3, 6, 50.0),
+    ] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let reports = TxProcessor::process(tx_receiver, ProcessorOptions { top: 2, ..Default::default() }).await;
+    assert!(reports.iter().all(|r| r.largest.len() <= 2));
+
+    let mut report = Vec::new();
+    output::write_top(&mut report, &reports, 2).expect("failed to write report");
+    assert_eq!(
+        String::from_utf8(report).expect("invalid report"),
+        format!(
+            "{}\nclient,1,,1,,14.0000\nclient,2,,2,,12.0000\ntransaction,1,,2,3,12.0000\ntransaction,2,,1,1,10.0000\n",
+            output::TOP_HEADER
+        )
+    );
+}
+
+#[tokio::test]
+async fn decision_trace_has_row_per_transaction() {
+    use txp::tx::{ProcessorOptions, TxProcessor};
+
+    let (tx_sender, tx_receiver) = tokio::sync::mpsc::channel(4);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let resubmitted = Transaction { idempotency_key: Some("a".to_string()), timestamp: None, ..deposit(1, 1, 0) };
+    let withdrawal = Transaction { tx_type: TxType::Withdrawal, tx_id: 2, amount: 5.0, idempotency_key: None, ..resubmitted.clone() };
+    for t in [resubmitted.clone(), resubmitted, withdrawal] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let options = ProcessorOptions { events: Some(events_sender), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let mut trace = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(e) = events_receiver.recv().await {
+        output::write_decision(&mut trace, &e).expect("failed to write decision");
+        errors.extend(output::ErrorRecord::rejected(&e));
+    }
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].client, errors[0].tx, errors[0].code), (Some(1), Some(2), "E1001"));
+    output::write_skipped(&mut trace, &deposit(2, 3, 0), "filtered").expect("failed to write decision");
+    assert_eq!(
+        String::from_utf8(trace).expect("invalid trace"),
+        ",1,deposit,1,1.0000,applied,\"\",1.0000,0.0000,1.0000,false\n\
+         ,1,deposit,1,1.0000,skipped,\"duplicate\",1.0000,0.0000,1.0000,false\n\
+         ,1,withdrawal,2,5.0000,rejected,\"insufficient funds on account 1: withdrawal of 5.0000, available 1.0000\",1.0000,0.0000,1.0000,false\n\
+         ,2,deposit,3,1.0000,skipped,\"filtered\",,,,\n"
+    );
+}
+
+#[test]
+fn flushing_writer_buffers_until_policy_says_otherwise() {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    // shares written bytes with the test
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let sink = Sink::default();
+    let mut w = output::FlushingWriter::new(sink.clone(), output::FlushPolicy { bytes: 16, interval: None });
+    w.write_all(b"0123456789\n").expect("write failed");
+    assert!(sink.0.lock().unwrap().is_empty());
+    // buffer is full, the buffered line is written out
+    w.write_all(b"0123456789\n").expect("write failed");
+    assert_eq!(sink.0.lock().unwrap().len(), 11);
+    // the rest is written out when the writer is dropped
+    drop(w);
+    assert_eq!(sink.0.lock().unwrap().len(), 22);
+
+    let sink = Sink::default();
+    let mut w = output::FlushingWriter::new(sink.clone(), output::FlushPolicy { bytes: 1024, interval: Some(Duration::ZERO) });
+    w.write_all(b"line\n").expect("write failed");
+    assert_eq!(sink.0.lock().unwrap().as_slice(), b"line\n");
+}
+
+#[test]
+fn only_changed_accounts_are_retained() {
+    let mut engine = Engine::default();
+    for t in [deposit(1, 1, 0), deposit(2, 2, 0), deposit(3, 3, 0)] {
+        engine.apply(t).expect("deposit failed");
+    }
+    let initial = engine.snapshot();
+    // client 2 deposits and withdraws the same amount, its balances end where they started
+    engine.apply(deposit(1, 4, DAY)).expect("deposit failed");
+    engine.apply(deposit(2, 5, DAY)).expect("deposit failed");
+    engine.apply(Transaction { tx_type: TxType::Withdrawal, tx_id: 6, ..deposit(2, 6, DAY) }).expect("withdrawal failed");
+    engine.apply(deposit(4, 7, DAY)).expect("deposit failed");
+    let mut accounts = engine.accounts();
+
+    let untouched = output::retain_changed(&mut accounts, &initial.into_accounts());
+    assert_eq!(untouched, 2);
+    let clients: Vec<_> = accounts.iter().map(|a| a.client_id).collect();
+    assert_eq!(clients, vec![1, 4]);
+}
+
+#[tokio::test]
+async fn metadata_columns_are_appended_to_rejects_and_statements() {
+    use txp::{history::HistoryRetention, tx::{ProcessorOptions, TxProcessor}};
+
+    let (tx_sender, tx_receiver) = tokio::sync::mpsc::channel(8);
+    let with = |t: Transaction, metadata: &[(&str, &str)]| Transaction {
+        metadata: metadata.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        timestamp: None,
+        ..t
+    };
+    for t in [
+        with(deposit(1, 1, 0), &[("merchant", "Acme \"East\"")]),
+        with(Transaction { tx_type: TxType::Withdrawal, amount: 5.0, ..deposit(1, 2, 0) }, &[("reference", "INV-7")]),
+    ] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { rejects: true, history: HistoryRetention::Applied, ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    let mut rejects = Vec::new();
+    output::write_rejects(&mut rejects, &reports).expect("failed to write rejects");
+    let rejects = String::from_utf8(rejects).expect("invalid rejects");
+    let mut lines = rejects.lines();
+    assert_eq!(lines.next(), Some(format!("{},reference", output::REJECTS_HEADER).as_str()));
+    assert!(lines.next().is_some_and(|l| l.starts_with(",1,withdrawal,2,") && l.ends_with(",\"INV-7\"")), "{}", rejects);
+
+    let dir = std::env::temp_dir().join(format!("txp-metadata-statements-{}", std::process::id()));
+    output::write_statements(&dir, &reports, &Default::default()).expect("failed to write statements");
+    let statement = std::fs::read_to_string(dir.join("1.csv")).expect("failed to read statement");
+    std::fs::remove_dir_all(&dir).expect("failed to remove statements");
+    assert_eq!(
+        statement,
+        format!(
+            "{},merchant\ndeposit,1,1.0000,1.0000,0.0000,1.0000,false,\"Acme \"\"East\"\"\"\nclosing,,,1.0000,0.0000,1.0000,false,\n",
+            output::STATEMENT_HEADER
+        )
+    );
+}
+
+#[test]
+fn accounts_are_split_by_status() {
+    use testing::tx;
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Withdrawal, 1, 2, 8.0)).expect("withdrawal failed");
+    // chargeback of a partly withdrawn deposit locks the account with negative balance
+    engine.apply(tx(TxType::Dispute, 1, 1, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Chargeback, 1, 1, 0.0)).expect("chargeback failed");
+    engine.apply(tx(TxType::Deposit, 2, 3, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Withdrawal, 2, 4, 8.0)).expect("withdrawal failed");
+    engine.apply(tx(TxType::Dispute, 2, 3, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Deposit, 3, 5, 1.0)).expect("deposit failed");
+    let accounts = engine.accounts();
+
+    let dir = std::env::temp_dir().join(format!("txp_split_by_status_{}", std::process::id()));
+    output::write_status_files(&dir, &accounts, 4, &Default::default()).expect("failed to write files");
+    let read = |group: output::StatusGroup| {
+        let file = std::fs::read_to_string(dir.join(group.file_name())).expect("file missing");
+        file.lines().skip(1).map(|l| l.split(',').next().unwrap_or_default().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(read(output::StatusGroup::Locked), vec!["1"]);
+    assert_eq!(read(output::StatusGroup::Negative), vec!["2"]);
+    assert_eq!(read(output::StatusGroup::Normal), vec!["3"]);
+    std::fs::remove_dir_all(&dir).expect("failed to remove dir");
+}
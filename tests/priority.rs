@@ -0,0 +1,69 @@
+use tokio::sync::mpsc::channel;
+use txp::{
+    priority::PriorityQueue,
+    testing,
+    tx::{ProcessorOptions, TxProcessor},
+    Transaction, TxType,
+};
+
+fn tx(tx_type: TxType, tx_id: u32, amount: f32) -> Transaction {
+    testing::tx(tx_type, 1, tx_id, amount)
+}
+
+#[test]
+fn disputes_and_chargebacks_overtake_other_transactions() {
+    let mut queue = PriorityQueue::new(5);
+    for t in [
+        tx(TxType::Deposit, 1, 1.0),
+        tx(TxType::Deposit, 2, 1.0),
+        tx(TxType::Dispute, 2, 0.0),
+        tx(TxType::Dispute, 1, 0.0),
+        tx(TxType::Chargeback, 1, 0.0),
+    ] {
+        queue.push(t);
+    }
+    assert!(queue.is_full());
+
+    let order: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|t| (t.tx_type, t.tx_id)).collect();
+    // disputes wait for the deposits they refer to, the chargeback for the dispute
+    assert_eq!(
+        order,
+        [
+            (TxType::Deposit, 1),
+            (TxType::Dispute, 1),
+            (TxType::Chargeback, 1),
+            (TxType::Deposit, 2),
+            (TxType::Dispute, 2),
+        ]
+    );
+    assert!(queue.is_empty());
+}
+
+async fn run(priority_window: usize) -> (f32, f32, f32) {
+    let (tx_sender, tx_receiver) = channel(8);
+    for t in [
+        tx(TxType::Deposit, 1, 10.0),
+        tx(TxType::Withdrawal, 2, 10.0),
+        tx(TxType::Deposit, 3, 5.0),
+        tx(TxType::Dispute, 3, 0.0),
+        tx(TxType::Dispute, 1, 0.0),
+    ] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { buffer_size: 8, priority_window, ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+    let account = &reports[0].account;
+    (account.available_amount, account.held_amount, account.total_amount)
+}
+
+#[tokio::test]
+async fn dispute_is_applied_before_queued_withdrawal() {
+    // the withdrawal is rejected as the disputed deposit is already held
+    assert_eq!(run(8).await, (0.0, 15.0, 15.0));
+}
+
+#[tokio::test]
+async fn transactions_keep_their_order_without_window() {
+    assert_eq!(run(0).await, (-10.0, 15.0, 5.0));
+}
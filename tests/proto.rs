@@ -0,0 +1,53 @@
+#![cfg(feature = "proto")]
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    engine::Engine,
+    proto,
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    Transaction, TxType,
+};
+
+#[test]
+fn transactions_and_snapshots_round_trip() {
+    let t = Transaction { tenant: Some("eu".to_string()), timestamp: Some(1_700_000_000), idempotency_key: Some("k".to_string()), seq: Some(7), line: Some(3), ..tx(TxType::Chargeback, 42, 9, 0.0) };
+    assert_eq!(proto::decode_transaction(&proto::encode_transaction(&t)).expect("failed to decode"), t);
+
+    let mut engine = Engine::default();
+    engine.apply(tx(TxType::Deposit, 1, 1, 10.0)).expect("deposit failed");
+    engine.apply(tx(TxType::Dispute, 1, 1, 0.0)).expect("dispute failed");
+    engine.apply(tx(TxType::Deposit, 2, 2, 1.5)).expect("deposit failed");
+    let snapshot = engine.snapshot();
+    let encoded = proto::encode_snapshot(&snapshot);
+    assert!(encoded.len() < serde_json::to_vec(&snapshot).expect("failed to serialize").len());
+    assert_eq!(proto::decode_snapshot(&encoded).expect("failed to decode"), snapshot);
+}
+
+#[test]
+fn invalid_input_is_rejected() {
+    use prost::Message;
+
+    let wrong_client = proto::Transaction { client_id: 70_000, ..proto::Transaction::from(&tx(TxType::Deposit, 1, 1, 1.0)) };
+    assert!(proto::decode_transaction(&wrong_client.encode_to_vec()).is_err());
+    let newer = proto::Snapshot { version: proto::FORMAT_VERSION + 1, accounts: Vec::new(), seen_checkpoint: None };
+    assert!(proto::decode_snapshot(&newer.encode_to_vec()).is_err());
+    assert!(proto::decode_transaction(&[0xff, 0xff]).is_err());
+}
+
+#[tokio::test]
+async fn events_are_encoded() {
+    let (tx_sender, tx_receiver) = channel(4);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    tx_sender.send(Some(tx(TxType::Withdrawal, 1, 1, 5.0))).await.expect("failed to send tx");
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { events: Some(events_sender), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let e = events_receiver.recv().await.expect("missing event");
+    let decoded = proto::decode_event(&proto::encode_event(&e)).expect("failed to decode");
+    assert_eq!(decoded.outcome(), proto::Outcome::Rejected);
+    assert_eq!(decoded.tx_type(), proto::TxType::Withdrawal);
+    assert_eq!(decoded.code.as_deref(), Some("E1001"));
+    assert_eq!(decoded.account.map(|a| (a.client_id, a.total)), Some((1, 0.0)));
+}
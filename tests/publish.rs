@@ -0,0 +1,105 @@
+use tokio::sync::mpsc::channel;
+use txp::{
+    csv::RawAccount,
+    publish::{RotatingFiles, SnapshotEvery, SnapshotPublisher, SnapshotSink},
+    testing,
+    tx::{ProcessorOptions, TxProcessor},
+    Result, Transaction, TxType,
+};
+
+// client ids with totals of the published accounts
+type Published = Vec<(u16, f32)>;
+
+// collects published snapshots as (seq, delta, accounts)
+#[derive(Default)]
+struct Collect(Vec<(u64, bool, Published)>);
+
+impl SnapshotSink for &mut Collect {
+    fn publish(&mut self, seq: u64, delta: bool, accounts: &[RawAccount]) -> Result<()> {
+        self.0.push((seq, delta, accounts.iter().map(|a| (a.client_id, a.total_amount)).collect()));
+        Ok(())
+    }
+}
+
+fn deposit(client_id: u16, tx_id: u32) -> Transaction {
+    testing::tx(TxType::Deposit, client_id, tx_id, 1.0)
+}
+
+#[test]
+fn snapshot_every_is_parsed() {
+    use std::time::Duration;
+
+    assert_eq!("10000tx".parse(), Ok(SnapshotEvery::Transactions(10000)));
+    assert_eq!("5-tx".parse(), Ok(SnapshotEvery::Transactions(5)));
+    assert_eq!("250ms".parse(), Ok(SnapshotEvery::Interval(Duration::from_millis(250))));
+    assert_eq!("2m".parse(), Ok(SnapshotEvery::Interval(Duration::from_secs(120))));
+    for invalid in ["", "10", "0tx", "5x", "s"] {
+        assert!(invalid.parse::<SnapshotEvery>().is_err(), "'{}' accepted", invalid);
+    }
+}
+
+#[tokio::test]
+async fn snapshots_are_published_every_n_transactions() {
+    let (tx_sender, tx_receiver) = channel(8);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    for t in [deposit(1, 1), deposit(2, 2), deposit(1, 3), deposit(3, 4), deposit(3, 5)] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { events: Some(events_sender), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+    let mut events = Vec::new();
+    while let Some(e) = events_receiver.recv().await {
+        events.push(e);
+    }
+    // accounts are processed in parallel, order events as they were read
+    events.sort_by_key(|e| e.tx_id);
+
+    for delta in [false, true] {
+        let mut sink = Collect::default();
+        let mut publisher = SnapshotPublisher::new(SnapshotEvery::Transactions(2), delta, &mut sink);
+        for e in &events {
+            publisher.record(e).expect("failed to record event");
+        }
+        publisher.finish().expect("failed to finish");
+        let expected = match delta {
+            false => vec![
+                (1, false, vec![(1, 1.0), (2, 1.0)]),
+                (2, false, vec![(1, 2.0), (2, 1.0), (3, 1.0)]),
+                (3, false, vec![(1, 2.0), (2, 1.0), (3, 2.0)]),
+            ],
+            true => vec![(1, true, vec![(1, 1.0), (2, 1.0)]), (2, true, vec![(1, 2.0), (3, 1.0)]), (3, true, vec![(3, 2.0)])],
+        };
+        assert_eq!(sink.0, expected);
+    }
+}
+
+#[tokio::test]
+async fn rotating_files_keep_latest_snapshots() {
+    let dir = std::env::temp_dir().join(format!("txp_snapshots_{}", std::process::id()));
+    let (tx_sender, tx_receiver) = channel(8);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+    for t in [deposit(1, 1), deposit(1, 2), deposit(1, 3)] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { events: Some(events_sender), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let files = RotatingFiles::new(dir.clone(), Some(2), 4).expect("failed to create snapshot dir");
+    let mut publisher = SnapshotPublisher::new(SnapshotEvery::Transactions(1), false, files);
+    while let Some(e) = events_receiver.recv().await {
+        publisher.record(&e).expect("failed to record event");
+    }
+    publisher.finish().expect("failed to finish");
+
+    let mut names: Vec<_> = std::fs::read_dir(&dir)
+        .expect("failed to list snapshot dir")
+        .map(|f| f.expect("failed to read entry").file_name().into_string().expect("invalid name"))
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["snapshot-000002.csv", "snapshot-000003.csv"]);
+    let latest = std::fs::read_to_string(dir.join("snapshot-000003.csv")).expect("failed to read snapshot");
+    std::fs::remove_dir_all(&dir).expect("failed to remove snapshot dir");
+    assert!(latest.ends_with("1,3.0000,0.0000,3.0000,false,active\n"), "{}", latest);
+}
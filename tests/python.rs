@@ -0,0 +1,60 @@
+#![cfg(feature = "python")]
+
+use std::ffi::CString;
+
+use pyo3::{prelude::*, wrap_pymodule};
+
+// runs the script with `txp` module importable, as it is when built with maturin
+fn run(script: &str) -> PyResult<()> {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = wrap_pymodule!(txp::python::txp)(py);
+        py.import("sys")?.getattr("modules")?.set_item("txp", module)?;
+        let script = CString::new(script).expect("script with nul byte");
+        py.run(&script, None, None)
+    })
+}
+
+#[test]
+fn engine_applies_transactions_incrementally() {
+    run(r#"
+import txp
+engine = txp.Engine()
+account = engine.apply("deposit", 1, 1, 10.5)
+assert (account.client, account.available, account.total) == (1, 10.5, 10.5), account
+account = engine.apply("dispute", 1, 1)
+assert (account.available, account.held) == (0.0, 10.5), account
+try:
+    engine.apply("withdrawal", 1, 2, 1.0)
+    raise AssertionError("withdrawal of held funds applied")
+except ValueError as e:
+    assert "insufficient funds" in str(e), e
+account = engine.apply("chargeback", 1, 1)
+assert (account.locked, account.status) == (True, "locked"), account
+assert engine.account(2) is None
+assert [a.client for a in engine.accounts()] == [1]
+for invalid in [("transfer", 1, 3, 1.0), ("deposit", 1, 3, -1.0)]:
+    try:
+        engine.apply(*invalid)
+        raise AssertionError(f"{invalid} applied")
+    except ValueError:
+        pass
+"#)
+    .expect("script failed");
+}
+
+#[test]
+fn process_file_returns_final_accounts() {
+    run(r#"
+import txp
+accounts = txp.process_file("tests/transactions.csv")
+assert [a.client for a in accounts] == sorted(a.client for a in accounts)
+assert all(a.total == a.available + a.held for a in accounts), accounts
+try:
+    txp.process_file("tests/transactions_wrong_type.csv")
+    raise AssertionError("invalid file processed")
+except ValueError as e:
+    assert "E2001" in str(e), e
+"#)
+    .expect("script failed");
+}
@@ -0,0 +1,41 @@
+use txp::{
+    config::Config,
+    ratelimit::{OnLimit, RateLimiter},
+};
+
+#[tokio::test]
+async fn rows_over_the_limit_are_shed() {
+    let config = Config::parse("[rate_limit]\non_limit = \"shed\"\nglobal = { rate = 1.0, burst = 3.0 }\nper_source = { rate = 1.0, burst = 2.0 }\n")
+        .expect("failed to parse");
+    let rate_limit = config.rate_limit.expect("missing rate limit");
+    assert_eq!(rate_limit.on_limit, OnLimit::Shed);
+
+    let limiter = RateLimiter::new(&rate_limit).expect("invalid rate limit");
+    let (first, second) = (limiter.for_source(), limiter.for_source());
+    let mut admitted = Vec::new();
+    for source in [&first, &first, &first, &second, &second] {
+        admitted.push(source.admit().await);
+    }
+    // every source gets its burst, both share the global one
+    assert_eq!(admitted, vec![true, true, false, true, false]);
+
+    let stats = limiter.stats();
+    assert_eq!((stats.admitted, stats.shed, stats.delayed), (3, 2, 0));
+}
+
+#[tokio::test]
+async fn rows_over_the_limit_wait_in_queue() {
+    let config = Config::parse("[rate_limit]\nglobal = { rate = 1000.0, burst = 1.0 }\n").expect("failed to parse");
+    let limiter = RateLimiter::new(&config.rate_limit.expect("missing rate limit")).expect("invalid rate limit");
+
+    let source = limiter.for_source();
+    for _ in 0..3 {
+        assert!(source.admit().await);
+    }
+    let stats = limiter.stats();
+    assert_eq!((stats.admitted, stats.shed), (3, 0));
+    assert!(stats.delayed >= 1);
+
+    let config = Config::parse("[rate_limit]\nper_source = { rate = 0.0 }\n").expect("failed to parse");
+    assert!(RateLimiter::new(&config.rate_limit.expect("missing rate limit")).is_err());
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    history::HistoryRetention,
+    resources::{AllocatorStats, CountingAllocator, ResourceGauges, ResourceUsage},
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    TxType,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[tokio::test]
+async fn account_tasks_and_retained_entries_are_counted() {
+    let gauges = Arc::new(ResourceGauges::default());
+    let options = ProcessorOptions { history: HistoryRetention::All, gauges: Some(gauges.clone()), ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let processor = tokio::spawn(TxProcessor::process(receiver, options));
+    for t in [
+        tx(TxType::Deposit, 1, 1, 10.0),
+        tx(TxType::Withdrawal, 1, 2, 5.0),
+        tx(TxType::Deposit, 2, 3, 1.0),
+    ] {
+        sender.send(Some(t)).await.expect("processor stopped");
+    }
+    sender.send(None).await.expect("processor stopped");
+    let reports = processor.await.expect("processor failed");
+    assert_eq!(reports.len(), 2);
+
+    let usage = ResourceUsage::measure(&gauges);
+    assert_eq!(usage.account_tasks, 2);
+    // every transaction is kept for disputes and in the history
+    assert_eq!(usage.history_entries, 6);
+    let allocator = usage.allocator.clone().expect("allocator stats not available");
+    assert!(allocator.allocations > 0);
+    assert!(allocator.peak_bytes >= allocator.allocated_bytes);
+    assert!(usage.to_string().starts_with("resource,value\n"));
+}
+
+#[test]
+fn allocator_counts_allocations() {
+    let before = AllocatorStats::current();
+    let buffer = vec![0u8; 1 << 20];
+    let during = AllocatorStats::current();
+    drop(buffer);
+    assert!(during.allocations > before.allocations);
+    assert!(during.peak_bytes >= 1 << 20);
+}
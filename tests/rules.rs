@@ -0,0 +1,77 @@
+use txp::{config::Config, engine::Engine, testing::tx, AccountError, TxType};
+
+#[test]
+fn reject_large_withdrawal_unless_client_allowed() {
+    let mut engine = Engine::default();
+    engine.set_config(
+        Config::parse(
+            r#"
+            [[rules]]
+            name = "large-withdrawal"
+            tx_types = ["withdrawal"]
+            amount_above = 100.0
+            clients_not_in = [2]
+            action = "reject"
+            "#,
+        )
+        .expect("failed to parse"),
+    );
+
+    for client in [1, 2] {
+        engine.apply(tx(TxType::Deposit, client, client as u32, 1000.0)).expect("deposit failed");
+    }
+
+    match engine.apply(tx(TxType::Withdrawal, 1, 3, 500.0)) {
+        Err(AccountError::RejectedByRule(3, rule)) => assert_eq!(rule, "large-withdrawal"),
+        r => panic!("unexpected result {:?}", r),
+    }
+    engine.apply(tx(TxType::Withdrawal, 1, 4, 50.0)).expect("small withdrawal failed");
+    engine.apply(tx(TxType::Withdrawal, 2, 5, 500.0)).expect("allowed client withdrawal failed");
+}
+
+#[test]
+fn reject_deposits_over_frequency() {
+    let mut engine = Engine::default();
+    engine.set_config(
+        Config::parse(
+            r#"
+            [[rules]]
+            name = "deposit-burst"
+            tx_types = ["deposit"]
+            frequency = { window = 3, above = 2 }
+            action = "reject"
+            "#,
+        )
+        .expect("failed to parse"),
+    );
+
+    engine.apply(tx(TxType::Deposit, 1, 1, 1.0)).expect("first deposit failed");
+    engine.apply(tx(TxType::Deposit, 1, 2, 1.0)).expect("second deposit failed");
+    assert!(engine.apply(tx(TxType::Deposit, 1, 3, 1.0)).is_err());
+
+    // other transactions within the window make room for deposits again
+    engine.apply(tx(TxType::Withdrawal, 1, 4, 1.0)).expect("withdrawal failed");
+    engine.apply(tx(TxType::Withdrawal, 1, 5, 1.0)).expect("withdrawal failed");
+    engine.apply(tx(TxType::Deposit, 1, 6, 1.0)).expect("deposit after withdrawals failed");
+}
+
+#[test]
+fn flag_and_log_do_not_reject() {
+    let mut engine = Engine::default();
+    engine.set_config(
+        Config::parse(
+            r#"
+            [[rules]]
+            name = "flag-all"
+            action = "flag"
+
+            [[rules]]
+            name = "log-all"
+            action = "log"
+            "#,
+        )
+        .expect("failed to parse"),
+    );
+
+    engine.apply(tx(TxType::Deposit, 1, 1, 1.0)).expect("deposit failed");
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "scripting")]
+
+use std::sync::Arc;
+
+use txp::{config::Config, engine::Engine, scripting::ScriptHook, testing, AccountError, Transaction, TxType};
+
+fn tx(tx_type: TxType, tx_id: u32, amount: f32) -> Transaction {
+    testing::tx(tx_type, 1, tx_id, amount)
+}
+
+fn engine_with_script(source: &str) -> Engine {
+    let mut engine = Engine::default();
+    engine.set_config(Config { hook: Some(Arc::new(ScriptHook::compile(source).expect("failed to compile"))), ..Default::default() });
+    engine
+}
+
+#[test]
+fn script_can_allow_reject_and_modify() {
+    let mut engine = engine_with_script(
+        r#"
+        fn validate(tx, account) {
+            if tx.type == "withdrawal" && tx.amount > account.available / 2.0 {
+                return #{ action: "reject", reason: "more than half of available funds" };
+            }
+            if tx.type == "deposit" && tx.amount > 100.0 {
+                return #{ action: "modify", amount: 100.0 };
+            }
+            "allow"
+        }
+        "#,
+    );
+
+    let a = engine.apply(tx(TxType::Deposit, 1, 500.0)).expect("deposit failed");
+    assert_eq!(a.available_amount, 100.0);
+
+    match engine.apply(tx(TxType::Withdrawal, 2, 60.0)) {
+        Err(AccountError::RejectedByScript(2, reason)) => assert_eq!(reason, "more than half of available funds"),
+        r => panic!("unexpected result {:?}", r),
+    }
+    let a = engine.apply(tx(TxType::Withdrawal, 3, 40.0)).expect("withdrawal failed");
+    assert_eq!(a.available_amount, 60.0);
+}
+
+#[test]
+fn script_errors_reject_transaction() {
+    let mut engine = engine_with_script("fn validate(tx, account) { loop {} }");
+    assert!(matches!(engine.apply(tx(TxType::Deposit, 1, 1.0)), Err(AccountError::RejectedByScript(1, _))));
+}
+
+#[test]
+fn script_has_to_define_validate() {
+    assert!(ScriptHook::compile("fn check(tx) { true }").is_err());
+}
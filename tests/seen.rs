@@ -0,0 +1,88 @@
+use txp::seen::SeenStore;
+
+#[test]
+fn seen_store_survives_reopening() {
+    let path = std::env::temp_dir().join(format!("txp_seen_store_reopen_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let store = SeenStore::open(&path, 10, None).expect("failed to open store");
+    store.insert(&None, 1, 1);
+    store.insert(&Some("acme".to_string()), 1, 2);
+    assert_eq!(store.commit().expect("failed to commit"), 1);
+    drop(store);
+
+    let store = SeenStore::open(&path, 10, Some(1)).expect("failed to reopen store");
+    assert!(store.contains(&None, 1, 1));
+    assert!(store.contains(&Some("acme".to_string()), 1, 2));
+    assert!(!store.contains(&None, 1, 2));
+
+    std::fs::remove_file(&path).expect("failed to remove store");
+}
+
+#[test]
+fn seen_store_keeps_only_entries_committed_with_the_state() {
+    let path = std::env::temp_dir().join(format!("txp_seen_store_state_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let store = SeenStore::open(&path, 10, None).expect("failed to open store");
+    store.insert(&None, 1, 1);
+    store.commit().expect("failed to commit");
+    // crashed before the state of checkpoint 2 was saved, and before tx 3 was committed
+    store.insert(&None, 1, 2);
+    store.commit().expect("failed to commit");
+    store.insert(&None, 1, 3);
+    drop(store);
+
+    let store = SeenStore::open(&path, 10, Some(1)).expect("failed to reopen store");
+    assert!(store.contains(&None, 1, 1));
+    assert!(!store.contains(&None, 1, 2) && !store.contains(&None, 1, 3));
+    drop(store);
+    // the state is newer than the store, its deposits would be applied twice, or missing
+    assert!(SeenStore::open(&path, 10, Some(2)).is_err());
+    assert!(SeenStore::open(&path, 10, None).is_err());
+
+    std::fs::remove_file(&path).expect("failed to remove store");
+}
+
+#[test]
+fn seen_store_compacts_entries_outside_horizon() {
+    let path = std::env::temp_dir().join(format!("txp_seen_store_compact_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let store = SeenStore::open(&path, 2, None).expect("failed to open store");
+    for tx_id in 1..=5 {
+        store.insert(&None, 1, tx_id);
+    }
+    store.commit().expect("failed to commit");
+    assert_eq!(store.len(), 2);
+    assert!(!store.contains(&None, 1, 3));
+    assert!(store.contains(&None, 1, 5));
+    drop(store);
+
+    // file was rewritten when it reached twice the horizon
+    let lines = std::fs::read_to_string(&path).expect("failed to read store").lines().count();
+    assert!(lines < 5);
+    assert_eq!(SeenStore::open(&path, 2, Some(1)).expect("failed to reopen store").len(), 2);
+
+    std::fs::remove_file(&path).expect("failed to remove store");
+}
+
+#[test]
+fn read_only_seen_store_is_not_written() {
+    let path = std::env::temp_dir().join(format!("txp_seen_store_read_only_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let store = SeenStore::open(&path, 10, None).expect("failed to open store");
+    store.insert(&None, 1, 1);
+    store.commit().expect("failed to commit");
+    drop(store);
+
+    let store = SeenStore::open_read_only(&path, 10, Some(1)).expect("failed to open store");
+    store.insert(&None, 1, 2);
+    assert!(store.contains(&None, 1, 1) && store.contains(&None, 1, 2));
+    assert!(store.commit().is_err());
+    drop(store);
+    assert!(!SeenStore::open(&path, 10, Some(1)).expect("failed to reopen store").contains(&None, 1, 2));
+
+    std::fs::remove_file(&path).expect("failed to remove store");
+}
@@ -0,0 +1,42 @@
+use txp::{
+    sequence::{SequenceConfig, SequenceMode, Sequencer},
+    testing,
+    Transaction, TxType,
+};
+
+fn row(seq: u64) -> Transaction {
+    Transaction { seq: Some(seq), ..testing::tx(TxType::Deposit, 1, seq as u32, 1.0) }
+}
+
+fn feed(mode: SequenceMode, window: usize, seqs: &[u64]) -> (Vec<u64>, Sequencer) {
+    let mut sequencer = Sequencer::new(SequenceConfig { mode, window });
+    let mut out: Vec<_> = seqs.iter().flat_map(|seq| sequencer.push(row(*seq))).collect();
+    out.extend(sequencer.finish());
+    (out.iter().filter_map(|t| t.seq).collect(), sequencer)
+}
+
+#[test]
+fn warn_mode_reports_gaps_and_out_of_order_rows() {
+    let (out, sequencer) = feed(SequenceMode::Warn, 0, &[1, 2, 5, 4, 6]);
+    assert_eq!(out, vec![1, 2, 5, 4, 6]);
+    let stats = sequencer.stats();
+    assert_eq!((stats.gaps, stats.missing, stats.out_of_order, stats.dropped), (1, 2, 1, 0));
+}
+
+#[test]
+fn reject_mode_drops_out_of_order_rows() {
+    let (out, sequencer) = feed(SequenceMode::Reject, 0, &[1, 3, 2, 3, 4]);
+    assert_eq!(out, vec![1, 3, 4]);
+    let stats = sequencer.stats();
+    assert_eq!((stats.gaps, stats.out_of_order, stats.dropped), (1, 2, 2));
+}
+
+#[test]
+fn reorder_mode_buffers_rows_within_window() {
+    let (out, sequencer) = feed(SequenceMode::Reorder, 2, &[1, 3, 2, 5, 6, 7, 4]);
+    // 4 did not arrive within the window, it is dropped as out of order
+    assert_eq!(out, vec![1, 2, 3, 5, 6, 7]);
+    let stats = sequencer.stats();
+    assert_eq!((stats.gaps, stats.missing, stats.out_of_order, stats.dropped), (1, 1, 1, 1));
+    assert_eq!(stats.reordered, 4);
+}
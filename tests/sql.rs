@@ -0,0 +1,57 @@
+#![cfg(feature = "sql")]
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    history::HistoryRetention,
+    sql::{self, Query},
+    testing::tx,
+    tx::{ProcessorOptions, TxProcessor},
+    TxType,
+};
+
+async fn query(history: HistoryRetention) -> Query {
+    let (tx_sender, tx_receiver) = channel(8);
+    let txs = [
+        tx(TxType::Deposit, 1, 1, 10.0),
+        tx(TxType::Withdrawal, 1, 2, 15.0),
+        tx(TxType::Deposit, 2, 3, 5.0),
+        tx(TxType::Dispute, 2, 3, 0.0),
+        tx(TxType::Chargeback, 2, 3, 0.0),
+    ];
+    for t in txs {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let reports = TxProcessor::process(tx_receiver, ProcessorOptions { history, ..Default::default() }).await;
+    let accounts: Vec<_> = reports.iter().map(|r| r.account.clone()).collect();
+    Query::default()
+        .with_history(&reports)
+        .expect("failed to register history")
+        .with_accounts(&accounts, 4)
+        .expect("failed to register accounts")
+}
+
+#[tokio::test]
+async fn accounts_and_history_can_be_queried() {
+    let query = query(HistoryRetention::All).await;
+
+    let locked = query.run("SELECT client, total FROM accounts WHERE locked").await.expect("query failed");
+    let table = sql::format(&locked).expect("failed to format result");
+    assert_eq!(table.lines().filter(|l| l.starts_with("| ")).count(), 2, "{}", table);
+    assert!(table.contains("| 2      | 0.0   |"), "{}", table);
+
+    let rejected = query
+        .run("SELECT client, tx, reason FROM history WHERE outcome = 'rejected' ORDER BY client, seq")
+        .await
+        .expect("query failed");
+    assert_eq!(rejected.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+    assert!(query.run("SELECT * FROM transactions").await.is_err());
+}
+
+#[tokio::test]
+async fn history_is_empty_when_not_kept() {
+    let query = query(HistoryRetention::None).await;
+    let count = query.run("SELECT count(*) AS n FROM history").await.expect("query failed");
+    assert!(sql::format(&count).expect("failed to format result").contains("| 0 |"));
+}
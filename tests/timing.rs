@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::mpsc::channel;
+use txp::{
+    testing,
+    timing::{RunTimings, StageTimings},
+    tx::{ProcessorOptions, TxProcessor},
+    Transaction, TxType,
+};
+
+fn deposit(client_id: u16, tx_id: u32) -> Transaction {
+    testing::tx(TxType::Deposit, client_id, tx_id, 1.0)
+}
+
+async fn process(n: u32, timings: &Arc<StageTimings>) {
+    let options = ProcessorOptions { timings: Some(timings.clone()), ..Default::default() };
+    let (sender, receiver) = channel(32);
+    let processor = tokio::spawn(TxProcessor::process(receiver, options));
+    for tx_id in 0..n {
+        sender.send(Some(deposit((tx_id % 4) as u16, tx_id))).await.expect("processor stopped");
+    }
+    sender.send(None).await.expect("processor stopped");
+    processor.await.expect("processor failed");
+}
+
+#[tokio::test]
+async fn stages_are_timed_and_latencies_sampled() {
+    let timings = Arc::new(StageTimings::default());
+    process(100, &timings).await;
+    timings.parsed(Duration::from_millis(3));
+
+    let report = RunTimings::new(&timings, Duration::from_secs(2), 100);
+    assert_eq!(report.wall_ms, 2000);
+    assert_eq!(report.rows_per_sec, 50.0);
+    assert_eq!(report.parse_ms, 3);
+    // every applied transaction is sampled while there are only few of them
+    assert_eq!(report.latency_samples, 100);
+    assert!(report.latency_p50_us <= report.latency_p99_us);
+}
+
+#[tokio::test]
+async fn latency_samples_are_bounded() {
+    let timings = Arc::new(StageTimings::default());
+    process(10_000, &timings).await;
+
+    let report = RunTimings::new(&timings, Duration::ZERO, 10_000);
+    assert_eq!(report.rows_per_sec, 0.0);
+    assert!(report.latency_samples > 1000 && report.latency_samples < 4096, "{:?}", report);
+}
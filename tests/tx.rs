@@ -1,28 +1,493 @@
-use txp::{tx::TxProcessor, Transaction};
+use txp::{testing::{self, VecSink}, tx::TxProcessor, Transaction};
 use tokio::sync::mpsc::{channel};
-use stdio_override::StdoutOverride;
 
 #[tokio::test]
-#[cfg(target_family = "unix")]
-async fn process_transaction_test_stdout() {
-    use std::fs;
+async fn process_transactions_into_sink() {
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
 
-    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(2);
+    for (client_id, tx_id) in [(2, 1), (1, 2), (2, 3)] {
+        let t = testing::tx(txp::TxType::Deposit, client_id, tx_id, 1.0);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let mut sink = VecSink::default();
+    TxProcessor::process_transactions_into(tx_receiver, 2, &mut sink).await;
+
+    assert_eq!(sink.finished, 1);
+    let rows: Vec<_> = sink.accounts.iter().map(|a| a.to_string()).collect();
+    // ordered by client id, the same rows `process_transactions` writes to stdout
+    assert_eq!(rows, ["1,1.0000,0.0000,1.0000,false,active", "2,2.0000,0.0000,2.0000,false,active"]);
+}
+#[tokio::test]
+async fn accounts_are_separated_by_tenant() {
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
+
+    for (tenant, amount) in [(Some("acme".to_string()), 1.0), (Some("globex".to_string()), 2.0), (None, 3.0)] {
+        let t = Transaction { tenant, ..testing::tx(txp::TxType::Deposit, 1, 1, amount) };
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let accounts = TxProcessor::collect_accounts(tx_receiver, 2).await;
+
+    let balances: Vec<(Option<&str>, f32)> = accounts.iter().map(|a| (a.tenant.as_deref(), a.total_amount)).collect();
+    assert_eq!(balances, vec![(None, 3.0), (Some("acme"), 1.0), (Some("globex"), 2.0)]);
+}
+
+#[tokio::test]
+async fn history_retains_all_applied_transactions() {
+    use txp::{history::HistoryRetention, tx::ProcessorOptions, TxType};
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
 
-    let t = Transaction { tx_type: txp::TxType::Deposit, client_id: 1, tx_id: 1, amount: 1.0, in_dispute: false };
+    for (tx_type, tx_id, amount) in [(TxType::Deposit, 1, 5.0), (TxType::Withdrawal, 2, 10.0), (TxType::Dispute, 1, 0.0)] {
+        let t = testing::tx(tx_type, 1, tx_id, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let options = ProcessorOptions { history: HistoryRetention::Applied, ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    // withdrawal is rejected for insufficient funds and is not part of the history
+    let history: Vec<(TxType, f32, f32)> = reports[0].history.iter().map(|e| (e.transaction.tx_type.clone(), e.available_amount, e.held_amount)).collect();
+    assert_eq!(history, vec![(TxType::Deposit, 5.0, 0.0), (TxType::Dispute, 0.0, 5.0)]);
+}
+
+#[tokio::test]
+async fn full_history_contains_rejected_transactions() {
+    use txp::{history::HistoryRetention, tx::ProcessorOptions, TxType};
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
+
+    let t = testing::tx(TxType::Withdrawal, 1, 1, 10.0);
     tx_sender.send(Some(t)).await.expect("failed to send tx");
     tx_sender.send(None).await.expect("failed to send None");
 
-    let file_name = "./test_stdout.txt";
-    let _guard = StdoutOverride::override_file(file_name).expect("faild to redirect stdout");
+    let options = ProcessorOptions { history: HistoryRetention::All, ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    assert_eq!(reports[0].history.len(), 1);
+    assert!(!reports[0].history[0].is_applied());
+}
+
+#[tokio::test]
+async fn processing_events_are_reported() {
+    use txp::{tx::ProcessorOptions, TxType};
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
+    let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    for (tx_type, amount) in [(TxType::Deposit, 5.0), (TxType::Withdrawal, 10.0)] {
+        let t = testing::tx(tx_type, 1, 1, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let options = ProcessorOptions { events: Some(events_sender), ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let deposit = events_receiver.recv().await.expect("missing deposit event");
+    assert!(deposit.rejection.is_none());
+    assert_eq!(deposit.account.available_amount, 5.0);
+    let withdrawal = events_receiver.recv().await.expect("missing withdrawal event");
+    assert!(withdrawal.rejection.is_some());
+    // all senders are dropped once processing finished
+    assert!(events_receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn disputes_are_held_until_referenced_transaction_arrives() {
+    use txp::{
+        config::{self, Config},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+
+    let rows = [
+        // dispute arrives right before its deposit
+        (TxType::Dispute, 1, 1, 0.0),
+        (TxType::Deposit, 1, 1, 10.0),
+        // deposit arrives too late, the dispute is processed (and rejected) after 1 other transaction
+        (TxType::Dispute, 2, 2, 0.0),
+        (TxType::Deposit, 2, 3, 5.0),
+        (TxType::Deposit, 2, 2, 5.0),
+    ];
+    for (tx_type, client_id, tx_id, amount) in rows {
+        let t = testing::tx(tx_type, client_id, tx_id, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let config = Config::parse("[dispute_hold]\nmessages = 1\nseconds = 60\n").expect("failed to parse");
+    let options = ProcessorOptions { config: config::fixed(config), ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    assert_eq!((reports[0].account.available_amount, reports[0].account.held_amount), (0.0, 10.0));
+    assert_eq!((reports[1].account.available_amount, reports[1].account.held_amount), (10.0, 0.0));
+    assert_eq!(reports[1].summary.rejected.get("no_tx_for_dispute"), Some(&1));
+}
+
+#[tokio::test]
+async fn rejected_disputes_are_retried_when_transaction_arrives() {
+    use txp::{
+        config::{self, Config},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+
+    let rows = [(TxType::Dispute, 1, 0.0), (TxType::Chargeback, 2, 0.0), (TxType::Deposit, 1, 10.0)];
+    for (tx_type, tx_id, amount) in rows {
+        let t = testing::tx(tx_type, 1, tx_id, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let config = Config::parse("[dispute_retry]\ncapacity = 10\n").expect("failed to parse");
+    let options = ProcessorOptions { config: config::fixed(config), rejects: true, ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    assert_eq!(reports[0].account.held_amount, 10.0);
+    // deposit 2 never arrived
+    let unresolved: Vec<_> = reports[0].unresolved.iter().map(|t| (t.tx_type.clone(), t.tx_id)).collect();
+    assert_eq!(unresolved, vec![(TxType::Chargeback, 2)]);
+    // waiting for retry is not a rejection, only the unresolved chargeback is counted as rejected
+    let summary = &reports[0].summary;
+    assert_eq!((summary.processed, summary.applied), (3, 2));
+    assert_eq!(summary.rejected.get("no_tx_for_dispute"), Some(&1));
+    let rejects: Vec<_> = reports[0].rejects.iter().map(|r| r.transaction.tx_id).collect();
+    assert_eq!(rejects, vec![2]);
+}
+
+#[tokio::test]
+async fn global_idempotency_key_is_applied_once_across_account_tasks() {
+    use txp::{
+        config::{self, Config},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+    for client_id in 1..=3 {
+        let t = Transaction { idempotency_key: Some("k".to_string()), ..testing::tx(TxType::Deposit, client_id, client_id as u32, 10.0) };
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let config = Config::parse("[idempotency]\nscope = \"global\"\n").expect("failed to parse");
+    let options = ProcessorOptions { config: config::fixed(config), ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    // account tasks run concurrently, whichever comes first applies the deposit
+    let total: f32 = reports.iter().map(|r| r.account.total_amount).sum();
+    let duplicates: u64 = reports.iter().map(|r| r.summary.duplicates).sum();
+    assert_eq!((reports.len(), total, duplicates), (3, 10.0, 2));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn global_idempotency_key_of_rejected_transaction_is_applied_by_another_account() {
+    use txp::{
+        config::{self, Config},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    let keys = 50;
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+    let processor = tokio::spawn(TxProcessor::process(
+        tx_receiver,
+        ProcessorOptions {
+            config: config::fixed(Config::parse("[idempotency]\nscope = \"global\"\n").expect("failed to parse")),
+            ..Default::default()
+        },
+    ));
+    for k in 0..keys {
+        let key = Some(format!("k{}", k));
+        // withdrawal from the empty account is rejected, the deposit of another account with the same key must apply
+        let withdrawal = Transaction { idempotency_key: key.clone(), ..testing::tx(TxType::Withdrawal, 2 * k, 2 * k as u32, 10.0) };
+        let deposit = Transaction { idempotency_key: key, ..testing::tx(TxType::Deposit, 2 * k + 1, 2 * k as u32 + 1, 10.0) };
+        tx_sender.send(Some(withdrawal)).await.expect("failed to send tx");
+        tx_sender.send(Some(deposit)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let reports = processor.await.expect("processor failed");
+
+    // withdrawal coming after the deposit of its key is a duplicate instead of being rejected
+    let deposited = reports.iter().filter(|r| r.account.total_amount == 10.0).count();
+    let skipped_or_rejected: u64 = reports.iter().map(|r| r.summary.duplicates + r.summary.rejected_total()).sum();
+    assert_eq!((deposited, skipped_or_rejected), (keys as usize, keys as u64));
+}
+
+#[tokio::test]
+async fn processing_stats_are_returned_with_accounts() {
+    use txp::{tx::ProcessorOptions, TxType};
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+
+    let txs = [
+        (TxType::Deposit, 1, 1, 10.0),
+        (TxType::Deposit, 2, 2, 5.0),
+        (TxType::Withdrawal, 1, 3, 4.0),
+        (TxType::Withdrawal, 2, 4, 50.0),
+        (TxType::Dispute, 2, 2, 0.0),
+        (TxType::Chargeback, 2, 2, 0.0),
+    ];
+    for (tx_type, client_id, tx_id, amount) in txs {
+        let t = testing::tx(tx_type, client_id, tx_id, amount);
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let outcome = TxProcessor::run(tx_receiver, ProcessorOptions::default()).await;
+    let stats = outcome.stats;
+
+    assert_eq!((stats.accounts_created, stats.accounts_frozen), (2, 1));
+    let deposits = &stats.by_type["deposit"];
+    assert_eq!((deposits.processed, deposits.applied, deposits.amount), (2, 2, 15.0));
+    let withdrawals = &stats.by_type["withdrawal"];
+    assert_eq!((withdrawals.processed, withdrawals.applied, withdrawals.amount), (2, 1, 4.0));
+    assert_eq!(stats.by_type["chargeback"].applied, 1);
+    assert_eq!(stats.rejected.get("insufficient_funds"), Some(&1));
+}
+
+#[tokio::test]
+async fn aggregates_are_computed_in_the_same_pass() {
+    use std::sync::{Arc, Mutex};
+    use txp::{
+        aggregate::{Aggregates, Aggregator, Totals},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    // custom aggregator collecting ids of applied transactions
+    #[derive(Debug, Default)]
+    struct Applied(Mutex<Vec<u32>>);
+    impl Aggregator for Applied {
+        fn record(&self, t: &Transaction) {
+            self.0.lock().unwrap().push(t.tx_id);
+        }
+    }
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(8);
+    use testing::tx;
+    for t in [
+        tx(TxType::Deposit, 1, 1, 5.0),
+        tx(TxType::Deposit, 2, 2, 500.0),
+        tx(TxType::Withdrawal, 1, 3, 2.0),
+        // rejected, not aggregated
+        tx(TxType::Withdrawal, 2, 4, 900.0),
+        tx(TxType::Dispute, 1, 1, 0.0),
+    ] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+
+    let aggregates = Arc::new(Aggregates::default());
+    let applied = Arc::new(Applied::default());
+    let options = ProcessorOptions { aggregators: vec![aggregates.clone(), applied.clone()], ..Default::default() };
+    TxProcessor::process(tx_receiver, options).await;
+
+    let report = aggregates.report();
+    assert_eq!(report.by_type["deposit"], Totals { count: 2, amount: 505.0 });
+    assert_eq!(report.by_type["withdrawal"], Totals { count: 1, amount: 2.0 });
+    assert_eq!(report.by_type["dispute"].count, 1);
+    let clients: Vec<_> = report.clients.iter().map(|c| (c.client, c.deposits.count, c.withdrawals.count)).collect();
+    assert_eq!(clients, vec![(1, 1, 1), (2, 1, 0)]);
+    assert_eq!(report.histogram, vec![0, 2, 0, 1, 0, 0, 0]);
+
+    let mut ids = applied.0.lock().unwrap().clone();
+    ids.sort();
+    assert_eq!(ids, vec![1, 1, 2, 3]);
+}
+
+#[test]
+#[cfg(feature = "async-std-runtime")]
+fn processing_core_runs_on_async_std() {
+    async_std::task::block_on(async {
+        let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(2);
+        let t = testing::tx(txp::TxType::Deposit, 1, 1, 1.0);
+        let send = async_std::task::spawn(async move {
+            tx_sender.send(Some(t)).await.expect("failed to send tx");
+            tx_sender.send(None).await.expect("failed to send None");
+        });
+        let accounts = TxProcessor::collect_accounts(tx_receiver, 2).await;
+        send.await;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].total_amount, 1.0);
+    });
+}
+
+#[tokio::test]
+async fn accounts_continue_from_initial_state() {
+    use txp::{
+        snapshot::{AccountSnapshot, Snapshot},
+        tx::ProcessorOptions,
+        TxType,
+    };
+
+    let account = |client_id, total_amount, transactions| AccountSnapshot {
+        tenant: None,
+        client_id,
+        available_amount: total_amount,
+        held_amount: 0.0,
+        total_amount,
+        is_locked: false,
+        is_pre_frozen: false,
+        is_closed: false,
+        last_activity: None,
+        version: 0,
+        pending_amount: 0.0,
+        pending: vec![],
+        refunded: Default::default(),
+        transactions,
+    };
+    use testing::tx;
+    let initial = Snapshot { accounts: vec![account(1, 10.0, vec![tx(TxType::Deposit, 1, 1, 10.0)]), account(2, 3.0, vec![])], ..Default::default() };
+
+    let (tx_sender, tx_receiver) = channel::<Option<Transaction>>(4);
+    // deposit of the previous run can still be disputed
+    for t in [tx(TxType::Withdrawal, 1, 2, 4.0), tx(TxType::Dispute, 1, 1, 0.0), tx(TxType::Deposit, 3, 3, 1.0)] {
+        tx_sender.send(Some(t)).await.expect("failed to send tx");
+    }
+    tx_sender.send(None).await.expect("failed to send None");
+    let options = ProcessorOptions { initial: initial.into_accounts(), ..Default::default() };
+    let reports = TxProcessor::process(tx_receiver, options).await;
+
+    let accounts: Vec<_> = reports.iter().map(|r| (r.account.client_id, r.account.available_amount, r.account.held_amount)).collect();
+    assert_eq!(accounts, vec![(1, -4.0, 10.0), (2, 3.0, 0.0), (3, 1.0, 0.0)]);
+}
+
+#[tokio::test]
+async fn intermediate_outputs_are_requested_without_shutting_down() {
+    use tokio::sync::oneshot;
+    use txp::tx::{ProcessorOptions, TxMessage};
+
+    let deposit = |client_id, tx_id, amount| testing::tx(txp::TxType::Deposit, client_id, tx_id, amount);
+    let (tx_sender, tx_receiver) = channel::<TxMessage>(4);
+    let processor = tokio::spawn(TxProcessor::process(tx_receiver, ProcessorOptions::default()));
+
+    tx_sender.send(deposit(2, 1, 1.0).into()).await.expect("failed to send tx");
+    tx_sender.send(deposit(1, 2, 2.0).into()).await.expect("failed to send tx");
+    let (reply, flushed) = oneshot::channel();
+    tx_sender.send(TxMessage::Flush(reply)).await.expect("failed to send flush");
+    let accounts = flushed.await.expect("no reply to flush");
+    let totals: Vec<_> = accounts.iter().map(|a| (a.client_id, a.total_amount)).collect();
+    assert_eq!(totals, [(1, 2.0), (2, 1.0)]);
+
+    tx_sender.send(deposit(1, 3, 3.0).into()).await.expect("failed to send tx");
+    let (reply, snapshot) = oneshot::channel();
+    tx_sender.send(TxMessage::Snapshot(reply)).await.expect("failed to send snapshot");
+    let snapshot = snapshot.await.expect("no reply to snapshot");
+    assert_eq!(snapshot.accounts[0].total_amount, 5.0);
+    assert_eq!(snapshot.accounts[0].transactions.iter().map(|t| t.tx_id).collect::<Vec<_>>(), [2, 3]);
 
-    TxProcessor::process_transactions(tx_receiver, 2).await;
+    tx_sender.send(TxMessage::Shutdown).await.expect("failed to send shutdown");
+    let reports = processor.await.expect("processor failed");
+    assert_eq!(reports.iter().map(|r| r.account.total_amount).collect::<Vec<_>>(), [5.0, 1.0]);
+}
 
-    let captured_stdout = fs::read_to_string(file_name).expect("failed to captured stdout file content");
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn snapshot_all_is_consistent_while_transactions_arrive() {
+    use txp::tx::{ProcessorHandle, ProcessorOptions, TxMessage};
+
+    let (tx_sender, tx_receiver) = channel::<TxMessage>(16);
+    let processor = tokio::spawn(TxProcessor::process(tx_receiver, ProcessorOptions::default()));
+    let handle = ProcessorHandle::new(tx_sender);
+
+    let producer = handle.clone();
+    let produced = tokio::spawn(async move {
+        for tx_id in 1..=2000u32 {
+            let t = testing::tx(txp::TxType::Deposit, (tx_id % 8) as u16, tx_id, 1.0);
+            producer.send(t).await.expect("processor stopped");
+        }
+    });
+
+    let mut previous = 0;
+    for _ in 0..20 {
+        let accounts = handle.snapshot_all().await.expect("processor stopped");
+        // a consistent cut of the stream contains exactly the transactions sent before it, from every account
+        let mut ids: Vec<_> = accounts.values().flat_map(|a| a.transactions.iter().map(|t| t.tx_id)).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (1..=ids.len() as u32).collect::<Vec<_>>());
+        let total: f32 = accounts.values().map(|a| a.total_amount).sum();
+        assert_eq!(total, ids.len() as f32);
+        assert!(ids.len() >= previous);
+        previous = ids.len();
+        tokio::task::yield_now().await;
+    }
+
+    produced.await.expect("producer failed");
+    handle.shutdown().await.expect("processor stopped");
+    let reports = processor.await.expect("processor failed");
+    assert_eq!(reports.iter().map(|r| r.account.total_amount).sum::<f32>(), 2000.0);
+    assert!(handle.snapshot_all().await.is_err());
+}
+
+#[tokio::test]
+async fn held_dispute_is_released_when_mock_clock_advances() {
+    use std::{sync::Arc, time::Duration};
+    use txp::{
+        clock::MockClock,
+        config::{self, Config},
+        events::Outcome,
+        tx::{ProcessorHandle, ProcessorOptions},
+        TxType,
+    };
+
+    let clock = Arc::new(MockClock::default());
+    let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let config = Config::parse("[dispute_hold]\nmessages = 100\nseconds = 60\n").expect("failed to parse");
+    let options = ProcessorOptions { config: config::fixed(config), events: Some(events), clock: clock.clone(), ..Default::default() };
+    let (sender, receiver) = channel(8);
+    let handle = ProcessorHandle::new(sender);
+    let processor = tokio::spawn(TxProcessor::run(receiver, options));
+
+    // the deposit never arrives, the dispute is held until its time is up
+    let t = testing::tx(TxType::Dispute, 1, 1, 0.0);
+    handle.send(t).await.expect("processor stopped");
+    // the snapshot is answered once the dispute is held
+    assert!(handle.snapshot_all().await.expect("processor stopped").contains_key(&(None, 1)));
+    clock.advance(Duration::from_secs(59));
+    handle.snapshot_all().await.expect("processor stopped");
+    assert!(received.try_recv().is_err());
+
+    clock.advance(Duration::from_secs(1));
+    let event = tokio::time::timeout(Duration::from_secs(5), received.recv()).await.expect("dispute still held");
+    assert_eq!(event.map(|e| (e.tx_id, e.outcome)), Some((1, Outcome::Rejected)));
+    handle.shutdown().await.expect("processor stopped");
+    processor.await.expect("processor failed");
+}
+
+#[tokio::test]
+async fn strict_run_stops_routing_after_the_first_rejection() {
+    use tokio::sync::oneshot;
+    use txp::{
+        tx::{ProcessorOptions, TxMessage},
+        AccountError, TxType,
+    };
 
-    fs::remove_file(file_name).expect("failed to remove file");
+    let t = |tx_type, client_id, tx_id, amount| Transaction { line: Some(tx_id as u64 + 1), ..testing::tx(tx_type, client_id, tx_id, amount) };
+    let (tx_sender, tx_receiver) = channel::<TxMessage>(4);
+    let processor = tokio::spawn(TxProcessor::run(tx_receiver, ProcessorOptions { strict: true, ..Default::default() }));
 
-    let expected_output = "1,1.0000,0.0000,1.0000,false\n".to_string();
+    tx_sender.send(t(TxType::Deposit, 1, 1, 5.0).into()).await.expect("failed to send tx");
+    tx_sender.send(t(TxType::Withdrawal, 1, 2, 10.0).into()).await.expect("failed to send tx");
+    // the rejection is known once the account answered
+    let (reply, flushed) = oneshot::channel();
+    tx_sender.send(TxMessage::Flush(reply)).await.expect("failed to send flush");
+    flushed.await.expect("no reply to flush");
+    // later transactions are taken from the channel but not routed
+    tx_sender.send(t(TxType::Deposit, 2, 3, 1.0).into()).await.expect("failed to send tx");
+    tx_sender.send(TxMessage::Shutdown).await.expect("failed to send shutdown");
+    let outcome = processor.await.expect("processor failed");
 
-    assert_eq!(captured_stdout, expected_output);
-}
\ No newline at end of file
+    let aborted = outcome.aborted.expect("run not aborted");
+    assert_eq!((aborted.transaction.tx_id, aborted.error.clone()), (2, AccountError::InssuficientFundsForWithdrawal(1, 10.0, 5.0)));
+    assert!(aborted.to_string().starts_with("line 3: client 1 tx 2 rejected: E1001 "), "{}", aborted);
+    assert_eq!(outcome.reports.iter().map(|r| r.account.client_id).collect::<Vec<_>>(), [1]);
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "xlsx")]
+
+use std::path::PathBuf;
+
+use txp::{
+    csv::{CsvTransactionReader, ParseError, ParseErrorKind, RawTransaction},
+    xlsx, TxType,
+};
+
+// 2024-03-01 12:00
+const MARCH_1_NOON: u64 = 19_783 * 24 * 60 * 60 + 12 * 60 * 60;
+
+#[test]
+fn numeric_cells_are_written_like_csv_fields() {
+    let rows = xlsx::read_sheet(include_bytes!("transactions.xlsx")).expect("failed to read workbook");
+    let field = |row: usize, column: usize| rows[row].1[column].as_str();
+    // the empty row 4 is left out
+    assert_eq!(rows.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![1, 2, 3, 5, 6]);
+    assert_eq!((field(1, 1), field(1, 2), field(1, 3)), ("1", "1", "10.1"));
+    assert_eq!(field(1, 4), MARCH_1_NOON.to_string());
+    // 0.1 + 0.2
+    assert_eq!(field(2, 3), "0.3");
+    assert_eq!(field(4, 3), "");
+
+    assert!(xlsx::read_sheet(b"type,client,tx,amount\n").is_err());
+}
+
+#[tokio::test]
+async fn first_sheet_is_read_with_column_mapping() {
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let reader = CsvTransactionReader::with_error_handler(std::sync::Arc::new(move |e: &ParseError| {
+        reported.lock().unwrap().push((e.kind, e.line))
+    }))
+    .skip_bad_lines(1)
+    .with_columns(vec![("client".to_string(), "customer".to_string())]);
+
+    let rows = std::sync::Mutex::new(Vec::new());
+    reader
+        .read_xlsx(PathBuf::from("tests/transactions.xlsx"), |t: Option<RawTransaction>| {
+            rows.lock().unwrap().extend(t.map(|t| (t.tx_type, t.client_id, t.tx_id, t.amount, t.timestamp, t.line)));
+            async { Ok(()) }
+        })
+        .await;
+    assert_eq!(
+        rows.into_inner().unwrap(),
+        vec![
+            (TxType::Deposit, 1, 1, Some("10.1".to_string()), Some(MARCH_1_NOON), Some(2)),
+            (TxType::Withdrawal, 1, 2, Some("0.3".to_string()), None, Some(3)),
+            (TxType::Dispute, 1, 1, None, None, Some(6)),
+        ]
+    );
+    assert_eq!(*errors.lock().unwrap(), vec![(ParseErrorKind::InvalidAmount, Some(5))]);
+
+    // without the mapping the header does not match the input format
+    let reader = CsvTransactionReader::default();
+    let read = tokio::spawn(async move {
+        reader.read_xlsx(PathBuf::from("tests/transactions.xlsx"), |_: Option<RawTransaction>| async { Ok(()) }).await
+    });
+    assert!(read.await.is_err(), "reader should stop on the header");
+}